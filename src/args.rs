@@ -1,10 +1,19 @@
 // Copyright (C) 2024 Daniel Mueller <deso@posteo.net>
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use std::ffi::OsString;
 use std::fmt::Debug;
 use std::path::PathBuf;
 
+use clap::error::Error as ClapError;
+use clap::error::ErrorKind;
+use clap::Args as Arguments;
+use clap::CommandFactory as _;
 use clap::Parser;
+use clap::Subcommand;
+use clap::ValueEnum;
+
+use crate::date::Date;
 
 
 /// A terminal based task and TODO management software.
@@ -21,4 +30,201 @@ pub struct Args {
   /// instance terminated improperly.
   #[clap(short, long)]
   pub force: bool,
+  /// Synchronize tasks with the CalDAV server configured in `sync.json`
+  /// inside the configuration directory and exit.
+  ///
+  /// Cannot be combined with a command.
+  #[clap(long)]
+  pub sync: bool,
+  /// A command to run instead of starting the terminal based UI.
+  #[clap(subcommand)]
+  pub command: Option<Command>,
+}
+
+impl Args {
+  /// Parse arguments from the provided iterator, additionally checking
+  /// constraints that `clap` cannot express itself.
+  pub fn try_parse_args<I, T>(iter: I) -> Result<Self, ClapError>
+  where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+  {
+    let args = Self::try_parse_from(iter)?;
+    // `clap` has no notion of an argument conflicting with a
+    // sub-command, so we check for that ourselves.
+    if args.sync && args.command.is_some() {
+      let err = Self::command().error(
+        ErrorKind::ArgumentConflict,
+        "the argument '--sync' cannot be used with a command",
+      );
+      return Err(err)
+    }
+    Ok(args)
+  }
+}
+
+
+/// A command for working with tasks non-interactively.
+///
+/// Commands print affected tasks as JSON objects, one per line.
+#[derive(Debug, Subcommand)]
+pub enum Command {
+  /// A command operating on individual tasks.
+  #[clap(flatten)]
+  Task(TaskCommand),
+  /// Export all tasks and tag templates to standard output.
+  Export(Export),
+  /// Import tasks and tag templates, as previously exported, replacing
+  /// the current ones.
+  ///
+  /// iCalendar data is merged into the current tasks instead, with tag
+  /// templates being created as needed.
+  Import(Import),
+}
+
+
+/// A command operating on individual tasks.
+#[derive(Debug, Subcommand)]
+pub enum TaskCommand {
+  /// Add a new task.
+  Add(Add),
+  /// List tasks.
+  List(List),
+  /// Mark a task as done by setting the configured toggle tag on it.
+  Done(TaskRef),
+  /// Set tags on a task.
+  Tag(Tags),
+  /// Remove tags from a task.
+  Untag(Tags),
+  /// Edit a task.
+  Edit(Edit),
+  /// Remove a task.
+  Rm(TaskRef),
+}
+
+
+/// The format used for exporting and importing tasks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+  /// A single JSON document.
+  #[default]
+  Json,
+  /// Newline delimited JSON, with the tag templates on the first line
+  /// followed by one task per line.
+  Ndjson,
+  /// A single iCalendar file with one TODO per task and tags
+  /// represented as categories, as used by many other task managers.
+  Ics,
+}
+
+
+/// A type representing the `export` command.
+#[derive(Debug, Arguments)]
+pub struct Export {
+  /// The format to export in.
+  #[clap(long, value_enum, default_value = "json")]
+  pub format: Format,
+}
+
+
+/// A type representing the `import` command.
+#[derive(Debug, Arguments)]
+pub struct Import {
+  /// The format to import from.
+  #[clap(long, value_enum, default_value = "json")]
+  pub format: Format,
+  /// Replace existing tasks, if any. Not supported for iCalendar data.
+  #[clap(long)]
+  pub replace: bool,
+  /// The file to import from, instead of standard input.
+  pub file: Option<PathBuf>,
+}
+
+
+/// A type representing the `add` command.
+#[derive(Debug, Arguments)]
+pub struct Add {
+  /// The summary of the task.
+  pub summary: String,
+  /// The details of the task.
+  #[clap(short, long)]
+  pub details: Option<String>,
+  /// The name of a tag to set on the task. May be provided multiple
+  /// times.
+  #[clap(short, long = "tag")]
+  pub tags: Vec<String>,
+  /// The date at which the task is due, in the form YYYY-MM-DD
+  /// [HH:MM[:SS]].
+  #[clap(long)]
+  pub due: Option<Date>,
+  /// The date at which work on the task is meant to start, in the form
+  /// YYYY-MM-DD [HH:MM[:SS]].
+  #[clap(long)]
+  pub start: Option<Date>,
+}
+
+
+/// A type representing the `list` command.
+#[derive(Debug, Arguments)]
+pub struct List {
+  /// The name of the view whose tasks to list, instead of all tasks.
+  #[clap(short, long)]
+  pub view: Option<String>,
+}
+
+
+/// A type representing a reference to a single task.
+#[derive(Debug, Arguments)]
+pub struct TaskRef {
+  /// The ID of the task or a unique prefix thereof.
+  pub id: String,
+}
+
+
+/// A type representing the `tag` and `untag` commands.
+#[derive(Debug, Arguments)]
+pub struct Tags {
+  /// The ID of the task or a unique prefix thereof.
+  pub id: String,
+  /// The names of the tags.
+  #[clap(required = true)]
+  pub tags: Vec<String>,
+}
+
+
+/// A type representing the `edit` command.
+#[derive(Debug, Arguments)]
+pub struct Edit {
+  /// The ID of the task or a unique prefix thereof.
+  pub id: String,
+  /// The new summary of the task.
+  #[clap(short, long)]
+  pub summary: Option<String>,
+  /// The new details of the task.
+  #[clap(short, long)]
+  pub details: Option<String>,
+  /// The new due date of the task; an empty string clears it.
+  #[clap(long)]
+  pub due: Option<String>,
+  /// The new start date of the task; an empty string clears it.
+  #[clap(long)]
+  pub start: Option<String>,
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+
+  /// Check that `--sync` cannot be combined with a command.
+  #[test]
+  fn sync_conflicts_with_command() {
+    let args = Args::try_parse_args(["notnow", "--sync"]).unwrap();
+    assert!(args.sync);
+    assert!(args.command.is_none());
+
+    let err = Args::try_parse_args(["notnow", "--sync", "list"]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+  }
 }
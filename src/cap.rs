@@ -27,6 +27,8 @@ use tokio::fs::read_dir;
 use tokio::fs::set_permissions;
 use tokio::runtime::Handle;
 
+use crate::transaction::recover;
+
 
 /// Change the provided `Permissions` by making it read-only.
 #[cfg(unix)]
@@ -188,7 +190,17 @@ pub struct DirCap {
 
 impl DirCap {
   /// Create a [`DirCap`] object for the provided directory.
+  ///
+  /// Changes of a [`Transaction`][crate::transaction::Transaction]
+  /// that got interrupted while being committed to the directory are
+  /// recovered first.
   pub async fn for_dir(directory: PathBuf) -> Result<Self> {
+    let () = recover(&directory).await.with_context(|| {
+      format!(
+        "failed to recover interrupted changes in {}",
+        directory.display()
+      )
+    })?;
     let slf = Self { directory };
     let () = slf.protect().await?;
 
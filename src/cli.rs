@@ -0,0 +1,828 @@
+// Copyright (C) 2025 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Non-interactive commands for working with tasks, meant to be used
+//! from scripts and the like.
+
+use std::collections::HashSet;
+use std::io::stdin;
+use std::io::Read as _;
+use std::io::Write;
+use std::ops::Deref as _;
+use std::path::Path;
+use std::rc::Rc;
+use std::str::from_utf8;
+use std::str::FromStr as _;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context as _;
+use anyhow::Result;
+
+use serde_json::from_str as from_json;
+use serde_json::json;
+use serde_json::to_string as to_json;
+use serde_json::Value;
+
+use tokio::fs::read;
+
+use crate::args::Add;
+use crate::args::Command;
+use crate::args::Edit;
+use crate::args::Export;
+use crate::args::Format;
+use crate::args::Import;
+use crate::args::List;
+use crate::args::TaskCommand;
+use crate::args::TaskRef;
+use crate::args::Tags;
+use crate::cap::DirCap;
+use crate::date::Date;
+use crate::paths::Paths;
+use crate::ser::backends::iCal;
+use crate::ser::backends::Backend;
+use crate::ser::backends::Json;
+use crate::ser::state::TaskState as SerTaskState;
+use crate::ser::tasks::Tasks as SerTasks;
+use crate::ser::tasks::TasksMeta as SerTasksMeta;
+use crate::ser::ToSerde as _;
+use crate::state::TaskState;
+use crate::tags::Tag;
+use crate::tasks::Task;
+use crate::tasks::Tasks;
+use crate::ui::Config as UiConfig;
+use crate::view::View;
+use crate::LINE_END;
+use crate::LINE_END_STR;
+
+
+/// Convert a task into a JSON object suitable for output.
+fn task_to_json(task: &Task) -> Value {
+  let tags = task.tags(|iter| iter.map(|tag| tag.name().to_string()).collect::<Vec<_>>());
+
+  json!({
+    "id": task.id().as_hyphenated().to_string(),
+    "summary": task.summary(),
+    "details": task.details().replace(LINE_END, "\n"),
+    "tags": tags,
+    "due": task.due().map(|due| due.to_string()),
+    "start": task.start().map(|start| start.to_string()),
+  })
+}
+
+
+/// Print a task as a single line JSON object.
+fn print_task(out: &mut dyn Write, task: &Task) -> Result<()> {
+  writeln!(out, "{}", task_to_json(task)).context("failed to write task")
+}
+
+
+/// Find the task with the given ID or ID prefix.
+fn find_task(tasks: &Tasks, id: &str) -> Result<Rc<Task>> {
+  let id = id.trim().to_ascii_lowercase();
+  if id.is_empty() {
+    bail!("no task ID provided")
+  }
+
+  let mut matches = tasks.iter(|iter| {
+    iter
+      .filter(|task| task.id().as_hyphenated().to_string().starts_with(&id))
+      .cloned()
+      .collect::<Vec<_>>()
+  });
+
+  match matches.len() {
+    0 => bail!("no task matches ID `{id}`"),
+    1 => Ok(matches.remove(0)),
+    count => bail!("ID `{id}` is ambiguous; it matches {count} tasks"),
+  }
+}
+
+
+/// Look up the tags with the given names.
+fn find_tags(task_state: &TaskState, names: &[String]) -> Result<Vec<Tag>> {
+  names
+    .iter()
+    .map(|name| {
+      task_state
+        .templates()
+        .instantiate_from_name(name)
+        .ok_or_else(|| anyhow!("tag `{name}` does not exist"))
+    })
+    .collect()
+}
+
+
+/// Parse a date, with an empty string meaning no date.
+fn parse_date(text: &str) -> Result<Option<Date>> {
+  if text.trim().is_empty() {
+    Ok(None)
+  } else {
+    Date::from_str(text).map(Some)
+  }
+}
+
+/// Convert a newly provided date into the kind of the `original` one,
+/// if any, so that, say, a UTC date stays in UTC.
+fn retain_kind(date: Option<Date>, original: Option<Date>) -> Option<Date> {
+  match (date, original) {
+    (Some(date), Some(original)) => Some(date.into_kind_of(&original)),
+    (date, _) => date,
+  }
+}
+
+
+/// Update a task by applying the given function to a copy of it.
+fn update_task<F>(tasks: &Tasks, task: Rc<Task>, f: F) -> Rc<Task>
+where
+  F: FnOnce(&mut Task),
+{
+  let mut updated = Task::clone(task.deref());
+  let () = f(&mut updated);
+  let () = tasks.update(Rc::clone(&task), updated);
+  task
+}
+
+
+/// Export all tasks and templates in the given directory.
+async fn export(export: Export, tasks_dir_cap: &DirCap, out: &mut dyn Write) -> Result<()> {
+  let Export { format } = export;
+  let task_state = TaskState::load(tasks_dir_cap.path())
+    .await
+    .context("failed to load task state")?
+    .to_serde();
+
+  match format {
+    Format::Json => {
+      let data = Json::serialize(&task_state).context("failed to serialize task state")?;
+      let () = out.write_all(&data).context("failed to write task state")?;
+      writeln!(out).context("failed to write task state")
+    },
+    Format::Ndjson => {
+      let meta = to_json(&task_state.tasks_meta).context("failed to serialize task meta data")?;
+      let () = writeln!(out, "{meta}").context("failed to write task meta data")?;
+      task_state.tasks.0.iter().try_for_each(|task| {
+        let task = to_json(task).context("failed to serialize task")?;
+        writeln!(out, "{task}").context("failed to write task")
+      })
+    },
+    Format::Ics => {
+      let data = iCal::serialize(&task_state).context("failed to serialize task state")?;
+      out.write_all(&data).context("failed to write task state")
+    },
+  }
+}
+
+
+/// Parse exported task state in the given format.
+fn parse_task_state(format: Format, data: &[u8]) -> Result<SerTaskState> {
+  match format {
+    Format::Json => Json::deserialize(data).context("failed to parse JSON document"),
+    Format::Ndjson => {
+      let data = from_utf8(data).context("input is not valid UTF-8")?;
+      let mut lines = data
+        .lines()
+        .enumerate()
+        .filter(|(_idx, line)| !line.trim().is_empty());
+
+      let tasks_meta = lines
+        .next()
+        .map(|(idx, line)| {
+          from_json::<SerTasksMeta>(line)
+            .with_context(|| format!("failed to parse task meta data on line {}", idx + 1))
+        })
+        .transpose()?
+        .unwrap_or_default();
+      let tasks = lines
+        .map(|(idx, line)| {
+          from_json(line).with_context(|| format!("failed to parse task on line {}", idx + 1))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+      let task_state = SerTaskState {
+        tasks_meta,
+        tasks: SerTasks(tasks),
+      };
+      Ok(task_state)
+    },
+    Format::Ics => {
+      <iCal as Backend<_>>::deserialize(data).context("failed to parse iCalendar data")
+    },
+  }
+}
+
+
+/// Merge `imported` task state into `current`, matching up tag
+/// templates by name and creating ones not known yet.
+///
+/// Imported tasks replace existing ones with the same ID, while all
+/// others are added.
+fn merge_task_state(current: SerTaskState, imported: SerTaskState) -> SerTaskState {
+  let SerTaskState {
+    tasks_meta: SerTasksMeta { mut templates },
+    tasks: SerTasks(mut tasks),
+  } = current;
+
+  let ids = templates.merge(imported.tasks_meta.templates);
+  for mut task in imported.tasks.0 {
+    let () = task.remap_tags(&ids);
+    if let Some(existing) = tasks.iter_mut().find(|existing| existing.id == task.id) {
+      *existing = task;
+    } else {
+      let () = tasks.push(task);
+    }
+  }
+
+  SerTaskState {
+    tasks_meta: SerTasksMeta { templates },
+    tasks: SerTasks(tasks),
+  }
+}
+
+
+/// Check that the UI configuration at `ui_config_path` remains valid
+/// with the `imported` task state replacing the `current` one.
+async fn check_ui_config(
+  ui_config_path: &Path,
+  current: &TaskState,
+  imported: &TaskState,
+) -> Result<()> {
+  // The current configuration may very well not be valid to begin
+  // with, for example, if it stems from the same source as the
+  // imported task state.
+  let current = UiConfig::load(ui_config_path, current).await.ok();
+  let imported = UiConfig::load(ui_config_path, imported)
+    .await
+    .context("UI configuration is not compatible with imported task state")?;
+
+  // The toggle tag is referenced by ID, which may now belong to a
+  // different template.
+  let toggle_tag = |config: &UiConfig| {
+    config
+      .toggle_tag
+      .as_ref()
+      .map(|tag| tag.name().to_string())
+  };
+  if let (Some(current), Some(imported)) = (
+    current.as_ref().and_then(toggle_tag),
+    toggle_tag(&imported),
+  ) {
+    if current != imported {
+      bail!("toggle tag `{current}` refers to tag `{imported}` in imported task state")
+    }
+  }
+
+  for (idx, view) in imported.views.iter().enumerate() {
+    let unknown = current
+      .as_ref()
+      .and_then(|config| config.views.get(idx))
+      .map(View::unknown_tags)
+      .unwrap_or_default();
+    if let Some(name) = view.unknown_tags().iter().find(|name| !unknown.contains(name)) {
+      bail!(
+        "view `{}` references tag `{name}` not present in imported task state",
+        view.name()
+      )
+    }
+  }
+  Ok(())
+}
+
+
+/// Import tasks and templates into the given directory.
+async fn import(
+  import: Import,
+  tasks_dir_cap: &mut DirCap,
+  ui_config_path: &Path,
+  out: &mut dyn Write,
+) -> Result<()> {
+  let Import {
+    format,
+    replace,
+    file,
+  } = import;
+
+  let data = if let Some(file) = &file {
+    read(file)
+      .await
+      .with_context(|| format!("failed to read {}", file.display()))?
+  } else {
+    let mut data = Vec::new();
+    let _count = stdin()
+      .read_to_end(&mut data)
+      .context("failed to read standard input")?;
+    data
+  };
+  let task_state = parse_task_state(format, &data)?;
+  let imported = task_state
+    .tasks
+    .0
+    .iter()
+    .map(|task| task.id)
+    .collect::<HashSet<_>>();
+
+  let current = TaskState::load(tasks_dir_cap.path())
+    .await
+    .context("failed to load task state")?;
+  let task_state = if format == Format::Ics {
+    // iCalendar data typically stems from other programs and does not
+    // carry our tag IDs, so we merge it into the existing tasks.
+    if replace {
+      bail!("iCalendar data is merged into existing tasks and cannot replace them")
+    }
+    merge_task_state(current.to_serde(), task_state)
+  } else {
+    if !replace {
+      let count = current.tasks().iter(|iter| iter.count());
+      if count > 0 {
+        bail!("refusing to replace {count} existing task(s); use --replace to do so anyway")
+      }
+    }
+    task_state
+  };
+
+  let task_state =
+    TaskState::with_serde(task_state).context("failed to instantiate imported task state")?;
+  let () = check_ui_config(ui_config_path, &current, &task_state).await?;
+  let () = task_state
+    .save(tasks_dir_cap)
+    .await
+    .context("failed to save task state")?;
+  task_state.tasks().iter(|iter| {
+    iter
+      .filter(|task| imported.contains(&task.id()))
+      .try_for_each(|task| print_task(out, task))
+  })
+}
+
+
+/// Run a command operating on individual tasks in the given directory.
+async fn run_task_command(
+  command: TaskCommand,
+  tasks_dir_cap: &mut DirCap,
+  ui_config_path: &Path,
+  out: &mut dyn Write,
+) -> Result<()> {
+  let task_state = TaskState::load(tasks_dir_cap.path())
+    .await
+    .context("failed to load task state")?;
+  let ui_config = UiConfig::load(ui_config_path, &task_state)
+    .await
+    .context("failed to load UI configuration")?;
+  let toggle_tag = ui_config.toggle_tag;
+  let tasks = task_state.tasks();
+  let () = tasks.set_completion_tag(toggle_tag.clone());
+
+  let task = match command {
+    TaskCommand::List(List { view }) => {
+      return if let Some(name) = view {
+        let view = ui_config
+          .views
+          .iter()
+          .find(|view| *view.name() == *name)
+          .ok_or_else(|| anyhow!("view `{name}` does not exist"))?;
+        view.iter(|mut iter| iter.try_for_each(|task| print_task(out, task)))
+      } else {
+        tasks.iter(|mut iter| iter.try_for_each(|task| print_task(out, task)))
+      }
+    },
+    TaskCommand::Add(Add {
+      summary,
+      details,
+      tags,
+      due,
+      start,
+    }) => {
+      let tags = find_tags(&task_state, &tags)?;
+      let details = details.unwrap_or_default().replace('\n', LINE_END_STR);
+      let builder = Task::builder()
+        .set_summary(summary)
+        .set_details(details)
+        .set_tags(tags)
+        .set_due(due)
+        .set_start(start);
+      tasks.add(builder, None)
+    },
+    TaskCommand::Done(TaskRef { id }) => {
+      let tag = toggle_tag.ok_or_else(|| anyhow!("no toggle tag is configured"))?;
+      let task = find_task(tasks, &id)?;
+      update_task(tasks, task, |task| {
+        let _set = task.set_tag(tag);
+      })
+    },
+    TaskCommand::Tag(Tags { id, tags }) => {
+      let tags = find_tags(&task_state, &tags)?;
+      let task = find_task(tasks, &id)?;
+      update_task(tasks, task, |task| {
+        tags.into_iter().for_each(|tag| {
+          let _set = task.set_tag(tag);
+        })
+      })
+    },
+    TaskCommand::Untag(Tags { id, tags }) => {
+      let tags = find_tags(&task_state, &tags)?;
+      let task = find_task(tasks, &id)?;
+      update_task(tasks, task, |task| {
+        tags.iter().for_each(|tag| {
+          let _unset = task.unset_tag(tag);
+        })
+      })
+    },
+    TaskCommand::Edit(Edit {
+      id,
+      summary,
+      details,
+      due,
+      start,
+    }) => {
+      let due = due.as_deref().map(parse_date).transpose()?;
+      let start = start.as_deref().map(parse_date).transpose()?;
+      let task = find_task(tasks, &id)?;
+      update_task(tasks, task, |task| {
+        if let Some(summary) = summary {
+          let () = task.set_summary(summary);
+        }
+        if let Some(details) = details {
+          let () = task.set_details(details.replace('\n', LINE_END_STR));
+        }
+        if let Some(due) = due {
+          let () = task.set_due(retain_kind(due, task.due()));
+        }
+        if let Some(start) = start {
+          let () = task.set_start(retain_kind(start, task.start()));
+        }
+      })
+    },
+    TaskCommand::Rm(TaskRef { id }) => {
+      let task = find_task(tasks, &id)?;
+      let () = tasks.remove(Rc::clone(&task));
+      task
+    },
+  };
+
+  let () = task_state
+    .save(tasks_dir_cap)
+    .await
+    .context("failed to save task state")?;
+  print_task(out, &task)
+}
+
+
+/// Run a command on the tasks in the given directory.
+async fn run_command(
+  command: Command,
+  tasks_dir_cap: &mut DirCap,
+  ui_config_path: &Path,
+  out: &mut dyn Write,
+) -> Result<()> {
+  // Exporting and importing work on the task state alone. Importing,
+  // in particular, may provide templates referenced by the UI
+  // configuration, meaning that we should not attempt loading the
+  // latter beforehand.
+  match command {
+    Command::Task(command) => run_task_command(command, tasks_dir_cap, ui_config_path, out).await,
+    Command::Export(args) => export(args, tasks_dir_cap, out).await,
+    Command::Import(args) => import(args, tasks_dir_cap, ui_config_path, out).await,
+  }
+}
+
+
+/// Run a command on the tasks of the program instance described by
+/// `paths`, printing affected tasks to `out`.
+pub async fn run(command: Command, paths: &Paths, out: &mut dyn Write) -> Result<()> {
+  let mut tasks_dir_cap = DirCap::for_dir(paths.tasks_dir()).await?;
+  let ui_config_path = paths.ui_config_dir().join(paths.ui_config_file());
+  run_command(command, &mut tasks_dir_cap, &ui_config_path, out).await
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::fs::write;
+  use std::path::PathBuf;
+
+  use clap::Parser as _;
+
+  use tempfile::TempDir;
+
+  use tokio::test;
+
+  use crate::args::Args;
+  use crate::test::default_tasks_and_tags;
+  use crate::test::COMPLETE_TAG;
+
+
+  /// A helper for running commands on a set of tasks stored in a
+  /// temporary directory.
+  struct TestCli {
+    /// The directory containing all state.
+    root: TempDir,
+    /// The capability to the tasks directory.
+    tasks_dir_cap: DirCap,
+    /// The path to the UI configuration.
+    ui_config_path: PathBuf,
+  }
+
+  impl TestCli {
+    /// Create a `TestCli` object using the state provided by
+    /// `default_tasks_and_tags`.
+    async fn new() -> Self {
+      let root = TempDir::new().unwrap();
+      let (ui_config, task_state) = default_tasks_and_tags();
+
+      let tasks_dir = root.path().join("tasks");
+      let mut tasks_dir_cap = DirCap::for_dir(tasks_dir).await.unwrap();
+      let task_state = TaskState::with_serde(task_state).unwrap();
+      let () = task_state.save(&mut tasks_dir_cap).await.unwrap();
+
+      let ui_config_path = root.path().join("notnow.json");
+      let () = write(&ui_config_path, Json::serialize(&ui_config).unwrap()).unwrap();
+
+      Self {
+        root,
+        tasks_dir_cap,
+        ui_config_path,
+      }
+    }
+
+    /// Run the command described by the provided arguments and return
+    /// its raw output.
+    async fn run_raw(&mut self, args: &[&str]) -> Result<String> {
+      let args = Args::try_parse_from(["notnow"].iter().chain(args)).unwrap();
+      let mut out = Vec::new();
+      let () = run_command(
+        args.command.unwrap(),
+        &mut self.tasks_dir_cap,
+        &self.ui_config_path,
+        &mut out,
+      )
+      .await?;
+
+      Ok(String::from_utf8(out).unwrap())
+    }
+
+    /// Run the command described by the provided arguments and return
+    /// the printed tasks.
+    async fn run(&mut self, args: &[&str]) -> Result<Vec<Value>> {
+      let tasks = self
+        .run_raw(args)
+        .await?
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+      Ok(tasks)
+    }
+  }
+
+
+  /// Check that we can add a task and list it afterwards.
+  #[test]
+  async fn add_and_list_tasks() {
+    let mut cli = TestCli::new().await;
+    let tasks = cli.run(&["list"]).await.unwrap();
+    assert_eq!(tasks.len(), 15);
+
+    let added = cli
+      .run(&[
+        "add",
+        "new task",
+        "--tag",
+        "tag1",
+        "--details",
+        "multi\nline",
+        "--due",
+        "2025-03-01",
+      ])
+      .await
+      .unwrap();
+    assert_eq!(added.len(), 1);
+    assert_eq!(added[0]["summary"], "new task");
+    assert_eq!(added[0]["details"], "multi\nline");
+    assert_eq!(added[0]["tags"], json!(["tag1"]));
+    assert_eq!(added[0]["due"], "2025-03-01");
+    assert_eq!(added[0]["start"], Value::Null);
+
+    let tasks = cli.run(&["list"]).await.unwrap();
+    assert_eq!(tasks.len(), 16);
+    assert_eq!(tasks[15], added[0]);
+
+    let error = cli.run(&["add", "task", "--tag", "foo"]).await.unwrap_err();
+    assert_eq!(error.to_string(), "tag `foo` does not exist");
+  }
+
+  /// Check that we can list the tasks of a view.
+  #[test]
+  async fn list_view_tasks() {
+    let mut cli = TestCli::new().await;
+    let tasks = cli.run(&["list", "--view", "tag complete"]).await.unwrap();
+    assert_eq!(tasks.len(), 7);
+    assert!(tasks
+      .iter()
+      .all(|task| task["tags"].as_array().unwrap().contains(&json!(COMPLETE_TAG))));
+
+    let error = cli.run(&["list", "--view", "foo"]).await.unwrap_err();
+    assert_eq!(error.to_string(), "view `foo` does not exist");
+  }
+
+  /// Check that we can modify and remove tasks.
+  #[test]
+  async fn modify_and_remove_task() {
+    let mut cli = TestCli::new().await;
+    let tasks = cli.run(&["list"]).await.unwrap();
+    let id = tasks[0]["id"].as_str().unwrap().to_string();
+    let prefix = &id[..8];
+
+    let task = cli.run(&["done", prefix]).await.unwrap();
+    assert_eq!(task[0]["tags"], json!([COMPLETE_TAG]));
+
+    let task = cli.run(&["tag", &id, "tag1", "tag2"]).await.unwrap();
+    assert_eq!(task[0]["tags"], json!([COMPLETE_TAG, "tag1", "tag2"]));
+
+    let task = cli.run(&["untag", &id, COMPLETE_TAG, "tag2"]).await.unwrap();
+    assert_eq!(task[0]["tags"], json!(["tag1"]));
+
+    let task = cli
+      .run(&["edit", &id, "--summary", "edited", "--start", "2025-03-01 12:00"])
+      .await
+      .unwrap();
+    assert_eq!(task[0]["summary"], "edited");
+    assert_eq!(task[0]["start"], "2025-03-01 12:00");
+
+    let task = cli.run(&["edit", &id, "--start", ""]).await.unwrap();
+    assert_eq!(task[0]["start"], Value::Null);
+
+    // All changes should have been persisted.
+    let tasks = cli.run(&["list"]).await.unwrap();
+    assert_eq!(tasks[0], task[0]);
+
+    let removed = cli.run(&["rm", &id]).await.unwrap();
+    assert_eq!(removed, task);
+
+    let tasks = cli.run(&["list"]).await.unwrap();
+    assert_eq!(tasks.len(), 14);
+    assert!(tasks.iter().all(|task| task["id"] != id.as_str()));
+  }
+
+  /// Make sure that we report an error for task IDs not referencing
+  /// exactly one task.
+  #[test]
+  async fn invalid_task_ids() {
+    let mut cli = TestCli::new().await;
+    let error = cli.run(&["rm", "xyz"]).await.unwrap_err();
+    assert_eq!(error.to_string(), "no task matches ID `xyz`");
+
+    let error = cli.run(&["rm", ""]).await.unwrap_err();
+    assert_eq!(error.to_string(), "no task ID provided");
+
+    let tasks = cli.run(&["list"]).await.unwrap();
+    assert_eq!(tasks.len(), 15);
+  }
+
+  /// Check that we can export tasks and import them again, in all
+  /// supported formats.
+  #[test]
+  async fn export_import_round_trip() {
+    for format in ["json", "ndjson", "ics"] {
+      let mut cli = TestCli::new().await;
+      let tasks = cli.run(&["list"]).await.unwrap();
+      let export = cli.run_raw(&["export", "--format", format]).await.unwrap();
+
+      let file = cli.root.path().join("export");
+      let () = write(&file, &export).unwrap();
+      let file = file.to_str().unwrap();
+
+      let mut other = TestCli::new().await;
+      // iCalendar data is always merged into existing tasks.
+      let replace = if format == "ics" { None } else { Some("--replace") };
+      let args = ["import", "--format", format]
+        .into_iter()
+        .chain(replace)
+        .chain([file])
+        .collect::<Vec<_>>();
+      let imported = other.run(&args).await.unwrap();
+      // Tasks should retain their IDs, order, and all other data.
+      assert_eq!(imported, tasks);
+      let mut listed = other.run(&["list"]).await.unwrap();
+      if format == "ics" {
+        // The other instance's tasks are retained as well.
+        assert_eq!(listed.len(), 30);
+        let () = listed.retain(|task| tasks.contains(task));
+      }
+      assert_eq!(listed, tasks);
+
+      // iCalendar exports contain time stamps and do not include unused
+      // tag templates, so only compare JSON based ones verbatim.
+      if format != "ics" {
+        assert_eq!(
+          other.run_raw(&["export", "--format", format]).await.unwrap(),
+          export
+        );
+      }
+    }
+  }
+
+  /// Make sure that importing tasks does not silently replace existing
+  /// ones.
+  #[test]
+  async fn import_refuses_replacing_tasks() {
+    let mut cli = TestCli::new().await;
+    let export = cli.run_raw(&["export"]).await.unwrap();
+    let file = cli.root.path().join("export.json");
+    let () = write(&file, &export).unwrap();
+    let file = file.to_str().unwrap();
+
+    let error = cli.run(&["import", file]).await.unwrap_err();
+    assert_eq!(
+      error.to_string(),
+      "refusing to replace 15 existing task(s); use --replace to do so anyway"
+    );
+
+    let () = write(cli.root.path().join("export.json"), "{").unwrap();
+    let error = cli.run(&["import", "--replace", file]).await.unwrap_err();
+    assert_eq!(error.to_string(), "failed to parse JSON document");
+
+    // The original tasks should have been left untouched.
+    let tasks = cli.run(&["list"]).await.unwrap();
+    assert_eq!(tasks.len(), 15);
+  }
+
+  /// Check that iCalendar data is merged into existing tasks, with tag
+  /// templates being created as needed.
+  #[test]
+  async fn import_ics_merges() {
+    let mut cli = TestCli::new().await;
+    let data = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:other\r\n\
+                BEGIN:VTODO\r\nUID:first@example.com\r\nSUMMARY:first\r\n\
+                CATEGORIES:tag1,new\r\nEND:VTODO\r\n\
+                BEGIN:VTODO\r\nUID:second@example.com\r\nSUMMARY:second\r\n\
+                END:VTODO\r\nEND:VCALENDAR\r\n";
+    let file = cli.root.path().join("import.ics");
+    let () = write(&file, data).unwrap();
+    let file = file.to_str().unwrap();
+
+    let imported = cli.run(&["import", "--format", "ics", file]).await.unwrap();
+    assert_eq!(imported.len(), 2);
+    assert_eq!(imported[0]["summary"], "first");
+    assert_eq!(imported[0]["tags"], json!(["tag1", "new"]));
+    assert_eq!(imported[1]["summary"], "second");
+
+    let tasks = cli.run(&["list"]).await.unwrap();
+    assert_eq!(tasks.len(), 17);
+    assert_eq!(tasks[15..], imported);
+
+    // Importing the same data again updates the tasks in place.
+    let reimported = cli.run(&["import", "--format", "ics", file]).await.unwrap();
+    assert_eq!(reimported, imported);
+    assert_eq!(cli.run(&["list"]).await.unwrap(), tasks);
+
+    let error = cli
+      .run(&["import", "--format", "ics", "--replace", file])
+      .await
+      .unwrap_err();
+    assert_eq!(
+      error.to_string(),
+      "iCalendar data is merged into existing tasks and cannot replace them"
+    );
+  }
+
+  /// Check that we refuse importing tasks that would leave the UI
+  /// configuration referencing tags that do not exist.
+  #[test]
+  async fn import_checks_ui_config() {
+    let mut cli = TestCli::new().await;
+    let export = cli.run_raw(&["export"]).await.unwrap();
+    let file = cli.root.path().join("export.json");
+    let file_str = file.to_str().unwrap();
+
+    let rename = |renames: &[(&str, &str)]| {
+      let mut state = from_json::<Value>(&export).unwrap();
+      let templates = state["tasks_meta"]["templates"].as_array_mut().unwrap();
+      for template in templates {
+        if let Some((_, to)) = renames.iter().find(|(from, _)| template["name"] == *from) {
+          template["name"] = json!(to);
+        }
+      }
+      let () = write(&file, state.to_string()).unwrap();
+    };
+
+    let () = rename(&[("tag2", "other")]);
+    let error = cli.run(&["import", "--replace", file_str]).await.unwrap_err();
+    assert_eq!(
+      error.to_string(),
+      "view `tag2 || tag3` references tag `tag2` not present in imported task state"
+    );
+
+    let () = rename(&[(COMPLETE_TAG, "tag9"), ("tag1", COMPLETE_TAG)]);
+    let error = cli.run(&["import", "--replace", file_str]).await.unwrap_err();
+    assert_eq!(
+      error.to_string(),
+      format!("toggle tag `{COMPLETE_TAG}` refers to tag `tag9` in imported task state")
+    );
+
+    // The original tasks should have been left untouched.
+    let tasks = cli.run(&["list", "--view", "tag complete"]).await.unwrap();
+    assert_eq!(tasks.len(), 7);
+  }
+}
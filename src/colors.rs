@@ -171,6 +171,18 @@ pub struct Colors {
   pub task_done_fg: Color,
   #[serde(default = "Color::reset")]
   pub task_done_bg: Color,
+  #[serde(default = "Color::color240")]
+  pub task_date_fg: Color,
+  #[serde(default = "Color::reset")]
+  pub task_date_bg: Color,
+  #[serde(default = "Color::soft_red")]
+  pub task_overdue_fg: Color,
+  #[serde(default = "Color::reset")]
+  pub task_overdue_bg: Color,
+  #[serde(default = "Color::color15")]
+  pub task_section_fg: Color,
+  #[serde(default = "Color::color235")]
+  pub task_section_bg: Color,
   #[serde(default = "Color::dark_white")]
   pub detail_dialog_bg: Color,
   #[serde(default = "Color::color0")]
@@ -226,6 +238,12 @@ impl Default for Colors {
       task_not_started_bg: Color::reset(),
       task_done_fg: Color::bright_green(),
       task_done_bg: Color::reset(),
+      task_date_fg: Color::color240(),
+      task_date_bg: Color::reset(),
+      task_overdue_fg: Color::soft_red(),
+      task_overdue_bg: Color::reset(),
+      task_section_fg: Color::color15(),
+      task_section_bg: Color::color235(),
       detail_dialog_fg: Color::color0(),
       detail_dialog_bg: Color::dark_white(),
       tag_dialog_fg: Color::color0(),
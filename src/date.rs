@@ -0,0 +1,378 @@
+// Copyright (C) 2025 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! A module providing a type for representing dates associated with
+//! tasks, such as due and start dates.
+
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use anyhow::Error;
+
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Local;
+use chrono::LocalResult;
+use chrono::NaiveDate;
+use chrono::NaiveDateTime;
+use chrono::Offset as _;
+use chrono::TimeZone;
+use chrono::Timelike as _;
+use chrono::Utc;
+
+use chrono_tz::Tz;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+
+/// The format used for representing a date without time.
+const DATE_FORMAT: &str = "%Y-%m-%d";
+/// The format used for representing a date and time, without seconds.
+const DATE_TIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+/// The format used for representing a date and time, including
+/// seconds.
+const DATE_TIME_SECS_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+
+/// Look up the time zone with the given (IANA) ID.
+fn find_zone(tzid: &str) -> Option<Tz> {
+  tzid.parse::<Tz>().ok()
+}
+
+/// Convert a date and time in the given time zone into UTC.
+///
+/// A time that exists twice (because the clock is turned back) is
+/// interpreted as the earlier of the two, while a time that is skipped
+/// (because the clock is advanced) is interpreted using the offset in
+/// effect before the skip.
+fn zoned_to_utc(zone: &Tz, date_time: &NaiveDateTime) -> DateTime<Utc> {
+  match zone.from_local_datetime(date_time) {
+    LocalResult::Single(zoned) | LocalResult::Ambiguous(zoned, _) => {
+      zoned.with_timezone(&Utc)
+    },
+    LocalResult::None => {
+      let offset = zone
+        .offset_from_utc_datetime(&(*date_time - Duration::days(1)))
+        .fix();
+      (*date_time - Duration::seconds(offset.local_minus_utc().into())).and_utc()
+    },
+  }
+}
+
+
+/// A date, optionally including a time, as associated with a task.
+///
+/// The variants closely follow what iCalendar supports, so that we can
+/// round-trip dates we did not create ourselves.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Date {
+  /// A date without a time component.
+  Day(NaiveDate),
+  /// A date and time not bound to a time zone ("floating").
+  Floating(NaiveDateTime),
+  /// A date and time in UTC.
+  Utc(DateTime<Utc>),
+  /// A date and time in the time zone with the given (IANA) ID.
+  ///
+  /// The time zone is resolved using the bundled IANA time zone
+  /// database. Dates in unknown time zones are treated as local to the
+  /// user.
+  Zoned(NaiveDateTime, String),
+}
+
+impl Date {
+  /// Retrieve the date and time as local to the user, if it includes a
+  /// time component.
+  fn to_local(&self) -> Option<NaiveDateTime> {
+    self.to_local_in(&Local)
+  }
+
+  /// Retrieve the date and time in the time zone `local`, representing
+  /// the one of the user, if it includes a time component.
+  fn to_local_in<Z>(&self, local: &Z) -> Option<NaiveDateTime>
+  where
+    Z: TimeZone,
+  {
+    match self {
+      Self::Day(..) => None,
+      Self::Floating(date_time) => Some(*date_time),
+      Self::Utc(date_time) => Some(date_time.with_timezone(local).naive_local()),
+      Self::Zoned(date_time, tzid) => match find_zone(tzid) {
+        Some(zone) => Some(
+          zoned_to_utc(&zone, date_time)
+            .with_timezone(local)
+            .naive_local(),
+        ),
+        None => Some(*date_time),
+      },
+    }
+  }
+
+  /// Convert a date as entered by the user, which is always local to
+  /// them, into the same kind as `original`.
+  ///
+  /// Dates without a time component are left as they are.
+  #[inline]
+  pub fn into_kind_of(self, original: &Date) -> Self {
+    self.into_kind_of_in(original, &Local)
+  }
+
+  /// Convert a date as entered by the user in the time zone `local`
+  /// into the same kind as `original`.
+  fn into_kind_of_in<Z>(self, original: &Date, local: &Z) -> Self
+  where
+    Z: TimeZone,
+  {
+    let Self::Floating(date_time) = self else {
+      return self
+    };
+    let local_to_utc = || {
+      local
+        .from_local_datetime(&date_time)
+        .earliest()
+        .map(|date_time| date_time.with_timezone(&Utc))
+    };
+
+    match original {
+      Self::Day(..) | Self::Floating(..) => self,
+      Self::Utc(..) => local_to_utc().map(Self::Utc).unwrap_or(self),
+      Self::Zoned(_, tzid) => {
+        let date_time = find_zone(tzid)
+          .zip(local_to_utc())
+          .map(|(zone, utc)| utc.with_timezone(&zone).naive_local())
+          .unwrap_or(date_time);
+        Self::Zoned(date_time, tzid.clone())
+      },
+    }
+  }
+
+  /// Check whether the date lies in the past, relative to the given
+  /// (local) point in time.
+  ///
+  /// A date without a time component lies in the past only once the
+  /// day is over.
+  #[inline]
+  pub fn is_past(&self, now: &NaiveDateTime) -> bool {
+    self.is_past_in(now, &Local)
+  }
+
+  /// Check whether the date lies in the past, relative to the given
+  /// point in time in the time zone `local`.
+  fn is_past_in<Z>(&self, now: &NaiveDateTime, local: &Z) -> bool
+  where
+    Z: TimeZone,
+  {
+    match self {
+      Self::Day(date) => *date < now.date(),
+      _ => {
+        // SANITY: All variants but `Day` have a time component.
+        self.to_local_in(local).unwrap() < *now
+      },
+    }
+  }
+
+  /// Check whether the date lies in the past, relative to the current
+  /// time.
+  #[inline]
+  pub fn is_past_now(&self) -> bool {
+    self.is_past(&Local::now().naive_local())
+  }
+}
+
+impl Display for Date {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    match self {
+      Self::Day(date) => write!(f, "{}", date.format(DATE_FORMAT)),
+      _ => {
+        // SANITY: All variants but `Day` have a time component.
+        let date_time = self.to_local().unwrap();
+        let format = if date_time.second() != 0 {
+          DATE_TIME_SECS_FORMAT
+        } else {
+          DATE_TIME_FORMAT
+        };
+        write!(f, "{}", date_time.format(format))
+      },
+    }
+  }
+}
+
+impl FromStr for Date {
+  type Err = Error;
+
+  /// Parse a date in the form `YYYY-MM-DD`, optionally followed by a
+  /// time in the form `HH:MM` or `HH:MM:SS`.
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let s = s.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(s, DATE_FORMAT) {
+      return Ok(Self::Day(date))
+    }
+
+    let s = s.replacen('T', " ", 1);
+    [DATE_TIME_FORMAT, DATE_TIME_SECS_FORMAT]
+      .into_iter()
+      .find_map(|format| NaiveDateTime::parse_from_str(&s, format).ok())
+      .map(Self::Floating)
+      .ok_or_else(|| anyhow!("invalid date `{s}`; expected YYYY-MM-DD [HH:MM[:SS]]"))
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+
+  /// Create a `NaiveDateTime` from the given components.
+  fn date_time(y: i32, m: u32, d: u32, h: u32, min: u32, s: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(y, m, d)
+      .unwrap()
+      .and_hms_opt(h, min, s)
+      .unwrap()
+  }
+
+
+  /// Check that we can parse dates in the supported formats.
+  #[test]
+  fn date_parsing() {
+    let date = Date::from_str("2025-03-01").unwrap();
+    assert_eq!(
+      date,
+      Date::Day(NaiveDate::from_ymd_opt(2025, 3, 1).unwrap())
+    );
+
+    let date = Date::from_str(" 2025-03-01 13:37 ").unwrap();
+    assert_eq!(date, Date::Floating(date_time(2025, 3, 1, 13, 37, 0)));
+
+    let date = Date::from_str("2025-03-01T13:37:42").unwrap();
+    assert_eq!(date, Date::Floating(date_time(2025, 3, 1, 13, 37, 42)));
+
+    assert!(Date::from_str("").is_err());
+    assert!(Date::from_str("tomorrow").is_err());
+    assert!(Date::from_str("2025-02-30").is_err());
+    assert!(Date::from_str("2025-03-01 25:00").is_err());
+  }
+
+  /// Make sure that displaying a date and parsing it back yields the
+  /// original value.
+  #[test]
+  fn date_display_parse_round_trip() {
+    let dates = [
+      Date::Day(NaiveDate::from_ymd_opt(2025, 12, 31).unwrap()),
+      Date::Floating(date_time(2025, 1, 2, 3, 4, 0)),
+      Date::Floating(date_time(2025, 1, 2, 3, 4, 5)),
+    ];
+
+    for date in dates {
+      let string = date.to_string();
+      assert_eq!(Date::from_str(&string).unwrap(), date, "{string}");
+    }
+  }
+
+  /// Check that dates entered by the user retain the kind of the date
+  /// they replace.
+  #[test]
+  fn date_kind_retention() {
+    // We pin the user's time zone, so as to not depend on the one of
+    // the system.
+    let local = Tz::America__New_York;
+    let dates = [
+      Date::Floating(date_time(2025, 1, 2, 3, 4, 0)),
+      Date::Utc(date_time(2025, 1, 2, 3, 4, 0).and_utc()),
+      Date::Zoned(date_time(2025, 7, 2, 3, 4, 0), "Europe/Berlin".to_string()),
+      Date::Zoned(date_time(2025, 7, 2, 3, 4, 0), "Unknown/Zone".to_string()),
+    ];
+
+    for date in dates {
+      let entered = Date::Floating(date.to_local_in(&local).unwrap());
+      assert_eq!(entered.into_kind_of_in(&date, &local), date);
+    }
+
+    let entered = Date::Floating(date_time(2025, 1, 1, 22, 4, 0));
+    let utc = Date::Utc(date_time(2025, 1, 2, 3, 4, 0).and_utc());
+    assert_eq!(entered.into_kind_of_in(&utc, &local), utc);
+
+    let day = Date::from_str("2025-01-02").unwrap();
+    assert_eq!(day.clone().into_kind_of_in(&utc, &local), day);
+  }
+
+  /// Check that we correctly determine whether a date lies in the past.
+  #[test]
+  fn date_in_past() {
+    let local = Tz::Europe__Berlin;
+    let now = date_time(2025, 3, 1, 12, 0, 0);
+
+    let date = Date::Day(NaiveDate::from_ymd_opt(2025, 2, 28).unwrap());
+    assert!(date.is_past_in(&now, &local));
+    // A day is only considered past once it is over.
+    let date = Date::Day(NaiveDate::from_ymd_opt(2025, 3, 1).unwrap());
+    assert!(!date.is_past_in(&now, &local));
+
+    let date = Date::Floating(date_time(2025, 3, 1, 11, 59, 59));
+    assert!(date.is_past_in(&now, &local));
+    let date = Date::Floating(date_time(2025, 3, 1, 12, 0, 1));
+    assert!(!date.is_past_in(&now, &local));
+
+    let date = Date::Zoned(
+      date_time(2025, 3, 1, 5, 59, 59),
+      "America/New_York".to_string(),
+    );
+    assert!(date.is_past_in(&now, &local));
+    let date = Date::Zoned(
+      date_time(2025, 3, 1, 6, 0, 1),
+      "America/New_York".to_string(),
+    );
+    assert!(!date.is_past_in(&now, &local));
+
+    let date = Date::Utc(date_time(2025, 3, 1, 10, 59, 59).and_utc());
+    assert!(date.is_past_in(&now, &local));
+    let date = Date::Utc(date_time(2025, 3, 1, 11, 0, 1).and_utc());
+    assert!(!date.is_past_in(&now, &local));
+  }
+
+  /// Check that we correctly convert dates in named time zones into
+  /// UTC.
+  #[test]
+  fn zone_conversion() {
+    let zone = find_zone("Europe/Berlin").unwrap();
+    let times = [
+      (date_time(2025, 3, 1, 12, 0, 0), date_time(2025, 3, 1, 11, 0, 0)),
+      (date_time(2025, 7, 1, 12, 0, 0), date_time(2025, 7, 1, 10, 0, 0)),
+      (date_time(2060, 7, 1, 12, 0, 0), date_time(2060, 7, 1, 10, 0, 0)),
+      (date_time(2060, 12, 1, 12, 0, 0), date_time(2060, 12, 1, 11, 0, 0)),
+    ];
+    for (local, utc) in times {
+      assert_eq!(zoned_to_utc(&zone, &local), utc.and_utc(), "{local}");
+      assert_eq!(utc.and_utc().with_timezone(&zone).naive_local(), local, "{utc}");
+    }
+
+    // 02:30 does not exist on the day daylight saving time starts and
+    // is interpreted as standard time.
+    let local = date_time(2025, 3, 30, 2, 30, 0);
+    assert_eq!(
+      zoned_to_utc(&zone, &local),
+      date_time(2025, 3, 30, 1, 30, 0).and_utc()
+    );
+    // 02:30 exists twice on the day it ends, and we pick the earlier.
+    let local = date_time(2025, 10, 26, 2, 30, 0);
+    assert_eq!(
+      zoned_to_utc(&zone, &local),
+      date_time(2025, 10, 26, 0, 30, 0).and_utc()
+    );
+
+    let zone = find_zone("America/New_York").unwrap();
+    let local = date_time(2025, 1, 15, 12, 0, 0);
+    assert_eq!(
+      zoned_to_utc(&zone, &local),
+      date_time(2025, 1, 15, 17, 0, 0).and_utc()
+    );
+
+    assert_eq!(find_zone("Unknown/Zone"), None);
+    assert_eq!(find_zone("../zoneinfo/UTC"), None);
+  }
+}
@@ -4,7 +4,6 @@
 use std::cell::Cell;
 use std::cell::RefCell;
 use std::collections::HashMap;
-#[cfg(test)]
 use std::collections::HashSet;
 use std::fmt::Debug;
 use std::fmt::Formatter;
@@ -298,10 +297,30 @@ impl<T> Db<T, ()> {
   pub fn try_from_iter<I>(iter: I) -> Result<Self, Rc<T>>
   where
     I: IntoIterator<Item = Rc<T>>,
+  {
+    Self::try_from_iter_with_aux(iter.into_iter().map(|item| (item, ())))
+  }
+
+  /// Create a database from an iterator of items.
+  #[cfg(test)]
+  pub fn from_iter<I>(iter: I) -> Self
+  where
+    I: IntoIterator<Item = T>,
+  {
+    Self::from_iter_with_aux(iter.into_iter().map(|item| (item, ())))
+  }
+}
+
+impl<T, Aux> Db<T, Aux> {
+  /// Create a database from the items contained in the provided
+  /// iterator, along with auxiliary data.
+  pub fn try_from_iter_with_aux<I>(iter: I) -> Result<Self, Rc<T>>
+  where
+    I: IntoIterator<Item = (Rc<T>, Aux)>,
   {
     let data = iter
       .into_iter()
-      .map(|item| (item, Cell::default()))
+      .map(|(item, aux)| (item, Cell::new(aux)))
       .collect::<Vec<_>>();
     // Check that all pointers provided are unique.
     let set = HashSet::with_capacity(data.len());
@@ -321,17 +340,6 @@ impl<T> Db<T, ()> {
     Ok(slf)
   }
 
-  /// Create a database from an iterator of items.
-  #[cfg(test)]
-  pub fn from_iter<I>(iter: I) -> Self
-  where
-    I: IntoIterator<Item = T>,
-  {
-    Self::from_iter_with_aux(iter.into_iter().map(|item| (item, ())))
-  }
-}
-
-impl<T, Aux> Db<T, Aux> {
   /// Create a database from an iterator of items.
   pub fn from_iter_with_aux<I>(iter: I) -> Self
   where
@@ -1,6 +1,8 @@
 // Copyright (C) 2025 Daniel Mueller <deso@posteo.net>
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use std::borrow::Cow;
+use std::error::Error as StdError;
 use std::fmt::Display;
 use std::fmt::Formatter;
 use std::fmt::Result as FmtResult;
@@ -9,7 +11,6 @@ use std::ops::BitOr;
 use std::ops::Not;
 use std::str::FromStr;
 
-use anyhow::anyhow;
 use anyhow::Error;
 
 
@@ -17,11 +18,101 @@ type ParseResult<'i, Output> = Result<(&'i str, Output), &'i str>;
 type Var = String;
 
 
+/// The context in which parsing happens.
+///
+/// The context is passed to all parsers, which record in it the furthest
+/// point into the input at which they failed, along with descriptions
+/// of what was expected there instead.
+#[derive(Debug)]
+struct Context {
+  /// The length of the input remaining at the furthest point of
+  /// failure.
+  remaining: usize,
+  /// Descriptions of what was expected at said point.
+  expected: Vec<&'static str>,
+}
+
+impl Default for Context {
+  fn default() -> Self {
+    Self {
+      remaining: usize::MAX,
+      expected: Vec::new(),
+    }
+  }
+}
+
+impl Context {
+  /// Record that `expected` could not be found at the start of
+  /// `input`.
+  ///
+  /// Only failures at the furthest point into the input are retained,
+  /// as they are the ones most likely to reflect the user's intent.
+  fn expect(&mut self, input: &str, expected: &'static str) {
+    if input.len() < self.remaining {
+      self.remaining = input.len();
+      let () = self.expected.clear();
+    }
+    if input.len() == self.remaining && !self.expected.contains(&expected) {
+      let () = self.expected.push(expected);
+    }
+  }
+}
+
+
+/// An error describing why a formula could not be parsed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseError {
+  /// The byte offset into the formula at which parsing failed.
+  offset: usize,
+  /// The character found at `offset`, if any.
+  found: Option<char>,
+  /// Descriptions of the tokens that would have been accepted at
+  /// `offset`.
+  expected: Vec<&'static str>,
+}
+
+impl ParseError {
+  /// Retrieve the byte offset into the formula at which parsing
+  /// failed.
+  #[inline]
+  pub fn offset(&self) -> usize {
+    self.offset
+  }
+
+  /// Retrieve descriptions of the tokens that would have been accepted
+  /// at the point of failure.
+  #[cfg(test)]
+  #[inline]
+  pub fn expected(&self) -> &[&'static str] {
+    &self.expected
+  }
+}
+
+impl Display for ParseError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    match self.found {
+      Some(c) => write!(f, "unexpected `{c}`")?,
+      None => write!(f, "unexpected end of input")?,
+    }
+    write!(f, " at offset {}", self.offset)?;
+
+    match self.expected.as_slice() {
+      [] => Ok(()),
+      [expected] => write!(f, "; expected {expected}"),
+      [expected1, expected2] => write!(f, "; expected {expected1} or {expected2}"),
+      [expected @ .., last] => write!(f, "; expected {}, or {last}", expected.join(", ")),
+    }
+  }
+}
+
+impl StdError for ParseError {}
+
+
 /// A trait representing something that can parse data from a string.
 trait Parser<'i> {
   type Output: 'i;
 
-  fn parse(&self, input: &'i str) -> ParseResult<'i, Self::Output>;
+  fn parse(&self, input: &'i str, ctx: &mut Context) -> ParseResult<'i, Self::Output>;
 
   /// Combine two parsers, running both in succession.
   fn chain<P, O>(self, parser: P) -> impl Parser<'i, Output = (Self::Output, O)>
@@ -30,8 +121,8 @@ trait Parser<'i> {
     P: Parser<'i, Output = O>,
     O: 'i,
   {
-    move |input| match self.parse(input) {
-      Ok((next_input, result1)) => match parser.parse(next_input) {
+    move |input: &'i str, ctx: &mut Context| match self.parse(input, ctx) {
+      Ok((next_input, result1)) => match parser.parse(next_input, ctx) {
         Ok((final_input, result2)) => Ok((final_input, (result1, result2))),
         Err(_) => Err(input),
       },
@@ -44,10 +135,10 @@ trait Parser<'i> {
     Self: Sized,
     P: Parser<'i, Output = Self::Output>,
   {
-    move |input| {
-      self.parse(input).or_else(|err_input| {
+    move |input: &'i str, ctx: &mut Context| {
+      self.parse(input, ctx).or_else(|err_input| {
         debug_assert_eq!(input, err_input);
-        parser.parse(input)
+        parser.parse(input, ctx)
       })
     }
   }
@@ -59,8 +150,8 @@ trait Parser<'i> {
     F: Fn(Self::Output) -> P,
     O: 'i,
   {
-    move |input| match self.parse(input) {
-      Ok((next_input, result)) => match f(result).parse(next_input) {
+    move |input: &'i str, ctx: &mut Context| match self.parse(input, ctx) {
+      Ok((next_input, result)) => match f(result).parse(next_input, ctx) {
         Ok((final_input, result2)) => Ok((final_input, result2)),
         Err(_) => Err(input),
       },
@@ -76,9 +167,9 @@ trait Parser<'i> {
     F: Fn(Self::Output) -> O,
     O: 'i,
   {
-    move |input| {
+    move |input: &'i str, ctx: &mut Context| {
       self
-        .parse(input)
+        .parse(input, ctx)
         .map(|(next_input, result)| (next_input, map_fn(result)))
     }
   }
@@ -86,19 +177,41 @@ trait Parser<'i> {
 
 impl<'i, F, O> Parser<'i> for F
 where
-  F: Fn(&'i str) -> Result<(&'i str, O), &'i str>,
+  F: Fn(&'i str, &mut Context) -> Result<(&'i str, O), &'i str>,
   O: 'i,
 {
   type Output = O;
 
-  fn parse(&self, input: &'i str) -> ParseResult<'i, Self::Output> {
-    (self)(input)
+  fn parse(&self, input: &'i str, ctx: &mut Context) -> ParseResult<'i, Self::Output> {
+    (self)(input, ctx)
   }
 }
 
 
-/// Parse a variable from a string.
-fn parse_var(input: &str) -> ParseResult<'_, Var> {
+/// The character used for quoting variables and strings.
+const QUOTE: char = '"';
+/// The character used for escaping characters inside quotes.
+const ESCAPE: char = '\\';
+/// The operator checking whether a field contains a string.
+const CONTAINS: &str = "~";
+/// The prefix of a reference to another view.
+const VIEW_REF: &str = "@";
+
+/// The keyword representing a formula that is always true.
+const TRUE: &str = "true";
+/// The keyword representing a formula that is always false.
+const FALSE: &str = "false";
+/// The keyword checking whether a task has details.
+const HAS_DETAILS: &str = "has-details";
+/// All keywords, which cannot be used as unquoted variables.
+const KEYWORDS: [&str; 3] = [TRUE, FALSE, HAS_DETAILS];
+
+
+/// Parse an unquoted variable from a string.
+///
+/// Such a variable starts with an ASCII letter, followed by ASCII
+/// alphanumerics, `-`, or `_`.
+fn parse_plain_var<'i>(input: &'i str, ctx: &mut Context) -> ParseResult<'i, Var> {
   let mut end = 0;
   let mut chars = input.chars();
 
@@ -117,6 +230,7 @@ fn parse_var(input: &str) -> ParseResult<'_, Var> {
   }
 
   if end == 0 {
+    let () = ctx.expect(input, "tag");
     return Err(input)
   }
 
@@ -124,25 +238,260 @@ fn parse_var(input: &str) -> ParseResult<'_, Var> {
   Ok((rest, var.to_string()))
 }
 
+/// Parse a quoted string, e.g., `"some text"`.
+///
+/// Inside the quotes, `"` and `\` have to be escaped with a `\`.
+fn parse_string<'i>(input: &'i str, ctx: &mut Context) -> ParseResult<'i, String> {
+  let mut chars = input.char_indices();
+  if !matches!(chars.next(), Some((_, QUOTE))) {
+    let () = ctx.expect(input, "string");
+    return Err(input)
+  }
+
+  let mut string = String::new();
+  while let Some((idx, c)) = chars.next() {
+    match c {
+      QUOTE => return Ok((&input[idx + c.len_utf8()..], string)),
+      ESCAPE => match chars.next() {
+        Some((_, c @ (QUOTE | ESCAPE))) => string.push(c),
+        Some((idx, _c)) => {
+          let () = ctx.expect(&input[idx..], "`\"` or `\\` after `\\`");
+          return Err(input)
+        },
+        None => break,
+      },
+      c => string.push(c),
+    }
+  }
+
+  let () = ctx.expect(&input[input.len()..], "closing `\"`");
+  Err(input)
+}
+
+/// Parse a quoted variable, e.g., `"my tag"`, from a string.
+fn parse_quoted_var<'i>(input: &'i str, ctx: &mut Context) -> ParseResult<'i, Var> {
+  // Variables always start with an ASCII letter or a quote. Without the
+  // latter, there is no need to report an expected string, as we
+  // already report an expected variable.
+  if !input.starts_with(QUOTE) {
+    return Err(input)
+  }
+
+  match parse_string(input, ctx) {
+    Ok((_rest, var)) if var.is_empty() => Err(input),
+    result => result,
+  }
+}
+
+/// Parse a variable, quoted or not, from a string.
+fn parse_var<'i>(input: &'i str, ctx: &mut Context) -> ParseResult<'i, Var> {
+  parse_plain_var.or(parse_quoted_var).parse(input, ctx)
+}
+
+
+/// Check whether `name` is a keyword of the formula grammar.
+pub fn is_keyword(name: &str) -> bool {
+  KEYWORDS.contains(&name)
+}
+
+
+/// Check whether `name` is a valid variable name, i.e., one that can
+/// be used in a formula without quoting.
+///
+/// This is the grammar that all newly created tag names adhere to.
+pub fn is_valid_var(name: &str) -> bool {
+  let mut ctx = Context::default();
+  matches!(parse_plain_var(name, &mut ctx), Ok(("", _))) && !is_keyword(name)
+}
+
+
+/// Quote a string for it to be usable in a formula.
+fn quote_string(string: &str) -> String {
+  let mut quoted = String::with_capacity(string.len() + 2);
+  let () = quoted.push(QUOTE);
+  for c in string.chars() {
+    if matches!(c, QUOTE | ESCAPE) {
+      let () = quoted.push(ESCAPE);
+    }
+    let () = quoted.push(c);
+  }
+  let () = quoted.push(QUOTE);
+  quoted
+}
+
+
+/// Quote a variable name, if necessary, for it to be usable in a
+/// formula.
+pub fn quote_var(name: &str) -> Cow<'_, str> {
+  if is_valid_var(name) {
+    Cow::Borrowed(name)
+  } else {
+    Cow::Owned(quote_string(name))
+  }
+}
+
 
 /// Create a parser for the given string.
 fn match_str<'i>(s: &'static str) -> impl Parser<'i, Output = ()> {
-  move |input: &'i str| match input.get(0..s.len()) {
+  move |input: &'i str, _ctx: &mut Context| match input.get(0..s.len()) {
     Some(next) if next == s => Ok((&input[s.len()..], ())),
     _ => Err(input),
   }
 }
 
-fn parse_not(input: &str) -> ParseResult<'_, ()> {
-  match_str("!").parse(input)
+/// Create a parser for the given token, recording it as expected in
+/// case it could not be found.
+fn match_token<'i>(token: &'static str, expected: &'static str) -> impl Parser<'i, Output = ()> {
+  move |input: &'i str, ctx: &mut Context| {
+    match_str(token).parse(input, ctx).inspect_err(|input| {
+      let () = ctx.expect(input, expected);
+    })
+  }
 }
 
-fn parse_or(input: &str) -> ParseResult<'_, ()> {
-  match_str("|").parse(input)
+fn parse_not<'i>(input: &'i str, ctx: &mut Context) -> ParseResult<'i, ()> {
+  match_token("!", "`!`").parse(input, ctx)
 }
 
-fn parse_and(input: &str) -> ParseResult<'_, ()> {
-  match_str("&").parse(input)
+fn parse_or<'i>(input: &'i str, ctx: &mut Context) -> ParseResult<'i, ()> {
+  match_token("|", "`|`").parse(input, ctx)
+}
+
+fn parse_and<'i>(input: &'i str, ctx: &mut Context) -> ParseResult<'i, ()> {
+  match_token("&", "`&`").parse(input, ctx)
+}
+
+/// Parse a keyword, e.g., `true`.
+fn parse_keyword<'i>(input: &'i str, ctx: &mut Context) -> ParseResult<'i, Formula> {
+  let (rest, word) = parse_plain_var(input, ctx)?;
+  let formula = match word.as_str() {
+    TRUE => Formula::Const(true),
+    FALSE => Formula::Const(false),
+    HAS_DETAILS => Formula::HasDetails,
+    _ => return Err(input),
+  };
+  Ok((rest, formula))
+}
+
+/// Parse a reference to a view, e.g., `@work` or `@"my view"`.
+fn parse_view_ref<'i>(input: &'i str, ctx: &mut Context) -> ParseResult<'i, Var> {
+  match_token(VIEW_REF, "`@`")
+    .chain(parse_var)
+    .map(|((), name)| name)
+    .parse(input, ctx)
+}
+
+/// Parse a check whether a field contains a string, e.g.,
+/// `summary~"text"`.
+fn parse_contains<'i>(input: &'i str, ctx: &mut Context) -> ParseResult<'i, Formula> {
+  let (rest, word) = parse_plain_var(input, ctx)?;
+  let field = match word.as_str() {
+    SUMMARY => Field::Summary,
+    DETAILS => Field::Details,
+    _ => return Err(input),
+  };
+
+  parse_space0
+    .chain(match_token(CONTAINS, "`~`"))
+    .chain(parse_space0)
+    .chain(parse_string)
+    .map(move |((((), ()), ()), string)| Formula::Contains(field, string))
+    .parse(rest, ctx)
+    .map_err(|_rest| input)
+}
+
+
+/// A token of the textual representation of a formula.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Token<'s> {
+  /// A variable.
+  Var {
+    /// The name of the variable, with quotes removed.
+    name: Var,
+    /// The variable as it appears in the formula.
+    text: &'s str,
+  },
+  /// A reference to a view.
+  View {
+    /// The name of the view, with quotes removed.
+    name: Var,
+    /// The reference as it appears in the formula, including the
+    /// leading `@`.
+    text: &'s str,
+  },
+  /// A keyword, e.g., `true`.
+  Keyword(&'s str),
+  /// Anything but a variable, view reference, or keyword, e.g.,
+  /// operators, parentheses, and spaces.
+  Other(&'s str),
+}
+
+
+/// Split the textual representation of a formula into variables, view
+/// references, keywords, and the text in between them.
+pub fn tokenize(mut input: &str) -> Vec<Token<'_>> {
+  let mut tokens = Vec::new();
+  // We are not interested in failures here.
+  let ctx = &mut Context::default();
+
+  while !input.is_empty() {
+    let (token, rest) = if let Ok((rest, _formula)) = parse_contains(input, ctx) {
+      let (other, rest) = input.split_at(input.len() - rest.len());
+      (Token::Other(other), rest)
+    } else if let Ok((rest, _formula)) = parse_keyword(input, ctx) {
+      let (keyword, rest) = input.split_at(input.len() - rest.len());
+      (Token::Keyword(keyword), rest)
+    } else if let Ok((rest, name)) = parse_view_ref(input, ctx) {
+      let text = &input[..input.len() - rest.len()];
+      (Token::View { name, text }, rest)
+    } else if let Ok((rest, name)) = parse_var(input, ctx) {
+      let text = &input[..input.len() - rest.len()];
+      (Token::Var { name, text }, rest)
+    } else {
+      // Note that we always consume at least one character, to make
+      // progress on stray quotes.
+      let end = input
+        .char_indices()
+        .skip(1)
+        .find(|(idx, c)| {
+          c.is_ascii_alphabetic() || *c == QUOTE || input[*idx..].starts_with(VIEW_REF)
+        })
+        .map(|(idx, _c)| idx)
+        .unwrap_or(input.len());
+      let (other, rest) = input.split_at(end);
+      (Token::Other(other), rest)
+    };
+
+    let () = tokens.push(token);
+    input = rest;
+  }
+  tokens
+}
+
+
+/// The name of the summary field.
+const SUMMARY: &str = "summary";
+/// The name of the details field.
+const DETAILS: &str = "details";
+
+
+/// A textual field of a task.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Field {
+  /// The task's summary.
+  Summary,
+  /// The task's details.
+  Details,
+}
+
+impl Field {
+  /// Retrieve the name of the field as used in formulas.
+  fn name(&self) -> &'static str {
+    match self {
+      Self::Summary => SUMMARY,
+      Self::Details => DETAILS,
+    }
+  }
 }
 
 
@@ -150,12 +499,32 @@ fn parse_and(input: &str) -> ParseResult<'_, ()> {
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Formula {
   Var(Var),
+  /// A constant truth value.
+  Const(bool),
+  /// A check whether a field of a task contains a string, ignoring
+  /// case.
+  Contains(Field, String),
+  /// A check whether a task has details.
+  HasDetails,
+  /// A reference to another view, which is true for all tasks that
+  /// are part of said view.
+  View(Var),
   Not(Box<Formula>),
   And(Box<Formula>, Box<Formula>),
   Or(Box<Formula>, Box<Formula>),
 }
 
 impl Formula {
+  /// Retrieve the keyword this formula is represented by, if any.
+  pub fn keyword(&self) -> Option<&'static str> {
+    match self {
+      Self::Const(true) => Some(TRUE),
+      Self::Const(false) => Some(FALSE),
+      Self::HasDetails => Some(HAS_DETAILS),
+      _ => None,
+    }
+  }
+
   #[cfg(test)]
   pub fn var<S>(s: S) -> Self
   where
@@ -222,8 +591,13 @@ impl Not for Box<Formula> {
 impl FromStr for Formula {
   type Err = Error;
 
+  /// Parse a formula from a string.
+  ///
+  /// Parse failures are reported as a [`ParseError`], which can be
+  /// retrieved from the returned error by means of
+  /// [`Error::downcast_ref`].
   fn from_str(s: &str) -> Result<Self, Self::Err> {
-    parse_formula(s).map_err(|rest| anyhow!("failed to parse formula starting at `{rest}`"))
+    parse_formula(s).map_err(Error::from)
   }
 }
 
@@ -231,10 +605,17 @@ impl Display for Formula {
   fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
     fn print<const T: char>(formula: &Formula, fmt: &mut Formatter<'_>) -> FmtResult {
       match formula {
-        Formula::Var(var) => write!(fmt, "{var}")?,
+        Formula::Var(var) => write!(fmt, "{}", quote_var(var))?,
+        Formula::Const(true) => write!(fmt, "{TRUE}")?,
+        Formula::Const(false) => write!(fmt, "{FALSE}")?,
+        Formula::Contains(field, string) => {
+          write!(fmt, "{}{CONTAINS}{}", field.name(), quote_string(string))?
+        },
+        Formula::HasDetails => write!(fmt, "{HAS_DETAILS}")?,
+        Formula::View(name) => write!(fmt, "{VIEW_REF}{}", quote_var(name))?,
         Formula::Not(formula) => {
           write!(fmt, "!")?;
-          let group = !matches!(formula.as_ref(), Formula::Var(..) | Formula::Not(..));
+          let group = matches!(formula.as_ref(), Formula::And(..) | Formula::Or(..));
 
           if group {
             write!(fmt, "(")?;
@@ -286,10 +667,10 @@ where
   P: Parser<'i, Output = A>,
   Acc: Default + Extend<A> + 'i,
 {
-  move |mut input| {
+  move |mut input: &'i str, ctx: &mut Context| {
     let mut result = Acc::default();
 
-    while let Ok((next_input, next_item)) = parser.parse(input) {
+    while let Ok((next_input, next_item)) = parser.parse(input, ctx) {
       input = next_input;
       result.extend([next_item]);
     }
@@ -298,26 +679,31 @@ where
   }
 }
 
-fn parse_space0(input: &str) -> ParseResult<'_, ()> {
+fn parse_space0<'i>(input: &'i str, ctx: &mut Context) -> ParseResult<'i, ()> {
   // TODO: We could get more fancy by accepting arbitrary white spaces
   //       etc.
-  zero_or_more::<_, _, ()>(match_str(" ")).parse(input)
+  zero_or_more::<_, _, ()>(match_str(" ")).parse(input, ctx)
 }
 
-fn parse_unary(input: &str) -> ParseResult<'_, Formula> {
+fn parse_unary<'i>(input: &'i str, ctx: &mut Context) -> ParseResult<'i, Formula> {
   let spaces = parse_space0;
-  let var = parse_var.map(Formula::Var);
+  // Note that keywords and checks take precedence over variables with
+  // the same name.
+  let atom = parse_contains
+    .or(parse_keyword)
+    .or(parse_view_ref.map(Formula::View))
+    .or(parse_var.map(Formula::Var));
   let negation = parse_not.chain(parse_unary).map(|((), formula)| !formula);
-  let grouping = match_str("(")
+  let grouping = match_token("(", "`(`")
     .chain(parse_formula_impl::<'a'>)
-    .chain(match_str(")"))
+    .chain(match_token(")", "`)`"))
     .map(|(((), formula), ())| formula);
 
   spaces
-    .chain(var.or(negation).or(grouping))
+    .chain(atom.or(negation).or(grouping))
     .chain(spaces)
     .map(|(((), formula), ())| formula)
-    .parse(input)
+    .parse(input, ctx)
 }
 
 
@@ -336,11 +722,11 @@ where
 {
   type Output = O;
 
-  fn parse(&self, input: &'i str) -> ParseResult<'i, Self::Output> {
+  fn parse(&self, input: &'i str, ctx: &mut Context) -> ParseResult<'i, Self::Output> {
     match self {
-      Self::A(a) => a.parse(input),
-      Self::B(b) => b.parse(input),
-      Self::C(c) => c.parse(input),
+      Self::A(a) => a.parse(input, ctx),
+      Self::B(b) => b.parse(input, ctx),
+      Self::C(c) => c.parse(input, ctx),
     }
   }
 }
@@ -352,7 +738,10 @@ where
 /// - 'c': conjunctions but no disjunctions
 /// - 'd': disjunctions but no conjunctions
 /// - anything else: no restriction
-fn parse_formula_impl<const T: char>(input: &str) -> ParseResult<'_, Formula> {
+fn parse_formula_impl<'i, const T: char>(
+  input: &'i str,
+  ctx: &mut Context,
+) -> ParseResult<'i, Formula> {
   parse_unary
     .and_then(|formula1| {
       // Attempt to parse the remainder of the input as a conjunction or
@@ -374,9 +763,9 @@ fn parse_formula_impl<const T: char>(input: &str) -> ParseResult<'_, Formula> {
         'd' => Either::B(disjunction),
         _ => Either::C(conjunction.or(disjunction)),
       }
-      .or(move |input| Ok((input, formula1.clone())))
+      .or(move |input: &'i str, _ctx: &mut Context| Ok((input, formula1.clone())))
     })
-    .parse(input)
+    .parse(input, ctx)
 }
 
 /// Parse a formula.
@@ -384,15 +773,27 @@ fn parse_formula_impl<const T: char>(input: &str) -> ParseResult<'_, Formula> {
 /// This is not a "regular" parser function in that it does not
 /// implement the [`Parser`] trait. That's because it does not return
 /// any unparsed input for subsequent parsers to consume, but ensures
-/// that everything has been parsed fully or, if not, report the
-/// unparsed remainder as an `Err`.
-fn parse_formula(input: &str) -> Result<Formula, &str> {
-  let (rest, formula) = parse_formula_impl::<'a'>(input)?;
-  if !rest.is_empty() {
-    Err(rest)
-  } else {
-    Ok(formula)
+/// that everything has been parsed fully or, if not, reports the
+/// furthest point into the input that could be reached along with
+/// what was expected there.
+fn parse_formula(input: &str) -> Result<Formula, ParseError> {
+  let mut ctx = Context::default();
+
+  match parse_formula_impl::<'a'>(input, &mut ctx) {
+    Ok(("", formula)) => return Ok(formula),
+    Ok((rest, _formula)) => ctx.expect(rest, "end of input"),
+    Err(_input) => (),
   }
+
+  // Any failure to parse should have been recorded along the way.
+  debug_assert!(ctx.remaining <= input.len());
+  let offset = input.len() - ctx.remaining.min(input.len());
+  let error = ParseError {
+    offset,
+    found: input[offset..].chars().next(),
+    expected: ctx.expected,
+  };
+  Err(error)
 }
 
 
@@ -410,113 +811,248 @@ mod tests {
   /// Check that we can parse a single number.
   #[test]
   fn variable_parsing() {
-    let (rest, var) = parse_var("a").unwrap();
+    let ctx = &mut Context::default();
+    let (rest, var) = parse_var("a", ctx).unwrap();
     assert_eq!(rest, "");
     assert_eq!(var, "a");
 
-    let (rest, var) = parse_var("abc").unwrap();
+    let (rest, var) = parse_var("abc", ctx).unwrap();
     assert_eq!(rest, "");
     assert_eq!(var, "abc");
 
-    let (rest, var) = parse_var("a12345").unwrap();
+    let (rest, var) = parse_var("a12345", ctx).unwrap();
     assert_eq!(rest, "");
     assert_eq!(var, "a12345");
 
-    let (rest, var) = parse_var("a12-").unwrap();
+    let (rest, var) = parse_var("a12-", ctx).unwrap();
     assert_eq!(rest, "");
     assert_eq!(var, "a12-");
 
-    let (rest, var) = parse_var("super-test").unwrap();
+    let (rest, var) = parse_var("super-test", ctx).unwrap();
     assert_eq!(rest, "");
     assert_eq!(var, "super-test");
 
-    let (rest, var) = parse_var("X_X").unwrap();
+    let (rest, var) = parse_var("X_X", ctx).unwrap();
     assert_eq!(rest, "");
     assert_eq!(var, "X_X");
 
-    let (rest, var) = parse_var("a#").unwrap();
+    let (rest, var) = parse_var("a#", ctx).unwrap();
     assert_eq!(rest, "#");
     assert_eq!(var, "a");
 
-    let err = parse_var("").unwrap_err();
+    let err = parse_var("", ctx).unwrap_err();
     assert_eq!(err, "");
 
-    let err = parse_var("1").unwrap_err();
+    let err = parse_var("1", ctx).unwrap_err();
     assert_eq!(err, "1");
   }
 
+  /// Check that we can parse quoted variables.
+  #[test]
+  fn quoted_variable_parsing() {
+    let ctx = &mut Context::default();
+    let (rest, var) = parse_var(r#""my tag""#, ctx).unwrap();
+    assert_eq!(rest, "");
+    assert_eq!(var, "my tag");
+
+    let (rest, var) = parse_var(r#""a|b" & c"#, ctx).unwrap();
+    assert_eq!(rest, " & c");
+    assert_eq!(var, "a|b");
+
+    let (rest, var) = parse_var(r#""\"\\""#, ctx).unwrap();
+    assert_eq!(rest, "");
+    assert_eq!(var, r#""\"#);
+
+    let (rest, var) = parse_var(r#""ü""#, ctx).unwrap();
+    assert_eq!(rest, "");
+    assert_eq!(var, "ü");
+
+    for input in [r#""""#, r#""abc"#, r#""a\b""#, r#""a\"#] {
+      let err = parse_var(input, ctx).unwrap_err();
+      assert_eq!(err, input);
+    }
+  }
+
+  /// Check that we can parse keywords and checks on fields of tasks.
+  #[test]
+  fn predicate_parsing() {
+    let formula = parse_formula(r#"summary~"deploy""#).unwrap();
+    let expected = Formula::Contains(Field::Summary, "deploy".to_string());
+    assert_eq!(formula, expected);
+
+    let formula = parse_formula(r#"details ~ "a \"b\"""#).unwrap();
+    let expected = Formula::Contains(Field::Details, r#"a "b""#.to_string());
+    assert_eq!(formula, expected);
+
+    let formula = parse_formula(r#"!has-details & (true | summary~"")"#).unwrap();
+    let expected = !Formula::HasDetails
+      & (Formula::Const(true) | Formula::Contains(Field::Summary, String::new()));
+    assert_eq!(formula, expected);
+
+    let formula = parse_formula("false").unwrap();
+    assert_eq!(formula, Formula::Const(false));
+
+    // Keywords can still be used as variables when quoted...
+    let formula = parse_formula(r#""true" & "has-details""#).unwrap();
+    let expected = Formula::var("true") & Formula::var("has-details");
+    assert_eq!(formula, expected);
+
+    // ... and field names are only special when followed by `~`.
+    let formula = parse_formula("summary | details | truex").unwrap();
+    let expected = Formula::var("summary") | (Formula::var("details") | Formula::var("truex"));
+    assert_eq!(formula, expected);
+
+    for input in [r#"tag~"x""#, "summary~x", r#"summary~"x"#, r#"summary "x""#] {
+      let _err = parse_formula(input).unwrap_err();
+    }
+  }
+
+  /// Check that we can parse references to views.
+  #[test]
+  fn view_reference_parsing() {
+    let formula = parse_formula("@work & urgent").unwrap();
+    let expected = Formula::View("work".to_string()) & Formula::var("urgent");
+    assert_eq!(formula, expected);
+
+    let formula = parse_formula(r#"!@"my view" | @true"#).unwrap();
+    let expected = !Formula::View("my view".to_string()) | Formula::View("true".to_string());
+    assert_eq!(formula, expected);
+
+    let err = parse_formula("@ work").unwrap_err();
+    assert_eq!(err.offset(), 1);
+    assert_eq!(err.expected(), ["tag"]);
+  }
+
+  /// Check that we correctly classify valid variable names.
+  #[test]
+  fn variable_name_validation() {
+    for name in ["a", "abc", "a12-", "super-test", "X_X"] {
+      assert!(is_valid_var(name), "{name}");
+    }
+
+    for name in [
+      "",
+      "1",
+      "-a",
+      "a b",
+      "a#",
+      "ü",
+      "a|b",
+      r#""a""#,
+      "true",
+      "false",
+      "has-details",
+    ] {
+      assert!(!is_valid_var(name), "{name}");
+    }
+  }
+
+  /// Check that quoted variable names can be parsed back.
+  #[test]
+  fn variable_quoting() {
+    let ctx = &mut Context::default();
+    assert_eq!(quote_var("abc"), "abc");
+    assert_eq!(quote_var("my tag"), r#""my tag""#);
+    assert_eq!(quote_var("true"), r#""true""#);
+    assert_eq!(quote_var(r#"a"b\c"#), r#""a\"b\\c""#);
+
+    let alphabet = ['a', '1', ' ', '"', '\\', '|', '&', '!', '(', ')', '-', 'ü'];
+    for c1 in alphabet {
+      for c2 in alphabet {
+        for c3 in alphabet {
+          let name = String::from_iter([c1, c2, c3]);
+          let quoted = quote_var(&name);
+          let (rest, var) = parse_var(&quoted, ctx).unwrap();
+          assert_eq!(rest, "");
+          assert_eq!(var, name);
+
+          let formula = parse_formula(&format!("!{quoted} & ({quoted} | a)")).unwrap();
+          let expected = !Formula::var(&name) & (Formula::var(&name) | Formula::var("a"));
+          assert_eq!(formula, expected);
+          assert_eq!(parse_formula(&formula.to_string()).unwrap(), formula);
+        }
+      }
+    }
+  }
+
   /// Test that we can parse a NOT ("!").
   #[test]
   fn not_parsing() {
-    let err = parse_not("f").unwrap_err();
+    let ctx = &mut Context::default();
+    let err = parse_not("f", ctx).unwrap_err();
     assert_eq!(err, "f");
 
-    let (rest, ()) = parse_not("!").unwrap();
+    let (rest, ()) = parse_not("!", ctx).unwrap();
     assert_eq!(rest, "");
   }
 
   /// Test that we can parse an OR ("||").
   #[test]
   fn or_parsing() {
-    let err = parse_or("foobar").unwrap_err();
+    let ctx = &mut Context::default();
+    let err = parse_or("foobar", ctx).unwrap_err();
     assert_eq!(err, "foobar");
 
-    let err = parse_or("x").unwrap_err();
+    let err = parse_or("x", ctx).unwrap_err();
     assert_eq!(err, "x");
 
-    let (rest, ()) = parse_or("|").unwrap();
+    let (rest, ()) = parse_or("|", ctx).unwrap();
     assert_eq!(rest, "");
   }
 
   /// Test that we can parse an AND ("&&").
   #[test]
   fn and_parsing() {
-    let err = parse_and("foobar").unwrap_err();
+    let ctx = &mut Context::default();
+    let err = parse_and("foobar", ctx).unwrap_err();
     assert_eq!(err, "foobar");
 
-    let err = parse_and("x").unwrap_err();
+    let err = parse_and("x", ctx).unwrap_err();
     assert_eq!(err, "x");
 
-    let (rest, ()) = parse_and("&").unwrap();
+    let (rest, ()) = parse_and("&", ctx).unwrap();
     assert_eq!(rest, "");
   }
 
   /// Make sure that we can chain parsers as expected.
   #[test]
   fn parser_chaining() {
+    let ctx = &mut Context::default();
     let parser = parse_or.chain(parse_and);
-    let (rest, ((), ())) = parser.parse("|&").unwrap();
+    let (rest, ((), ())) = parser.parse("|&", ctx).unwrap();
     assert_eq!(rest, "");
 
-    let (rest, ((), ())) = parser.parse("|&x").unwrap();
+    let (rest, ((), ())) = parser.parse("|&x", ctx).unwrap();
     assert_eq!(rest, "x");
 
-    let err = parser.parse("|x").unwrap_err();
+    let err = parser.parse("|x", ctx).unwrap_err();
     assert_eq!(err, "|x");
 
-    let err = parser.parse("x").unwrap_err();
+    let err = parser.parse("x", ctx).unwrap_err();
     assert_eq!(err, "x");
   }
 
   /// Make sure that we can chain parsers as expected.
   #[test]
   fn parser_anding() {
+    let ctx = &mut Context::default();
     let parser = parse_var.and_then(|_var| parse_or);
-    let (rest, ()) = parser.parse("hello|").unwrap();
+    let (rest, ()) = parser.parse("hello|", ctx).unwrap();
     assert_eq!(rest, "");
 
-    let err = parser.parse("hello").unwrap_err();
+    let err = parser.parse("hello", ctx).unwrap_err();
     assert_eq!(err, "hello");
   }
 
   /// Check that we can map the output of parsers.
   #[test]
   fn output_mapping() {
+    let ctx = &mut Context::default();
     let input = "hihi&";
     let (rest, var) = parse_var
       .chain(parse_and)
       .map(|(l, ())| l)
-      .parse(input)
+      .parse(input, ctx)
       .unwrap();
     assert_eq!(var, "hihi");
     assert_eq!(rest, "");
@@ -525,7 +1061,7 @@ mod tests {
     let (rest, var) = parse_or
       .chain(parse_var)
       .map(|((), r)| r)
-      .parse(input)
+      .parse(input, ctx)
       .unwrap();
     assert_eq!(var, "hoho");
     assert_eq!(rest, "");
@@ -534,16 +1070,17 @@ mod tests {
   /// Make sure that "OR"ing of parsers works as it should.
   #[test]
   fn parser_oring() {
+    let ctx = &mut Context::default();
     let input = "|";
-    let (rest, ()) = parse_or.or(parse_and).parse(input).unwrap();
+    let (rest, ()) = parse_or.or(parse_and).parse(input, ctx).unwrap();
     assert_eq!(rest, "");
 
     let input = "&";
-    let (rest, ()) = parse_or.or(parse_and).parse(input).unwrap();
+    let (rest, ()) = parse_or.or(parse_and).parse(input, ctx).unwrap();
     assert_eq!(rest, "");
 
     let input = "x";
-    let err = parse_or.or(parse_and).parse(input).unwrap_err();
+    let err = parse_or.or(parse_and).parse(input, ctx).unwrap_err();
     assert_eq!(err, "x");
   }
 
@@ -560,19 +1097,21 @@ mod tests {
     // "AND", we don't allow intermixing at all and require explicit
     // grouping instead.
     let err = parse_formula("a | b & c").unwrap_err();
-    assert_eq!(err, "& c");
+    assert_eq!(err.offset(), 6);
+    assert_eq!(err.expected(), ["`|`", "end of input"]);
 
     let err = parse_formula("a & b | c").unwrap_err();
-    assert_eq!(err, "| c");
+    assert_eq!(err.offset(), 6);
+    assert_eq!(err.expected(), ["`&`", "end of input"]);
 
     let err = parse_formula("a & b | !c").unwrap_err();
-    assert_eq!(err, "| !c");
+    assert_eq!(err.offset(), 6);
 
     let err = parse_formula("a & !b | c").unwrap_err();
-    assert_eq!(err, "| c");
+    assert_eq!(err.offset(), 7);
 
     let err = parse_formula("!a & b | c").unwrap_err();
-    assert_eq!(err, "| c");
+    assert_eq!(err.offset(), 7);
   }
 
   /// Check that various formulas can be parsed successfully.
@@ -616,7 +1155,7 @@ mod tests {
     assert_eq!(formula, expected);
 
     let err = parse_formula("123").unwrap_err();
-    assert_eq!(err, "123");
+    assert_eq!(err.offset(), 0);
   }
 
   /// Check that various combinations of white spaces in formulas don't
@@ -672,13 +1211,110 @@ mod tests {
   fn formula_parsing_error() {
     // Trailing negation symbol.
     let err = parse_formula("!!a!").unwrap_err();
-    assert_eq!(err, "!");
+    assert_eq!(err.offset(), 3);
+    assert_eq!(err.expected(), ["`&`", "`|`", "end of input"]);
 
     let err = parse_formula("(a & b").unwrap_err();
-    assert_eq!(err, "(a & b");
+    assert_eq!(err.offset(), 6);
+    assert_eq!(err.expected(), ["`&`", "`)`"]);
+
+    let err = parse_formula("a && b").unwrap_err();
+    assert_eq!(err.offset(), 3);
+    assert_eq!(err.expected(), ["tag", "`@`", "`!`", "`(`"]);
+
+    let err = parse_formula("").unwrap_err();
+    assert_eq!(err.offset(), 0);
+    assert_eq!(err.expected(), ["tag", "`@`", "`!`", "`(`"]);
 
+    let err = parse_formula("summary ~ x").unwrap_err();
+    assert_eq!(err.offset(), 10);
+    assert_eq!(err.expected(), ["string"]);
+
+    let err = parse_formula(r#"a | details~"x"#).unwrap_err();
+    assert_eq!(err.offset(), 14);
+    assert_eq!(err.expected(), ["closing `\"`"]);
+
+    let err = parse_formula(r#""a\b""#).unwrap_err();
+    assert_eq!(err.offset(), 3);
+    assert_eq!(err.expected(), [r#"`"` or `\` after `\`"#]);
+  }
+
+  /// Check that parse errors are formatted in a human readable way.
+  #[test]
+  fn formula_parsing_error_display() {
     let err = parse_formula("a && b").unwrap_err();
-    assert_eq!(err, "&& b");
+    assert_eq!(
+      err.to_string(),
+      "unexpected `&` at offset 3; expected tag, `@`, `!`, or `(`"
+    );
+
+    let err = parse_formula("(a & b").unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      "unexpected end of input at offset 6; expected `&` or `)`"
+    );
+
+    let err = parse_formula("summary~x").unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      "unexpected `x` at offset 8; expected string"
+    );
+
+    let err = Formula::from_str("ü").unwrap_err();
+    let err = err.downcast_ref::<ParseError>().unwrap();
+    assert_eq!(err.offset(), 0);
+  }
+
+  /// Check that we can split a formula into variables and everything
+  /// else.
+  #[test]
+  fn formula_tokenization() {
+    assert_eq!(tokenize(""), Vec::new());
+    fn var<'s>(name: &str, text: &'s str) -> Token<'s> {
+      Token::Var {
+        name: name.to_string(),
+        text,
+      }
+    }
+
+    assert_eq!(tokenize("a"), vec![var("a", "a")]);
+    assert_eq!(
+      tokenize(r#"!(tag-1 |"b 2")& "c" ""#),
+      vec![
+        Token::Other("!("),
+        var("tag-1", "tag-1"),
+        Token::Other(" |"),
+        var("b 2", r#""b 2""#),
+        Token::Other(")& "),
+        var("c", r#""c""#),
+        Token::Other(" "),
+        Token::Other("\""),
+      ]
+    );
+    assert_eq!(
+      tokenize(r#"summary ~ "a b"& true|true-ish"#),
+      vec![
+        Token::Other(r#"summary ~ "a b""#),
+        Token::Other("& "),
+        Token::Keyword("true"),
+        Token::Other("|"),
+        var("true-ish", "true-ish"),
+      ]
+    );
+    assert_eq!(
+      tokenize(r#"@work&!@"a b""#),
+      vec![
+        Token::View {
+          name: "work".to_string(),
+          text: "@work",
+        },
+        Token::Other("&!"),
+        Token::View {
+          name: "a b".to_string(),
+          text: r#"@"a b""#,
+        },
+      ]
+    );
   }
 
   /// Make sure that our formula formatting works as expected.
@@ -700,6 +1336,10 @@ mod tests {
     test("a & !b & !c");
     test("!(!xy & g) & h");
     test("!!(a | b)");
+    test("true | !false");
+    test(r#"summary~"a \\ \"b\"" & !has-details"#);
+    test(r#"!details~"http" | "true""#);
+    test(r#"@work & !@"my view""#);
   }
 
   /// Benchmark the parsing of a formula.
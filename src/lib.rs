@@ -13,7 +13,9 @@ mod log;
 
 mod args;
 mod cap;
+mod cli;
 mod colors;
+mod date;
 mod db;
 mod formula;
 mod id;
@@ -23,13 +25,17 @@ mod position;
 mod resize;
 mod ser;
 mod state;
+mod sync;
 mod tags;
 mod tasks;
 #[cfg(any(test, feature = "test"))]
 pub mod test;
 mod text;
+mod transaction;
 mod ui;
 mod view;
+#[cfg(target_os = "linux")]
+mod watch;
 
 pub use crate::cap::DirCap;
 pub use crate::paths::Paths;
@@ -58,7 +64,6 @@ use anyhow::Context as _;
 use anyhow::Result;
 
 use clap::error::ErrorKind as ClapError;
-use clap::Parser as _;
 
 #[cfg(feature = "coredump")]
 use coredump::register_panic_handler;
@@ -71,16 +76,23 @@ use termion::screen::IntoAlternateScreen as _;
 
 use tokio::runtime::Builder;
 
+use gui::MutCap as _;
 use gui::Ui;
 
 use crate::args::Args;
+use crate::args::Command;
 use crate::resize::receive_window_resizes;
+#[cfg(target_os = "linux")]
+use crate::state::TASKS_META_DIR;
 use crate::ui::Event as UiEvent;
 use crate::ui::Ids;
+use crate::ui::InOut;
 use crate::ui::Message;
 use crate::ui::Renderer as TermUiRenderer;
 use crate::ui::Ui as TermUi;
 use crate::ui::UiData as TermUiData;
+#[cfg(target_os = "linux")]
+use crate::watch::receive_modifications;
 
 
 /// The line end character used internally by the program.
@@ -102,6 +114,11 @@ pub enum Event {
   Key(Key, Vec<u8>),
   /// The window has been resized.
   Resize,
+  /// Tasks or the UI configuration were modified by another program.
+  Modified,
+  /// Watching for modifications made by other programs failed, for the
+  /// provided reason. No further modifications are reported.
+  WatchFailed(String),
 }
 
 
@@ -151,6 +168,10 @@ async fn run_loop<W>(
 where
   W: Write,
 {
+  // Whether tasks or configuration were modified by another program
+  // and we have yet to reload them.
+  let mut modified = false;
+
   'handler: loop {
     let mut to_render = ToRender::None;
     // We want to read keys in batches in order to avoid unnecessary
@@ -181,6 +202,28 @@ where
           }
         },
         Event::Resize => to_render = ToRender::All,
+        Event::Modified => modified = true,
+        Event::WatchFailed(error) => {
+          let root = ui.root_id();
+          let message = Message::SetInOut(InOut::Error(error));
+          if let Some(Message::Updated(ids)) = ui.send(root, message).await {
+            to_render = to_render.merge_with(ids);
+          }
+        },
+      }
+    }
+
+    if modified {
+      // Reloading may have to be deferred, e.g., while the user is
+      // editing a task. We will try again after handling the next batch
+      // of events then.
+      let mut message = Message::ReloadModified(false);
+      let root = ui.root_id();
+      if let Some(Message::Updated(ids)) = ui.call(root, &mut message).await {
+        to_render = to_render.merge_with(ids);
+      }
+      if let Message::ReloadModified(done) = message {
+        modified = !done;
       }
     }
 
@@ -205,12 +248,22 @@ where
   R: Read + Send + 'static,
   W: Write + AsFd,
 {
+  // Capabilities are acquired before loading anything, as doing so
+  // recovers from a previously interrupted save.
+  let ui_config_dir_cap = DirCap::for_dir(paths.ui_config_dir().to_path_buf()).await?;
+  let ui_config_file = paths.ui_config_file().to_os_string();
+
+  let ui_state_dir_cap = DirCap::for_dir(paths.ui_state_dir().to_path_buf()).await?;
+  let ui_state_file = paths.ui_state_file().to_os_string();
+
+  let tasks_root_cap = DirCap::for_dir(paths.tasks_dir()).await?;
+
   let task_state = TaskState::load(&paths.tasks_dir())
     .await
     .context("failed to load task state")?;
-  let ui_config_file = paths.ui_config_dir().join(paths.ui_config_file());
-  let ui_state_file = paths.ui_state_dir().join(paths.ui_state_file());
-  let ui_config = UiConfig::load(&ui_config_file, &task_state)
+  let ui_config_path = paths.ui_config_dir().join(paths.ui_config_file());
+  let ui_state_path = paths.ui_state_dir().join(paths.ui_state_file());
+  let ui_config = UiConfig::load(&ui_config_path, &task_state)
     .await
     .context("failed to load UI configuration")?;
   let UiConfig {
@@ -219,7 +272,7 @@ where
     views,
   } = ui_config;
 
-  let ui_state = UiState::load(&ui_state_file)
+  let ui_state = UiState::load(&ui_state_path)
     .await
     .context("failed to load UI state")?;
   let screen = out
@@ -230,14 +283,6 @@ where
   let mut renderer =
     TermUiRenderer::new(screen, colors).context("failed to instantiate terminal based renderer")?;
 
-  let ui_config_dir_cap = DirCap::for_dir(paths.ui_config_dir().to_path_buf()).await?;
-  let ui_config_file = paths.ui_config_file().to_os_string();
-
-  let ui_state_dir_cap = DirCap::for_dir(paths.ui_state_dir().to_path_buf()).await?;
-  let ui_state_file = paths.ui_state_file().to_os_string();
-
-  let tasks_root_cap = DirCap::for_dir(paths.tasks_dir()).await?;
-
   let (ui, _) = Ui::new(
     || {
       Box::new(TermUiData::new(
@@ -255,6 +300,22 @@ where
   let (send_event, recv_event) = channel();
   receive_window_resizes(send_event.clone())
     .context("failed to instantiate infrastructure for handling window resize events")?;
+  #[cfg(target_os = "linux")]
+  {
+    let tasks_dir = paths.tasks_dir();
+    let tasks_meta_dir = tasks_dir.join(TASKS_META_DIR);
+    let dirs = [
+      (tasks_dir.as_path(), None),
+      (tasks_meta_dir.as_path(), None),
+      (paths.ui_config_dir(), Some(paths.ui_config_file())),
+    ];
+    if let Err(err) = receive_modifications(&dirs, send_event.clone()) {
+      // Watching for modifications is merely a convenience and we
+      // continue without it.
+      let error = format!("failed to watch tasks and configuration for modifications: {err}");
+      let _result = send_event.send(Ok(Event::WatchFailed(error)));
+    }
+  }
   receive_keys(in_, send_event);
 
   // Initially we need to trigger a render in order to have the most
@@ -328,9 +389,34 @@ fn run_now(paths: Paths) -> Result<()> {
   rt.block_on(future)
 }
 
+/// Synchronize tasks with the configured CalDAV server and report the
+/// outcome.
+fn run_sync(paths: Paths) -> Result<()> {
+  let rt = Builder::new_current_thread()
+    .build()
+    .context("failed to instantiate async runtime")?;
+
+  let report = rt
+    .block_on(sync::sync(&paths))
+    .context("failed to synchronize tasks")?;
+  println!("{report}");
+  Ok(())
+}
+
+/// Run a non-interactive command on the program's tasks.
+fn run_command(command: Command, paths: Paths) -> Result<()> {
+  let rt = Builder::new_current_thread()
+    .build()
+    .context("failed to instantiate async runtime")?;
+
+  let mut stdout = stdout().lock();
+  let future = cli::run(command, &paths, &mut stdout);
+  rt.block_on(future)
+}
+
 /// Parse the arguments and run the program.
 fn run_with_args() -> Result<()> {
-  let args = match Args::try_parse_from(args_os()) {
+  let args = match Args::try_parse_args(args_os()) {
     Ok(args) => args,
     Err(err) => match err.kind() {
       ClapError::DisplayHelp | ClapError::DisplayVersion => {
@@ -342,7 +428,13 @@ fn run_with_args() -> Result<()> {
   };
 
   let paths = Paths::new(args.config_dir)?;
-  with_lockfile(&paths.lock_file(), args.force, || run_now(paths))
+  if args.sync {
+    with_lockfile(&paths.lock_file(), args.force, || run_sync(paths))
+  } else if let Some(command) = args.command {
+    with_lockfile(&paths.lock_file(), args.force, || run_command(command, paths))
+  } else {
+    with_lockfile(&paths.lock_file(), args.force, || run_now(paths))
+  }
 }
 
 /// Run the program.
@@ -184,6 +184,15 @@ impl Paths {
     OsStr::new("notnow.json")
   }
 
+  /// Retrieve the file name of the CalDAV synchronization
+  /// configuration.
+  ///
+  /// The file is located in the directory returned by
+  /// [`ui_config_dir`][Self::ui_config_dir].
+  pub fn sync_config_file(&self) -> &OsStr {
+    OsStr::new("sync.json")
+  }
+
   /// Retrieve the path to the program's task directory.
   pub fn tasks_dir(&self) -> PathBuf {
     self.ui_config_dir().join("tasks")
@@ -199,6 +208,14 @@ impl Paths {
     OsStr::new("ui-state.json")
   }
 
+  /// Retrieve the file name of the CalDAV synchronization state.
+  ///
+  /// The file is located in the directory returned by
+  /// [`ui_state_dir`][Self::ui_state_dir].
+  pub fn sync_state_file(&self) -> &OsStr {
+    OsStr::new("sync-state.json")
+  }
+
   /// Retrieve the path to the program's lock file.
   pub(crate) fn lock_file(&self) -> PathBuf {
     self.state_dir.join("notnow.lock")
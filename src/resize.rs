@@ -81,7 +81,7 @@ pub fn receive_window_resizes(send_event: Sender<Result<Event>>) -> Result<()> {
   let mut fds = [0, 0];
   unsafe {
     check(pipe(fds.as_mut_ptr()), -1)?;
-    check(signal(SIGWINCH, handler as size_t), SIG_ERR)?;
+    check(signal(SIGWINCH, handler as *const () as size_t), SIG_ERR)?;
     WRITE_FD = fds[1]
   }
   let read_fd = fds[0];
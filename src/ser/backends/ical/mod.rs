@@ -1,6 +1,7 @@
 // Copyright (C) 2022-2025 Daniel Mueller <deso@posteo.net>
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+mod state;
 mod task;
 mod tasks_meta;
 mod util;
@@ -16,6 +17,9 @@ use anyhow::Result;
 
 use super::Backend;
 
+pub(crate) use task::categories_to_tags;
+pub(crate) use task::tags_to_categories;
+
 
 /// A wrapper around a boxed error that allows us to use it in
 /// conjunction with `anyhow`.
@@ -0,0 +1,257 @@
+// Copyright (C) 2025 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::str::FromStr as _;
+
+use anyhow::Context as _;
+use anyhow::Error;
+use anyhow::Result;
+
+use icalendar::Calendar;
+
+use crate::ser::state::TaskState;
+use crate::ser::tags::Templates;
+use crate::ser::tasks::Task;
+use crate::ser::tasks::Tasks;
+use crate::ser::tasks::TasksMeta;
+
+use super::task::categories_to_tags;
+use super::task::tags_to_categories;
+use super::util::content_lines;
+use super::util::scan_opaque;
+use super::util::split_property;
+use super::util::split_todos;
+use super::SerICal;
+
+
+/// Retrieve the ID of the time zone defined by the given calendar
+/// component, if it is a time zone definition.
+fn timezone_id(component: &str) -> Option<String> {
+  let mut lines = content_lines(component).into_iter();
+  let begin = lines.next()?;
+  if !split_property(&begin).1.eq_ignore_ascii_case("VTIMEZONE") {
+    return None
+  }
+
+  lines.find_map(|line| {
+    let (name, value) = split_property(&line);
+    name
+      .eq_ignore_ascii_case("TZID")
+      .then(|| value.trim_end().to_string())
+  })
+}
+
+
+/// The iCalendar representation of a `TaskState` is a single calendar
+/// containing one TODO per task, as understood by other task managers.
+/// Tags are represented as categories, by name.
+impl SerICal for TaskState {
+  fn to_ical_string(&self) -> String {
+    // Calendar level components of tasks, such as time zone
+    // definitions, are shared by all of them.
+    let mut components = Vec::new();
+    let () = self
+      .tasks
+      .0
+      .iter()
+      .flat_map(|task| task.opaque.calendar_components.iter())
+      .for_each(|component| {
+        if !components.contains(component) {
+          let () = components.push(component.clone());
+        }
+      });
+
+    let todos = self
+      .tasks
+      .0
+      .iter()
+      .map(|task| {
+        let task = tags_to_categories(task, &self.tasks_meta.templates);
+        split_todos(&task.to_ical_string()).concat()
+      })
+      .collect::<String>();
+
+    let mut string = Calendar::new().to_string();
+    // SANITY: A rendered calendar always ends with the calendar's
+    //         `END` line.
+    let idx = string.rfind("END:VCALENDAR").unwrap();
+    let () = string.insert_str(idx, &todos);
+    let () = string.insert_str(idx, &components.concat());
+    string
+  }
+
+  fn from_ical_string(data: &str) -> Result<Self, Error> {
+    // Make sure that we are dealing with a valid calendar to begin
+    // with. Each TODO is converted on its own, though, so that we can
+    // preserve data not interpreted by us.
+    let _calendar = Calendar::from_str(data).map_err(Error::msg)?;
+
+    let timezones = scan_opaque(data, &[])
+      .calendar_components
+      .into_iter()
+      .filter_map(|component| Some((timezone_id(&component)?, component)))
+      .collect::<Vec<_>>();
+
+    let mut templates = Templates::default();
+    let tasks = split_todos(data)
+      .into_iter()
+      .enumerate()
+      .map(|(idx, todo)| {
+        let data = format!("BEGIN:VCALENDAR\r\n{todo}END:VCALENDAR\r\n");
+        let mut task = Task::from_ical_string(&data)
+          .with_context(|| format!("failed to convert TODO #{} into task", idx + 1))?;
+        // Tag IDs are meaningless outside of our own task directory.
+        task.tags = Vec::new();
+        // Keep the definitions of time zones the TODO refers to.
+        task.opaque.calendar_components = timezones
+          .iter()
+          .filter(|(tzid, _)| todo.contains(&format!("TZID={tzid}")))
+          .map(|(_, component)| component.clone())
+          .collect();
+        let () = categories_to_tags(&mut task, &mut templates)?;
+        Ok(task)
+      })
+      .collect::<Result<Vec<_>>>()?;
+
+    let task_state = TaskState {
+      tasks_meta: TasksMeta { templates },
+      tasks: Tasks(tasks),
+    };
+    Ok(task_state)
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use crate::ser::tags::Id as TagId;
+  use crate::ser::tags::Tag;
+  use crate::ser::tags::Template;
+  use crate::ser::tasks::Status;
+
+  use super::super::iCal;
+  use super::super::Backend;
+
+
+  /// Check that we can convert a calendar containing multiple TODOs, as
+  /// exported by other programs, into tasks.
+  #[test]
+  fn deserialize_foreign_calendar() {
+    let data = b"BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:other\r\n\
+                 BEGIN:VTODO\r\nUID:9d3c6f5c-5dd6-4d46-b6a4-4a8e0c2b9f5a\r\n\
+                 SUMMARY:first\r\nCATEGORIES:work,home,work\r\nPRIORITY:1\r\n\
+                 END:VTODO\r\n\
+                 BEGIN:VEVENT\r\nUID:event\r\nSUMMARY:ignored\r\nEND:VEVENT\r\n\
+                 BEGIN:VTODO\r\nUID:0b7f6d2e-1c3a-4e5b-8f9a-2d4c6e8a0b1c\r\n\
+                 SUMMARY:second\r\nSTATUS:COMPLETED\r\nCATEGORIES:home\r\n\
+                 CATEGORIES:a\\, b\r\nEND:VTODO\r\nEND:VCALENDAR\r\n";
+    let state = <iCal as Backend<TaskState>>::deserialize(data).unwrap();
+
+    let names = state
+      .tasks_meta
+      .templates
+      .0
+      .iter()
+      .map(|template| template.name.as_str())
+      .collect::<Vec<_>>();
+    assert_eq!(names, ["work", "home", "a, b"]);
+
+    let tasks = &state.tasks.0;
+    assert_eq!(tasks.len(), 2);
+    assert_eq!(tasks[0].summary, "first");
+    assert_eq!(
+      tasks[0].tags,
+      [1, 2].map(|id| Tag::from(TagId::try_from(id).unwrap()))
+    );
+    assert_eq!(
+      tasks[0].opaque.properties,
+      vec!["PRIORITY:1\r\n".to_string()]
+    );
+    assert_eq!(tasks[1].summary, "second");
+    assert_eq!(
+      tasks[1].tags,
+      [2, 3].map(|id| Tag::from(TagId::try_from(id).unwrap()))
+    );
+    assert_eq!(tasks[1].status, Some(Status::Completed(None)));
+    assert!(tasks[1].opaque.is_empty());
+  }
+
+  /// Make sure that we can serialize a `TaskState` into a single
+  /// calendar and deserialize it again.
+  #[test]
+  fn serialize_deserialize_task_state() {
+    let templates = ["work", "a, b; c"]
+      .into_iter()
+      .enumerate()
+      .map(|(idx, name)| Template {
+        id: TagId::try_from(idx + 1).unwrap(),
+        name: name.to_string(),
+      })
+      .collect();
+    let tags = |ids: &[usize]| {
+      ids
+        .iter()
+        .map(|id| Tag::from(TagId::try_from(*id).unwrap()))
+        .collect::<Vec<_>>()
+    };
+    let mut task = Task::new("third").with_tags(tags(&[2]));
+    task.opaque.properties = vec!["PRIORITY:1\r\n".to_string()];
+
+    let state = TaskState {
+      tasks_meta: TasksMeta {
+        templates: Templates(templates),
+      },
+      tasks: Tasks(vec![
+        Task::new("first").with_tags(tags(&[1, 2])),
+        Task::new("second"),
+        task,
+      ]),
+    };
+
+    let data = iCal::serialize(&state).unwrap();
+    let string = String::from_utf8(data.clone()).unwrap();
+    assert_eq!(string.matches("BEGIN:VTODO").count(), 3, "{string}");
+    assert!(string.contains("CATEGORIES:a\\, b\\; c\r\n"), "{string}");
+
+    let new_state = <iCal as Backend<TaskState>>::deserialize(&data).unwrap();
+    assert_eq!(new_state, state);
+  }
+
+  /// Check that time zone definitions are attached to the tasks
+  /// referencing them and emitted only once when serializing.
+  #[test]
+  fn serialize_deserialize_timezones() {
+    let timezone = "BEGIN:VTIMEZONE\r\nTZID:Europe/Berlin\r\nEND:VTIMEZONE\r\n";
+    let data = format!(
+      "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:other\r\n{timezone}\
+       BEGIN:VTIMEZONE\r\nTZID:Unused\r\nEND:VTIMEZONE\r\n\
+       BEGIN:VTODO\r\nUID:9d3c6f5c-5dd6-4d46-b6a4-4a8e0c2b9f5a\r\n\
+       SUMMARY:first\r\nDUE;TZID=Europe/Berlin:20250301T120000\r\nEND:VTODO\r\n\
+       BEGIN:VTODO\r\nUID:0b7f6d2e-1c3a-4e5b-8f9a-2d4c6e8a0b1c\r\n\
+       SUMMARY:second\r\nDUE;TZID=Europe/Berlin:20250302T120000\r\nEND:VTODO\r\n\
+       BEGIN:VTODO\r\nUID:2d4c6e8a-0b1c-4e5b-8f9a-9d3c6f5c5dd6\r\n\
+       SUMMARY:third\r\nEND:VTODO\r\nEND:VCALENDAR\r\n"
+    );
+    let state = <iCal as Backend<TaskState>>::deserialize(data.as_bytes()).unwrap();
+    let tasks = &state.tasks.0;
+    assert_eq!(
+      tasks[0].opaque.calendar_components,
+      vec![timezone.to_string()]
+    );
+    assert_eq!(
+      tasks[1].opaque.calendar_components,
+      vec![timezone.to_string()]
+    );
+    assert!(tasks[2].opaque.is_empty());
+
+    let data = iCal::serialize(&state).unwrap();
+    let string = String::from_utf8(data.clone()).unwrap();
+    assert_eq!(string.matches(timezone).count(), 1, "{string}");
+    assert!(!string.contains("Unused"), "{string}");
+
+    let new_state = <iCal as Backend<TaskState>>::deserialize(&data).unwrap();
+    assert_eq!(new_state, state);
+  }
+}
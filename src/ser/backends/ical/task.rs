@@ -2,31 +2,197 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use std::convert::TryFrom;
+use std::mem::take;
 use std::str::FromStr as _;
 
 use anyhow::Error;
 use anyhow::Result;
 
 use icalendar::Calendar;
+use icalendar::CalendarDateTime;
 use icalendar::Component as _;
+use icalendar::DatePerhapsTime;
+use icalendar::EventLike as _;
 use icalendar::Todo;
+use icalendar::TodoStatus;
 
+use uuid::Builder;
+
+use crate::date::Date;
+use crate::ser::tags::Id as TagId;
 use crate::ser::tags::Tag;
+use crate::ser::tags::Template;
+use crate::ser::tags::Templates;
 use crate::ser::tasks::Id as TaskId;
+use crate::ser::tasks::Opaque;
+use crate::ser::tasks::Status;
 use crate::ser::tasks::Task;
 use crate::LINE_END;
 use crate::LINE_END_STR;
 
-use super::util::emit_list;
-use super::util::parse_list;
+use super::util::add_list_property;
+use super::util::parse_list_property;
+use super::util::parse_text_list;
+use super::util::scan_opaque;
+use super::util::splice_opaque;
+use super::util::split_property;
 use super::util::try_from_calendar_with_single_todo;
 use super::SerICal;
 
 
-/// The name of the property used for storing a task's tags.
+/// The name of the property that was used for storing a task's tags,
+/// by ID, before we switched to categories.
 const TAGS_PROPERTY: &str = "TAGS";
+/// The name of the property used for storing a task's tags by name, in
+/// a form understood by other programs.
+const CATEGORIES_PROPERTY: &str = "CATEGORIES";
 /// The name of the property used for storing a task's "position".
 const POSITION_PROPERTY: &str = "POSITION";
+/// The names of the properties that we interpret (or regenerate) and
+/// that, hence, are not preserved opaquely.
+const KNOWN_PROPERTIES: [&str; 11] = [
+  "UID",
+  "DTSTAMP",
+  "CREATED",
+  "SUMMARY",
+  "DESCRIPTION",
+  "DUE",
+  "DTSTART",
+  "STATUS",
+  "COMPLETED",
+  TAGS_PROPERTY,
+  POSITION_PROPERTY,
+];
+
+
+/// The offset basis of the 128 bit FNV-1a hash function.
+const FNV_OFFSET_BASIS: u128 = 0x6c62272e07bb014262b821756295c58d;
+/// The prime of the 128 bit FNV-1a hash function.
+const FNV_PRIME: u128 = 0x0000000001000000000000000000013b;
+
+
+/// Derive a task ID from an iCalendar UID.
+///
+/// UIDs are not required to be UUIDs. For ones that are not, we derive
+/// an ID from a hash of the UID, so that importing the same data again
+/// maps to the same tasks.
+fn task_id(uid: &str) -> TaskId {
+  TaskId::from_str(uid).unwrap_or_else(|_| {
+    let hash = uid.bytes().fold(FNV_OFFSET_BASIS, |hash, byte| {
+      (hash ^ u128::from(byte)).wrapping_mul(FNV_PRIME)
+    });
+    Builder::from_custom_bytes(hash.to_be_bytes()).into_uuid()
+  })
+}
+
+
+/// Convert a task into the form in which it is persisted, with its tags
+/// expressed as categories, by name.
+///
+/// Tags without a corresponding template are dropped.
+pub(crate) fn tags_to_categories(task: &Task, templates: &Templates) -> Task {
+  let mut todo = Todo::new();
+  for tag in &task.tags {
+    if let Some(template) = templates.0.iter().find(|template| template.id == tag.id) {
+      todo.add_multi_property(CATEGORIES_PROPERTY, &template.name);
+    }
+  }
+
+  // We let `icalendar` take care of escaping and folding and just pick
+  // up the resulting content lines.
+  let data = Calendar::from([todo]).to_string();
+  let mut properties = scan_opaque(&data, &KNOWN_PROPERTIES).properties;
+  let () = properties.extend(
+    task
+      .opaque
+      .properties
+      .iter()
+      .filter(|line| !is_categories(line))
+      .cloned(),
+  );
+
+  let mut task = task.clone();
+  task.tags = Vec::new();
+  task.opaque.properties = properties;
+  task
+}
+
+
+/// Resolve the categories of a task, as persisted, against the provided
+/// templates and convert them into tags.
+///
+/// Templates for categories not yet known are created. Tags stored by
+/// ID, as done by earlier versions of the program, are retained.
+pub(crate) fn categories_to_tags(task: &mut Task, templates: &mut Templates) -> Result<()> {
+  let mut categories = Vec::new();
+  let () = task.opaque.properties.retain(|line| {
+    if is_categories(line) {
+      let (_name, value) = split_property(line);
+      let () = categories.extend(
+        parse_text_list(value)
+          .into_iter()
+          .filter(|category| !category.is_empty()),
+      );
+      false
+    } else {
+      true
+    }
+  });
+
+  let mut tags = take(&mut task.tags);
+  for category in categories {
+    let id = if let Some(template) = templates.0.iter().find(|t| t.name == category) {
+      template.id
+    } else {
+      let id = templates.0.iter().map(|t| t.id.get()).max().unwrap_or(0) + 1;
+      let id = TagId::try_from(id)?;
+      let () = templates.0.push(Template { id, name: category });
+      id
+    };
+
+    let tag = Tag::from(id);
+    if !tags.contains(&tag) {
+      let () = tags.push(tag);
+    }
+  }
+  task.tags = tags;
+  Ok(())
+}
+
+
+/// Check whether a content line represents a `CATEGORIES` property.
+fn is_categories(line: &str) -> bool {
+  let (name, _value) = split_property(line);
+  name.eq_ignore_ascii_case(CATEGORIES_PROPERTY)
+}
+
+
+impl From<&Date> for DatePerhapsTime {
+  fn from(date: &Date) -> Self {
+    match date {
+      Date::Day(date) => Self::Date(*date),
+      Date::Floating(date_time) => Self::DateTime(CalendarDateTime::Floating(*date_time)),
+      Date::Utc(date_time) => Self::DateTime(CalendarDateTime::Utc(*date_time)),
+      Date::Zoned(date_time, tzid) => Self::DateTime(CalendarDateTime::WithTimezone {
+        date_time: *date_time,
+        tzid: tzid.clone(),
+      }),
+    }
+  }
+}
+
+impl From<DatePerhapsTime> for Date {
+  fn from(date: DatePerhapsTime) -> Self {
+    match date {
+      DatePerhapsTime::Date(date) => Self::Day(date),
+      DatePerhapsTime::DateTime(CalendarDateTime::Floating(date_time)) => Self::Floating(date_time),
+      DatePerhapsTime::DateTime(CalendarDateTime::Utc(date_time)) => Self::Utc(date_time),
+      DatePerhapsTime::DateTime(CalendarDateTime::WithTimezone { date_time, tzid }) => {
+        Self::Zoned(date_time, tzid)
+      },
+    }
+  }
+}
 
 
 impl From<&Task> for Todo {
@@ -39,12 +205,41 @@ impl From<&Task> for Todo {
       todo.description(&task.details.replace(LINE_END, "\n"));
     }
 
-    if let Some(tags) = emit_list(&task.tags) {
-      todo.add_property(TAGS_PROPERTY, &tags);
+    if let Some(due) = &task.due {
+      todo.due(DatePerhapsTime::from(due));
+    }
+    if let Some(start) = &task.start {
+      todo.starts(DatePerhapsTime::from(start));
+    }
+    if let Some(created) = task.created {
+      todo.created(created);
     }
+
+    match &task.status {
+      Some(Status::NeedsAction) => {
+        todo.status(TodoStatus::NeedsAction);
+      },
+      Some(Status::InProcess) => {
+        todo.status(TodoStatus::InProcess);
+      },
+      Some(Status::Completed(completed)) => {
+        todo.status(TodoStatus::Completed);
+        if let Some(completed) = completed {
+          todo.completed(*completed);
+        }
+      },
+      Some(Status::Cancelled) => {
+        todo.status(TodoStatus::Cancelled);
+      },
+      None => (),
+    }
+
+    let () = add_list_property(&mut todo, TAGS_PROPERTY, &task.tags);
     if let Some(position) = &task.position {
       todo.add_property(POSITION_PROPERTY, position.to_string());
     }
+    // Note that opaque data is taken care of when emitting the
+    // `Calendar` as a whole.
 
     todo
   }
@@ -64,25 +259,26 @@ impl TryFrom<&Todo> for Task {
   type Error = Error;
 
   fn try_from(todo: &Todo) -> Result<Self, Self::Error> {
-    // TODO: Ideally we would hook up the TODO's completion state. The
-    //       problem is that currently doing so would require a lot of
-    //       outside knowledge as to which tag actually maps to that.
-
-    let id = todo
-      .get_uid()
-      .map(TaskId::from_str)
-      .transpose()?
-      .unwrap_or_else(TaskId::new_v4);
+    let id = todo.get_uid().map(task_id).unwrap_or_else(TaskId::new_v4);
     let summary = todo.get_summary().unwrap_or("").to_string();
     let details = todo
       .get_description()
       .unwrap_or("")
       .to_string()
       .replace('\n', LINE_END_STR);
-    let tags = todo
-      .property_value(TAGS_PROPERTY)
-      .map(parse_list::<Tag>)
-      .unwrap_or_else(|| Ok(Vec::new()))?;
+    let tags = parse_list_property::<Tag>(todo, TAGS_PROPERTY)?;
+    let due = todo.get_due().map(Date::from);
+    let start = todo.get_start().map(Date::from);
+    let created = todo.get_created();
+    // Note that the mapping of the status to the tag representing
+    // completion happens at a higher layer, as we lack the knowledge
+    // of which tag that is.
+    let status = todo.get_status().map(|status| match status {
+      TodoStatus::NeedsAction => Status::NeedsAction,
+      TodoStatus::InProcess => Status::InProcess,
+      TodoStatus::Completed => Status::Completed(todo.get_completed()),
+      TodoStatus::Cancelled => Status::Cancelled,
+    });
     let position = todo
       .property_value(POSITION_PROPERTY)
       .map(f64::from_str)
@@ -93,6 +289,13 @@ impl TryFrom<&Todo> for Task {
       summary,
       details,
       tags,
+      due,
+      start,
+      created,
+      status,
+      // Opaque data is extracted from the textual representation
+      // directly, as `icalendar` does not preserve it faithfully.
+      opaque: Opaque::default(),
       position,
     })
   }
@@ -112,13 +315,16 @@ impl SerICal for Task {
   #[inline]
   fn to_ical_string(&self) -> String {
     let calendar = Calendar::from(self);
-    calendar.to_string()
+    let mut string = calendar.to_string();
+    let () = splice_opaque(&mut string, &self.opaque);
+    string
   }
 
   #[inline]
   fn from_ical_string(data: &str) -> Result<Self, Error> {
     let calendar = Calendar::from_str(data).map_err(Error::msg)?;
-    let task = Task::try_from(&calendar)?;
+    let mut task = Task::try_from(&calendar)?;
+    task.opaque = scan_opaque(data, &KNOWN_PROPERTIES);
     Ok(task)
   }
 }
@@ -128,6 +334,8 @@ impl SerICal for Task {
 mod tests {
   use super::*;
 
+  use chrono::NaiveDate;
+
   use crate::ser::tags::Id as TagId;
   use crate::LINE_END;
 
@@ -159,6 +367,84 @@ mod tests {
     assert_eq!(new_task, task);
   }
 
+  /// Check that tags are persisted as categories and resolved by name
+  /// again.
+  #[test]
+  fn serialize_deserialize_task_with_categories() {
+    let template = |id, name: &str| Template {
+      id: TagId::try_from(id).unwrap(),
+      name: name.to_string(),
+    };
+    let templates = Templates(vec![template(1, "work"), template(3, "a, b; c")]);
+    let tags = [3, 1].map(|id| Tag::from(TagId::try_from(id).unwrap()));
+    let mut task = Task::new("test task").with_tags(tags);
+    task.opaque.properties = vec!["PRIORITY:1\r\n".to_string()];
+
+    let persisted = tags_to_categories(&task, &templates);
+    assert_eq!(persisted.tags, []);
+    let data = iCal::serialize(&persisted).unwrap();
+    let string = String::from_utf8(data.clone()).unwrap();
+    assert!(string.contains("CATEGORIES:a\\, b\\; c\r\n"), "{string}");
+    assert!(string.contains("CATEGORIES:work\r\n"), "{string}");
+    assert!(!string.contains("TAGS"), "{string}");
+
+    let mut new_task = <iCal as Backend<Task>>::deserialize(&data).unwrap();
+    assert_eq!(new_task, persisted);
+    let mut new_templates = templates.clone();
+    let () = categories_to_tags(&mut new_task, &mut new_templates).unwrap();
+    assert_eq!(new_task, task);
+    assert_eq!(new_templates, templates);
+  }
+
+  /// Check that unknown categories get templates created and that tags
+  /// stored by ID continue to be honored.
+  #[test]
+  fn deserialize_task_with_categories_and_tags() {
+    let data = b"BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:other\r\n\
+                 BEGIN:VTODO\r\nUID:9d3c6f5c-5dd6-4d46-b6a4-4a8e0c2b9f5a\r\n\
+                 SUMMARY:foreign\r\nTAGS:2\r\nCATEGORIES:home,new\r\n\
+                 END:VTODO\r\nEND:VCALENDAR\r\n";
+    let mut task = <iCal as Backend<Task>>::deserialize(data).unwrap();
+    let mut templates = Templates(vec![
+      Template {
+        id: TagId::try_from(2).unwrap(),
+        name: "home".to_string(),
+      },
+      Template {
+        id: TagId::try_from(5).unwrap(),
+        name: "work".to_string(),
+      },
+    ]);
+    let () = categories_to_tags(&mut task, &mut templates).unwrap();
+
+    assert_eq!(
+      task.tags,
+      [2, 6].map(|id| Tag::from(TagId::try_from(id).unwrap()))
+    );
+    assert!(task.opaque.is_empty());
+    assert_eq!(templates.0.len(), 3);
+    assert_eq!(templates.0[2].id, TagId::try_from(6).unwrap());
+    assert_eq!(templates.0[2].name, "new");
+  }
+
+  /// Check that we derive task IDs from UIDs that are not UUIDs.
+  #[test]
+  fn deserialize_task_with_non_uuid_uid() {
+    let data = "BEGIN:VCALENDAR\r\nBEGIN:VTODO\r\nUID:20250301T120000-42@example.com\r\n\
+                SUMMARY:foreign\r\nEND:VTODO\r\nEND:VCALENDAR\r\n";
+    let task1 = <iCal as Backend<Task>>::deserialize(data.as_bytes()).unwrap();
+    let task2 = <iCal as Backend<Task>>::deserialize(data.as_bytes()).unwrap();
+    assert_eq!(task1.id, task2.id);
+    assert_eq!(task1.id.get_version_num(), 8);
+
+    let other = data.replace("-42@", "-43@");
+    let task3 = <iCal as Backend<Task>>::deserialize(other.as_bytes()).unwrap();
+    assert_ne!(task3.id, task1.id);
+
+    let uuid = "9d3c6f5c-5dd6-4d46-b6a4-4a8e0c2b9f5a";
+    assert_eq!(task_id(uuid), TaskId::from_str(uuid).unwrap());
+  }
+
   /// Make sure that we can serialize and deserialize a `Task` with
   /// details spanning multiple lines.
   #[test]
@@ -171,4 +457,217 @@ mod tests {
 
     assert_eq!(new_task, task);
   }
+
+  /// Make sure that we can serialize and deserialize a `Task` with due
+  /// and start dates.
+  #[test]
+  fn serialize_deserialize_task_with_dates() {
+    let dates = [
+      Date::Day(NaiveDate::from_ymd_opt(2025, 3, 1).unwrap()),
+      Date::Floating(
+        NaiveDate::from_ymd_opt(2025, 3, 1)
+          .unwrap()
+          .and_hms_opt(13, 37, 0)
+          .unwrap(),
+      ),
+      Date::Utc(
+        NaiveDate::from_ymd_opt(2025, 3, 1)
+          .unwrap()
+          .and_hms_opt(13, 37, 0)
+          .unwrap()
+          .and_utc(),
+      ),
+      Date::Zoned(
+        NaiveDate::from_ymd_opt(2025, 3, 1)
+          .unwrap()
+          .and_hms_opt(13, 37, 0)
+          .unwrap(),
+        "Europe/Berlin".to_string(),
+      ),
+    ];
+
+    for date in dates {
+      let task = Task::new("test task")
+        .with_due(date.clone())
+        .with_start(date);
+
+      let data = iCal::serialize(&task).unwrap();
+      let new_task = <iCal as Backend<Task>>::deserialize(&data).unwrap();
+
+      assert_eq!(new_task, task);
+    }
+  }
+
+  /// Check that we pick up due and start dates from VTODO items created
+  /// by other programs.
+  #[test]
+  fn deserialize_foreign_task_with_dates() {
+    let data = b"BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:other\r\n\
+                 BEGIN:VTODO\r\nUID:9d3c6f5c-5dd6-4d46-b6a4-4a8e0c2b9f5a\r\n\
+                 SUMMARY:foreign\r\nDTSTART;VALUE=DATE:20250301\r\n\
+                 DUE:20250302T100000Z\r\nEND:VTODO\r\nEND:VCALENDAR\r\n";
+    let task = <iCal as Backend<Task>>::deserialize(data).unwrap();
+
+    let start = NaiveDate::from_ymd_opt(2025, 3, 1).unwrap();
+    assert_eq!(task.start, Some(Date::Day(start)));
+    let due = NaiveDate::from_ymd_opt(2025, 3, 2)
+      .unwrap()
+      .and_hms_opt(10, 0, 0)
+      .unwrap()
+      .and_utc();
+    assert_eq!(task.due, Some(Date::Utc(due)));
+  }
+
+  /// Make sure that we can serialize and deserialize a `Task` with a
+  /// status.
+  #[test]
+  fn serialize_deserialize_task_with_status() {
+    let completed = NaiveDate::from_ymd_opt(2025, 3, 1)
+      .unwrap()
+      .and_hms_opt(13, 37, 0)
+      .unwrap()
+      .and_utc();
+    let statuses = [
+      Status::NeedsAction,
+      Status::InProcess,
+      Status::Completed(None),
+      Status::Completed(Some(completed)),
+      Status::Cancelled,
+    ];
+
+    for status in statuses {
+      let task = Task::new("test task").with_status(status);
+      let data = iCal::serialize(&task).unwrap();
+      let new_task = <iCal as Backend<Task>>::deserialize(&data).unwrap();
+
+      assert_eq!(new_task, task);
+    }
+  }
+
+  /// Make sure that we can serialize and deserialize a `Task` with a
+  /// creation time.
+  #[test]
+  fn serialize_deserialize_task_with_creation_time() {
+    let created = NaiveDate::from_ymd_opt(2025, 3, 1)
+      .unwrap()
+      .and_hms_opt(13, 37, 0)
+      .unwrap()
+      .and_utc();
+    let task = Task {
+      created: Some(created),
+      ..Task::new("test task")
+    };
+    let data = iCal::serialize(&task).unwrap();
+    assert!(String::from_utf8_lossy(&data).contains("CREATED:20250301T133700Z"));
+
+    let new_task = <iCal as Backend<Task>>::deserialize(&data).unwrap();
+    assert_eq!(new_task, task);
+  }
+
+  /// Check that we pick up the status of VTODO items completed by other
+  /// programs.
+  #[test]
+  fn deserialize_foreign_completed_task() {
+    let data = b"BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:other\r\n\
+                 BEGIN:VTODO\r\nUID:9d3c6f5c-5dd6-4d46-b6a4-4a8e0c2b9f5a\r\n\
+                 SUMMARY:foreign\r\nSTATUS:COMPLETED\r\n\
+                 COMPLETED:20250302T100000Z\r\nEND:VTODO\r\nEND:VCALENDAR\r\n";
+    let task = <iCal as Backend<Task>>::deserialize(data).unwrap();
+
+    let completed = NaiveDate::from_ymd_opt(2025, 3, 2)
+      .unwrap()
+      .and_hms_opt(10, 0, 0)
+      .unwrap()
+      .and_utc();
+    assert_eq!(task.status, Some(Status::Completed(Some(completed))));
+  }
+
+  /// Make sure that we preserve properties and components that we do
+  /// not interpret.
+  #[test]
+  fn serialize_deserialize_task_with_opaque_data() {
+    let mut task = Task::new("test task");
+    task.opaque = Opaque {
+      properties: vec![
+        "PRIORITY:1\r\n".to_string(),
+        "RELATED-TO;RELTYPE=PARENT:5c2b1d3a-7e4f-4d6b-9a8c-1f2e3d4c5b6a\r\n".to_string(),
+        "X-FOO:bar\r\n".to_string(),
+      ],
+      components: vec![
+        "BEGIN:VALARM\r\nACTION:DISPLAY\r\nTRIGGER:-PT15M\r\nEND:VALARM\r\n".to_string(),
+      ],
+      calendar_properties: vec!["X-WR-CALNAME:tasks\r\n".to_string()],
+      calendar_components: vec![
+        "BEGIN:VTIMEZONE\r\nTZID:Europe/Berlin\r\nEND:VTIMEZONE\r\n".to_string()
+      ],
+    };
+
+    let data = iCal::serialize(&task).unwrap();
+    let new_task = <iCal as Backend<Task>>::deserialize(&data).unwrap();
+    assert_eq!(new_task, task);
+  }
+
+  /// Check that we write back properties and components set by other
+  /// programs unchanged.
+  #[test]
+  fn deserialize_serialize_foreign_task() {
+    let properties = "PRIORITY:5\r\nCATEGORIES:work,home\r\n\
+                      X-OTHER-PROP;X-PARAM=value:some\\, text that is long enough \r\n \
+                      to be folded\r\n";
+    let alarm = "BEGIN:VALARM\r\nACTION:DISPLAY\r\nDESCRIPTION:Reminder\r\n\
+                 TRIGGER;RELATED=END:-PT1H\r\nEND:VALARM\r\n";
+    let data = format!(
+      "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:other\r\n\
+       BEGIN:VTODO\r\nUID:9d3c6f5c-5dd6-4d46-b6a4-4a8e0c2b9f5a\r\n\
+       DTSTAMP:20250301T120000Z\r\nSUMMARY:foreign\r\n\
+       {properties}{alarm}END:VTODO\r\nEND:VCALENDAR\r\n"
+    );
+    let task = <iCal as Backend<Task>>::deserialize(data.as_bytes()).unwrap();
+    assert_eq!(task.summary, "foreign");
+    assert_eq!(task.opaque.properties.concat(), properties);
+    assert_eq!(task.opaque.components, vec![alarm.to_string()]);
+
+    let data = iCal::serialize(&task).unwrap();
+    let string = String::from_utf8(data.clone()).unwrap();
+    assert!(
+      string.contains(&format!("{properties}{alarm}END:VTODO")),
+      "{string}"
+    );
+
+    let new_task = <iCal as Backend<Task>>::deserialize(&data).unwrap();
+    assert_eq!(new_task, task);
+  }
+
+  /// Check that we preserve the time zone definition of a date
+  /// referencing it when writing back a task.
+  #[test]
+  fn deserialize_serialize_foreign_task_with_timezone() {
+    let timezone = "BEGIN:VTIMEZONE\r\nTZID:Europe/Berlin\r\n\
+                    BEGIN:STANDARD\r\nDTSTART:19701025T030000\r\n\
+                    TZOFFSETFROM:+0200\r\nTZOFFSETTO:+0100\r\nEND:STANDARD\r\n\
+                    END:VTIMEZONE\r\n";
+    let data = format!(
+      "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:other\r\nX-WR-CALNAME:tasks\r\n\
+       {timezone}BEGIN:VTODO\r\nUID:9d3c6f5c-5dd6-4d46-b6a4-4a8e0c2b9f5a\r\n\
+       DTSTAMP:20250301T120000Z\r\nSUMMARY:foreign\r\n\
+       DUE;TZID=Europe/Berlin:20250301T120000\r\nEND:VTODO\r\nEND:VCALENDAR\r\n"
+    );
+    let task = <iCal as Backend<Task>>::deserialize(data.as_bytes()).unwrap();
+    assert!(matches!(task.due, Some(Date::Zoned(..))));
+    assert_eq!(task.opaque.calendar_components, vec![timezone.to_string()]);
+    assert_eq!(
+      task.opaque.calendar_properties,
+      vec!["X-WR-CALNAME:tasks\r\n".to_string()]
+    );
+
+    let data = iCal::serialize(&task).unwrap();
+    let string = String::from_utf8(data.clone()).unwrap();
+    assert!(
+      string.contains(&format!("{timezone}BEGIN:VTODO")),
+      "{string}"
+    );
+
+    let new_task = <iCal as Backend<Task>>::deserialize(&data).unwrap();
+    assert_eq!(new_task, task);
+  }
 }
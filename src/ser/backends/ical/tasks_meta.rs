@@ -16,8 +16,8 @@ use crate::ser::tags::Template;
 use crate::ser::tags::Templates;
 use crate::ser::tasks::TasksMeta;
 
-use super::util::emit_list;
-use super::util::parse_list;
+use super::util::add_list_property;
+use super::util::parse_list_property;
 use super::util::try_from_calendar_with_single_todo;
 use super::SerICal;
 
@@ -37,9 +37,7 @@ impl From<&TasksMeta> for Todo {
         .to_string(),
     );
 
-    if let Some(templates) = emit_list(&tasks_meta.templates.0) {
-      todo.add_property(TEMPLATES_PROPERTY, &templates);
-    }
+    let () = add_list_property(&mut todo, TEMPLATES_PROPERTY, &tasks_meta.templates.0);
 
     todo
   }
@@ -59,10 +57,7 @@ impl TryFrom<&Todo> for TasksMeta {
   type Error = Error;
 
   fn try_from(todo: &Todo) -> Result<Self, Self::Error> {
-    let templates = todo
-      .property_value(TEMPLATES_PROPERTY)
-      .map(|s| parse_list::<Template>(s).map(Templates))
-      .unwrap_or_else(|| Ok(Templates::default()))?;
+    let templates = Templates(parse_list_property::<Template>(todo, TEMPLATES_PROPERTY)?);
 
     let tasks_meta = TasksMeta { templates };
     Ok(tasks_meta)
@@ -126,4 +121,56 @@ mod tests {
 
     assert_eq!(deserialized, tasks_meta);
   }
+
+  /// Check that templates with arbitrary names survive a round trip
+  /// through iCalendar.
+  #[test]
+  fn serialize_deserialize_adversarial_template_names() {
+    let alphabet = [
+      "", "a", "|", "\\", ",", ";", ":", "\"", "\n", "\r\n", "\t", " ", "\u{0}", "ä", "😀",
+    ];
+    let mut names = Vec::new();
+    for c1 in alphabet {
+      for c2 in alphabet {
+        let () = names.push(format!("{c1}{c2}"));
+      }
+    }
+    // Long names are folded across multiple lines.
+    let () = names.push(alphabet.concat().repeat(10));
+    let () = names.push("😀".repeat(100));
+
+    let templates = names
+      .into_iter()
+      .enumerate()
+      .map(|(idx, name)| Template {
+        id: TagId::try_from(idx + 1).unwrap(),
+        name,
+      })
+      .collect();
+    let tasks_meta = TasksMeta {
+      templates: Templates(templates),
+    };
+
+    let serialized = iCal::serialize(&tasks_meta).unwrap();
+    let deserialized = <iCal as Backend<TasksMeta>>::deserialize(&serialized).unwrap();
+    assert_eq!(deserialized, tasks_meta);
+  }
+
+  /// Check that we can still load task meta data emitted without any
+  /// escaping.
+  #[test]
+  fn deserialize_unescaped_tasks_meta() {
+    let data = b"BEGIN:VCALENDAR\r\nBEGIN:VTODO\r\n\
+                 UID:00000000-0000-0000-0000-000000000000\r\n\
+                 TEMPLATES:1,a\\b|2,c,d|3,\\u{41}\r\n\
+                 END:VTODO\r\nEND:VCALENDAR\r\n";
+    let tasks_meta = <iCal as Backend<TasksMeta>>::deserialize(data).unwrap();
+    let names = tasks_meta
+      .templates
+      .0
+      .iter()
+      .map(|template| template.name.as_str())
+      .collect::<Vec<_>>();
+    assert_eq!(names, ["a\\b", "c,d", "\\u{41}"]);
+  }
 }
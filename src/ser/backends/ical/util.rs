@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use std::fmt::Display;
+use std::mem::take;
 use std::str::FromStr;
 
 use anyhow::bail;
@@ -12,15 +13,93 @@ use anyhow::Result;
 use icalendar::Calendar;
 use icalendar::CalendarComponent;
 use icalendar::Component as _;
+use icalendar::Property;
 use icalendar::Todo;
 
+use crate::ser::tasks::Opaque;
+
 
 /// The separator to use between list items.
 const LIST_ITEM_SEPARATOR: char = '|';
+/// The character introducing an escape sequence in list items.
+const LIST_ESCAPE_CHARACTER: char = '\\';
+/// The name of the parameter marking a property value as a list with
+/// escaped items.
+///
+/// Lists emitted by earlier versions of the program did not escape
+/// items. By only interpreting escape sequences in the presence of this
+/// parameter, such lists continue to be parsed exactly as before.
+const LIST_ESCAPED_PARAMETER: &str = "X-ESCAPED";
+
+
+/// Escape a single list item.
+///
+/// Besides the escape character itself and the list item separator,
+/// control characters are escaped, as they are not permitted as part of
+/// iCalendar property values. They are represented as `\u{<hex>}`,
+/// similar to Rust's escaping scheme.
+fn escape_item(item: &str) -> String {
+  let mut escaped = String::with_capacity(item.len());
+  for c in item.chars() {
+    match c {
+      LIST_ESCAPE_CHARACTER | LIST_ITEM_SEPARATOR => {
+        let () = escaped.push(LIST_ESCAPE_CHARACTER);
+        let () = escaped.push(c);
+      },
+      c if c.is_control() => {
+        let () = escaped.push_str(&format!("{LIST_ESCAPE_CHARACTER}u{{{:x}}}", u32::from(c)));
+      },
+      c => escaped.push(c),
+    }
+  }
+  escaped
+}
+
+
+/// Split a list of escaped items, unescaping each of them.
+fn split_escaped(string: &str) -> Result<Vec<String>> {
+  let mut items = Vec::new();
+  let mut item = String::new();
+  let mut chars = string.chars();
+
+  while let Some(c) = chars.next() {
+    match c {
+      LIST_ESCAPE_CHARACTER => match chars.next() {
+        Some(c @ (LIST_ESCAPE_CHARACTER | LIST_ITEM_SEPARATOR)) => item.push(c),
+        Some('u') => {
+          let rest = chars.as_str();
+          let c = rest
+            .strip_prefix('{')
+            .and_then(|rest| rest.split_once('}'))
+            .and_then(|(hex, rest)| {
+              let c = u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)?;
+              Some((c, rest))
+            });
+          let (c, rest) =
+            c.with_context(|| format!("encountered invalid escape sequence in '{string}'"))?;
+          let () = item.push(c);
+          chars = rest.chars();
+        },
+        Some(c) => {
+          bail!("encountered invalid escape sequence '{LIST_ESCAPE_CHARACTER}{c}' in '{string}'")
+        },
+        None => bail!("encountered incomplete escape sequence in '{string}'"),
+      },
+      LIST_ITEM_SEPARATOR => items.push(take(&mut item)),
+      c => item.push(c),
+    }
+  }
+  let () = items.push(item);
+  Ok(items)
+}
 
 
 /// Parse a list of items that can be built from strings.
-pub(super) fn parse_list<T>(string: &str) -> Result<Vec<T>>
+///
+/// If `escaped` is `true`, the list is expected to have been emitted by
+/// [`emit_list`] and escape sequences in items are interpreted.
+/// Otherwise items are taken verbatim.
+fn parse_list<T>(string: &str, escaped: bool) -> Result<Vec<T>>
 where
   T: FromStr,
   Error: From<<T as FromStr>::Err>,
@@ -29,10 +108,19 @@ where
   // really be represented as `Option<&str>` anyway.
   debug_assert!(!string.is_empty());
 
-  let items = string
-    .split(LIST_ITEM_SEPARATOR)
+  let items = if escaped {
+    split_escaped(string)?
+  } else {
+    string
+      .split(LIST_ITEM_SEPARATOR)
+      .map(str::to_string)
+      .collect()
+  };
+
+  let items = items
+    .into_iter()
     .map(|s| {
-      T::from_str(s)
+      T::from_str(&s)
         .map_err(Error::from)
         .with_context(|| format!("failed to parse object from string '{s}'"))
     })
@@ -41,13 +129,8 @@ where
 }
 
 
-/// Emit a list of items as a string.
-///
-/// # Notes
-/// This function does no escaping on the individual item's contents and
-/// there is risk of a clash when parsing again if they contain
-/// `LIST_ITEM_SEPARATOR` characters.
-pub(super) fn emit_list<I, T>(iter: I) -> Option<String>
+/// Emit a list of items as a string, escaping each item's contents.
+fn emit_list<I, T>(iter: I) -> Option<String>
 where
   I: IntoIterator<Item = T>,
   T: Display,
@@ -58,13 +141,264 @@ where
 
   let mut iter = iter.into_iter();
   iter.next().map(|first| {
-    iter.fold(first.to_string(), |list, item| {
-      format!("{list}{LIST_ITEM_SEPARATOR}{item}")
+    iter.fold(escape_item(&first.to_string()), |list, item| {
+      format!(
+        "{list}{LIST_ITEM_SEPARATOR}{}",
+        escape_item(&item.to_string())
+      )
     })
   })
 }
 
 
+/// Add a property with the given name, containing a list of items, to a
+/// TODO.
+///
+/// Nothing is added if the list is empty.
+pub(super) fn add_list_property<I, T>(todo: &mut Todo, name: &str, iter: I)
+where
+  I: IntoIterator<Item = T>,
+  T: Display,
+{
+  if let Some(list) = emit_list(iter) {
+    let mut property = Property::new(name, list);
+    let _property = property.add_parameter(LIST_ESCAPED_PARAMETER, "TRUE");
+    let _todo = todo.append_property(property);
+  }
+}
+
+
+/// Parse a list of items from the property with the given name of a
+/// TODO.
+///
+/// An empty list is returned if the property is not present.
+pub(super) fn parse_list_property<T>(todo: &Todo, name: &str) -> Result<Vec<T>>
+where
+  T: FromStr,
+  Error: From<<T as FromStr>::Err>,
+{
+  if let Some(property) = todo.properties().get(name) {
+    let escaped = property
+      .params()
+      .get(LIST_ESCAPED_PARAMETER)
+      .is_some_and(|param| param.value().eq_ignore_ascii_case("TRUE"));
+    parse_list(property.value(), escaped)
+  } else {
+    Ok(Vec::new())
+  }
+}
+
+
+/// Split iCalendar data into content lines, each including any
+/// continuation lines it was folded into.
+///
+/// Line endings are normalized to CRLF.
+pub(super) fn content_lines(data: &str) -> Vec<String> {
+  let mut lines = Vec::<String>::new();
+  for line in data.lines() {
+    let line = format!("{line}\r\n");
+    match lines.last_mut() {
+      Some(last) if line.starts_with([' ', '\t']) => {
+        let () = last.push_str(&line);
+      },
+      _ => {
+        let () = lines.push(line);
+      },
+    }
+  }
+  lines
+}
+
+
+/// Split a content line into the property's name and its value.
+///
+/// Parameters are dropped from the name.
+pub(super) fn split_property(line: &str) -> (&str, &str) {
+  let (name, value) = line
+    .split_once(':')
+    .map(|(name, value)| (name, value.trim()))
+    .unwrap_or((line.trim_end(), ""));
+  let name = name.split(';').next().unwrap_or("").trim();
+  (name, value)
+}
+
+
+/// Parse a property value comprising a list of comma separated text
+/// items, as used by `CATEGORIES`, for example.
+///
+/// The value may still be folded.
+pub(super) fn parse_text_list(value: &str) -> Vec<String> {
+  let value = value.replace("\r\n ", "").replace("\r\n\t", "");
+  let mut items = Vec::new();
+  let mut item = String::new();
+  let mut chars = value.chars();
+
+  while let Some(c) = chars.next() {
+    match c {
+      '\\' => match chars.next() {
+        Some('n' | 'N') => item.push('\n'),
+        Some(c) => item.push(c),
+        None => (),
+      },
+      ',' => items.push(take(&mut item)),
+      c => item.push(c),
+    }
+  }
+  let () = items.push(item);
+  items
+}
+
+
+/// Split iCalendar data into the (top-level) TODO components it
+/// contains.
+///
+/// Each TODO is provided verbatim, with line endings normalized to
+/// CRLF.
+pub(super) fn split_todos(data: &str) -> Vec<String> {
+  let mut todos = Vec::new();
+  let mut depth = 0usize;
+  let mut todo = String::new();
+
+  for line in content_lines(data) {
+    let (name, value) = split_property(&line);
+    if name.eq_ignore_ascii_case("BEGIN") {
+      if depth > 0 {
+        depth += 1;
+      } else if value.eq_ignore_ascii_case("VTODO") {
+        depth = 1;
+      }
+    }
+
+    if depth > 0 {
+      let () = todo.push_str(&line);
+    }
+
+    if name.eq_ignore_ascii_case("END") && depth > 0 {
+      depth -= 1;
+      if depth == 0 {
+        let () = todos.push(take(&mut todo));
+      }
+    }
+  }
+  todos
+}
+
+
+/// Calendar level properties that we always emit ourselves and that,
+/// hence, are not preserved opaquely.
+const KNOWN_CALENDAR_PROPERTIES: [&str; 3] = ["VERSION", "PRODID", "CALSCALE"];
+
+
+/// The destination of a component being extracted as opaque data.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Target {
+  /// The component is nested in the TODO.
+  Todo,
+  /// The component is part of the calendar itself, such as a time
+  /// zone definition.
+  Calendar,
+  /// The component is not of interest, such as an additional TODO.
+  Discard,
+}
+
+
+/// Extract the content of the (first) TODO in the provided iCalendar
+/// data that is not covered by the list of `known` properties, along
+/// with that of the surrounding calendar (besides the TODO itself).
+///
+/// The data is extracted verbatim from its textual representation,
+/// because `icalendar` does not round-trip all values faithfully.
+pub(super) fn scan_opaque(data: &str, known: &[&str]) -> Opaque {
+  let mut opaque = Opaque::default();
+  let mut in_todo = false;
+  let mut todo_seen = false;
+  // The nesting depth inside the component currently being extracted,
+  // with zero meaning that there is none.
+  let mut depth = 0usize;
+  let mut target = Target::Discard;
+  let mut component = String::new();
+
+  for line in content_lines(data) {
+    let (name, value) = split_property(&line);
+    if depth > 0 {
+      let () = component.push_str(&line);
+      if name.eq_ignore_ascii_case("BEGIN") {
+        depth += 1;
+      } else if name.eq_ignore_ascii_case("END") {
+        depth -= 1;
+        if depth == 0 {
+          let component = take(&mut component);
+          match target {
+            Target::Todo => opaque.components.push(component),
+            Target::Calendar => opaque.calendar_components.push(component),
+            Target::Discard => (),
+          }
+        }
+      }
+    } else if name.eq_ignore_ascii_case("BEGIN") {
+      if value.eq_ignore_ascii_case("VCALENDAR") && !in_todo {
+        continue
+      }
+
+      if value.eq_ignore_ascii_case("VTODO") && !in_todo && !todo_seen {
+        in_todo = true;
+        todo_seen = true;
+      } else {
+        depth = 1;
+        target = if in_todo {
+          Target::Todo
+        } else if value.eq_ignore_ascii_case("VTODO") {
+          Target::Discard
+        } else {
+          Target::Calendar
+        };
+        let () = component.push_str(&line);
+      }
+    } else if name.eq_ignore_ascii_case("END") {
+      if in_todo && value.eq_ignore_ascii_case("VTODO") {
+        in_todo = false;
+      }
+    } else if in_todo {
+      if !known.iter().any(|known| name.eq_ignore_ascii_case(known)) {
+        let () = opaque.properties.push(line);
+      }
+    } else if !KNOWN_CALENDAR_PROPERTIES
+      .iter()
+      .any(|known| name.eq_ignore_ascii_case(known))
+    {
+      let () = opaque.calendar_properties.push(line);
+    }
+  }
+  opaque
+}
+
+
+/// Insert opaque data into the (single) TODO in the provided iCalendar
+/// data and the calendar surrounding it.
+pub(super) fn splice_opaque(data: &mut String, opaque: &Opaque) {
+  let Opaque {
+    properties,
+    components,
+    calendar_properties,
+    calendar_components,
+  } = opaque;
+
+  if !properties.is_empty() || !components.is_empty() {
+    // SANITY: We only ever work with calendars containing a TODO.
+    let idx = data.rfind("END:VTODO").unwrap();
+    let () = data.insert_str(idx, &components.concat());
+    let () = data.insert_str(idx, &properties.concat());
+  }
+
+  if !calendar_properties.is_empty() || !calendar_components.is_empty() {
+    // SANITY: We only ever work with calendars containing a TODO.
+    let idx = data.find("BEGIN:VTODO").unwrap();
+    let () = data.insert_str(idx, &calendar_components.concat());
+    let () = data.insert_str(idx, &calendar_properties.concat());
+  }
+}
+
+
 /// Attempt to extract a custom object from a [`Calendar`] with a single
 /// [`Todo`] component.
 pub(super) fn try_from_calendar_with_single_todo<T>(calendar: &Calendar) -> Result<T>
@@ -75,7 +409,15 @@ where
   where
     T: for<'todo> TryFrom<&'todo Todo, Error = Error>,
   {
-    match calendar.components.as_slice() {
+    // Components we do not know about, such as time zone definitions,
+    // are preserved as opaque data, so we do not count them here.
+    let components = calendar
+      .components
+      .iter()
+      .filter(|component| !matches!(component, CalendarComponent::Other(..)))
+      .collect::<Vec<_>>();
+
+    match components.as_slice() {
       [component] => {
         if let CalendarComponent::Todo(todo) = component {
           T::try_from(todo).with_context(|| {
@@ -115,13 +457,65 @@ mod tests {
     assert_eq!(emit_list::<_, Tag>([]), None);
 
     let tags = [Tag::from(TagId::try_from(1).unwrap())];
-    assert_eq!(parse_list::<Tag>(&emit_list(tags).unwrap()).unwrap(), tags);
+    assert_eq!(
+      parse_list::<Tag>(&emit_list(tags).unwrap(), true).unwrap(),
+      tags
+    );
 
     let tags = [
       Tag::from(TagId::try_from(42).unwrap()),
       Tag::from(TagId::try_from(37).unwrap()),
     ];
-    assert_eq!(parse_list::<Tag>(&emit_list(tags).unwrap()).unwrap(), tags);
+    assert_eq!(
+      parse_list::<Tag>(&emit_list(tags).unwrap(), true).unwrap(),
+      tags
+    );
+  }
+
+  /// Check that lists of arbitrary strings survive a round trip through
+  /// [`emit_list`] and [`parse_list`].
+  #[test]
+  fn emit_parse_adversarial_list() {
+    // Characters with special meaning to us or to iCalendar, along with
+    // some regular and some multi-byte ones.
+    let alphabet = [
+      "", "a", "|", "\\", ",", ";", ":", "u", "{", "}", "\n", "\r", "\t", " ", "\u{7f}", "ä", "😀",
+    ];
+
+    for c1 in alphabet {
+      for c2 in alphabet {
+        for c3 in alphabet {
+          let item = format!("{c1}{c2}{c3}");
+          // A list consisting of a single empty item can't be told
+          // apart from an empty list, which we never parse.
+          if !item.is_empty() {
+            let items = [item.as_str()];
+            let list = emit_list(items).unwrap();
+            assert!(!list.chars().any(char::is_control), "{list:?}");
+            assert_eq!(parse_list::<String>(&list, true).unwrap(), items);
+          }
+
+          let items = [c1, item.as_str(), c3];
+          let list = emit_list(items).unwrap();
+          assert_eq!(parse_list::<String>(&list, true).unwrap(), items);
+        }
+      }
+    }
+  }
+
+  /// Check that lists without escaping are parsed verbatim.
+  #[test]
+  fn parse_unescaped_list() {
+    let items = parse_list::<String>("a\\|b\\u{41}|\\|", false).unwrap();
+    assert_eq!(items, ["a\\", "b\\u{41}", "\\", ""]);
+  }
+
+  /// Check that we report invalid escape sequences as errors.
+  #[test]
+  fn parse_invalid_escape_sequences() {
+    for list in ["\\", "a\\b", "\\u", "\\u{", "\\u{}", "\\u{d800}", "\\u{41"] {
+      assert!(parse_list::<String>(list, true).is_err(), "{list}");
+    }
   }
 
   /// Check that we fail conversion from a `Calendar` object if it does
@@ -147,4 +541,59 @@ mod tests {
       "calendar contains multiple components"
     );
   }
+
+  /// Check that we extract opaque data from iCalendar data as expected.
+  #[test]
+  fn opaque_data_scanning() {
+    let data = "BEGIN:VCALENDAR\nVERSION:2.0\nX-CAL:value\nBEGIN:VTODO\nUID:1\nx-foo;a=b:c\n  d\n\
+                BEGIN:VALARM\nBEGIN:X-NESTED\nX-BAR:1\nEND:X-NESTED\nEND:VALARM\n\
+                END:VTODO\nEND:VCALENDAR\n";
+    let opaque = scan_opaque(data, &["UID"]);
+    assert_eq!(
+      opaque.properties,
+      vec!["x-foo;a=b:c\r\n  d\r\n".to_string()]
+    );
+    assert_eq!(
+      opaque.components,
+      vec![
+        "BEGIN:VALARM\r\nBEGIN:X-NESTED\r\nX-BAR:1\r\nEND:X-NESTED\r\nEND:VALARM\r\n".to_string()
+      ]
+    );
+    assert_eq!(
+      opaque.calendar_properties,
+      vec!["X-CAL:value\r\n".to_string()]
+    );
+    assert!(opaque.calendar_components.is_empty());
+
+    let mut data = "BEGIN:VTODO\r\nUID:1\r\nEND:VTODO\r\n".to_string();
+    let () = splice_opaque(&mut data, &opaque);
+    assert_eq!(scan_opaque(&data, &["UID"]), opaque);
+  }
+
+  /// Check that we extract calendar level components, such as time zone
+  /// definitions, but ignore additional TODOs.
+  #[test]
+  fn opaque_calendar_component_scanning() {
+    let timezone = "BEGIN:VTIMEZONE\r\nTZID:Europe/Berlin\r\n\
+                    BEGIN:STANDARD\r\nDTSTART:19701025T030000\r\n\
+                    TZOFFSETFROM:+0200\r\nTZOFFSETTO:+0100\r\nEND:STANDARD\r\n\
+                    END:VTIMEZONE\r\n";
+    let data = format!(
+      "BEGIN:VCALENDAR\r\nPRODID:other\r\n{timezone}\
+       BEGIN:VTODO\r\nUID:1\r\nEND:VTODO\r\n\
+       BEGIN:VTODO\r\nUID:2\r\nX-FOO:bar\r\nEND:VTODO\r\n\
+       END:VCALENDAR\r\n"
+    );
+    let opaque = scan_opaque(&data, &["UID"]);
+    assert!(opaque.properties.is_empty());
+    assert!(opaque.components.is_empty());
+    assert!(opaque.calendar_properties.is_empty());
+    assert_eq!(opaque.calendar_components, vec![timezone.to_string()]);
+
+    let mut data =
+      "BEGIN:VCALENDAR\r\nBEGIN:VTODO\r\nUID:1\r\nEND:VTODO\r\nEND:VCALENDAR\r\n".to_string();
+    let () = splice_opaque(&mut data, &opaque);
+    assert!(data.contains(&format!("{timezone}BEGIN:VTODO")), "{data}");
+    assert_eq!(scan_opaque(&data, &["UID"]), opaque);
+  }
 }
@@ -9,5 +9,7 @@ mod ical;
 mod json;
 
 pub use backend::Backend;
+pub(crate) use ical::categories_to_tags;
+pub(crate) use ical::tags_to_categories;
 pub use ical::iCal;
 pub use json::Json;
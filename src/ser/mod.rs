@@ -6,6 +6,7 @@
 pub mod backends;
 pub mod id;
 pub mod state;
+pub mod sync;
 pub mod tags;
 pub mod tasks;
 pub mod view;
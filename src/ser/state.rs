@@ -37,16 +37,17 @@ pub struct UiState {
   pub selected_tasks: Vec<Option<usize>>,
   #[serde(default, skip_serializing_if = "Option::is_none")]
   pub selected_view: Option<usize>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub collapsed_sections: Vec<Vec<Option<String>>>,
 }
 
 
 /// A struct comprising the task state of the program.
 ///
-/// Note that this type is not actually serialized or deserialized in
-/// this form directly. It merely acts as a way of grouping
-/// functionality that is frequently used alongside each other.
-///
-#[derive(Debug, Default, PartialEq)]
+/// Note that this type is not persisted in this form directly. Aside
+/// from grouping functionality that is frequently used alongside each
+/// other, it is used for exporting and importing all tasks at once.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
 pub struct TaskState {
   /// Meta data about tasks.
   pub tasks_meta: TasksMeta,
@@ -0,0 +1,57 @@
+// Copyright (C) 2025 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! A module providing serialization and deserialization support for
+//! CalDAV synchronization related configuration and state.
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::ser::tasks::Id as TaskId;
+
+
+/// The configuration describing how to synchronize tasks with a CalDAV
+/// server.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+pub struct SyncConfig {
+  /// The URL of the CalDAV calendar collection to synchronize with.
+  pub url: String,
+  /// The user name to use for authentication, if any.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub username: Option<String>,
+  /// The password to use for authentication, if any.
+  ///
+  /// Note that the password is stored in plain text.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub password: Option<String>,
+  /// A shell command printing the password to use for authentication,
+  /// as an alternative to storing it in `password`.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub password_command: Option<String>,
+}
+
+
+/// The synchronization state of a single item, as recorded after the
+/// last successful synchronization.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct SyncItem {
+  /// The path of the item inside the remote collection.
+  pub href: String,
+  /// The entity tag the server reported for the item, if any.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub etag: Option<String>,
+  /// A fingerprint of the iCal representation of the local version of
+  /// the item.
+  pub fingerprint: u64,
+}
+
+
+/// The state of synchronization with a CalDAV server.
+#[derive(Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+pub struct SyncState {
+  /// The synchronization state of all items, indexed by their ID.
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub items: BTreeMap<TaskId, SyncItem>,
+}
@@ -4,6 +4,7 @@
 //! A module providing serialization and deserialization support for
 //! task templates and tags.
 
+use std::collections::HashMap;
 use std::fmt::Display;
 use std::fmt::Formatter;
 use std::fmt::Result as FmtResult;
@@ -18,7 +19,7 @@ use serde::Serialize;
 use crate::ser::id::Id as IdT;
 
 
-#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct T(());
 
 pub type Id = IdT<T>;
@@ -33,13 +34,12 @@ const TEMPLATE_COMPONENT_SEPARATOR: char = ',';
 ///
 /// Objects of this type are used to describe what a tag looks like and
 /// are the form in which the concept of a particular tag is persisted.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
 pub struct Template {
   pub id: Id,
-  // TODO: Our `Formula` actually imposes restrictions on what can
-  //       constitute a tag ("variable"). We should probably do the same
-  //       here or we risk allowing templates that can never be
-  //       instantiated.
+  // Names of newly created templates adhere to the variable grammar
+  // of `Formula`, but templates persisted earlier or created by other
+  // programs may not. Those have to be quoted in formulas.
   pub name: String,
 }
 
@@ -97,9 +97,53 @@ impl Display for Tag {
 
 /// A container of `Template` objects that we can deserialize into and
 /// serialize from.
-#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
 pub struct Templates(pub Vec<Template>);
 
+impl Templates {
+  /// Merge `other` templates into this object, matching them up by name.
+  ///
+  /// Tag IDs are allocated locally and two diverging sets of templates
+  /// may use the same ID for different names or different IDs for the
+  /// same name. Hence, the name is what identifies a template here.
+  /// Templates with a name not known yet are added, keeping their ID
+  /// if it is still available and receiving a new one otherwise.
+  ///
+  /// The function returns a mapping from each ID in `other` to the ID
+  /// of the corresponding template in `self`.
+  pub fn merge(&mut self, other: Templates) -> HashMap<Id, Id> {
+    let mut other = other.0;
+    // Process templates in a well-defined order, so that ID allocation
+    // is deterministic.
+    let () = other.sort_by_key(|template| template.id);
+
+    let mut ids = HashMap::new();
+    for template in other {
+      let id = if let Some(known) = self.0.iter().find(|t| t.name == template.name) {
+        known.id
+      } else if !self.0.iter().any(|t| t.id == template.id) {
+        let id = template.id;
+        let () = self.0.push(template.clone());
+        id
+      } else {
+        let max = self.0.iter().map(|t| t.id.get()).max().unwrap_or(0);
+        // SANITY: We never get anywhere close to exhausting the ID
+        //         space.
+        let id = Id::try_from(max + 1).unwrap();
+        let () = self.0.push(Template {
+          id,
+          name: template.name.clone(),
+        });
+        id
+      };
+      let _prev = ids.insert(template.id, id);
+    }
+
+    let () = self.0.sort_by_key(|template| template.id);
+    ids
+  }
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -137,6 +181,72 @@ mod tests {
     assert_eq!(parsed, tag);
   }
 
+  /// Check that templates are merged by name and that conflicting IDs
+  /// are reassigned.
+  #[test]
+  fn merge_templates() {
+    fn template(id: usize, name: &str) -> Template {
+      Template {
+        id: Id::try_from(id).unwrap(),
+        name: name.to_string(),
+      }
+    }
+
+    let mut templates = Templates(vec![template(1, "a"), template(2, "b")]);
+    let other = Templates(vec![
+      template(4, "d"),
+      template(1, "b"),
+      template(2, "c"),
+      template(3, "a"),
+    ]);
+    let ids = templates.merge(other);
+
+    let expected = Templates(vec![
+      template(1, "a"),
+      template(2, "b"),
+      template(3, "c"),
+      template(4, "d"),
+    ]);
+    assert_eq!(templates, expected);
+
+    let id = |id| Id::try_from(id).unwrap();
+    assert_eq!(ids.len(), 4);
+    assert_eq!(ids[&id(1)], id(2));
+    assert_eq!(ids[&id(2)], id(3));
+    assert_eq!(ids[&id(3)], id(1));
+    assert_eq!(ids[&id(4)], id(4));
+  }
+
+  /// Check that merging templates is symmetric with respect to the set
+  /// of names known afterwards.
+  #[test]
+  fn merge_templates_symmetric() {
+    let names = |templates: &Templates| {
+      let mut names = templates
+        .0
+        .iter()
+        .map(|template| template.name.clone())
+        .collect::<Vec<_>>();
+      let () = names.sort();
+      names
+    };
+    let template = |id: usize, name: &str| Template {
+      id: Id::try_from(id).unwrap(),
+      name: name.to_string(),
+    };
+
+    let one = Templates(vec![template(1, "x"), template(2, "y")]);
+    let two = Templates(vec![template(1, "y"), template(2, "z")]);
+
+    let mut merged1 = one.clone();
+    let _ids = merged1.merge(two.clone());
+    let mut merged2 = two;
+    let _ids = merged2.merge(one);
+
+    assert_eq!(names(&merged1), ["x", "y", "z"]);
+    assert_eq!(names(&merged1), names(&merged2));
+  }
+
   /// Check that we can serialize and deserialize a `Tag`.
   #[test]
   fn serialize_deserialize_tag() {
@@ -4,8 +4,18 @@
 //! A module providing serialization and deserialization support for
 //! task objects.
 
+use std::collections::HashMap;
+
+use chrono::DateTime;
+use chrono::Utc;
+
+use serde::Deserialize;
+use serde::Serialize;
+
 use uuid::Uuid;
 
+use crate::date::Date;
+use crate::ser::tags::Id as TagId;
 use crate::ser::tags::Tag;
 use crate::ser::tags::Templates;
 
@@ -14,21 +24,111 @@ use crate::ser::tags::Templates;
 pub type Id = Uuid;
 
 
+/// The status of a task, mirroring what iCalendar supports.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Status {
+  /// The task needs action.
+  NeedsAction,
+  /// The task is in process.
+  InProcess,
+  /// The task got completed, optionally at the given time.
+  Completed(Option<DateTime<Utc>>),
+  /// The task got cancelled.
+  Cancelled,
+}
+
+impl Status {
+  /// Check whether the status indicates completion.
+  #[inline]
+  pub fn is_completed(&self) -> bool {
+    matches!(self, Self::Completed(..))
+  }
+}
+
+
+/// Data of a task that we do not interpret but preserve verbatim, such
+/// as properties and components set by other programs.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+pub struct Opaque {
+  /// Properties that we do not interpret, as (potentially folded)
+  /// iCalendar content lines.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub properties: Vec<String>,
+  /// Components nested in the task, in their iCalendar representation.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub components: Vec<String>,
+  /// Properties of the calendar containing the task, as (potentially
+  /// folded) iCalendar content lines.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub calendar_properties: Vec<String>,
+  /// Components of the calendar containing the task other than the
+  /// task itself, such as time zone definitions.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub calendar_components: Vec<String>,
+}
+
+impl Opaque {
+  /// Check whether there is no opaque data.
+  #[inline]
+  pub fn is_empty(&self) -> bool {
+    self.properties.is_empty()
+      && self.components.is_empty()
+      && self.calendar_properties.is_empty()
+      && self.calendar_components.is_empty()
+  }
+}
+
+
 /// A task that we deserialize into and serialize from.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct Task {
   /// The task's ID.
   pub id: Id,
   /// The task's summary.
   pub summary: String,
   /// Arbitrary (and optional) details (such as notes) for the task.
+  #[serde(default, skip_serializing_if = "String::is_empty")]
   pub details: String,
   /// The task's list of currently set tags.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
   pub tags: Vec<Tag>,
+  /// The date at which the task is due, if any.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub due: Option<Date>,
+  /// The date at which work on the task is meant to start, if any.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub start: Option<Date>,
+  /// The time at which the task was created, if known.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub created: Option<DateTime<Utc>>,
+  /// The task's status, if known.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub status: Option<Status>,
+  /// Data of the task that we preserve without interpreting it.
+  #[serde(default, skip_serializing_if = "Opaque::is_empty")]
+  pub opaque: Opaque,
   /// The task's position.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
   pub position: Option<f64>,
 }
 
+impl Task {
+  /// Replace the IDs of the task's tags as per the provided mapping.
+  ///
+  /// Tags with an ID not contained in the mapping are left untouched.
+  pub fn remap_tags(&mut self, ids: &HashMap<TagId, TagId>) {
+    let mut tags = Vec::with_capacity(self.tags.len());
+    for tag in &self.tags {
+      let tag = Tag::from(*ids.get(&tag.id).unwrap_or(&tag.id));
+      if !tags.contains(&tag) {
+        let () = tags.push(tag);
+      }
+    }
+    self.tags = tags;
+  }
+}
+
 #[cfg(any(test, feature = "test"))]
 impl Task {
   /// Create a new task with the given summary and no tags.
@@ -41,6 +141,11 @@ impl Task {
       summary: summary.into(),
       details: Default::default(),
       tags: Default::default(),
+      due: None,
+      start: None,
+      created: None,
+      status: None,
+      opaque: Opaque::default(),
       position: None,
     }
   }
@@ -62,11 +167,29 @@ impl Task {
     self.tags = tags.into_iter().collect();
     self
   }
+
+  /// A convenience helper for setting the task's due date.
+  pub fn with_due(mut self, due: Date) -> Self {
+    self.due = Some(due);
+    self
+  }
+
+  /// A convenience helper for setting the task's start date.
+  pub fn with_start(mut self, start: Date) -> Self {
+    self.start = Some(start);
+    self
+  }
+
+  /// A convenience helper for setting the task's status.
+  pub fn with_status(mut self, status: Status) -> Self {
+    self.status = Some(status);
+    self
+  }
 }
 
 
 /// Meta data for tasks that we deserialize into and serialize from.
-#[derive(Debug, Default, PartialEq, Eq)]
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
 pub struct TasksMeta {
   /// The templates used by the corresponding tasks.
   pub templates: Templates,
@@ -74,7 +197,7 @@ pub struct TasksMeta {
 
 
 /// A struct comprising a list of tasks.
-#[derive(Debug, Default, PartialEq)]
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
 pub struct Tasks(pub Vec<Task>);
 
 #[cfg(test)]
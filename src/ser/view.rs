@@ -4,42 +4,23 @@
 //! A module providing serialization and deserialization support for
 //! task views.
 
-use std::fmt::Debug;
+use std::collections::BTreeMap;
+use std::fmt::Display;
 use std::fmt::Formatter;
 use std::fmt::Result as FmtResult;
-use std::str::FromStr as _;
+use std::str::FromStr;
 
+use anyhow::bail;
+use anyhow::Error;
+
+use serde::de::Error as _;
 use serde::Deserialize;
+use serde::Deserializer;
 use serde::Serialize;
+use serde::Serializer;
 
 use crate::formula::Formula;
-
-
-/// A literal that can be serialized and deserialized.
-#[derive(Clone, Deserialize, PartialEq, Eq, Serialize)]
-#[serde(rename_all = "lowercase")]
-pub enum TagLit {
-  Pos(String),
-  Neg(String),
-}
-
-impl Debug for TagLit {
-  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-    match self {
-      Self::Pos(tag) => write!(f, "{tag}"),
-      Self::Neg(tag) => write!(f, "!{tag}"),
-    }
-  }
-}
-
-impl TagLit {
-  /// Retrieve the name of the tag.
-  pub fn name(&self) -> &str {
-    match self {
-      TagLit::Pos(tag) | TagLit::Neg(tag) => tag,
-    }
-  }
-}
+use crate::ser::tasks::Id as TaskId;
 
 
 #[derive(Clone, Debug, Default)]
@@ -77,7 +58,6 @@ mod formula {
   use super::*;
 
   use serde::de::Error;
-  use serde::de::Unexpected;
   use serde::Deserialize;
   use serde::Deserializer;
   use serde::Serializer;
@@ -91,11 +71,8 @@ mod formula {
     let s = Option::<String>::deserialize(deserializer)?;
     let f = match s.as_deref() {
       Some(s) if !s.is_empty() => {
-        // TODO: By using what currently are implementation details it
-        //       would be possible to provide specifically the bits that
-        //       could not be parsed.
         let f = Formula::from_str(s)
-          .map_err(|_err| Error::invalid_value(Unexpected::Str(s), &"a logical formula"))?;
+          .map_err(|err| Error::custom(format!("invalid formula `{s}`: {err}")))?;
         Some(f)
       },
       _ => None,
@@ -118,75 +95,123 @@ mod formula {
 }
 
 
-/// A view that can be serialized and deserialized.
-#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
-pub struct View {
-  pub name: String,
-  #[serde(with = "formula")]
-  pub formula: FormulaPair,
+/// The prefix of a sort key referencing a tag.
+const TAG_PREFIX: &str = "tag:";
+/// The prefix of a sort key reversing the order.
+const DESCENDING_PREFIX: &str = "-";
+
+
+/// A criterion by which to sort the tasks of a view.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SortBy {
+  /// Sort by summary, ignoring case.
+  Summary,
+  /// Sort by presence of the tag with the given name, with tasks that
+  /// have it set coming first.
+  Tag(String),
+  /// Sort by completion state, with incomplete tasks coming first.
+  Completed,
+  /// Sort by creation time, with the oldest tasks coming first.
+  Created,
 }
 
 
-/// Convert a formula into an equivalent CNF form.
+/// A key by which to sort the tasks of a view.
 ///
-/// If a tag with value zero is encountered, `None` will be returned.
-pub(crate) fn formula_to_cnf(formula: Formula) -> Option<Box<[Box<[TagLit]>]>> {
-  fn rewrite(formula: Formula) -> Option<Vec<Vec<TagLit>>> {
-    match formula {
-      Formula::Var(a) => Some(vec![vec![TagLit::Pos(a)]]),
-      Formula::Not(a) => match *a {
-        Formula::Var(b) => Some(vec![vec![TagLit::Neg(b)]]),
-        // Remove double negation.
-        Formula::Not(b) => rewrite(*b),
-        // De Morgan: !(b & c) -> (!b | !c)
-        Formula::And(b, c) => rewrite(!b | !c),
-        // De Morgan: !(b | c) -> (!b & !c)
-        Formula::Or(b, c) => rewrite(!b & !c),
-      },
-      Formula::And(a, b) => {
-        let mut a = rewrite(*a)?;
-        let b = rewrite(*b)?;
-        let () = a.extend(b);
-        Some(a)
-      },
-      Formula::Or(a, b) => {
-        // rewrite(A) must have the form A1 ^ A2 ^ ... ^ Am, and
-        // rewrite(B) must have the form B1 ^ B2 ^ ... ^ Bn,
-        // where all the Ai and Bi are dijunctions of literals.
-        // So we need a CNF formula equivalent to
-        //    (A1 ^ A2 ^ ... ^ Am) v (B1 ^ B2 ^ ... ^ Bn).
-        // So return (A1 v B1) ^ (A1 v B2) ^ ... ^ (A1 v Bn)
-        //         ^ (A2 v B1) ^ (A2 v B2) ^ ... ^ (A2 v Bn)
-        //           ...
-        //         ^ (Am v B1) ^ (Am v B2) ^ ... ^ (Am v Bn)
-
-        // TODO: We risk exponential blow up here. Different algorithms
-        //       may do better. Tseytin transformation would guarantee
-        //       size linear with respect to the input formula.
-        let a = rewrite(*a)?;
-        let b = rewrite(*b)?;
-
-        let mut c = Vec::new();
-        for ax in &a {
-          for bx in &b {
-            let conjunctions = ax
-              .iter()
-              .cloned()
-              .chain(bx.iter().cloned())
-              .collect::<Vec<_>>();
-            let () = c.push(conjunctions);
-          }
-        }
-        Some(c)
+/// Keys are represented as strings, i.e., `summary`, `tag:<name>`,
+/// `completed`, or `created`, optionally prefixed with `-` to reverse
+/// the order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SortKey {
+  /// The criterion to sort by.
+  pub by: SortBy,
+  /// Whether to reverse the order.
+  pub descending: bool,
+}
+
+impl FromStr for SortKey {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (descending, key) = match s.strip_prefix(DESCENDING_PREFIX) {
+      Some(key) => (true, key),
+      None => (false, s),
+    };
+
+    let by = match key {
+      "summary" => SortBy::Summary,
+      "completed" => SortBy::Completed,
+      "created" => SortBy::Created,
+      _ => match key.strip_prefix(TAG_PREFIX) {
+        Some(name) if !name.is_empty() => SortBy::Tag(name.to_string()),
+        _ => bail!(
+          "invalid sort key `{s}`; expected `summary`, `tag:<name>`, `completed`, or `created`, optionally prefixed with `-`"
+        ),
       },
+    };
+    Ok(Self { by, descending })
+  }
+}
+
+impl Display for SortKey {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    if self.descending {
+      write!(f, "{DESCENDING_PREFIX}")?;
+    }
+
+    match &self.by {
+      SortBy::Summary => write!(f, "summary"),
+      SortBy::Tag(name) => write!(f, "{TAG_PREFIX}{name}"),
+      SortBy::Completed => write!(f, "completed"),
+      SortBy::Created => write!(f, "created"),
     }
   }
+}
 
-  let cnf = rewrite(formula)?
-    .into_iter()
-    .map(|vec| vec.into_boxed_slice())
-    .collect::<Box<[_]>>();
-  Some(cnf)
+impl Serialize for SortKey {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    serializer.collect_str(self)
+  }
+}
+
+impl<'de> Deserialize<'de> for SortKey {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    let s = String::deserialize(deserializer)?;
+    Self::from_str(&s).map_err(D::Error::custom)
+  }
+}
+
+
+/// A view that can be serialized and deserialized.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct View {
+  pub name: String,
+  #[serde(with = "formula")]
+  pub formula: FormulaPair,
+  /// The keys by which to sort the view's tasks, in order of priority.
+  ///
+  /// Tasks comparing equal with respect to all keys are kept in their
+  /// manual order.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub sort: Vec<SortKey>,
+  /// The positions of tasks in the view's own manual order, if it
+  /// orders tasks independently of other views.
+  ///
+  /// Tasks without an entry are ordered as per their global position.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub order: Option<BTreeMap<TaskId, f64>>,
+  /// The tags by which to group the view's tasks into sections.
+  ///
+  /// An entry ending in `*` matches all tags with names starting with
+  /// what precedes it, forming one section each.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub groups: Vec<String>,
 }
 
 
@@ -201,10 +226,18 @@ mod tests {
   /// Check that we can serialize and deserialize a `View`.
   #[test]
   fn serialize_deserialize_view() {
-    fn test(formula: FormulaPair) {
+    fn test(
+      formula: FormulaPair,
+      sort: Vec<SortKey>,
+      order: Option<BTreeMap<TaskId, f64>>,
+      groups: Vec<String>,
+    ) {
       let view = View {
         name: "test-view".to_string(),
         formula,
+        sort,
+        order,
+        groups,
       };
 
       let serialized = Json::serialize(&view).unwrap();
@@ -214,25 +247,46 @@ mod tests {
     }
 
     let formula = Formula::from_str("a & (b | !c) & (!d | b)").unwrap();
-    let () = test(FormulaPair::from(formula));
-    let () = test(FormulaPair::default());
+    let () = test(FormulaPair::from(formula), Vec::new(), None, Vec::new());
+    let sort = vec![
+      SortKey::from_str("-tag:a").unwrap(),
+      SortKey::from_str("summary").unwrap(),
+    ];
+    let order = BTreeMap::from([(TaskId::new_v4(), 1.5), (TaskId::new_v4(), -3.0)]);
+    let groups = vec!["a".to_string(), "prio-*".to_string()];
+    let () = test(FormulaPair::default(), sort, Some(order), groups);
+  }
+
+  /// Check that we can parse and format sort keys.
+  #[test]
+  fn parse_sort_key() {
+    let keys = ["summary", "-tag:a b", "completed", "-created"];
+    for key in keys {
+      assert_eq!(SortKey::from_str(key).unwrap().to_string(), key);
+    }
+
+    let key = SortKey::from_str("-tag:a").unwrap();
+    assert_eq!(key.by, SortBy::Tag("a".to_string()));
+    assert!(key.descending);
+
+    for key in ["", "-", "tag:", "summary ", "--summary", "position"] {
+      let err = SortKey::from_str(key).unwrap_err();
+      assert!(err
+        .to_string()
+        .starts_with(&format!("invalid sort key `{key}`")));
+    }
   }
 
-  /// Check that we can convert a formula into an equivalent CNF form.
+  /// Check that we report where parsing of an invalid formula failed.
   #[test]
-  fn formula_cnf_conversion() {
-    // Formula already in CNF, just not the right type.
-    let formula = (Formula::var("a") | !Formula::var("b") | !Formula::var("c"))
-      & (!Formula::var("d") | Formula::var("e"));
-    let expected = Box::from([
-      Box::from([
-        TagLit::Pos("a".to_string()),
-        TagLit::Neg("b".to_string()),
-        TagLit::Neg("c".to_string()),
-      ]),
-      Box::from([TagLit::Neg("d".to_string()), TagLit::Pos("e".to_string())]),
-    ]);
-    let cnf = formula_to_cnf(formula).unwrap();
-    assert_eq!(cnf, expected);
+  fn deserialize_invalid_formula() {
+    let serialized = br#"{"name": "test-view", "formula": "a && b"}"#;
+    let err = <Json as Backend<View>>::deserialize(serialized).unwrap_err();
+    assert!(
+      err
+        .to_string()
+        .starts_with("invalid formula `a && b`: unexpected `&` at offset 3; expected tag"),
+      "{err}"
+    );
   }
 }
@@ -4,28 +4,44 @@
 //! Definitions pertaining UI configuration and task state of the
 //! program.
 
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
 use std::collections::HashSet;
+use std::error::Error as StdError;
 use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::fs::Metadata;
 use std::io::ErrorKind;
+use std::mem::take;
 use std::path::Path;
+use std::path::PathBuf;
 use std::rc::Rc;
+use std::time::SystemTime;
 
 use anyhow::anyhow;
 use anyhow::Context as _;
+use anyhow::Error;
 use anyhow::Result;
 
+use crate::ser::backends::categories_to_tags;
 use crate::ser::backends::iCal;
+use crate::ser::backends::tags_to_categories;
 use crate::ser::backends::Backend;
+use crate::ser::backends::Json;
 
 use tokio::fs::create_dir_all;
+use tokio::fs::metadata;
+use tokio::fs::read;
 use tokio::fs::read_dir;
-use tokio::fs::remove_file;
 use tokio::fs::DirEntry;
 use tokio::fs::File;
-use tokio::fs::OpenOptions;
 use tokio::fs::ReadDir;
 use tokio::io::AsyncReadExt as _;
-use tokio::io::AsyncWriteExt as _;
 
 use uuid::uuid;
 
@@ -33,6 +49,7 @@ use crate::cap::DirCap;
 use crate::cap::FileCap;
 use crate::cap::WriteGuard;
 use crate::ser::state::TaskState as SerTaskState;
+use crate::ser::tags::Templates as SerTemplates;
 use crate::ser::tasks::Id as SerTaskId;
 use crate::ser::tasks::Task as SerTask;
 use crate::ser::tasks::Tasks as SerTasks;
@@ -40,11 +57,40 @@ use crate::ser::tasks::TasksMeta as SerTasksMeta;
 use crate::ser::ToSerde;
 use crate::tags::Templates;
 use crate::tasks::Tasks;
+use crate::transaction::is_transaction_file;
+use crate::transaction::Transaction;
+
+/// The ID that was used for storing task meta data as part of the
+/// tasks themselves.
+// We used the "special" UUID 00000000-0000-0000-0000-000000000000 for
+// storing task meta data. Such files are still read, but no longer
+// written.
+pub(crate) const TASKS_META_ID: SerTaskId = uuid!("00000000-0000-0000-0000-000000000000");
+/// The name of the directory inside the tasks directory that contains
+/// data that are not part of the task collection itself.
+///
+/// Programs synchronizing the collection do not descend into
+/// directories and so its contents are kept local.
+pub(crate) const TASKS_META_DIR: &str = ".notnow";
+/// The name of the file inside `TASKS_META_DIR` storing task meta data.
+const TASKS_META_FILE: &str = "tasks-meta.json";
 
-/// The ID we use for storing task meta data.
-// We use the "special" UUID 00000000-0000-0000-0000-000000000000 for
-// storing task meta data.
-const TASKS_META_ID: SerTaskId = uuid!("00000000-0000-0000-0000-000000000000");
+
+/// Retrieve the path to the file storing task meta data, relative to
+/// the tasks directory.
+fn tasks_meta_path() -> PathBuf {
+  Path::new(TASKS_META_DIR).join(TASKS_META_FILE)
+}
+
+/// Calculate a hash of the provided data.
+///
+/// We use the 64 bit FNV-1a hash function, because contrary to
+/// `DefaultHasher` its results are guaranteed to be stable.
+pub(crate) fn content_hash(data: &[u8]) -> u64 {
+  data.iter().fold(0xcbf29ce484222325, |hash, byte| {
+    (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+  })
+}
 
 
 /// Load some serialized state from a file.
@@ -67,6 +113,15 @@ where
   }
 }
 
+/// Check whether a directory entry represents a directory.
+async fn is_dir(entry: &DirEntry) -> Result<bool> {
+  let file_type = entry
+    .file_type()
+    .await
+    .with_context(|| format!("failed to inquire file type of {}", entry.path().display()))?;
+  Ok(file_type.is_dir())
+}
+
 /// Load a task from a directory entry.
 async fn load_task_from_dir_entry(entry: &DirEntry) -> Result<Option<SerTask>> {
   let file_name = entry.file_name();
@@ -110,7 +165,15 @@ async fn load_tasks_from_read_dir(dir: ReadDir) -> Result<(Vec<SerTask>, Option<
     .await
     .context("failed to iterate directory contents")?
   {
-    if entry.file_name() == OsStr::new(tasks_meta_uuid) {
+    if is_transaction_file(&entry.file_name()) {
+      // Left overs of an interrupted save operation are of no concern
+      // to us.
+      continue
+    } else if is_dir(&entry).await? {
+      // Directories do not contain tasks, but may contain our meta
+      // data.
+      continue
+    } else if entry.file_name() == OsStr::new(tasks_meta_uuid) {
       debug_assert_eq!(
         tasks_meta, None,
         "encountered multiple task meta data files"
@@ -134,8 +197,25 @@ async fn load_tasks_from_dir(root: &Path) -> Result<SerTaskState> {
     result => result,
   }?;
 
-  let (tasks, tasks_meta) = load_tasks_from_read_dir(dir).await?;
-  let tasks_meta = tasks_meta.unwrap_or_default();
+  let (mut tasks, legacy_tasks_meta) = load_tasks_from_read_dir(dir).await?;
+  let path = root.join(tasks_meta_path());
+  let mut tasks_meta = load_state_from_file::<Json, SerTasksMeta>(&path)
+    .await
+    .with_context(|| format!("failed to load task meta data from {}", path.display()))?
+    .unwrap_or_default();
+  // Task meta data may still be stored as part of the tasks, e.g.,
+  // because it was written by an earlier version of the program. It
+  // will be moved on the next save.
+  // Tags stored by ID in such tasks refer to its templates.
+  if let Some(legacy_tasks_meta) = legacy_tasks_meta {
+    let ids = tasks_meta.templates.merge(legacy_tasks_meta.templates);
+    let () = tasks.iter_mut().for_each(|task| task.remap_tags(&ids));
+  }
+
+  for task in tasks.iter_mut() {
+    let () = categories_to_tags(task, &mut tasks_meta.templates)
+      .with_context(|| format!("failed to resolve tags of task {}", task.id))?;
+  }
 
   Ok(SerTaskState {
     tasks_meta,
@@ -154,8 +234,13 @@ where
     .map(|existing| existing.as_ref() != Some(state))
 }
 
-/// Save some state into a file.
-pub(crate) async fn save_state_to_file<B, T>(file_cap: &mut FileCap<'_>, state: &T) -> Result<()>
+/// Stage saving of some state into a file as part of the provided
+/// transaction.
+pub(crate) async fn stage_state_to_file<B, T>(
+  transaction: &mut Transaction,
+  file_cap: &mut FileCap<'_>,
+  state: &T,
+) -> Result<()>
 where
   B: Backend<T>,
   T: PartialEq,
@@ -168,145 +253,352 @@ where
     return Ok(())
   }
 
-  if let Some(dir) = path.parent() {
-    let () = create_dir_all(dir).await?;
-  }
-
   let serialized = B::serialize(state)?;
+  transaction.write(file_cap, serialized.as_ref()).await
+}
 
-  let () = file_cap
-    .with_writeable_path(|path| async {
-      let () = OpenOptions::new()
-        .create(true)
-        .truncate(true)
-        .write(true)
-        .open(path)
-        .await?
-        .write_all(serialized.as_ref())
-        .await?;
-      Ok(())
-    })
-    .await?;
-
-  Ok(())
+/// Save some state into a file.
+///
+/// The file is replaced atomically, i.e., it will contain either the
+/// previous or the new state, even if the program is interrupted.
+pub(crate) async fn save_state_to_file<B, T>(file_cap: &mut FileCap<'_>, state: &T) -> Result<()>
+where
+  B: Backend<T>,
+  T: PartialEq,
+{
+  let mut transaction = Transaction::new();
+  let () = stage_state_to_file::<B, T>(&mut transaction, file_cap, state).await?;
+  transaction.commit().await
 }
 
 /// Check whether we should save a task.
-async fn should_save_task(dir: &Path, task: &SerTask) -> Result<bool> {
+async fn should_save_task(dir: &Path, task: &SerTask, templates: &SerTemplates) -> Result<bool> {
   let path = dir.join(OsStr::new(&task.id.to_string()));
-  should_save_state::<iCal, _>(&path, task).await
+  let task = tags_to_categories(task, templates);
+  should_save_state::<iCal, _>(&path, &task).await
 }
 
-/// Save a task into a file in the given directory.
-async fn save_task_to_file(write_guard: &mut WriteGuard<'_>, task: &SerTask) -> Result<()> {
+/// Stage saving of a task into a file in the given directory.
+///
+/// The task's tags are stored as categories, by name, so that they are
+/// meaningful to other programs as well.
+async fn stage_task_to_file(
+  transaction: &mut Transaction,
+  write_guard: &WriteGuard<'_>,
+  task: &SerTask,
+  templates: &SerTemplates,
+) -> Result<()> {
   let mut file_cap = write_guard.file_cap(OsStr::new(&task.id.to_string()));
-  // TODO: It would be better if we were create a temporary file first
-  //       if one already exists and then rename atomically. But even
-  //       nicer would be if we somehow wrapped all saving in a
-  //       transaction of sorts. That would allow us to eliminate the
-  //       chance for *any* inconsistency, e.g., when saving UI
-  //       configuration before task state and the latter failing the
-  //       operation.
-  save_state_to_file::<iCal, _>(&mut file_cap, task).await
+  let task = tags_to_categories(task, templates);
+  stage_state_to_file::<iCal, _>(transaction, &mut file_cap, &task).await
 }
 
 /// Check whether we should save some tasks meta state.
 async fn should_save_tasks_meta(dir: &Path, tasks_meta: &SerTasksMeta) -> Result<bool> {
-  let path = dir.join(OsStr::new(&TASKS_META_ID.to_string()));
-  should_save_state::<iCal, _>(&path, tasks_meta).await
+  let path = dir.join(tasks_meta_path());
+  should_save_state::<Json, _>(&path, tasks_meta).await
 }
 
-/// Save a task meta data into a file in the provided directory.
-async fn save_tasks_meta_to_dir(
-  write_guard: &mut WriteGuard<'_>,
+/// Stage saving of task meta data into a file in the provided
+/// directory.
+///
+/// The meta data is kept outside of the task collection, so that it
+/// does not show up as a task in other programs.
+async fn stage_tasks_meta_to_dir(
+  transaction: &mut Transaction,
+  write_guard: &WriteGuard<'_>,
   tasks_meta: &SerTasksMeta,
 ) -> Result<()> {
-  let mut file_cap = write_guard.file_cap(OsStr::new(&TASKS_META_ID.to_string()));
-  save_state_to_file::<iCal, _>(&mut file_cap, tasks_meta).await
+  let () = create_dir_all(write_guard.path().join(TASKS_META_DIR)).await?;
+  let mut file_cap = write_guard.file_cap(tasks_meta_path().as_os_str());
+  stage_state_to_file::<Json, _>(transaction, &mut file_cap, tasks_meta).await
 }
 
-/// Check whether we should save task state.
-// TODO: The way we "walk" the task tree is duplicated with
-//       `save_tasks_to_dir`, but it's unclear how to deduplicate.
-async fn should_save_tasks(dir: &Path, tasks: &SerTaskState) -> Result<bool> {
-  for task in tasks.tasks.0.iter() {
-    if should_save_task(dir, task).await? {
-      return Ok(true)
-    }
-  }
-
-  if should_save_tasks_meta(dir, &tasks.tasks_meta).await? {
-    return Ok(true)
-  }
-
-  let ids = tasks
-    .tasks
-    .0
-    .iter()
-    .map(|task| task.id)
-    .collect::<HashSet<_>>();
+/// Find the files in the given tasks directory that do not correspond
+/// to any of the provided tasks and that should be removed.
+async fn find_stale_files(dir: &Path, tasks: &SerTasks) -> Result<Vec<OsString>> {
+  let ids = tasks.0.iter().map(|task| task.id).collect::<HashSet<_>>();
 
-  // Check whether there are files that do not correspond to a task we
-  // manage and which should be removed.
+  let mut stale = Vec::new();
   let mut dir = read_dir(dir).await?;
   while let Some(entry) = dir.next_entry().await? {
+    // Directories are not part of the task collection and we only ever
+    // create one for storing meta data.
+    if is_transaction_file(&entry.file_name()) || is_dir(&entry).await? {
+      continue
+    }
+
     let id = entry
       .file_name()
       .to_str()
       .and_then(|id| SerTaskId::try_parse(id).ok());
 
+    // Note that task meta data stored by earlier versions of the
+    // program is removed as well, as it moved elsewhere.
     let remove = if let Some(id) = id {
-      id != TASKS_META_ID && !ids.contains(&id)
+      !ids.contains(&id)
     } else {
       true
     };
 
     if remove {
+      let () = stale.push(entry.file_name());
+    }
+  }
+  Ok(stale)
+}
+
+/// Check whether we should save task state.
+async fn should_save_tasks(dir: &Path, tasks: &SerTaskState) -> Result<bool> {
+  for task in tasks.tasks.0.iter() {
+    if should_save_task(dir, task, &tasks.tasks_meta.templates).await? {
       return Ok(true)
     }
   }
-  Ok(false)
+
+  if should_save_tasks_meta(dir, &tasks.tasks_meta).await? {
+    return Ok(true)
+  }
+
+  let stale = find_stale_files(dir, &tasks.tasks).await?;
+  Ok(!stale.is_empty())
 }
 
-/// Save tasks into files in the provided directory.
-async fn save_tasks_to_dir(dir_cap: &mut DirCap, tasks: &SerTaskState) -> Result<()> {
-  let mut write_guard = dir_cap.write().await?;
+/// Stage saving of tasks into files in the provided directory.
+async fn stage_tasks_to_dir(
+  transaction: &mut Transaction,
+  write_guard: &WriteGuard<'_>,
+  tasks: &SerTaskState,
+) -> Result<()> {
+  let () = create_dir_all(write_guard.path()).await?;
+
+  // Remove all files that do not correspond to a task we are about to
+  // save. Note that we do this before staging any writes, so that we
+  // don't have to deal with the files created for them.
+  for file in find_stale_files(write_guard.path(), &tasks.tasks).await? {
+    let () = transaction.remove(&write_guard.file_cap(&file));
+  }
 
   for task in tasks.tasks.0.iter() {
-    let () = save_task_to_file(&mut write_guard, task).await?;
+    let () =
+      stage_task_to_file(transaction, write_guard, task, &tasks.tasks_meta.templates).await?;
   }
 
-  let () = save_tasks_meta_to_dir(&mut write_guard, &tasks.tasks_meta).await?;
-  let ids = tasks
+  stage_tasks_meta_to_dir(transaction, write_guard, &tasks.tasks_meta).await
+}
+
+
+/// The modification time and content hash of a file in the tasks
+/// directory or of the task meta data file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Stamp {
+  /// The time the file was last modified, if known.
+  modified: Option<SystemTime>,
+  /// A hash of the file's content.
+  hash: u64,
+}
+
+
+/// Record a stamp for the file at `path`.
+///
+/// If the file's modification time equals the one of the `known` stamp,
+/// the latter is reused, without reading the file's content again.
+async fn stamp_file(path: &Path, metadata: &Metadata, known: Option<&Stamp>) -> Result<Stamp> {
+  let modified = metadata.modified().ok();
+  let stamp = match known {
+    Some(stamp) if modified.is_some() && stamp.modified == modified => *stamp,
+    _ => {
+      let content = read(path)
+        .await
+        .with_context(|| format!("failed to read {}", path.display()))?;
+      Stamp {
+        modified,
+        hash: content_hash(&content),
+      }
+    },
+  };
+  Ok(stamp)
+}
+
+
+/// Record stamps for all files in a tasks directory, including the task
+/// meta data file.
+///
+/// Stamps are keyed by the path of the file relative to `root`. Stamps
+/// of files with a modification time equal to the one recorded in
+/// `known` are reused, without reading the file's content again.
+async fn stamp_dir(
+  root: &Path,
+  known: &BTreeMap<OsString, Stamp>,
+) -> Result<BTreeMap<OsString, Stamp>> {
+  let mut dir = match read_dir(root).await {
+    Err(e) if e.kind() == ErrorKind::NotFound => return Ok(BTreeMap::new()),
+    result => result,
+  }?;
+
+  let mut files = BTreeMap::new();
+  while let Some(entry) = dir
+    .next_entry()
+    .await
+    .context("failed to iterate directory contents")?
+  {
+    let file_name = entry.file_name();
+    if is_transaction_file(&file_name) || is_dir(&entry).await? {
+      continue
+    }
+
+    let path = entry.path();
+    let metadata = entry
+      .metadata()
+      .await
+      .with_context(|| format!("failed to retrieve metadata of {}", path.display()))?;
+    let stamp = stamp_file(&path, &metadata, known.get(&file_name)).await?;
+    let _prev = files.insert(file_name, stamp);
+  }
+
+  // Task meta data are stored in a sub-directory, but are just as much
+  // subject to modification by other programs.
+  let meta_path = tasks_meta_path().into_os_string();
+  let path = root.join(&meta_path);
+  match metadata(&path).await {
+    Ok(metadata) => {
+      let stamp = stamp_file(&path, &metadata, known.get(&meta_path)).await?;
+      let _prev = files.insert(meta_path, stamp);
+    },
+    Err(err) if err.kind() == ErrorKind::NotFound => (),
+    Err(err) => {
+      return Err(err)
+        .with_context(|| format!("failed to retrieve metadata of {}", path.display()))
+    },
+  }
+  Ok(files)
+}
+
+
+/// Determine the names of files that differ in content between two
+/// sets of stamps, including files present in only one of them.
+fn changed_files(
+  old: &BTreeMap<OsString, Stamp>,
+  new: &BTreeMap<OsString, Stamp>,
+) -> BTreeSet<OsString> {
+  old
+    .keys()
+    .chain(new.keys())
+    .filter(|file| {
+      let old = old.get(*file).map(|stamp| stamp.hash);
+      let new = new.get(*file).map(|stamp| stamp.hash);
+      old != new
+    })
+    .cloned()
+    .collect()
+}
+
+
+/// Make `state` use the tag IDs of `templates`.
+///
+/// Tag IDs are allocated locally and so a task state loaded from disk
+/// may use IDs different from ours, e.g., because another instance of
+/// the program created tags concurrently. Templates are matched up by
+/// name and the tags of all tasks are adjusted accordingly. Afterwards,
+/// the templates of `state` are a superset of `templates`.
+fn rebase_task_state(mut templates: SerTemplates, state: &mut SerTaskState) {
+  let ids = templates.merge(take(&mut state.tasks_meta.templates));
+  let () = state
+    .tasks
+    .0
+    .iter_mut()
+    .for_each(|task| task.remap_tags(&ids));
+  state.tasks_meta.templates = templates;
+}
+
+
+/// Merge tasks changed by another program into a local task state.
+///
+/// For tasks with an ID contained in `changed` the version from
+/// `remote` is used (which may mean removing them), unless they were
+/// changed locally since `base` as well, in which case the local version
+/// wins. `remote` and `base` are expected to use the tag IDs of `local`
+/// already (see [`rebase_task_state`]).
+fn merge_task_states(
+  base: &SerTaskState,
+  local: SerTaskState,
+  remote: SerTaskState,
+  changed: &HashSet<SerTaskId>,
+) -> SerTaskState {
+  let SerTaskState {
+    tasks_meta: _,
+    tasks: SerTasks(local),
+  } = local;
+  let SerTaskState {
+    tasks_meta,
+    tasks: remote,
+  } = remote;
+
+  let base = base
     .tasks
     .0
     .iter()
-    .map(|task| task.id)
-    .collect::<HashSet<_>>();
+    .map(|task| (task.id, task))
+    .collect::<HashMap<_, _>>();
+  let mut remote = remote
+    .0
+    .into_iter()
+    .filter(|task| changed.contains(&task.id))
+    .map(|task| (task.id, task))
+    .collect::<BTreeMap<_, _>>();
+
+  let mut tasks = local
+    .into_iter()
+    .filter_map(|task| {
+      let remote = remote.remove(&task.id);
+      if !changed.contains(&task.id) || base.get(&task.id) != Some(&&task) {
+        Some(task)
+      } else {
+        remote
+      }
+    })
+    .collect::<Vec<_>>();
+  // Everything left over was either added externally or removed locally
+  // while being modified externally. In both cases we keep the task, so
+  // as to not lose any data.
+  let () = tasks.extend(remote.into_values());
 
-  // Remove all files that do not correspond to an ID we just saved.
-  let mut dir = read_dir(write_guard.path()).await?;
-  while let Some(entry) = dir.next_entry().await? {
-    let id = entry
-      .file_name()
-      .to_str()
-      .and_then(|id| SerTaskId::try_parse(id).ok());
+  SerTaskState {
+    tasks_meta,
+    tasks: SerTasks(tasks),
+  }
+}
 
-    let remove = if let Some(id) = id {
-      id != TASKS_META_ID && !ids.contains(&id)
-    } else {
-      true
-    };
 
-    if remove {
-      // Note that we purposefully do not support the case of having a
-      // directory inside the root directory, as we'd never create one
-      // there programmatically.
-      let () = remove_file(entry.path()).await?;
-    }
+/// An error indicating that the tasks directory was modified by another
+/// program since we last loaded or saved tasks.
+#[derive(Debug)]
+pub struct Conflict {
+  /// The names of the files that were modified, added, or removed.
+  files: Vec<OsString>,
+}
+
+impl Display for Conflict {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    write!(
+      f,
+      "{} task file(s) were modified externally",
+      self.files.len()
+    )
   }
+}
+
+impl StdError for Conflict {}
+
 
-  Ok(())
+/// A record of the files in the tasks directory along with the task
+/// state they contained, as of the last time we loaded or saved tasks.
+#[derive(Debug)]
+struct Snapshot {
+  /// The stamps of all files in the directory.
+  files: BTreeMap<OsString, Stamp>,
+  /// The task state represented by the files.
+  state: SerTaskState,
 }
 
 
@@ -317,26 +609,35 @@ pub struct TaskState {
   templates: Rc<Templates>,
   /// The shared task database.
   tasks: Rc<Tasks>,
+  /// A snapshot of the tasks directory, used for detecting
+  /// modifications made by other programs.
+  ///
+  /// The snapshot is absent when the state was not loaded from or saved
+  /// to a directory yet.
+  snapshot: RefCell<Option<Snapshot>>,
 }
 
 impl TaskState {
   /// Load `TaskState` from a directory.
   pub async fn load(tasks_root: &Path) -> Result<Self> {
-    let task_state = load_tasks_from_dir(tasks_root).await.with_context(|| {
+    let context = || {
       format!(
         "failed to load tasks from directory {}",
         tasks_root.display()
       )
-    })?;
-
-    let templates = Templates::with_serde(task_state.tasks_meta.templates)
-      .map_err(|id| anyhow!("encountered duplicate tag ID {id}"))?;
-    let templates = Rc::new(templates);
-    let tasks = Tasks::with_serde(task_state.tasks, Rc::clone(&templates))
-      .context("failed to instantiate task database")?;
-    let tasks = Rc::new(tasks);
+    };
+    // Stamp files before loading them: should they be modified in the
+    // meantime we err on the side of reporting a spurious conflict.
+    let files = stamp_dir(tasks_root, &BTreeMap::new())
+      .await
+      .with_context(context)?;
+    let state = load_tasks_from_dir(tasks_root)
+      .await
+      .with_context(context)?;
+    let snapshot = Snapshot { files, state };
 
-    let slf = Self { templates, tasks };
+    let slf = Self::with_serde(snapshot.state.clone())?;
+    *slf.snapshot.borrow_mut() = Some(snapshot);
     Ok(slf)
   }
 
@@ -351,10 +652,20 @@ impl TaskState {
     let slf = Self {
       templates,
       tasks: Rc::new(tasks),
+      snapshot: RefCell::new(None),
     };
     Ok(slf)
   }
 
+  /// Replace the tasks with the ones from the provided serialized state.
+  ///
+  /// Tag templates not known yet are added, but no existing ones are
+  /// removed, as they may still be referenced.
+  fn replace(&self, state: SerTaskState) -> Result<()> {
+    let () = self.templates.merge_serde(state.tasks_meta.templates);
+    self.tasks.replace(state.tasks)
+  }
+
 
   /// Check whether any of the tasks were changed from the state in the
   /// given `root_dir`.
@@ -364,9 +675,110 @@ impl TaskState {
       .unwrap_or(true)
   }
 
+  /// Determine the files in `root_dir` that were modified by another
+  /// program since we last loaded or saved tasks.
+  pub(crate) async fn external_changes(&self, root_dir: &Path) -> Result<BTreeSet<OsString>> {
+    let known = match self.snapshot.borrow().as_ref() {
+      Some(snapshot) => snapshot.files.clone(),
+      None => return Ok(BTreeSet::new()),
+    };
+    let files = stamp_dir(root_dir, &known).await?;
+    Ok(changed_files(&known, &files))
+  }
+
   /// Persist the state into a file.
   pub async fn save(&self, root_dir_cap: &mut DirCap) -> Result<()> {
-    save_tasks_to_dir(root_dir_cap, &self.to_serde()).await
+    let write_guard = root_dir_cap.write().await?;
+    let mut transaction = Transaction::new();
+    let () = self.stage(&mut transaction, &write_guard).await?;
+    let () = transaction.commit().await?;
+    self.mark_saved(write_guard.path()).await
+  }
+
+  /// Stage persisting of the state as part of the provided transaction.
+  ///
+  /// If the directory was modified by another program since we last
+  /// loaded or saved tasks, a [`Conflict`] error is reported.
+  pub(crate) async fn stage(
+    &self,
+    transaction: &mut Transaction,
+    write_guard: &WriteGuard<'_>,
+  ) -> Result<()> {
+    let changed = self.external_changes(write_guard.path()).await?;
+    if !changed.is_empty() {
+      let conflict = Conflict {
+        files: changed.into_iter().collect(),
+      };
+      return Err(Error::new(conflict))
+    }
+
+    stage_tasks_to_dir(transaction, write_guard, &self.to_serde()).await
+  }
+
+  /// Update our snapshot of the tasks directory after a successful save.
+  pub(crate) async fn mark_saved(&self, root_dir: &Path) -> Result<()> {
+    let known = self
+      .snapshot
+      .borrow()
+      .as_ref()
+      .map(|snapshot| snapshot.files.clone())
+      .unwrap_or_default();
+    let files = stamp_dir(root_dir, &known).await?;
+    let state = self.to_serde();
+    *self.snapshot.borrow_mut() = Some(Snapshot { files, state });
+    Ok(())
+  }
+
+  /// Replace all tasks with the ones stored in `root_dir`, discarding
+  /// any unsaved changes.
+  pub(crate) async fn reload(&self, root_dir: &Path) -> Result<()> {
+    let files = stamp_dir(root_dir, &BTreeMap::new()).await?;
+    let mut state = load_tasks_from_dir(root_dir).await?;
+    let () = rebase_task_state(self.templates.to_serde(), &mut state);
+    let () = self.replace(state.clone())?;
+    *self.snapshot.borrow_mut() = Some(Snapshot { files, state });
+    Ok(())
+  }
+
+  /// Merge modifications made by another program to the tasks stored in
+  /// `root_dir` into our state.
+  ///
+  /// Tasks changed on both ends retain their local version.
+  pub(crate) async fn merge(&self, root_dir: &Path) -> Result<()> {
+    let (known, base) = match self.snapshot.borrow_mut().take() {
+      Some(Snapshot { files, state }) => (files, state),
+      None => Default::default(),
+    };
+    let files = stamp_dir(root_dir, &BTreeMap::new()).await?;
+    let mut remote = load_tasks_from_dir(root_dir).await?;
+    let () = rebase_task_state(self.templates.to_serde(), &mut remote);
+    let changed = changed_files(&known, &files)
+      .iter()
+      .filter_map(|file| file.to_str().and_then(|id| SerTaskId::try_parse(id).ok()))
+      .collect::<HashSet<_>>();
+
+    let merged = merge_task_states(&base, self.to_serde(), remote.clone(), &changed);
+    let () = self.replace(merged)?;
+    *self.snapshot.borrow_mut() = Some(Snapshot {
+      files,
+      state: remote,
+    });
+    Ok(())
+  }
+
+  /// Accept the current contents of `root_dir` as they are, meaning
+  /// that a subsequent save will overwrite any modifications made by
+  /// other programs.
+  pub(crate) async fn accept_external_changes(&self, root_dir: &Path) -> Result<()> {
+    let known = match self.snapshot.borrow().as_ref() {
+      Some(snapshot) => snapshot.files.clone(),
+      None => return Ok(()),
+    };
+    let files = stamp_dir(root_dir, &known).await?;
+    if let Some(snapshot) = self.snapshot.borrow_mut().as_mut() {
+      snapshot.files = files;
+    }
+    Ok(())
   }
 
   /// Retrieve the `Templates` object associated with this `TaskState`
@@ -402,17 +814,23 @@ pub mod tests {
   use super::*;
 
   use std::env::temp_dir;
+  use std::fs::File as StdFile;
+  use std::ops::Deref as _;
+  use std::time::Duration;
 
   use tempfile::TempDir;
 
+  use tokio::fs::copy;
+  use tokio::fs::read_to_string;
   use tokio::fs::remove_dir_all;
+  use tokio::fs::write;
   use tokio::test;
 
   use crate::ser::backends::Json;
   use crate::ser::tags::Id as SerId;
   use crate::ser::tags::Tag as SerTag;
   use crate::ser::tags::Template as SerTemplate;
-  use crate::ser::tags::Templates as SerTemplates;
+  use crate::tasks::Task;
   use crate::test::make_tasks;
 
 
@@ -426,6 +844,29 @@ pub mod tests {
     task_state
   }
 
+  /// Retrieve the summaries of all tasks of a `TaskState` object.
+  fn summaries(task_state: &TaskState) -> Vec<String> {
+    task_state
+      .tasks()
+      .iter(|iter| iter.map(|task| task.summary()).collect())
+  }
+
+  /// Change the summary of the task at index `idx`.
+  fn set_summary(task_state: &TaskState, idx: usize, summary: &str) {
+    let tasks = task_state.tasks();
+    let task = tasks.iter(|mut iter| Rc::clone(iter.nth(idx).unwrap()));
+    let mut updated = Task::clone(task.deref());
+    let () = updated.set_summary(summary.to_string());
+    let () = tasks.update(task, updated);
+  }
+
+  /// Remove the task at index `idx`.
+  fn remove(task_state: &TaskState, idx: usize) {
+    let tasks = task_state.tasks();
+    let task = tasks.iter(|mut iter| Rc::clone(iter.nth(idx).unwrap()));
+    let () = tasks.remove(task);
+  }
+
   /// Check that we can save tasks into a directory and load them back
   /// from there.
   #[test]
@@ -437,11 +878,10 @@ pub mod tests {
         },
         tasks: SerTasks::from(tasks),
       };
-      let task_state = TaskState::with_serde(task_state).unwrap().to_serde();
+      let task_state = TaskState::with_serde(task_state).unwrap();
       let mut tasks_root_cap = DirCap::for_dir(root.to_path_buf()).await.unwrap();
-      let () = save_tasks_to_dir(&mut tasks_root_cap, &task_state)
-        .await
-        .unwrap();
+      let () = task_state.save(&mut tasks_root_cap).await.unwrap();
+      let task_state = task_state.to_serde();
       let mut loaded = load_tasks_from_dir(root).await.unwrap();
 
       // The order of tasks is undefined at this point of the loading
@@ -680,7 +1120,7 @@ pub mod tests {
 
     let task1 = it.next().unwrap();
     let () = task1.tags(|mut iter| {
-      assert_eq!(iter.next().unwrap().name(), "tag2");
+      assert_eq!(&*iter.next().unwrap().name(), "tag2");
       assert!(iter.next().is_none());
     });
 
@@ -689,7 +1129,7 @@ pub mod tests {
 
     let task3 = it.next().unwrap();
     let () = task3.tags(|mut iter| {
-      assert_eq!(iter.next().unwrap().name(), "tag1");
+      assert_eq!(&*iter.next().unwrap().name(), "tag1");
       assert!(iter.next().is_none());
     });
 
@@ -700,4 +1140,390 @@ pub mod tests {
       assert!(iter.next().is_none());
     });
   }
+
+  /// Check that tags are stored as categories, by name, and that tags
+  /// stored by ID by earlier versions continue to be loaded.
+  #[test]
+  async fn load_save_tags_as_categories() {
+    /// Retrieve the names of the tags of all tasks.
+    fn tag_names(task_state: &TaskState) -> Vec<Vec<String>> {
+      task_state.tasks().iter(|iter| {
+        iter
+          .map(|task| task.tags(|iter| iter.map(|tag| tag.name().to_string()).collect()))
+          .collect()
+      })
+    }
+
+    let root = TempDir::new().unwrap();
+    let root = root.path();
+    let mut root_cap = DirCap::for_dir(root.to_path_buf()).await.unwrap();
+
+    let id = SerId::try_from(1).unwrap();
+    let templates = SerTemplates(vec![SerTemplate {
+      id,
+      name: "tag1".to_string(),
+    }]);
+    let task = SerTask::new("tagged").with_tags([SerTag { id }]);
+    let task_id = task.id;
+    let task_state = SerTaskState {
+      tasks_meta: SerTasksMeta { templates },
+      tasks: SerTasks::from(vec![task]),
+    };
+    let task_state = TaskState::with_serde(task_state).unwrap();
+    let () = task_state.save(&mut root_cap).await.unwrap();
+
+    let data = read_to_string(root.join(task_id.to_string()))
+      .await
+      .unwrap();
+    assert!(data.contains("CATEGORIES:tag1\r\n"), "{data}");
+    assert!(!data.contains("TAGS"), "{data}");
+
+    // A task as stored by an earlier version of the program, with an
+    // additional category set by another one.
+    let legacy_id = SerTaskId::new_v4();
+    let data = format!(
+      "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:other\r\n\
+       BEGIN:VTODO\r\nUID:{legacy_id}\r\nSUMMARY:legacy\r\nTAGS:1\r\n\
+       CATEGORIES:new\r\nEND:VTODO\r\nEND:VCALENDAR\r\n"
+    );
+    let () = write(root.join(legacy_id.to_string()), data).await.unwrap();
+
+    let task_state = TaskState::load(root).await.unwrap();
+    let mut names = tag_names(&task_state);
+    let () = names.sort();
+    assert_eq!(names, [vec!["tag1"], vec!["tag1", "new"]]);
+
+    let () = task_state.save(&mut root_cap).await.unwrap();
+    let data = read_to_string(root.join(legacy_id.to_string()))
+      .await
+      .unwrap();
+    assert!(data.contains("CATEGORIES:tag1\r\n"), "{data}");
+    assert!(data.contains("CATEGORIES:new\r\n"), "{data}");
+    assert!(!data.contains("TAGS"), "{data}");
+  }
+
+  /// Retrieve the names of the tags of all tasks of a `TaskState`
+  /// object, keyed by task summary.
+  fn tag_names(task_state: &TaskState) -> BTreeMap<String, Vec<String>> {
+    task_state.tasks().iter(|iter| {
+      iter
+        .map(|task| {
+          let tags = task.tags(|iter| iter.map(|tag| tag.name().to_string()).collect());
+          (task.summary(), tags)
+        })
+        .collect()
+    })
+  }
+
+  /// Create a serialized task state with templates having the provided
+  /// names (in ID order) and tasks with the provided summaries and tags
+  /// names.
+  fn make_divergent_state(names: &[&str], tasks: &[(&str, &[&str])]) -> SerTaskState {
+    let templates = names
+      .iter()
+      .enumerate()
+      .map(|(idx, name)| SerTemplate {
+        id: SerId::try_from(idx + 1).unwrap(),
+        name: name.to_string(),
+      })
+      .collect::<Vec<_>>();
+    let tasks = tasks
+      .iter()
+      .map(|(summary, tags)| {
+        let tags = tags.iter().map(|tag| {
+          let template = templates.iter().find(|t| t.name == *tag).unwrap();
+          SerTag { id: template.id }
+        });
+        SerTask::new(*summary).with_tags(tags)
+      })
+      .collect::<Vec<_>>();
+
+    SerTaskState {
+      tasks_meta: SerTasksMeta {
+        templates: SerTemplates(templates),
+      },
+      tasks: SerTasks::from(tasks),
+    }
+  }
+
+  /// Check that two task directories that diverged, each having tags
+  /// allocated independently, can be merged without tags getting mixed
+  /// up.
+  #[test]
+  async fn merge_divergent_directories() {
+    let root1 = TempDir::new().unwrap();
+    let root1 = root1.path();
+    let mut root_cap1 = DirCap::for_dir(root1.to_path_buf()).await.unwrap();
+    let root2 = TempDir::new().unwrap();
+    let root2 = root2.path();
+    let mut root_cap2 = DirCap::for_dir(root2.to_path_buf()).await.unwrap();
+
+    // Both directories use the same tag IDs for different tags and
+    // different IDs for the same tag.
+    let state1 = make_divergent_state(&["a", "b"], &[("1a", &["a"]), ("1b", &["a", "b"])]);
+    let state2 = make_divergent_state(&["c", "a"], &[("2a", &["a"]), ("2c", &["c"])]);
+    let task_state1 = TaskState::with_serde(state1).unwrap();
+    let () = task_state1.save(&mut root_cap1).await.unwrap();
+    let task_state2 = TaskState::with_serde(state2).unwrap();
+    let () = task_state2.save(&mut root_cap2).await.unwrap();
+
+    // Copy over the contents of the second directory into the first
+    // one, including its meta data, as may happen when the task
+    // collection is synchronized by other means.
+    let mut entries = read_dir(root2).await.unwrap();
+    while let Some(entry) = entries.next_entry().await.unwrap() {
+      if !is_dir(&entry).await.unwrap() {
+        let _count = copy(entry.path(), root1.join(entry.file_name()))
+          .await
+          .unwrap();
+      }
+    }
+    let _count = copy(root2.join(tasks_meta_path()), root1.join(tasks_meta_path()))
+      .await
+      .unwrap();
+
+    let expected = BTreeMap::from([
+      ("1a".to_string(), vec!["a".to_string()]),
+      ("1b".to_string(), vec!["a".to_string(), "b".to_string()]),
+      ("2a".to_string(), vec!["a".to_string()]),
+      ("2c".to_string(), vec!["c".to_string()]),
+    ]);
+
+    let () = task_state1.merge(root1).await.unwrap();
+    assert_eq!(tag_names(&task_state1), expected);
+    assert_eq!(task_state1.templates().iter().count(), 3);
+
+    let () = task_state1.save(&mut root_cap1).await.unwrap();
+    let task_state = TaskState::load(root1).await.unwrap();
+    assert_eq!(tag_names(&task_state), expected);
+  }
+
+  /// Check that tasks referring to tags by the IDs of meta data stored
+  /// by an earlier version of the program on another system are
+  /// resolved correctly.
+  #[test]
+  async fn load_divergent_legacy_tasks_meta() {
+    let root = TempDir::new().unwrap();
+    let root = root.path();
+
+    let local = make_divergent_state(&["a"], &[("local", &["a"])]);
+    let task_state = TaskState::with_serde(local).unwrap();
+    let mut root_cap = DirCap::for_dir(root.to_path_buf()).await.unwrap();
+    let () = task_state.save(&mut root_cap).await.unwrap();
+
+    let legacy = make_divergent_state(&["b", "a"], &[("legacy", &["a", "b"])]);
+    let () = write(
+      root.join(TASKS_META_ID.to_string()),
+      iCal::serialize(&legacy.tasks_meta).unwrap(),
+    )
+    .await
+    .unwrap();
+    let task = &legacy.tasks.0[0];
+    let () = write(
+      root.join(task.id.to_string()),
+      iCal::serialize(task).unwrap(),
+    )
+    .await
+    .unwrap();
+
+    let task_state = TaskState::load(root).await.unwrap();
+    let expected = BTreeMap::from([
+      ("legacy".to_string(), vec!["a".to_string(), "b".to_string()]),
+      ("local".to_string(), vec!["a".to_string()]),
+    ]);
+    assert_eq!(tag_names(&task_state), expected);
+    assert_eq!(task_state.templates().iter().count(), 2);
+  }
+
+  /// Check that task meta data is stored outside of the task
+  /// collection and that meta data stored as part of it by earlier
+  /// versions is migrated.
+  #[test]
+  async fn migrate_tasks_meta() {
+    let root = TempDir::new().unwrap();
+    let root = root.path();
+    let mut root_cap = DirCap::for_dir(root.to_path_buf()).await.unwrap();
+
+    let id = SerId::try_from(3).unwrap();
+    let tasks_meta = SerTasksMeta {
+      templates: SerTemplates(vec![SerTemplate {
+        id,
+        name: "legacy".to_string(),
+      }]),
+    };
+    let task = SerTask::new("tagged").with_tags([SerTag { id }]);
+    let () = write(
+      root.join(TASKS_META_ID.to_string()),
+      iCal::serialize(&tasks_meta).unwrap(),
+    )
+    .await
+    .unwrap();
+    let () = write(
+      root.join(task.id.to_string()),
+      iCal::serialize(&task).unwrap(),
+    )
+    .await
+    .unwrap();
+
+    let task_state = TaskState::load(root).await.unwrap();
+    assert_eq!(task_state.to_serde().tasks_meta, tasks_meta);
+    assert!(task_state.is_changed(root).await);
+
+    let () = task_state.save(&mut root_cap).await.unwrap();
+    assert!(!task_state.is_changed(root).await);
+    assert!(!root.join(TASKS_META_ID.to_string()).exists());
+    assert!(root.join(tasks_meta_path()).exists());
+
+    let task_state = TaskState::load(root).await.unwrap();
+    assert_eq!(task_state.to_serde().tasks_meta, tasks_meta);
+    let tags = task_state.tasks().iter(|mut iter| {
+      iter
+        .next()
+        .unwrap()
+        .tags(|iter| iter.map(|tag| tag.name().to_string()).collect::<Vec<_>>())
+    });
+    assert_eq!(tags, ["legacy"]);
+  }
+
+  /// Check that we detect modifications made to the tasks directory by
+  /// another program and refuse to overwrite them without being asked
+  /// to.
+  #[test]
+  async fn detect_external_modifications() {
+    let root = TempDir::new().unwrap();
+    let root = root.path();
+    let mut root_cap = DirCap::for_dir(root.to_path_buf()).await.unwrap();
+    let () = make_task_state(make_tasks(2))
+      .save(&mut root_cap)
+      .await
+      .unwrap();
+
+    let task_state = TaskState::load(root).await.unwrap();
+    // Saving without anybody else having touched the directory just
+    // works.
+    let () = set_summary(&task_state, 0, "local");
+    let () = task_state.save(&mut root_cap).await.unwrap();
+
+    // Merely touching a file does not constitute a modification.
+    let id = task_state
+      .tasks()
+      .iter(|mut iter| iter.next().unwrap().id());
+    let write_guard = root_cap.write().await.unwrap();
+    let mut file_cap = write_guard.file_cap(OsStr::new(&id.to_string()));
+    let () = file_cap
+      .with_writeable_path(|path| async move {
+        let file = StdFile::options().write(true).open(path)?;
+        let () = file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(42))?;
+        Ok(())
+      })
+      .await
+      .unwrap();
+    drop(write_guard);
+    let () = task_state.save(&mut root_cap).await.unwrap();
+
+    let other = TaskState::load(root).await.unwrap();
+    let _task = other
+      .tasks()
+      .add(Task::builder().set_summary("other"), None);
+    let () = other.save(&mut root_cap).await.unwrap();
+
+    let err = task_state.save(&mut root_cap).await.unwrap_err();
+    let conflict = err.downcast_ref::<Conflict>().unwrap();
+    assert_eq!(conflict.files.len(), 1);
+    assert_eq!(err.to_string(), "1 task file(s) were modified externally");
+
+    let () = task_state.accept_external_changes(root).await.unwrap();
+    let () = task_state.save(&mut root_cap).await.unwrap();
+    let loaded = TaskState::load(root).await.unwrap();
+    assert_eq!(summaries(&loaded), ["local", "2"]);
+  }
+
+  /// Check that we detect modifications made to task meta data by
+  /// another program.
+  #[test]
+  async fn detect_external_meta_data_modifications() {
+    let root = TempDir::new().unwrap();
+    let root = root.path();
+    let mut root_cap = DirCap::for_dir(root.to_path_buf()).await.unwrap();
+    let () = make_task_state(make_tasks(2))
+      .save(&mut root_cap)
+      .await
+      .unwrap();
+
+    let task_state = TaskState::load(root).await.unwrap();
+    let other = TaskState::load(root).await.unwrap();
+    // Only task meta data change when creating a tag template.
+    let _tag = other.tasks().add_template("other".to_string()).unwrap();
+    let () = other.save(&mut root_cap).await.unwrap();
+
+    let () = set_summary(&task_state, 0, "local");
+    let err = task_state.save(&mut root_cap).await.unwrap_err();
+    let conflict = err.downcast_ref::<Conflict>().unwrap();
+    assert_eq!(conflict.files, [tasks_meta_path().into_os_string()]);
+
+    let () = task_state.merge(root).await.unwrap();
+    let () = task_state.save(&mut root_cap).await.unwrap();
+    let loaded = TaskState::load(root).await.unwrap();
+    assert_eq!(summaries(&loaded), ["local", "2"]);
+    assert!(loaded.templates().instantiate_from_name("other").is_some());
+  }
+
+  /// Check that we can merge modifications made to the tasks directory
+  /// by another program into our state.
+  #[test]
+  async fn merge_external_modifications() {
+    let root = TempDir::new().unwrap();
+    let root = root.path();
+    let mut root_cap = DirCap::for_dir(root.to_path_buf()).await.unwrap();
+    let () = make_task_state(make_tasks(4))
+      .save(&mut root_cap)
+      .await
+      .unwrap();
+
+    let task_state = TaskState::load(root).await.unwrap();
+    let other = TaskState::load(root).await.unwrap();
+
+    let () = set_summary(&other, 0, "1-other");
+    let () = set_summary(&other, 1, "2-other");
+    let () = remove(&other, 2);
+    let _task = other.tasks().add(Task::builder().set_summary("5"), None);
+    let () = other.save(&mut root_cap).await.unwrap();
+
+    let () = set_summary(&task_state, 0, "1-local");
+    let () = remove(&task_state, 3);
+    let err = task_state.save(&mut root_cap).await.unwrap_err();
+    assert!(err.downcast_ref::<Conflict>().is_some());
+
+    let () = task_state.merge(root).await.unwrap();
+    // The task changed on both ends retains its local version.
+    assert_eq!(summaries(&task_state), ["1-local", "2-other", "5"]);
+
+    let () = task_state.save(&mut root_cap).await.unwrap();
+    let loaded = TaskState::load(root).await.unwrap();
+    assert_eq!(summaries(&loaded), ["1-local", "2-other", "5"]);
+  }
+
+  /// Check that reloading tasks discards local changes in favor of
+  /// what is stored in the tasks directory.
+  #[test]
+  async fn reload_external_modifications() {
+    let root = TempDir::new().unwrap();
+    let root = root.path();
+    let mut root_cap = DirCap::for_dir(root.to_path_buf()).await.unwrap();
+    let () = make_task_state(make_tasks(2))
+      .save(&mut root_cap)
+      .await
+      .unwrap();
+
+    let task_state = TaskState::load(root).await.unwrap();
+    let other = TaskState::load(root).await.unwrap();
+    let () = remove(&other, 0);
+    let () = other.save(&mut root_cap).await.unwrap();
+
+    let () = set_summary(&task_state, 1, "local");
+    let () = task_state.reload(root).await.unwrap();
+    assert_eq!(summaries(&task_state), ["2"]);
+    // No conflict should be reported after the reload.
+    let () = task_state.save(&mut root_cap).await.unwrap();
+  }
 }
@@ -0,0 +1,480 @@
+// Copyright (C) 2025 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::process::Command;
+use std::str::FromStr as _;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context as _;
+use anyhow::Result;
+
+use crate::ser::sync::SyncConfig;
+use crate::ser::tasks::Id as TaskId;
+
+use super::http::basic_auth;
+use super::http::Client;
+use super::http::Response;
+use super::http::Url;
+use super::xml;
+
+
+/// The body of the `PROPFIND` request used for listing a collection.
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:">
+  <d:prop>
+    <d:resourcetype/>
+    <d:getetag/>
+  </d:prop>
+</d:propfind>
+"#;
+
+
+/// An item as stored in a remote collection.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) struct RemoteItem {
+  /// The item's path.
+  pub href: String,
+  /// The item's entity tag.
+  pub etag: String,
+}
+
+
+/// Strip the scheme and authority from an `href`, if present.
+fn normalize_href(href: &str) -> &str {
+  if let Some(rest) = href
+    .strip_prefix("http://")
+    .or_else(|| href.strip_prefix("https://"))
+  {
+    rest.find('/').map(|idx| &rest[idx..]).unwrap_or("/")
+  } else {
+    href
+  }
+}
+
+
+/// Retrieve the content of the property with the given name from a
+/// `response` element of a multi-status response.
+///
+/// Only properties reported with a successful status are considered.
+/// Servers report properties they do not know about with a different
+/// status, usually as empty elements.
+fn property<'x>(response: &'x str, name: &str) -> Option<&'x str> {
+  xml::elements(response, "propstat")
+    .into_iter()
+    .filter(|propstat| {
+      // Be lenient with servers not reporting a status at all.
+      xml::element(propstat, "status").map_or(true, |status| {
+        xml::text(status)
+          .split_whitespace()
+          .nth(1)
+          .is_some_and(|code| code.starts_with('2'))
+      })
+    })
+    .find_map(|propstat| xml::element(xml::element(propstat, "prop")?, name))
+}
+
+
+/// Parse the response to a `PROPFIND` request, reporting all items but
+/// collections along with their entity tags, if available.
+fn parse_propfind(text: &str, collection: &str) -> Vec<(String, Option<String>)> {
+  let collection = collection.trim_end_matches('/');
+  xml::elements(text, "response")
+    .into_iter()
+    .filter_map(|response| {
+      let href = xml::text(xml::element(response, "href")?);
+      let href = normalize_href(&href);
+      let is_collection = property(response, "resourcetype")
+        .is_some_and(|types| xml::element(types, "collection").is_some());
+      if is_collection || href.trim_end_matches('/') == collection {
+        return None
+      }
+
+      let etag = property(response, "getetag")
+        .map(xml::text)
+        .filter(|etag| !etag.is_empty());
+      Some((href.to_string(), etag))
+    })
+    .collect()
+}
+
+
+/// Parse the response to a `calendar-multiget` `REPORT` request.
+///
+/// Items that the server did not report data for are omitted.
+fn parse_multiget(text: &str) -> Vec<(RemoteItem, String)> {
+  xml::elements(text, "response")
+    .into_iter()
+    .filter_map(|response| {
+      let href = xml::text(xml::element(response, "href")?);
+      let etag = xml::text(property(response, "getetag")?);
+      let data = xml::text(property(response, "calendar-data")?);
+      if etag.is_empty() || data.is_empty() {
+        return None
+      }
+
+      let item = RemoteItem {
+        href: normalize_href(&href).to_string(),
+        etag,
+      };
+      Some((item, data))
+    })
+    .collect()
+}
+
+
+/// Check that a response carries one of the given status codes.
+fn check_status(response: &Response, expected: &[u16], what: &str) -> Result<()> {
+  if expected.contains(&response.status) {
+    Ok(())
+  } else if response.status == 412 {
+    bail!("{what} failed because the remote item changed concurrently; please synchronize again")
+  } else {
+    bail!("{what} failed with HTTP status {}", response.status)
+  }
+}
+
+
+/// Retrieve the password to use as per the provided configuration.
+///
+/// A configured password command takes precedence over a password
+/// stored in plain text.
+fn password(config: &SyncConfig) -> Result<Option<String>> {
+  if let Some(command) = &config.password_command {
+    let output = Command::new("sh")
+      .arg("-c")
+      .arg(command)
+      .output()
+      .with_context(|| format!("failed to run password command `{command}`"))?;
+    if !output.status.success() {
+      bail!("password command `{command}` failed: {}", output.status)
+    }
+
+    let password = String::from_utf8(output.stdout)
+      .map_err(|_| anyhow!("password command `{command}` printed invalid UTF-8"))?;
+    // Only the first line counts, as is common for password managers.
+    let password = password.lines().next().unwrap_or("").to_string();
+    Ok(Some(password))
+  } else {
+    Ok(config.password.clone())
+  }
+}
+
+
+/// A CalDAV calendar collection on a remote server.
+#[derive(Debug)]
+pub(super) struct Collection {
+  /// The client for talking to the server hosting the collection.
+  client: Client,
+  /// The value of the `Authorization` header to use, if any.
+  auth: Option<String>,
+}
+
+impl Collection {
+  /// Create a `Collection` object from a sync configuration.
+  pub fn new(config: &SyncConfig) -> Result<Self> {
+    let mut url = Url::from_str(&config.url)?;
+    if !url.path.ends_with('/') {
+      let () = url.path.push('/');
+    }
+
+    let auth = if let Some(username) = &config.username {
+      // Basic authentication transmits credentials in plain text, so
+      // we only ever send them over secured connections or to the
+      // local host.
+      if !url.tls && !url.is_loopback() {
+        bail!(
+          "refusing to send credentials to `{}` over an unencrypted connection; please use an https URL",
+          config.url
+        )
+      }
+      let password = password(config)?;
+      Some(basic_auth(username, password.as_deref().unwrap_or("")))
+    } else {
+      None
+    };
+
+    let client = Client::new(url)?;
+    Ok(Self { client, auth })
+  }
+
+  /// Send a request to the server.
+  fn send(
+    &self,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: &[u8],
+  ) -> Result<Response> {
+    let mut all = headers.to_vec();
+    if let Some(auth) = &self.auth {
+      let () = all.push(("Authorization", auth));
+    }
+    self.client.request(method, path, &all, body)
+  }
+
+  /// Retrieve the `href` at which to store a new item with the given
+  /// ID.
+  pub fn href_for(&self, id: &TaskId) -> String {
+    format!("{}{}.ics", self.client.url().path, id.as_hyphenated())
+  }
+
+  /// List all items in the collection along with their entity tags.
+  pub fn list(&self) -> Result<Vec<RemoteItem>> {
+    self
+      .propfind(&self.client.url().path, "1")
+      .and_then(|items| {
+        items
+          .into_iter()
+          .map(|(href, etag)| {
+            // Without an entity tag we could not tell whether the item
+            // changed, so we better not continue.
+            let etag = etag.ok_or_else(|| anyhow!("server did not report entity tag of {href}"))?;
+            Ok(RemoteItem { href, etag })
+          })
+          .collect()
+      })
+      .context("failed to list remote collection")
+  }
+
+  /// Retrieve the entity tag of the item with the given `href`, if the
+  /// server reports one.
+  fn etag(&self, href: &str) -> Result<Option<String>> {
+    let etag = self
+      .propfind(href, "0")
+      .with_context(|| format!("failed to retrieve entity tag of {href}"))?
+      .into_iter()
+      .find(|(item, _etag)| item == href)
+      .and_then(|(_item, etag)| etag);
+    Ok(etag)
+  }
+
+  /// Issue a `PROPFIND` request for the given path, reporting all items
+  /// but collections along with their entity tags, if available.
+  fn propfind(&self, path: &str, depth: &str) -> Result<Vec<(String, Option<String>)>> {
+    let headers = [
+      ("Depth", depth),
+      ("Content-Type", "application/xml; charset=utf-8"),
+    ];
+    let response = self.send("PROPFIND", path, &headers, PROPFIND_BODY.as_bytes())?;
+    let () = check_status(&response, &[207], &format!("listing {path}"))?;
+
+    let items = parse_propfind(response.text()?, &self.client.url().path);
+    Ok(items)
+  }
+
+  /// Retrieve the data of the items with the given `href`s.
+  pub fn fetch(&self, hrefs: &[String]) -> Result<Vec<(RemoteItem, String)>> {
+    if hrefs.is_empty() {
+      return Ok(Vec::new())
+    }
+
+    let mut body = String::from(
+      r#"<?xml version="1.0" encoding="utf-8"?>
+<c:calendar-multiget xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+  <d:prop>
+    <d:getetag/>
+    <c:calendar-data/>
+  </d:prop>
+"#,
+    );
+    for href in hrefs {
+      let () = body.push_str(&format!("  <d:href>{}</d:href>\n", xml::escape(href)));
+    }
+    let () = body.push_str("</c:calendar-multiget>\n");
+
+    let headers = [
+      ("Depth", "1"),
+      ("Content-Type", "application/xml; charset=utf-8"),
+    ];
+    let response = self
+      .send("REPORT", &self.client.url().path, &headers, body.as_bytes())
+      .context("failed to retrieve remote items")?;
+    let () = check_status(&response, &[207], "retrieving remote items")?;
+
+    let items = parse_multiget(response.text()?);
+    Ok(items)
+  }
+
+  /// Upload an item.
+  ///
+  /// If `etag` is `None`, the item is expected to not yet exist on the
+  /// server. Otherwise the server's version is expected to carry the
+  /// provided entity tag. The function returns the item's new entity
+  /// tag. If the server did not report it as part of the response, it
+  /// is retrieved separately.
+  pub fn put(&self, href: &str, data: &[u8], etag: Option<&str>) -> Result<Option<String>> {
+    let mut headers = vec![("Content-Type", "text/calendar; charset=utf-8")];
+    match etag {
+      Some(etag) => headers.push(("If-Match", etag)),
+      None => headers.push(("If-None-Match", "*")),
+    }
+
+    let response = self
+      .send("PUT", href, &headers, data)
+      .with_context(|| format!("failed to upload {href}"))?;
+    let () = check_status(&response, &[200, 201, 204], &format!("uploading {href}"))?;
+    match response.header("etag") {
+      Some(etag) => Ok(Some(etag.to_string())),
+      None => self.etag(href),
+    }
+  }
+
+  /// Delete an item from the collection.
+  pub fn delete(&self, href: &str, etag: &str) -> Result<()> {
+    let headers = [("If-Match", etag)];
+    let response = self
+      .send("DELETE", href, &headers, &[])
+      .with_context(|| format!("failed to delete {href}"))?;
+    // If the item is already gone we reached our goal just as well.
+    check_status(&response, &[200, 204, 404], &format!("deleting {href}"))
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+
+  /// Check that a password command takes precedence over a stored
+  /// password.
+  #[test]
+  fn password_retrieval() {
+    let mut config = SyncConfig {
+      password: Some("stored".to_string()),
+      ..Default::default()
+    };
+    assert_eq!(password(&config).unwrap().as_deref(), Some("stored"));
+
+    config.password_command = Some("printf 'secret\\nother'".to_string());
+    assert_eq!(password(&config).unwrap().as_deref(), Some("secret"));
+
+    config.password_command = Some("false".to_string());
+    let err = password(&config).unwrap_err();
+    assert!(err
+      .to_string()
+      .starts_with("password command `false` failed"));
+  }
+
+  /// Check that we refuse to send credentials over unencrypted
+  /// connections to remote hosts.
+  #[test]
+  fn credentials_over_plain_http() {
+    let mut config = SyncConfig {
+      url: "http://example.com/dav/".to_string(),
+      username: Some("user".to_string()),
+      password: Some("secret".to_string()),
+      ..Default::default()
+    };
+    let err = Collection::new(&config).unwrap_err();
+    assert!(err.to_string().starts_with("refusing to send credentials"));
+
+    config.url = "http://127.0.0.1:8080/dav/".to_string();
+    let _collection = Collection::new(&config).unwrap();
+
+    config.url = "http://example.com/dav/".to_string();
+    config.username = None;
+    let _collection = Collection::new(&config).unwrap();
+  }
+
+  /// Check that we normalize `href` values as expected.
+  #[test]
+  fn href_normalization() {
+    assert_eq!(normalize_href("/dav/a.ics"), "/dav/a.ics");
+    assert_eq!(normalize_href("http://host:80/dav/a.ics"), "/dav/a.ics");
+    assert_eq!(normalize_href("https://host"), "/");
+  }
+
+  /// Check that we correctly interpret responses to `PROPFIND`
+  /// requests.
+  #[test]
+  fn propfind_parsing() {
+    let text = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:multistatus xmlns:d="DAV:">
+  <d:response>
+    <d:href>https://example.com/dav/tasks/</d:href>
+    <d:propstat>
+      <d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop>
+      <d:status>HTTP/1.1 200 OK</d:status>
+    </d:propstat>
+    <d:propstat>
+      <d:prop><d:getetag/></d:prop>
+      <d:status>HTTP/1.1 404 Not Found</d:status>
+    </d:propstat>
+  </d:response>
+  <d:response>
+    <d:href>/dav/tasks/a.ics</d:href>
+    <d:propstat>
+      <d:prop><d:resourcetype/><d:getetag>&quot;1&quot;</d:getetag></d:prop>
+      <d:status>HTTP/1.1 200 OK</d:status>
+    </d:propstat>
+  </d:response>
+  <d:response>
+    <d:href>/dav/tasks/b.ics</d:href>
+    <d:propstat>
+      <d:prop><d:resourcetype/></d:prop>
+      <d:status>HTTP/1.1 200 OK</d:status>
+    </d:propstat>
+    <d:propstat>
+      <d:prop><d:getetag/></d:prop>
+      <d:status>HTTP/1.1 404 Not Found</d:status>
+    </d:propstat>
+  </d:response>
+  <d:response>
+    <d:href>/dav/tasks/sub/</d:href>
+    <d:propstat>
+      <d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop>
+      <d:status>HTTP/1.1 200 OK</d:status>
+    </d:propstat>
+  </d:response>
+</d:multistatus>"#;
+
+    let items = parse_propfind(text, "/dav/tasks/");
+    assert_eq!(
+      items,
+      vec![
+        ("/dav/tasks/a.ics".to_string(), Some("\"1\"".to_string())),
+        ("/dav/tasks/b.ics".to_string(), None),
+      ]
+    );
+  }
+
+  /// Check that we correctly interpret responses to
+  /// `calendar-multiget` `REPORT` requests.
+  #[test]
+  fn multiget_parsing() {
+    let text = r#"<?xml version="1.0" encoding="utf-8"?>
+<D:multistatus xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+  <D:response>
+    <D:href>/dav/tasks/a.ics</D:href>
+    <D:propstat>
+      <D:prop>
+        <D:getetag>"1"</D:getetag>
+        <C:calendar-data><![CDATA[BEGIN:VCALENDAR
+END:VCALENDAR]]></C:calendar-data>
+      </D:prop>
+      <D:status>HTTP/1.1 200 OK</D:status>
+    </D:propstat>
+  </D:response>
+  <D:response>
+    <D:href>/dav/tasks/b.ics</D:href>
+    <D:propstat>
+      <D:prop><D:getetag/><C:calendar-data/></D:prop>
+      <D:status>HTTP/1.1 404 Not Found</D:status>
+    </D:propstat>
+  </D:response>
+  <D:response>
+    <D:href>/dav/tasks/c.ics</D:href>
+    <D:status>HTTP/1.1 404 Not Found</D:status>
+  </D:response>
+</D:multistatus>"#;
+
+    let items = parse_multiget(text);
+    assert_eq!(items.len(), 1);
+    let (item, data) = &items[0];
+    assert_eq!(item.href, "/dav/tasks/a.ics");
+    assert_eq!(item.etag, "\"1\"");
+    assert_eq!(data, "BEGIN:VCALENDAR\nEND:VCALENDAR");
+  }
+}
@@ -0,0 +1,919 @@
+// Copyright (C) 2025 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! A module providing native synchronization of tasks with a CalDAV
+//! server.
+//!
+//! Synchronization works on the level of individual iCal items, each
+//! representing a task. Task meta data, as stored as part of the
+//! collection by earlier versions of the program, is merged locally and
+//! removed from the server. For every item we
+//! remember the entity tag reported by the server as well as a
+//! fingerprint of the local version as it was at the time of the last
+//! synchronization. Comparing against these we can tell which side
+//! changed an item and transfer it accordingly.
+//!
+//! Fingerprints are calculated over the iCal representation of an
+//! item, as it is sent to the server, so that they stay valid across
+//! changes to our internal data structures.
+
+mod caldav;
+mod http;
+#[cfg(test)]
+mod server;
+mod xml;
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+
+use anyhow::anyhow;
+use anyhow::Context as _;
+use anyhow::Result;
+
+use tokio::task::spawn_blocking;
+
+use crate::cap::DirCap;
+use crate::paths::Paths;
+use crate::ser::backends::categories_to_tags;
+use crate::ser::backends::iCal;
+use crate::ser::backends::tags_to_categories;
+use crate::ser::backends::Backend;
+use crate::ser::backends::Json;
+use crate::ser::state::TaskState as SerTaskState;
+use crate::ser::sync::SyncConfig as SerSyncConfig;
+use crate::ser::sync::SyncItem as SerSyncItem;
+use crate::ser::sync::SyncState as SerSyncState;
+use crate::ser::tags::Templates as SerTemplates;
+use crate::ser::tasks::Id as TaskId;
+use crate::ser::tasks::Task as SerTask;
+use crate::ser::tasks::Tasks as SerTasks;
+use crate::ser::tasks::TasksMeta as SerTasksMeta;
+use crate::ser::ToSerde as _;
+use crate::state::content_hash;
+use crate::state::load_state_from_file;
+use crate::state::stage_state_to_file;
+use crate::state::TaskState;
+use crate::state::TASKS_META_ID;
+use crate::transaction::Transaction;
+
+use self::caldav::Collection;
+use self::caldav::RemoteItem;
+
+
+/// The names of iCal properties that are generated anew on every
+/// serialization or that describe the producing software rather than
+/// the item itself. They are not considered part of a fingerprint.
+const VOLATILE_PROPERTIES: [&str; 2] = ["DTSTAMP", "PRODID"];
+
+
+/// Check whether a content line represents one of the provided
+/// properties.
+fn is_property(line: &[u8], names: &[&str]) -> bool {
+  names.iter().any(|name| {
+    let name = name.as_bytes();
+    line.len() > name.len()
+      && line[..name.len()].eq_ignore_ascii_case(name)
+      && matches!(line[name.len()], b':' | b';')
+  })
+}
+
+
+/// Calculate a fingerprint of an item's iCal representation.
+fn fingerprint(data: &[u8]) -> u64 {
+  let data = data
+    .split_inclusive(|byte| *byte == b'\n')
+    .filter(|line| !is_property(line, &VOLATILE_PROPERTIES))
+    .flatten()
+    .copied()
+    .collect::<Vec<_>>();
+  content_hash(&data)
+}
+
+
+/// An item subject to synchronization.
+// The vast majority of items are tasks, so boxing them would not buy us
+// anything.
+#[expect(clippy::large_enum_variant)]
+#[derive(Debug, PartialEq)]
+enum Item {
+  /// The task meta data.
+  Meta(SerTasksMeta),
+  /// A task.
+  Task(SerTask),
+}
+
+impl Item {
+  /// Create an `Item` from iCal data.
+  ///
+  /// Tags of tasks are still expressed as categories at this point and
+  /// have yet to be resolved.
+  fn from_ical(data: &[u8]) -> Result<Self> {
+    let task = <iCal as Backend<SerTask>>::deserialize(data)?;
+    if task.id == TASKS_META_ID {
+      let tasks_meta = <iCal as Backend<SerTasksMeta>>::deserialize(data)?;
+      Ok(Self::Meta(tasks_meta))
+    } else {
+      Ok(Self::Task(task))
+    }
+  }
+
+  /// Convert the item into iCal data, expressing tags of tasks as
+  /// categories based on the provided templates.
+  fn to_ical(&self, templates: &SerTemplates) -> Result<Vec<u8>> {
+    let data = match self {
+      Self::Meta(tasks_meta) => iCal::serialize(tasks_meta)?,
+      Self::Task(task) => iCal::serialize(&tags_to_categories(task, templates))?,
+    };
+    Ok(data)
+  }
+
+  /// Retrieve the item's ID.
+  fn id(&self) -> TaskId {
+    match self {
+      Self::Meta(..) => TASKS_META_ID,
+      Self::Task(task) => task.id,
+    }
+  }
+
+  /// Calculate the item's fingerprint, expressing tags of tasks as
+  /// categories based on the provided templates.
+  fn fingerprint(&self, templates: &SerTemplates) -> Result<u64> {
+    let data = self.to_ical(templates)?;
+    Ok(fingerprint(&data))
+  }
+}
+
+
+/// A summary of the changes made by a synchronization run.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Report {
+  /// The number of items uploaded to the server.
+  pub uploaded: usize,
+  /// The number of items downloaded from the server.
+  pub downloaded: usize,
+  /// The number of items removed from the server.
+  pub removed_remote: usize,
+  /// The number of items removed locally.
+  pub removed_local: usize,
+  /// The number of items changed on both sides.
+  pub conflicts: usize,
+  /// The number of remote items that could not be interpreted.
+  pub skipped: usize,
+}
+
+impl Display for Report {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    let Self {
+      uploaded,
+      downloaded,
+      removed_remote,
+      removed_local,
+      conflicts,
+      skipped,
+    } = self;
+
+    write!(
+      f,
+      "uploaded: {uploaded}, downloaded: {downloaded}, \
+       removed remotely: {removed_remote}, removed locally: {removed_local}, \
+       conflicts: {conflicts}, skipped: {skipped}"
+    )
+  }
+}
+
+
+/// The state of an item on the server, relative to the last
+/// synchronization.
+#[expect(clippy::large_enum_variant)]
+enum Remote {
+  /// The item has not changed and carries the given entity tag.
+  Unchanged(String),
+  /// The item is new or changed.
+  Changed(RemoteItem, Item),
+  /// The item got removed.
+  Removed,
+  /// The item never existed on the server.
+  Absent,
+}
+
+
+/// Synchronize the given task state with a remote collection.
+fn sync_items(
+  collection: &Collection,
+  local: SerTaskState,
+  state: SerSyncState,
+) -> Result<(TaskState, SerSyncState, Report)> {
+  let mut report = Report::default();
+
+  // Normalize the local state, so that we do not spuriously detect
+  // changes to it later on (e.g., because positions got assigned).
+  let SerTaskState { tasks_meta, tasks } = TaskState::with_serde(local)
+    .context("failed to instantiate local task state")?
+    .to_serde();
+  let mut templates = tasks_meta.templates;
+  let order = tasks.0.iter().map(|task| task.id).collect::<Vec<_>>();
+  let mut local = tasks
+    .0
+    .into_iter()
+    .map(Item::Task)
+    .map(|item| (item.id(), item))
+    .collect::<BTreeMap<_, _>>();
+
+  let etags = collection
+    .list()?
+    .into_iter()
+    .map(|item| (item.href, item.etag))
+    .collect::<HashMap<_, _>>();
+
+  let known = state
+    .items
+    .iter()
+    .map(|(id, item)| (item.href.as_str(), id))
+    .collect::<HashMap<_, _>>();
+  let to_fetch = etags
+    .iter()
+    .filter(|(href, etag)| match known.get(href.as_str()) {
+      Some(id) => state.items[id].etag.as_ref() != Some(*etag),
+      None => true,
+    })
+    .map(|(href, _etag)| href.clone())
+    .collect::<Vec<_>>();
+
+  let mut fetched = BTreeMap::new();
+  for (item, data) in collection.fetch(&to_fetch)? {
+    match Item::from_ical(data.as_bytes()) {
+      Ok(parsed) => {
+        let _prev = fetched.insert(parsed.id(), (item, parsed));
+      },
+      Err(..) => report.skipped += 1,
+    }
+  }
+
+  // Tags of remote tasks are stored by name and need to be resolved
+  // against the templates we know about. Tasks written by earlier
+  // versions may still refer to tags by the IDs used in remotely stored
+  // meta data instead, which we have to match up with ours by name.
+  let ids = if let Some((_item, Item::Meta(tasks_meta))) = fetched.get(&TASKS_META_ID) {
+    templates.merge(tasks_meta.templates.clone())
+  } else {
+    HashMap::new()
+  };
+  for (_item, parsed) in fetched.values_mut() {
+    if let Item::Task(task) = parsed {
+      let () = task.remap_tags(&ids);
+      let () = categories_to_tags(task, &mut templates)?;
+    }
+  }
+
+  // Process items in local order first, to preserve it for tasks that
+  // do not carry a position.
+  let mut seen = HashSet::new();
+  let ids = order
+    .into_iter()
+    .chain(state.items.keys().copied())
+    .chain(fetched.keys().copied())
+    .filter(|id| seen.insert(*id))
+    .collect::<Vec<_>>();
+
+  let mut items = Vec::new();
+  let mut new_state = SerSyncState::default();
+  let mut downloaded = HashSet::new();
+
+  for id in ids {
+    let known = state.items.get(&id);
+    let remote = match (known, fetched.remove(&id)) {
+      (_, Some((item, parsed))) => Remote::Changed(item, parsed),
+      (Some(known), None) => match etags.get(&known.href) {
+        Some(etag) if Some(etag) == known.etag.as_ref() => Remote::Unchanged(etag.clone()),
+        Some(..) => {
+          // The item changed remotely, but we failed to interpret the
+          // new version. Leave everything as it is.
+          if let Some(item) = local.remove(&id) {
+            let () = items.push(item);
+          }
+          let _prev = new_state.items.insert(id, known.clone());
+          continue
+        },
+        None => Remote::Removed,
+      },
+      (None, None) => Remote::Absent,
+    };
+
+    if id == TASKS_META_ID {
+      // Its templates have been merged already. Meta data is no longer
+      // part of the collection, so just remove it.
+      let remote = match remote {
+        Remote::Changed(remote, _parsed) => Some((remote.href, remote.etag)),
+        // SANITY: An unchanged remote item is always known.
+        Remote::Unchanged(etag) => Some((known.unwrap().href.clone(), etag)),
+        Remote::Removed | Remote::Absent => None,
+      };
+      if let Some((href, etag)) = remote {
+        let () = collection.delete(&href, &etag)?;
+        report.removed_remote += 1;
+      }
+      continue
+    }
+
+    let local = local.remove(&id);
+    let local_changed = match (&local, known) {
+      (Some(item), Some(known)) => item.fingerprint(&templates)? != known.fingerprint,
+      (None, None) => false,
+      _ => true,
+    };
+
+    match (remote, local) {
+      (Remote::Unchanged(..), Some(local)) if !local_changed => {
+        // SANITY: An unchanged remote item is always known.
+        let _prev = new_state.items.insert(id, known.unwrap().clone());
+        let () = items.push(local);
+      },
+      (Remote::Unchanged(etag), Some(local)) => {
+        // SANITY: An unchanged remote item is always known.
+        let known = known.unwrap();
+        let data = local.to_ical(&templates)?;
+        let etag = collection.put(&known.href, &data, Some(&etag))?;
+        let item = SerSyncItem {
+          href: known.href.clone(),
+          etag,
+          fingerprint: fingerprint(&data),
+        };
+        let _prev = new_state.items.insert(id, item);
+        let () = items.push(local);
+        report.uploaded += 1;
+      },
+      (Remote::Unchanged(etag), None) => {
+        // SANITY: An unchanged remote item is always known.
+        let known = known.unwrap();
+        let () = collection.delete(&known.href, &etag)?;
+        report.removed_remote += 1;
+      },
+      (Remote::Removed, Some(..)) if !local_changed => {
+        report.removed_local += 1;
+      },
+      (remote @ (Remote::Absent | Remote::Removed), Some(local)) => {
+        if matches!(remote, Remote::Removed) {
+          // The item got removed remotely, but changed locally. Restore
+          // it on the server.
+          report.conflicts += 1;
+        }
+
+        let href = known
+          .map(|known| known.href.clone())
+          .unwrap_or_else(|| collection.href_for(&id));
+        let data = local.to_ical(&templates)?;
+        let etag = collection.put(&href, &data, None)?;
+        let item = SerSyncItem {
+          href,
+          etag,
+          fingerprint: fingerprint(&data),
+        };
+        let _prev = new_state.items.insert(id, item);
+        let () = items.push(local);
+        report.uploaded += 1;
+      },
+      (Remote::Absent | Remote::Removed, None) => (),
+      (Remote::Changed(remote, parsed), local) => {
+        let deleted = local.is_none();
+        // We only care about local changes, in which case the local
+        // version wins.
+        let merged = local.filter(|local| local_changed && *local != parsed);
+
+        if let Some(merged) = merged {
+          let data = merged.to_ical(&templates)?;
+          let etag = collection.put(&remote.href, &data, Some(&remote.etag))?;
+          let item = SerSyncItem {
+            href: remote.href,
+            etag,
+            fingerprint: fingerprint(&data),
+          };
+          let _prev = new_state.items.insert(id, item);
+          let () = items.push(merged);
+          report.uploaded += 1;
+
+          // The item changed on both sides. So as to not lose any of
+          // the remote changes, we keep the remote version around as a
+          // separate task.
+          if let Item::Task(mut task) = parsed {
+            task.id = TaskId::new_v4();
+            let duplicate = Item::Task(task);
+            let href = collection.href_for(&duplicate.id());
+            let data = duplicate.to_ical(&templates)?;
+            let etag = collection.put(&href, &data, None)?;
+            let item = SerSyncItem {
+              href,
+              etag,
+              fingerprint: fingerprint(&data),
+            };
+            let _prev = new_state.items.insert(duplicate.id(), item);
+            let _inserted = downloaded.insert(duplicate.id());
+            let () = items.push(duplicate);
+            report.uploaded += 1;
+          }
+          report.conflicts += 1;
+        } else {
+          if deleted && local_changed {
+            // The item got removed locally but changed remotely. We
+            // restore it locally.
+            report.conflicts += 1;
+          }
+
+          let item = SerSyncItem {
+            href: remote.href,
+            etag: Some(remote.etag),
+            fingerprint: parsed.fingerprint(&templates)?,
+          };
+          let _prev = new_state.items.insert(id, item);
+          let () = items.push(parsed);
+          let _inserted = downloaded.insert(id);
+          report.downloaded += 1;
+        }
+      },
+    }
+  }
+
+  let tasks = items
+    .into_iter()
+    .filter_map(|item| match item {
+      Item::Meta(..) => None,
+      Item::Task(task) => Some(task),
+    })
+    .collect();
+  // Templates may have been created for categories of downloaded tasks
+  // or have been merged from remote meta data.
+  let tasks_meta = SerTasksMeta { templates };
+
+  let state = SerTaskState {
+    tasks_meta,
+    tasks: SerTasks(tasks),
+  };
+  let task_state =
+    TaskState::with_serde(state).context("failed to instantiate synchronized task state")?;
+
+  // Instantiating the task state may have normalized data of downloaded
+  // items (e.g., by assigning positions). Make sure that fingerprints
+  // reflect what we are going to store locally, or we would consider
+  // these items locally changed during the next synchronization.
+  let state = task_state.to_serde();
+  let templates = state.tasks_meta.templates;
+  let items = state.tasks.0.into_iter().map(Item::Task);
+  for item in items {
+    if downloaded.contains(&item.id()) {
+      if let Some(state) = new_state.items.get_mut(&item.id()) {
+        state.fingerprint = item.fingerprint(&templates)?;
+      }
+    }
+  }
+
+  Ok((task_state, new_state, report))
+}
+
+
+/// Synchronize the tasks in the provided directory with a CalDAV
+/// collection, persisting synchronization state in `state_file` inside
+/// `state_dir_cap`.
+async fn sync_with_config(
+  config: &SerSyncConfig,
+  tasks_dir_cap: &mut DirCap,
+  state_dir_cap: &mut DirCap,
+  state_file: &OsStr,
+) -> Result<Report> {
+  let task_state = TaskState::load(tasks_dir_cap.path())
+    .await
+    .context("failed to load task state")?;
+  let state_path = state_dir_cap.path().join(state_file);
+  let sync_state = load_state_from_file::<Json, SerSyncState>(&state_path)
+    .await
+    .with_context(|| {
+      format!(
+        "failed to load synchronization state from {}",
+        state_path.display()
+      )
+    })?
+    .unwrap_or_default();
+
+  // Communication with the server is blocking, so we perform it on a
+  // dedicated thread in order to not stall the runtime. Task state is
+  // not `Send`, requiring us to transfer its serializable form.
+  let config = config.clone();
+  let local = task_state.to_serde();
+  let (task_state, sync_state, report) = spawn_blocking(move || {
+    let collection = Collection::new(&config)?;
+    let (task_state, sync_state, report) = sync_items(&collection, local, sync_state)?;
+    Result::<_>::Ok((task_state.to_serde(), sync_state, report))
+  })
+  .await
+  .context("failed to run synchronization")??;
+  let task_state =
+    TaskState::with_serde(task_state).context("failed to instantiate synchronized task state")?;
+
+  let tasks_guard = tasks_dir_cap.write().await?;
+  let state_guard = state_dir_cap.write().await?;
+
+  // Fingerprints and entity tags have to match the tasks on disk, or we
+  // would misattribute changes during the next synchronization. Hence,
+  // we commit both in one transaction.
+  let mut transaction = Transaction::new();
+  let () = task_state
+    .stage(&mut transaction, &tasks_guard)
+    .await
+    .context("failed to save task state")?;
+  let mut file_cap = state_guard.file_cap(state_file);
+  let () = stage_state_to_file::<Json, _>(&mut transaction, &mut file_cap, &sync_state)
+    .await
+    .context("failed to save synchronization state")?;
+  let () = transaction
+    .commit()
+    .await
+    .context("failed to save synchronized state")?;
+
+  Ok(report)
+}
+
+
+/// Synchronize tasks with the CalDAV server configured for the provided
+/// paths.
+pub async fn sync(paths: &Paths) -> Result<Report> {
+  let config_path = paths.ui_config_dir().join(paths.sync_config_file());
+  let config = load_state_from_file::<Json, SerSyncConfig>(&config_path)
+    .await
+    .with_context(|| {
+      format!(
+        "failed to load synchronization configuration from {}",
+        config_path.display()
+      )
+    })?
+    .ok_or_else(|| {
+      anyhow!(
+        "no synchronization configuration found at {}",
+        config_path.display()
+      )
+    })?;
+
+  let mut tasks_dir_cap = DirCap::for_dir(paths.tasks_dir()).await?;
+  let mut state_dir_cap = DirCap::for_dir(paths.ui_state_dir().to_path_buf()).await?;
+  sync_with_config(
+    &config,
+    &mut tasks_dir_cap,
+    &mut state_dir_cap,
+    paths.sync_state_file(),
+  )
+  .await
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use tempfile::TempDir;
+
+  use tokio::test;
+
+  use crate::ser::tags::Id as SerTagId;
+  use crate::ser::tags::Tag as SerTag;
+  use crate::ser::tags::Template as SerTemplate;
+  use crate::ser::tags::Templates as SerTemplates;
+  use crate::test::make_tasks;
+
+  use super::server::Server;
+  use super::server::COLLECTION;
+
+
+  /// Create a `SerSyncConfig` for the given server.
+  fn config(server: &Server) -> SerSyncConfig {
+    SerSyncConfig {
+      url: server.url(),
+      ..Default::default()
+    }
+  }
+
+  /// Create a `SerTaskState` from the provided tasks.
+  fn task_state(tasks: Vec<SerTask>) -> SerTaskState {
+    SerTaskState {
+      tasks_meta: Default::default(),
+      tasks: SerTasks::from(tasks),
+    }
+  }
+
+  /// Retrieve the summaries of all tasks in a `TaskState`.
+  fn summaries(task_state: &TaskState) -> Vec<String> {
+    task_state
+      .tasks()
+      .iter(|iter| iter.map(|task| task.summary()).collect())
+  }
+
+  /// Retrieve the `href` of the remote item representing the given
+  /// task.
+  fn href(task: &SerTask) -> String {
+    format!("{COLLECTION}{}.ics", task.id)
+  }
+
+
+  /// Check that we upload all items on initial synchronization.
+  #[test]
+  async fn initial_upload() {
+    let server = Server::start();
+    let collection = Collection::new(&config(&server)).unwrap();
+    let tasks = make_tasks(3);
+    let local = task_state(tasks.clone());
+
+    let (task_state, state, report) =
+      sync_items(&collection, local, SerSyncState::default()).unwrap();
+    // Task meta data is not part of the collection.
+    assert_eq!(report.uploaded, 3);
+    assert_eq!(report.downloaded, 0);
+    assert_eq!(state.items.len(), 3);
+    assert_eq!(summaries(&task_state), vec!["1", "2", "3"]);
+
+    let items = server.items().lock().unwrap();
+    assert_eq!(items.items.len(), 3);
+    for task in &tasks {
+      assert!(items.items.contains_key(&href(task)));
+    }
+  }
+
+  /// Check that we retrieve entity tags of uploaded items separately
+  /// if the server does not report them on upload.
+  #[test]
+  async fn upload_without_etag() {
+    let server = Server::start();
+    server.items().lock().unwrap().omit_put_etag = true;
+    let collection = Collection::new(&config(&server)).unwrap();
+    let local = task_state(make_tasks(2));
+
+    let (task_state, state, report) =
+      sync_items(&collection, local, SerSyncState::default()).unwrap();
+    assert_eq!(report.uploaded, 2);
+    {
+      let items = server.items().lock().unwrap();
+      for item in state.items.values() {
+        let (etag, _data) = &items.items[&item.href];
+        assert_eq!(item.etag.as_ref(), Some(etag));
+      }
+    }
+
+    let (_task_state, _state, report) =
+      sync_items(&collection, task_state.to_serde(), state).unwrap();
+    assert_eq!(report, Report::default());
+  }
+
+  /// Check that a second synchronization without any changes is a
+  /// no-op.
+  #[test]
+  async fn sync_without_changes() {
+    let server = Server::start();
+    let collection = Collection::new(&config(&server)).unwrap();
+    let local = task_state(make_tasks(2));
+
+    let (task_state, state, _report) =
+      sync_items(&collection, local, SerSyncState::default()).unwrap();
+    let (_task_state, new_state, report) =
+      sync_items(&collection, task_state.to_serde(), state).unwrap();
+    assert_eq!(report, Report::default());
+    assert_eq!(new_state.items.len(), 2);
+  }
+
+  /// Check that remote additions, modifications, and removals are
+  /// reflected locally.
+  #[test]
+  async fn download_remote_changes() {
+    let server = Server::start();
+    let collection = Collection::new(&config(&server)).unwrap();
+    let local = task_state(make_tasks(3));
+
+    let (task_state, state, _report) =
+      sync_items(&collection, local, SerSyncState::default()).unwrap();
+    let tasks = task_state.to_serde().tasks.0;
+
+    {
+      let mut items = server.items().lock().unwrap();
+      let mut changed = tasks[0].clone();
+      changed.summary = "changed".to_string();
+      let data = iCal::serialize(&changed).unwrap();
+      let _etag = items.insert(&href(&changed), &String::from_utf8(data).unwrap());
+
+      let _removed = items.items.remove(&href(&tasks[1])).unwrap();
+
+      // A task created by a different client, lacking all our custom
+      // properties.
+      let foreign = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:other\r\n\
+                     BEGIN:VTODO\r\nUID:9d3c6f5c-5dd6-4d46-b6a4-4a8e0c2b9f5a\r\n\
+                     SUMMARY:foreign\r\nEND:VTODO\r\nEND:VCALENDAR\r\n";
+      let _etag = items.insert(&format!("{COLLECTION}foreign.ics"), foreign);
+    }
+
+    let (task_state, state, report) =
+      sync_items(&collection, task_state.to_serde(), state).unwrap();
+    assert_eq!(report.downloaded, 2);
+    assert_eq!(report.removed_local, 1);
+    assert_eq!(report.uploaded, 0);
+    assert_eq!(summaries(&task_state), vec!["changed", "3", "foreign"]);
+
+    // Fingerprints should reflect the local state, so that nothing is
+    // considered changed now.
+    let (_task_state, _state, report) =
+      sync_items(&collection, task_state.to_serde(), state).unwrap();
+    assert_eq!(report, Report::default());
+  }
+
+  /// Check that local modifications and removals are propagated to the
+  /// server.
+  #[test]
+  async fn upload_local_changes() {
+    let server = Server::start();
+    let collection = Collection::new(&config(&server)).unwrap();
+    let tasks = make_tasks(3);
+    let local = task_state(tasks.clone());
+
+    let (task_state, state, _report) =
+      sync_items(&collection, local, SerSyncState::default()).unwrap();
+
+    let mut local = task_state.to_serde();
+    local.tasks.0[0].summary = "changed".to_string();
+    let _removed = local.tasks.0.remove(1);
+
+    let (_task_state, state, report) = sync_items(&collection, local, state).unwrap();
+    assert_eq!(report.uploaded, 1);
+    assert_eq!(report.removed_remote, 1);
+    assert_eq!(state.items.len(), 2);
+
+    let items = server.items().lock().unwrap();
+    assert!(!items.items.contains_key(&href(&tasks[1])));
+    let (_etag, data) = items.items.get(&href(&tasks[0])).unwrap();
+    let task = <iCal as Backend<SerTask>>::deserialize(data.as_bytes()).unwrap();
+    assert_eq!(task.summary, "changed");
+  }
+
+  /// Check that conflicting changes are resolved as expected.
+  #[test]
+  async fn conflict_resolution() {
+    let server = Server::start();
+    let collection = Collection::new(&config(&server)).unwrap();
+    let tasks = make_tasks(1);
+    let template1 = SerTemplate {
+      id: SerTagId::try_from(1).unwrap(),
+      name: "tag1".to_string(),
+    };
+    let template2 = SerTemplate {
+      id: SerTagId::try_from(2).unwrap(),
+      name: "tag2".to_string(),
+    };
+    let mut local = task_state(tasks.clone());
+    local.tasks_meta.templates = SerTemplates(vec![template1.clone()]);
+
+    let (task_state, state, _report) =
+      sync_items(&collection, local, SerSyncState::default()).unwrap();
+
+    {
+      let mut items = server.items().lock().unwrap();
+      let mut changed = tasks[0].clone();
+      changed.summary = "remote".to_string();
+      let data = iCal::serialize(&changed).unwrap();
+      let _etag = items.insert(&href(&changed), &String::from_utf8(data).unwrap());
+
+      let meta = SerTasksMeta {
+        templates: SerTemplates(vec![template2.clone()]),
+      };
+      let data = iCal::serialize(&meta).unwrap();
+      let href = format!("{COLLECTION}{TASKS_META_ID}.ics");
+      let _etag = items.insert(&href, &String::from_utf8(data).unwrap());
+    }
+
+    let mut local = task_state.to_serde();
+    local.tasks.0[0].summary = "local".to_string();
+    local.tasks.0[0].tags = vec![SerTag { id: template1.id }];
+
+    let (task_state, state, report) = sync_items(&collection, local, state).unwrap();
+    assert_eq!(report.conflicts, 1);
+    assert_eq!(report.uploaded, 2);
+    // The local version of the task wins, but the remote one is kept
+    // as a separate task.
+    assert_eq!(summaries(&task_state), vec!["local", "remote"]);
+    assert_eq!(state.items.len(), 2);
+    assert_eq!(server.items().lock().unwrap().items.len(), 2);
+    // Tag templates of meta data stored by earlier versions got merged
+    // and the meta data removed from the server.
+    let names = task_state
+      .templates()
+      .iter()
+      .map(|template| template.name().to_string())
+      .collect::<Vec<_>>();
+    assert_eq!(names, vec!["tag1", "tag2"]);
+    assert_eq!(report.removed_remote, 1);
+    let href = format!("{COLLECTION}{TASKS_META_ID}.ics");
+    assert!(!server.items().lock().unwrap().items.contains_key(&href));
+
+    // Both versions are considered synchronized now.
+    let local = task_state.to_serde();
+    let (_task_state, _state, report) = sync_items(&collection, local, state).unwrap();
+    assert_eq!(report, Report::default());
+  }
+
+  /// Check that a remote removal of a locally changed task restores it
+  /// on the server.
+  #[test]
+  async fn restore_remotely_removed() {
+    let server = Server::start();
+    let collection = Collection::new(&config(&server)).unwrap();
+    let tasks = make_tasks(1);
+    let local = task_state(tasks.clone());
+
+    let (task_state, state, _report) =
+      sync_items(&collection, local, SerSyncState::default()).unwrap();
+    let _removed = server
+      .items()
+      .lock()
+      .unwrap()
+      .items
+      .remove(&href(&tasks[0]))
+      .unwrap();
+
+    let mut local = task_state.to_serde();
+    local.tasks.0[0].summary = "changed".to_string();
+
+    let (task_state, _state, report) = sync_items(&collection, local, state).unwrap();
+    assert_eq!(report.conflicts, 1);
+    assert_eq!(report.uploaded, 1);
+    assert_eq!(summaries(&task_state), vec!["changed"]);
+    assert!(server
+      .items()
+      .lock()
+      .unwrap()
+      .items
+      .contains_key(&href(&tasks[0])));
+  }
+
+  /// Check that properties generated anew on every serialization, such
+  /// as `DTSTAMP`, do not cause tasks to be considered changed locally.
+  #[test]
+  async fn fingerprint_ignores_volatile_properties() {
+    let server = Server::start();
+    let collection = Collection::new(&config(&server)).unwrap();
+    // The task as uploaded at a different point in time and by a
+    // different program.
+    let data = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//Other//Program//EN\r\n\
+                CALSCALE:GREGORIAN\r\nBEGIN:VTODO\r\nDTSTAMP:20250301T120000Z\r\n\
+                POSITION:0\r\nSUMMARY:task\r\n\
+                UID:6d1c8a8e-0e0b-4f5c-8f43-5d2a1c3c7b1e\r\n\
+                END:VTODO\r\nEND:VCALENDAR\r\n";
+    let task = <iCal as Backend<SerTask>>::deserialize(data.as_bytes()).unwrap();
+    let href = href(&task);
+    let etag = server.items().lock().unwrap().insert(&href, data);
+
+    let item = SerSyncItem {
+      href,
+      etag: Some(etag),
+      fingerprint: fingerprint(data.as_bytes()),
+    };
+    let state = SerSyncState {
+      items: BTreeMap::from([(task.id, item)]),
+    };
+
+    let (_task_state, _state, report) =
+      sync_items(&collection, task_state(vec![task]), state).unwrap();
+    assert_eq!(report, Report::default());
+  }
+
+  /// Check that we can synchronize a task directory end-to-end,
+  /// persisting synchronization state.
+  #[test]
+  async fn sync_directory() {
+    let server = Server::start();
+    let config = config(&server);
+    let tasks_dir = TempDir::new().unwrap();
+    let state_dir = TempDir::new().unwrap();
+    let state_file = OsStr::new("sync-state.json");
+
+    let task_state = TaskState::with_serde(task_state(make_tasks(2))).unwrap();
+    let mut tasks_dir_cap = DirCap::for_dir(tasks_dir.path().to_path_buf())
+      .await
+      .unwrap();
+    let () = task_state.save(&mut tasks_dir_cap).await.unwrap();
+    let mut state_dir_cap = DirCap::for_dir(state_dir.path().to_path_buf())
+      .await
+      .unwrap();
+
+    let report = sync_with_config(&config, &mut tasks_dir_cap, &mut state_dir_cap, state_file)
+      .await
+      .unwrap();
+    assert_eq!(report.uploaded, 2);
+
+    let report = sync_with_config(&config, &mut tasks_dir_cap, &mut state_dir_cap, state_file)
+      .await
+      .unwrap();
+    assert_eq!(report, Report::default());
+
+    let state_path = state_dir.path().join(state_file);
+    let state = load_state_from_file::<Json, SerSyncState>(&state_path)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(state.items.len(), 2);
+  }
+}
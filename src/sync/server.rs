@@ -0,0 +1,221 @@
+// Copyright (C) 2025 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! A stand-in CalDAV server used for testing synchronization.
+
+use std::collections::BTreeMap;
+use std::io::BufRead as _;
+use std::io::BufReader;
+use std::io::Read as _;
+use std::io::Write as _;
+use std::net::SocketAddr;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+use std::thread::JoinHandle;
+
+use super::xml;
+
+
+/// The path of the collection the server provides.
+pub(crate) const COLLECTION: &str = "/dav/tasks/";
+
+
+/// The items stored on the server, indexed by their `href`.
+#[derive(Debug, Default)]
+pub(crate) struct Items {
+  /// A counter used for generating entity tags.
+  counter: usize,
+  /// Whether to omit entity tags from responses to `PUT` requests, as
+  /// some servers do.
+  pub omit_put_etag: bool,
+  /// The items, mapping from `href` to entity tag and data.
+  pub items: BTreeMap<String, (String, String)>,
+}
+
+impl Items {
+  /// Insert or replace an item, assigning a new entity tag to it.
+  pub fn insert(&mut self, href: &str, data: &str) -> String {
+    self.counter += 1;
+    let etag = format!("\"{}\"", self.counter);
+    let _prev = self
+      .items
+      .insert(href.to_string(), (etag.clone(), data.to_string()));
+    etag
+  }
+}
+
+
+/// Handle a single request on the given connection.
+fn handle(stream: TcpStream, items: &Mutex<Items>) {
+  let mut reader = BufReader::new(stream);
+  let mut line = String::new();
+  let _count = reader.read_line(&mut line).unwrap();
+  let mut parts = line.split_whitespace();
+  let method = parts.next().unwrap_or("").to_string();
+  let path = parts.next().unwrap_or("").to_string();
+
+  let mut headers = BTreeMap::new();
+  loop {
+    let mut line = String::new();
+    let _count = reader.read_line(&mut line).unwrap();
+    let line = line.trim_end();
+    if line.is_empty() {
+      break
+    }
+    if let Some((key, value)) = line.split_once(':') {
+      let _prev = headers.insert(key.trim().to_ascii_lowercase(), value.trim().to_string());
+    }
+  }
+
+  let length = headers
+    .get("content-length")
+    .and_then(|length| length.parse().ok())
+    .unwrap_or(0);
+  let mut body = vec![0; length];
+  let () = reader.read_exact(&mut body).unwrap();
+  let body = String::from_utf8(body).unwrap();
+
+  let mut items = items.lock().unwrap();
+  let current = items.items.get(&path).map(|(etag, _)| etag.clone());
+  let precondition = match (headers.get("if-match"), headers.get("if-none-match")) {
+    (Some(etag), _) => current.as_ref() == Some(etag),
+    (None, Some(_)) => current.is_none(),
+    (None, None) => true,
+  };
+
+  let (status, etag, body) = match method.as_str() {
+    "PROPFIND" => {
+      let mut response = format!(
+        "<d:multistatus xmlns:d=\"DAV:\"><d:response><d:href>{COLLECTION}</d:href>\
+         <d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop>\
+         <d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>"
+      );
+      for (href, (etag, _)) in items.items.iter() {
+        let () = response.push_str(&format!(
+          "<d:response><d:href>{href}</d:href><d:propstat><d:prop>\
+           <d:resourcetype/><d:getetag>{}</d:getetag></d:prop>\
+           <d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>",
+          xml::escape(etag)
+        ));
+      }
+      let () = response.push_str("</d:multistatus>");
+      ("207 Multi-Status", None, response)
+    },
+    "REPORT" => {
+      let mut response = String::from(
+        "<d:multistatus xmlns:d=\"DAV:\" xmlns:c=\"urn:ietf:params:xml:ns:caldav\">",
+      );
+      for href in xml::elements(&body, "href") {
+        let href = xml::text(href);
+        if let Some((etag, data)) = items.items.get(&href) {
+          let () = response.push_str(&format!(
+            "<d:response><d:href>{href}</d:href><d:propstat><d:prop>\
+             <d:getetag>{}</d:getetag><c:calendar-data>{}</c:calendar-data>\
+             </d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>",
+            xml::escape(etag),
+            xml::escape(data)
+          ));
+        }
+      }
+      let () = response.push_str("</d:multistatus>");
+      ("207 Multi-Status", None, response)
+    },
+    "PUT" if !precondition => ("412 Precondition Failed", None, String::new()),
+    "PUT" => {
+      let status = if current.is_some() {
+        "204 No Content"
+      } else {
+        "201 Created"
+      };
+      let etag = items.insert(&path, &body);
+      let etag = (!items.omit_put_etag).then_some(etag);
+      (status, etag, String::new())
+    },
+    "DELETE" if !precondition => ("412 Precondition Failed", None, String::new()),
+    "DELETE" => match items.items.remove(&path) {
+      Some(..) => ("204 No Content", None, String::new()),
+      None => ("404 Not Found", None, String::new()),
+    },
+    _ => ("405 Method Not Allowed", None, String::new()),
+  };
+
+  let mut response = format!("HTTP/1.1 {status}\r\nContent-Length: {}\r\n", body.len());
+  if let Some(etag) = etag {
+    let () = response.push_str(&format!("ETag: {etag}\r\n"));
+  }
+  let () = response.push_str("\r\n");
+  let () = response.push_str(&body);
+
+  let mut stream = reader.into_inner();
+  let () = stream.write_all(response.as_bytes()).unwrap();
+}
+
+
+/// A stand-in CalDAV server, serving a single collection from memory.
+#[derive(Debug)]
+pub(crate) struct Server {
+  /// The address the server listens on.
+  addr: SocketAddr,
+  /// The items stored on the server.
+  items: Arc<Mutex<Items>>,
+  /// A flag indicating that the server should shut down.
+  done: Arc<AtomicBool>,
+  /// The thread serving requests.
+  thread: Option<JoinHandle<()>>,
+}
+
+impl Server {
+  /// Start a new server listening on a random local port.
+  pub fn start() -> Self {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let items = Arc::new(Mutex::new(Items::default()));
+    let done = Arc::new(AtomicBool::new(false));
+
+    let thread = {
+      let items = Arc::clone(&items);
+      let done = Arc::clone(&done);
+      thread::spawn(move || {
+        for stream in listener.incoming() {
+          if done.load(Ordering::SeqCst) {
+            break
+          }
+          let () = handle(stream.unwrap(), &items);
+        }
+      })
+    };
+
+    Self {
+      addr,
+      items,
+      done,
+      thread: Some(thread),
+    }
+  }
+
+  /// Retrieve the URL of the collection the server provides.
+  pub fn url(&self) -> String {
+    format!("http://{}{COLLECTION}", self.addr)
+  }
+
+  /// Retrieve the items stored on the server.
+  pub fn items(&self) -> &Mutex<Items> {
+    &self.items
+  }
+}
+
+impl Drop for Server {
+  fn drop(&mut self) {
+    let () = self.done.store(true, Ordering::SeqCst);
+    // Connect once more to wake up the server thread.
+    let _stream = TcpStream::connect(self.addr);
+    if let Some(thread) = self.thread.take() {
+      let _result = thread.join();
+    }
+  }
+}
@@ -0,0 +1,180 @@
+// Copyright (C) 2025 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Minimal functionality for working with the XML documents exchanged
+//! as part of WebDAV/CalDAV requests.
+//!
+//! We do not attempt to implement a full XML parser here. The documents
+//! we deal with are well structured and we only ever look for elements
+//! by their local name, ignoring namespace prefixes.
+
+
+/// Find the first occurrence of an element with the given local name,
+/// starting the search at byte offset `start`.
+///
+/// On success, the function returns the element's content as well as
+/// the offset just past the element.
+fn find_element<'x>(xml: &'x str, name: &str, start: usize) -> Option<(&'x str, usize)> {
+  let mut offset = start;
+
+  loop {
+    let open = offset + xml.get(offset..)?.find('<')?;
+    let rest = xml.get(open + 1..)?;
+    let end = rest.find(|c: char| c.is_whitespace() || c == '/' || c == '>')?;
+    let qname = rest.get(..end)?;
+    let local = qname.rsplit_once(':').map(|(_, local)| local).unwrap_or(qname);
+
+    if qname.starts_with(['/', '?', '!']) || local != name {
+      offset = open + 1;
+      continue
+    }
+
+    let close = open + 1 + rest.find('>')?;
+    // SANITY: `close` is always larger than `open`.
+    if xml.get(..close).unwrap().ends_with('/') {
+      // The element is self-closing and so has no content.
+      return Some(("", close + 1))
+    }
+
+    let content_start = close + 1;
+    let end_tag = format!("</{qname}");
+    let content_end = content_start + xml.get(content_start..)?.find(&end_tag)?;
+    let after = content_end + xml.get(content_end..)?.find('>')? + 1;
+    return Some((xml.get(content_start..content_end)?, after))
+  }
+}
+
+
+/// Retrieve the contents of all elements with the given local name.
+pub(super) fn elements<'x>(xml: &'x str, name: &str) -> Vec<&'x str> {
+  let mut result = Vec::new();
+  let mut offset = 0;
+
+  while let Some((content, next)) = find_element(xml, name, offset) {
+    let () = result.push(content);
+    offset = next;
+  }
+  result
+}
+
+
+/// Retrieve the contents of the first element with the given local
+/// name, if any.
+pub(super) fn element<'x>(xml: &'x str, name: &str) -> Option<&'x str> {
+  find_element(xml, name, 0).map(|(content, _)| content)
+}
+
+
+/// Convert the content of an element into the text it represents,
+/// resolving `CDATA` sections and entity references.
+pub(super) fn text(content: &str) -> String {
+  let content = content.trim();
+  if let Some(cdata) = content
+    .strip_prefix("<![CDATA[")
+    .and_then(|content| content.strip_suffix("]]>"))
+  {
+    return cdata.to_string()
+  }
+
+  let mut result = String::with_capacity(content.len());
+  let mut rest = content;
+
+  while let Some(idx) = rest.find('&') {
+    let () = result.push_str(&rest[..idx]);
+    rest = &rest[idx..];
+
+    let resolved = rest.find(';').and_then(|end| {
+      let c = match &rest[1..end] {
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "amp" => Some('&'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        entity => entity
+          .strip_prefix("#x")
+          .map(|hex| u32::from_str_radix(hex, 16))
+          .or_else(|| entity.strip_prefix('#').map(str::parse::<u32>))
+          .and_then(Result::ok)
+          .and_then(char::from_u32),
+      }?;
+      Some((c, end))
+    });
+
+    if let Some((c, end)) = resolved {
+      let () = result.push(c);
+      rest = &rest[end + 1..];
+    } else {
+      // Not an entity reference we understand. Just keep it verbatim.
+      let () = result.push('&');
+      rest = &rest[1..];
+    }
+  }
+
+  let () = result.push_str(rest);
+  result
+}
+
+
+/// Escape a string for inclusion in an XML document.
+pub(super) fn escape(string: &str) -> String {
+  let mut result = String::with_capacity(string.len());
+  for c in string.chars() {
+    match c {
+      '<' => result.push_str("&lt;"),
+      '>' => result.push_str("&gt;"),
+      '&' => result.push_str("&amp;"),
+      '"' => result.push_str("&quot;"),
+      c => result.push(c),
+    }
+  }
+  result
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+
+  /// Check that we can find elements irrespective of their namespace
+  /// prefix.
+  #[test]
+  fn element_finding() {
+    let xml = r#"<?xml version="1.0"?>
+<d:multistatus xmlns:d="DAV:">
+  <d:response><d:href>/a.ics</d:href><d:getetag>"1"</d:getetag></d:response>
+  <D:response><D:href>/b.ics</D:href><D:getetag/></D:response>
+  <response xmlns="DAV:"><href>/c.ics</href></response>
+</d:multistatus>"#;
+
+    let responses = elements(xml, "response");
+    assert_eq!(responses.len(), 3);
+    assert_eq!(element(responses[0], "href"), Some("/a.ics"));
+    assert_eq!(element(responses[0], "getetag"), Some("\"1\""));
+    assert_eq!(element(responses[1], "href"), Some("/b.ics"));
+    assert_eq!(element(responses[1], "getetag"), Some(""));
+    assert_eq!(element(responses[2], "href"), Some("/c.ics"));
+    assert_eq!(element(responses[2], "getetag"), None);
+    assert_eq!(element(xml, "multistatu"), None);
+  }
+
+  /// Make sure that we properly convert element content into text.
+  #[test]
+  fn text_conversion() {
+    assert_eq!(text(""), "");
+    assert_eq!(text("  abc "), "abc");
+    assert_eq!(text("&lt;a&gt; &amp;&amp; &quot;b&quot;"), "<a> && \"b\"");
+    assert_eq!(text("a&#13;&#10;b&#x41;"), "a\r\nbA");
+    assert_eq!(text("&unknown; &"), "&unknown; &");
+    assert_eq!(text("<![CDATA[<x> & y]]>"), "<x> & y");
+  }
+
+  /// Check that escaping and text conversion round trip.
+  #[test]
+  fn escape_text_round_trip() {
+    let strings = ["", "foo", "<a href=\"x\">&amp;</a>", "ä & ö"];
+    for string in strings {
+      assert_eq!(text(&escape(string)), string);
+    }
+  }
+}
@@ -1,12 +1,20 @@
 // Copyright (C) 2018-2025 Daniel Mueller <deso@posteo.net>
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use std::cell::Ref;
+use std::cell::RefCell;
 use std::cmp::Ordering;
 use std::collections::BTreeMap;
 use std::hash::Hash;
 use std::hash::Hasher;
+use std::num::NonZeroUsize;
 use std::rc::Rc;
 
+use anyhow::bail;
+use anyhow::Result;
+
+use crate::formula::is_keyword;
+use crate::formula::is_valid_var;
 use crate::id::AllocId as _;
 use crate::id::Id as IdT;
 use crate::ser::tags::Id as SerTagId;
@@ -23,7 +31,12 @@ type Id = IdT<T>;
 #[derive(Debug, Eq)]
 pub struct Template {
   id: Id,
-  name: String,
+  /// The template's name.
+  ///
+  /// The name is shared by all tags instantiated from the template and
+  /// it can be changed after the fact, effectively renaming all of
+  /// them.
+  name: RefCell<String>,
 }
 
 impl Template {
@@ -34,7 +47,7 @@ impl Template {
   {
     Self {
       id,
-      name: name.into(),
+      name: RefCell::new(name.into()),
     }
   }
 
@@ -51,8 +64,13 @@ impl Template {
 
   /// Retrieve the tag template's name.
   #[inline]
-  pub fn name(&self) -> &str {
-    &self.name
+  pub fn name(&self) -> Ref<'_, str> {
+    Ref::map(self.name.borrow(), String::as_str)
+  }
+
+  /// Change the tag template's name, returning the previous one.
+  pub(crate) fn set_name(&self, name: String) -> String {
+    self.name.replace(name)
   }
 }
 
@@ -68,7 +86,7 @@ impl Hash for Template {
 impl PartialEq for Template {
   fn eq(&self, other: &Template) -> bool {
     let result = self.id == other.id;
-    debug_assert!(!result || self.name == other.name);
+    debug_assert!(!result || *self.name() == *other.name());
     result
   }
 }
@@ -92,7 +110,7 @@ impl ToSerde for Template {
   fn to_serde(&self) -> Self::Output {
     SerTemplate {
       id: self.id.to_serde(),
-      name: self.name.clone(),
+      name: self.name().to_string(),
     }
   }
 }
@@ -112,7 +130,7 @@ impl Tag {
   }
 
   /// Retrieve the tag's name.
-  pub fn name(&self) -> &str {
+  pub fn name(&self) -> Ref<'_, str> {
     self.template.name()
   }
 
@@ -139,10 +157,9 @@ impl ToSerde for Tag {
 pub struct Templates {
   /// A mapping of all the tag templates, indexed by each one's `Id`,
   /// converted to `usize`.
-  templates: BTreeMap<usize, Rc<Template>>,
+  templates: RefCell<BTreeMap<usize, Rc<Template>>>,
 }
 
-#[cfg_attr(test, expect(clippy::new_without_default))]
 impl Templates {
   /// Create an empty `Templates` object.
   #[cfg(test)]
@@ -166,7 +183,24 @@ impl Templates {
           Ok(templates)
         })?;
 
-    Ok(Self { templates })
+    let slf = Self {
+      templates: RefCell::new(templates),
+    };
+    Ok(slf)
+  }
+
+  /// Add templates from a `SerTemplates` object that are not yet known.
+  ///
+  /// Templates with an ID that is already in use are ignored, meaning
+  /// that existing templates always take precedence.
+  pub(crate) fn merge_serde(&self, templates: SerTemplates) {
+    let mut borrow = self.templates.borrow_mut();
+    let () = templates.0.into_iter().for_each(|template| {
+      if let Some((id, entry)) = borrow.try_reserve_id(template.id.get()) {
+        let template = Rc::new(Template::with_serde(id, template));
+        let _value_ref = entry.insert(template);
+      }
+    });
   }
 
   /// Instantiate a tag from the given serialized tag ID.
@@ -176,6 +210,7 @@ impl Templates {
   pub fn instantiate(&self, id: SerTagId) -> Option<Tag> {
     self
       .templates
+      .borrow()
       .get(&id.get())
       .map(|template| Tag::new(Rc::clone(template)))
   }
@@ -184,14 +219,75 @@ impl Templates {
   pub fn instantiate_from_name(&self, name: &str) -> Option<Tag> {
     self
       .templates
+      .borrow()
       .values()
-      .find(|template| template.name() == name)
+      .find(|template| *template.name() == *name)
       .map(|template| Tag::new(Rc::clone(template)))
   }
 
+  /// Check that `name` is suitable for a new or renamed tag template.
+  pub(crate) fn validate_name(&self, name: &str) -> Result<()> {
+    if name.is_empty() {
+      bail!("tag name must not be empty")
+    }
+    if is_keyword(name) {
+      bail!("tag name `{name}` is reserved")
+    }
+    if !is_valid_var(name) {
+      bail!(
+        "tag name `{name}` is invalid; it must start with a letter and only contain letters, digits, `-`, and `_`"
+      )
+    }
+    if self.instantiate_from_name(name).is_some() {
+      bail!("tag `{name}` already exists")
+    }
+    Ok(())
+  }
+
+  /// Create a new tag template with the given name.
+  ///
+  /// The template is assigned an ID that is not currently in use, but
+  /// it is not added to this object. Use [`Templates::insert`] for that.
+  pub(crate) fn new_template(&self, name: String) -> Rc<Template> {
+    let borrow = self.templates.borrow();
+    let id = borrow.keys().next_back().map_or(1, |id| id + 1);
+    // SANITY: We never get anywhere close to exhausting the ID space
+    //         and `id` is not zero.
+    let id = Id::from_unique_id(NonZeroUsize::new(id).unwrap());
+    Rc::new(Template::new(id, name))
+  }
+
+  /// Add a tag template to this object.
+  ///
+  /// # Panics
+  /// This method panics if the template's ID is already in use.
+  pub(crate) fn insert(&self, template: Rc<Template>) {
+    let mut borrow = self.templates.borrow_mut();
+    let id = template.id().get().get();
+    let (_id, entry): (Id, _) = borrow
+      .try_reserve_id(id)
+      .unwrap_or_else(|| panic!("tag template ID {id} is already in use"));
+    let _value_ref = entry.insert(template);
+  }
+
+  /// Remove a tag template from this object.
+  pub(crate) fn remove(&self, template: &Template) {
+    let _removed = self
+      .templates
+      .borrow_mut()
+      .remove(&template.id().get().get());
+    debug_assert!(_removed.is_some());
+  }
+
   /// Retrieve an iterator over all the tag templates.
-  pub fn iter(&self) -> impl Iterator<Item = Rc<Template>> + '_ {
-    self.templates.values().cloned()
+  pub fn iter(&self) -> impl Iterator<Item = Rc<Template>> {
+    self
+      .templates
+      .borrow()
+      .values()
+      .cloned()
+      .collect::<Vec<_>>()
+      .into_iter()
   }
 }
 
@@ -205,7 +301,7 @@ where
     I: IntoIterator<Item = S>,
   {
     let () = iter.into_iter().for_each(|name| {
-      let (id, entry) = self.templates.allocate_id();
+      let (id, entry) = self.templates.get_mut().allocate_id();
       let template = Rc::new(Template::new(id, name));
       let _value_ref = entry.insert(template);
     });
@@ -220,6 +316,7 @@ impl ToSerde for Templates {
     SerTemplates(
       self
         .templates
+        .borrow()
         .values()
         .map(|template| template.to_serde())
         .collect(),
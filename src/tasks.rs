@@ -3,26 +3,39 @@
 
 use std::cell::RefCell;
 use std::collections::btree_set::Iter as BTreeSetIter;
+use std::collections::BTreeMap;
 use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::mem::take;
 use std::ops::Deref as _;
 use std::ops::DerefMut as _;
 use std::rc::Rc;
+use std::vec::IntoIter as VecIntoIter;
 
 use anyhow::anyhow;
+use anyhow::bail;
 use anyhow::Result;
 
+use chrono::DateTime;
+use chrono::SubsecRound as _;
+use chrono::Utc;
+
 use uuid::Uuid;
 
+use crate::date::Date;
 use crate::db::Db;
 use crate::db::Entry as DbEntry;
 use crate::db::Iter as DbIter;
 use crate::ops::Op;
 use crate::ops::Ops;
 use crate::position::Position;
+use crate::ser::tasks::Opaque;
+use crate::ser::tasks::Status;
 use crate::ser::tasks::Task as SerTask;
 use crate::ser::tasks::Tasks as SerTasks;
 use crate::ser::ToSerde;
 use crate::tags::Tag;
+use crate::tags::Template;
 use crate::tags::Templates;
 use crate::tasks::Builder as TaskBuilder;
 
@@ -45,6 +58,16 @@ struct TaskInner {
   details: String,
   /// The task's tags.
   tags: BTreeSet<Tag>,
+  /// The task's due date.
+  due: Option<Date>,
+  /// The task's start date.
+  start: Option<Date>,
+  /// The time at which the task was created, if known.
+  created: Option<DateTime<Utc>>,
+  /// The task's status, if known.
+  status: Option<Status>,
+  /// Data of the task that we preserve without interpreting it.
+  opaque: Opaque,
   /// Reference to the shared `Templates` object from which tags were
   /// instantiated.
   templates: Rc<Templates>,
@@ -60,6 +83,10 @@ pub struct Builder {
   details: String,
   /// The task's tags.
   tags: Vec<Tag>,
+  /// The task's due date.
+  due: Option<Date>,
+  /// The task's start date.
+  start: Option<Date>,
 }
 
 impl Builder {
@@ -84,12 +111,26 @@ impl Builder {
     self
   }
 
+  /// Set/change the task's due date.
+  pub fn set_due(mut self, due: Option<Date>) -> Self {
+    self.due = due;
+    self
+  }
+
+  /// Set/change the task's start date.
+  pub fn set_start(mut self, start: Option<Date>) -> Self {
+    self.start = start;
+    self
+  }
+
   /// Create the [`Task`] object.
   pub fn build(self, templates: Rc<Templates>) -> Task {
     let Self {
       summary,
       details,
       tags,
+      due,
+      start,
     } = self;
 
     let inner = TaskInner {
@@ -97,6 +138,12 @@ impl Builder {
       summary,
       details,
       tags: tags.into_iter().collect(),
+      due,
+      start,
+      // iCalendar only stores time stamps with second granularity.
+      created: Some(Utc::now().trunc_subsecs(0)),
+      status: None,
+      opaque: Opaque::default(),
       templates,
     };
 
@@ -147,13 +194,17 @@ impl Task {
       summary: task.summary,
       details: task.details,
       tags,
+      due: task.due,
+      start: task.start,
+      created: task.created,
+      status: task.status,
+      opaque: task.opaque,
       templates,
     };
     Ok(Self(RefCell::new(inner)))
   }
 
   /// Retrieve the [`Task`]'s ID.
-  #[cfg(test)]
   #[inline]
   pub fn id(&self) -> Id {
     // SANITY: The type's API surface prevents any borrows from escaping
@@ -171,6 +222,19 @@ impl Task {
     self.0.try_borrow().unwrap().summary.clone()
   }
 
+  /// Invoke a user-provided function on the [`Task`]'s summary,
+  /// without copying it.
+  #[inline]
+  pub fn with_summary<F, R>(&self, f: F) -> R
+  where
+    F: FnOnce(&str) -> R,
+  {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    f(&self.0.try_borrow().unwrap().summary)
+  }
+
   /// Change this [`Task`]'s summary.
   #[inline]
   pub fn set_summary(&mut self, summary: String) {
@@ -189,6 +253,19 @@ impl Task {
     self.0.try_borrow().unwrap().details.clone()
   }
 
+  /// Invoke a user-provided function on the [`Task`]'s details,
+  /// without copying them.
+  #[inline]
+  pub fn with_details<F, R>(&self, f: F) -> R
+  where
+    F: FnOnce(&str) -> R,
+  {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    f(&self.0.try_borrow().unwrap().details)
+  }
+
   /// Change this [`Task`]'s details.
   #[inline]
   pub fn set_details(&mut self, details: String) {
@@ -198,6 +275,76 @@ impl Task {
     self.0.try_borrow_mut().unwrap().details = details
   }
 
+  /// Retrieve the [`Task`]'s due date.
+  #[inline]
+  pub fn due(&self) -> Option<Date> {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    self.0.try_borrow().unwrap().due.clone()
+  }
+
+  /// Change this [`Task`]'s due date.
+  #[inline]
+  pub fn set_due(&mut self, due: Option<Date>) {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    self.0.try_borrow_mut().unwrap().due = due
+  }
+
+  /// Retrieve the [`Task`]'s start date.
+  #[inline]
+  pub fn start(&self) -> Option<Date> {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    self.0.try_borrow().unwrap().start.clone()
+  }
+
+  /// Change this [`Task`]'s start date.
+  #[inline]
+  pub fn set_start(&mut self, start: Option<Date>) {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    self.0.try_borrow_mut().unwrap().start = start
+  }
+
+  /// Retrieve the time at which the [`Task`] was created, if known.
+  #[inline]
+  pub fn created(&self) -> Option<DateTime<Utc>> {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    self.0.try_borrow().unwrap().created
+  }
+
+  /// Check whether the [`Task`] is completed, as per its status.
+  #[inline]
+  pub fn is_completed(&self) -> bool {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    self
+      .0
+      .try_borrow()
+      .unwrap()
+      .status
+      .as_ref()
+      .is_some_and(Status::is_completed)
+  }
+
+  /// Retrieve the [`Task`]'s status.
+  #[cfg(test)]
+  #[inline]
+  pub fn status(&self) -> Option<Status> {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    self.0.try_borrow().unwrap().status
+  }
+
   /// Invoke a user-provided function on an iterator over all the task's
   /// tags.
   #[inline]
@@ -249,6 +396,73 @@ impl Task {
     self.0.try_borrow_mut().unwrap().tags.remove(tag)
   }
 
+  /// Adjust the task's status to reflect whether or not the tag
+  /// representing completion is set.
+  fn sync_status(&self, completion: &Tag) {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    let mut borrow = self.0.try_borrow_mut().unwrap();
+    let completed = borrow.status.as_ref().is_some_and(Status::is_completed);
+
+    if borrow.tags.contains(completion) {
+      if !completed {
+        // iCalendar only stores time stamps with second granularity.
+        // Truncate ours accordingly, so that we don't detect spurious
+        // changes after a save.
+        borrow.status = Some(Status::Completed(Some(Utc::now().trunc_subsecs(0))));
+      }
+    } else if completed || borrow.status.is_none() {
+      borrow.status = Some(Status::NeedsAction);
+    }
+  }
+
+  /// Adjust the task's tags to reflect its status, as may have been
+  /// set by another program.
+  fn sync_tag(&self, completion: &Tag) {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    let legacy_completed = {
+      let mut borrow = self.0.try_borrow_mut().unwrap();
+      if let Some(status) = &borrow.status {
+        if status.is_completed() {
+          let _inserted = borrow.tags.insert(completion.clone());
+        } else {
+          let _removed = borrow.tags.remove(completion);
+        }
+        false
+      } else {
+        borrow.tags.contains(completion)
+      }
+    };
+
+    // Tasks without a status predate its introduction. For completed
+    // ones we derive it from the tag, so that other programs see them
+    // as such, but leave all others alone.
+    if legacy_completed {
+      let () = self.sync_status(completion);
+    }
+  }
+
+  /// Ensure that the provided tag is set on this task, bypassing the
+  /// `Ops` infrastructure.
+  fn insert_tag(&self, tag: Tag) -> bool {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    self.0.try_borrow_mut().unwrap().tags.insert(tag)
+  }
+
+  /// Ensure that the provided tag is not set on this task, bypassing
+  /// the `Ops` infrastructure.
+  fn remove_tag(&self, tag: &Tag) -> bool {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    self.0.try_borrow_mut().unwrap().tags.remove(tag)
+  }
+
   /// Update this task with the contents of `other`.
   fn update_from(&self, other: Task) {
     // SANITY: The type's API surface prevents any borrows from escaping
@@ -273,6 +487,11 @@ fn task_to_serde(task: &Task, position: Option<&Position>) -> SerTask {
     ref summary,
     ref details,
     ref tags,
+    ref due,
+    ref start,
+    ref created,
+    ref status,
+    ref opaque,
     ..
   } = borrow.deref();
 
@@ -281,6 +500,11 @@ fn task_to_serde(task: &Task, position: Option<&Position>) -> SerTask {
     summary: summary.clone(),
     details: details.clone(),
     tags: tags.iter().map(Tag::to_serde).collect(),
+    due: due.clone(),
+    start: start.clone(),
+    created: *created,
+    status: *status,
+    opaque: opaque.clone(),
     position: position.map(Position::to_serde),
   };
 
@@ -426,6 +650,122 @@ impl Target {
 }
 
 
+/// A manual ordering of tasks that is independent of their global one,
+/// as used by views.
+///
+/// Tasks that have not been positioned explicitly are ordered as per
+/// their global position.
+#[derive(Debug, Default)]
+pub struct Order(RefCell<HashMap<Id, Position>>);
+
+impl Order {
+  /// Create an `Order` object from a serializable one.
+  pub fn with_serde(positions: BTreeMap<Id, f64>) -> Self {
+    let positions = positions
+      .into_iter()
+      .map(|(id, position)| (id, Position::new(position)))
+      .collect();
+    Self(RefCell::new(positions))
+  }
+
+  /// Convert this object into a serializable one, omitting positions
+  /// of tasks no longer present in `tasks`.
+  pub fn to_serde(&self, tasks: &Tasks) -> BTreeMap<Id, f64> {
+    let positions = self.0.borrow();
+    tasks.iter(|iter| {
+      iter
+        .filter_map(|task| {
+          let id = task.id();
+          positions.get(&id).map(|position| (id, position.to_serde()))
+        })
+        .collect()
+    })
+  }
+
+  /// Retrieve all tasks along with their positions, in the order
+  /// described by this object.
+  fn sort<'db>(&self, tasks: &'db Db<Task, Position>) -> Vec<(&'db Rc<Task>, Position)> {
+    let positions = self.0.borrow();
+    let mut ordered = tasks
+      .iter()
+      .enumerate()
+      .map(|(idx, task)| {
+        let position = positions.get(&task.id()).copied().unwrap_or_else(|| {
+          // SANITY: We know the index is valid because we are just
+          //         counting items we iterate over anyway.
+          tasks.get(idx).unwrap().aux()
+        });
+        (task, position)
+      })
+      .collect::<Vec<_>>();
+
+    // Sorting is stable and so tasks with equal positions stay in
+    // their global order.
+    let () =
+      ordered.sort_by(|(_, position1), (_, position2)| position1.get().total_cmp(&position2.get()));
+    ordered
+  }
+
+  /// Move a task to the given target, returning the previous positions
+  /// of all tasks whose position changed as a result.
+  fn move_(
+    &self,
+    tasks: &Db<Task, Position>,
+    task: &Rc<Task>,
+    to: Target,
+  ) -> Vec<(Id, Option<Position>)> {
+    let ordered = self
+      .sort(tasks)
+      .into_iter()
+      .map(|(task, position)| (Rc::clone(task), position));
+    // SANITY: All tasks in a `Db` are unique.
+    let mut ordered = Db::try_from_iter_with_aux(ordered).unwrap();
+    // SANITY: The task really should be in our `Tasks` object or we are
+    //         in trouble.
+    let idx = ordered.find(task).unwrap().index();
+    let (removed, _aux) = ordered.remove(idx);
+    // Reuse the logic for positioning a task globally, which also
+    // takes care of adjusting positions of follow on tasks if
+    // necessary.
+    let _task = add_task(&mut ordered, removed, Some(to));
+
+    let mut positions = self.0.borrow_mut();
+    ordered
+      .iter()
+      .enumerate()
+      .filter_map(|(idx, task)| {
+        // SANITY: We know the index is valid because we are just
+        //         counting items we iterate over anyway.
+        let position = ordered.get(idx).unwrap().aux();
+        let id = task.id();
+        let previous = positions.get(&id).copied().unwrap_or_else(|| {
+          // SANITY: Both databases contain the same tasks.
+          tasks.find(task).unwrap().aux()
+        });
+
+        if previous != position {
+          Some((id, positions.insert(id, position)))
+        } else {
+          None
+        }
+      })
+      .collect()
+  }
+
+  /// Restore positions of tasks as returned by [`Order::move_`].
+  fn restore(&self, previous: &[(Id, Option<Position>)]) {
+    let mut positions = self.0.borrow_mut();
+    let () = previous.iter().for_each(|(id, position)| {
+      let _position = if let Some(position) = position {
+        positions.insert(*id, *position)
+      } else {
+        positions.remove(id)
+      };
+    });
+  }
+}
+
+
 /// An operation to be performed on a task in a `Tasks` object.
 #[derive(Debug)]
 enum TaskOp {
@@ -433,6 +773,10 @@ enum TaskOp {
   Add {
     task: Rc<Task>,
     after: Option<Rc<Task>>,
+    /// An order to position the task in after `after` as well.
+    order: Option<Rc<Order>>,
+    /// The previous positions of tasks in `order`.
+    positions: Vec<(Id, Option<Position>)>,
   },
   /// An operation removing a task.
   Remove {
@@ -440,9 +784,10 @@ enum TaskOp {
     position: Option<(usize, Position)>,
   },
   /// An operation updating a task.
+  // Task copies are boxed to keep the size of the variant in check.
   Update {
-    updated: (Rc<Task>, Task),
-    before: Option<Task>,
+    updated: (Rc<Task>, Box<Task>),
+    before: Option<Box<Task>>,
   },
   /// An operation changing a task's position.
   Move {
@@ -450,11 +795,44 @@ enum TaskOp {
     to: Target,
     position: Option<(usize, Position)>,
   },
+  /// An operation changing a task's position within an `Order`.
+  Reorder {
+    order: Rc<Order>,
+    task: Rc<Task>,
+    to: Target,
+    /// The previous positions of tasks in `order`.
+    positions: Vec<(Id, Option<Position>)>,
+  },
+  /// An operation adding a tag template.
+  AddTemplate {
+    templates: Rc<Templates>,
+    template: Rc<Template>,
+  },
+  /// An operation renaming a tag template.
+  ///
+  /// `name` is the name the template does not currently have, i.e., the
+  /// new one before the operation is executed and the old one after.
+  RenameTemplate {
+    template: Rc<Template>,
+    name: String,
+  },
+  /// An operation removing a tag template along with all tags
+  /// instantiated from it.
+  RemoveTemplate {
+    templates: Rc<Templates>,
+    template: Rc<Template>,
+    tagged: Vec<Rc<Task>>,
+  },
 }
 
 impl TaskOp {
-  fn add(task: Rc<Task>, after: Option<Rc<Task>>) -> Self {
-    Self::Add { task, after }
+  fn add(task: Rc<Task>, after: Option<Rc<Task>>, order: Option<Rc<Order>>) -> Self {
+    Self::Add {
+      task,
+      after,
+      order,
+      positions: Vec::new(),
+    }
   }
 
   fn remove(task: Rc<Task>) -> Self {
@@ -466,7 +844,7 @@ impl TaskOp {
 
   fn update(task: Rc<Task>, updated: Task) -> Self {
     Self::Update {
-      updated: (task, updated),
+      updated: (task, Box::new(updated)),
       before: None,
     }
   }
@@ -478,6 +856,34 @@ impl TaskOp {
       position: None,
     }
   }
+
+  fn reorder(order: Rc<Order>, task: Rc<Task>, to: Target) -> Self {
+    Self::Reorder {
+      order,
+      task,
+      to,
+      positions: Vec::new(),
+    }
+  }
+
+  fn add_template(templates: Rc<Templates>, template: Rc<Template>) -> Self {
+    Self::AddTemplate {
+      templates,
+      template,
+    }
+  }
+
+  fn rename_template(template: Rc<Template>, name: String) -> Self {
+    Self::RenameTemplate { template, name }
+  }
+
+  fn remove_template(templates: Rc<Templates>, template: Rc<Template>) -> Self {
+    Self::RemoveTemplate {
+      templates,
+      template,
+      tagged: Vec::new(),
+    }
+  }
 }
 
 impl Op<Db<Task, Position>, Option<Rc<Task>>> for TaskOp {
@@ -486,8 +892,13 @@ impl Op<Db<Task, Position>, Option<Rc<Task>>> for TaskOp {
       Self::Add {
         ref mut task,
         after,
+        order,
+        positions,
       } => {
         let added = add_task(tasks, Rc::clone(task), after.clone().map(Target::After));
+        if let (Some(order), Some(after)) = (order, after) {
+          *positions = order.move_(tasks, &added, Target::After(Rc::clone(after)));
+        }
         Some(added)
       },
       Self::Remove { task, position } => {
@@ -497,8 +908,8 @@ impl Op<Db<Task, Position>, Option<Rc<Task>>> for TaskOp {
       },
       Self::Update { updated, before } => {
         let task = &updated.0;
-        let _task = update_task(task, updated.1.clone());
-        *before = Some(_task);
+        let _task = update_task(task, Task::clone(&updated.1));
+        *before = Some(Box::new(_task));
         Some(Rc::clone(task))
       },
       Self::Move { task, to, position } => {
@@ -515,12 +926,58 @@ impl Op<Db<Task, Position>, Option<Rc<Task>>> for TaskOp {
         let task = add_task(tasks, removed, Some(to.clone()));
         Some(task)
       },
+      Self::Reorder {
+        order,
+        task,
+        to,
+        positions,
+      } => {
+        // We do not support the case of moving a task with itself as a
+        // target. Doing so should be prevented at a higher layer,
+        // though.
+        debug_assert!(!Rc::ptr_eq(task, to.task()));
+        *positions = order.move_(tasks, task, to.clone());
+        Some(Rc::clone(task))
+      },
+      Self::AddTemplate {
+        templates,
+        template,
+      } => {
+        let () = templates.insert(Rc::clone(template));
+        None
+      },
+      Self::RenameTemplate { template, name } => {
+        *name = template.set_name(take(name));
+        None
+      },
+      Self::RemoveTemplate {
+        templates,
+        template,
+        tagged,
+      } => {
+        let tag = Tag::new(Rc::clone(template));
+        *tagged = tasks
+          .iter()
+          .filter(|task| task.remove_tag(&tag))
+          .cloned()
+          .collect();
+        let () = templates.remove(template);
+        None
+      },
     }
   }
 
   fn undo(&mut self, tasks: &mut Db<Task, Position>) -> Option<Rc<Task>> {
     match self {
-      Self::Add { task, .. } => {
+      Self::Add {
+        task,
+        order,
+        positions,
+        ..
+      } => {
+        if let Some(order) = order {
+          let () = order.restore(positions);
+        }
         let (_task, _aux, _idx) = remove_task(tasks, task);
         None
       },
@@ -536,7 +993,7 @@ impl Op<Db<Task, Position>, Option<Rc<Task>>> for TaskOp {
       },
       Self::Update { updated, before } => {
         // SANITY: `before` is guaranteed to be set on this path.
-        let before = before.clone().unwrap();
+        let before = *before.clone().unwrap();
         let task = &updated.0;
         let _task = update_task(task, before);
         let entry = tasks.find(task).unwrap();
@@ -553,6 +1010,38 @@ impl Op<Db<Task, Position>, Option<Rc<Task>>> for TaskOp {
           .unwrap();
         Some(removed)
       },
+      Self::Reorder {
+        order,
+        task,
+        positions,
+        ..
+      } => {
+        let () = order.restore(positions);
+        Some(Rc::clone(task))
+      },
+      Self::AddTemplate {
+        templates,
+        template,
+      } => {
+        let () = templates.remove(template);
+        None
+      },
+      Self::RenameTemplate { template, name } => {
+        *name = template.set_name(take(name));
+        None
+      },
+      Self::RemoveTemplate {
+        templates,
+        template,
+        tagged,
+      } => {
+        let () = templates.insert(Rc::clone(template));
+        let tag = Tag::new(Rc::clone(template));
+        let () = tagged.iter().for_each(|task| {
+          let _inserted = task.insert_tag(tag.clone());
+        });
+        None
+      },
     }
   }
 }
@@ -561,14 +1050,53 @@ impl Op<Db<Task, Position>, Option<Rc<Task>>> for TaskOp {
 /// An iterator over tasks.
 pub type TaskIter<'tasks> = DbIter<'tasks, Task, Position>;
 
+/// An iterator over tasks in the order described by an [`Order`].
+pub type OrderIter<'tasks> = VecIntoIter<&'tasks Rc<Task>>;
+
+
+/// Create a task database from a serializable tasks object.
+fn db_with_serde(mut tasks: SerTasks, templates: &Rc<Templates>) -> Result<Db<Task, Position>> {
+  // If a task has no position we will just silently sort it last.
+  tasks.0.sort_by(|first, second| {
+    let first = first.position.unwrap_or(f64::MAX);
+    let second = second.position.unwrap_or(f64::MAX);
+    first.total_cmp(&second)
+  });
+
+  let len = tasks.0.len();
+  let tasks =
+    tasks
+      .0
+      .into_iter()
+      .try_fold(Vec::with_capacity(len), |mut vec, task| -> Result<_> {
+        let position = task.position;
+        let task = Task::with_serde(task, Rc::clone(templates))?;
+        let position = position.map(Position::new).unwrap_or_else(|| {
+          let prev_pos = vec.last().map(|(_task, position)| position);
+          // SANITY: Under real world scenarios we shall always find
+          //         another free position.
+          Position::between(prev_pos.cloned(), None).unwrap()
+        });
+        let () = vec.push((task, position));
+        Result::Ok(vec)
+      })?;
+  let tasks = Db::from_iter_with_aux(tasks);
+  Ok(tasks)
+}
+
 
 #[derive(Debug)]
 struct TasksInner {
   templates: Rc<Templates>,
+  /// The tag representing task completion, if any.
+  completion: Option<Tag>,
   /// The managed tasks.
   tasks: Db<Task, Position>,
   /// A record of operations in the order they were performed.
   operations: Ops<TaskOp, Db<Task, Position>, Option<Rc<Task>>>,
+  /// A counter increased on every modification, allowing for data
+  /// derived from the tasks to be invalidated.
+  generation: usize,
 }
 
 
@@ -578,37 +1106,15 @@ pub struct Tasks(RefCell<TasksInner>);
 
 impl Tasks {
   /// Create a new `Tasks` object from a serializable one.
-  pub fn with_serde(mut tasks: SerTasks, templates: Rc<Templates>) -> Result<Self> {
-    // If a task has no position we will just silently sort it last.
-    tasks.0.sort_by(|first, second| {
-      let first = first.position.unwrap_or(f64::MAX);
-      let second = second.position.unwrap_or(f64::MAX);
-      first.total_cmp(&second)
-    });
-
-    let len = tasks.0.len();
-    let tasks =
-      tasks
-        .0
-        .into_iter()
-        .try_fold(Vec::with_capacity(len), |mut vec, task| -> Result<_> {
-          let position = task.position;
-          let task = Task::with_serde(task, Rc::clone(&templates))?;
-          let position = position.map(Position::new).unwrap_or_else(|| {
-            let prev_pos = vec.last().map(|(_task, position)| position);
-            // SANITY: Under real world scenarios we shall always find
-            //         another free position.
-            Position::between(prev_pos.cloned(), None).unwrap()
-          });
-          let () = vec.push((task, position));
-          Result::Ok(vec)
-        })?;
-    let tasks = Db::from_iter_with_aux(tasks);
+  pub fn with_serde(tasks: SerTasks, templates: Rc<Templates>) -> Result<Self> {
+    let tasks = db_with_serde(tasks, &templates)?;
 
     let inner = TasksInner {
       templates,
+      completion: None,
       tasks,
       operations: Ops::new(MAX_UNDO_STEP_COUNT),
+      generation: 0,
     };
 
     Ok(Self(RefCell::new(inner)))
@@ -627,6 +1133,26 @@ impl Tasks {
     Self::with_serde(tasks, templates)
   }
 
+  /// Replace all tasks with the ones from a serializable object.
+  ///
+  /// Replacement cannot be undone and it clears the record of prior
+  /// operations, as these may refer to tasks no longer present.
+  pub(crate) fn replace(&self, tasks: SerTasks) -> Result<()> {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    let mut borrow = self.0.try_borrow_mut().unwrap();
+    let tasks = db_with_serde(tasks, &borrow.templates)?;
+    if let Some(completion) = &borrow.completion {
+      let () = tasks.iter().for_each(|task| task.sync_tag(completion));
+    }
+
+    borrow.tasks = tasks;
+    borrow.operations = Ops::new(MAX_UNDO_STEP_COUNT);
+    borrow.generation += 1;
+    Ok(())
+  }
+
   /// Convert this object into a serializable one.
   pub fn to_serde(&self) -> SerTasks {
     // SANITY: The type's API surface prevents any borrows from escaping
@@ -649,6 +1175,36 @@ impl Tasks {
     SerTasks(tasks)
   }
 
+  /// Set the tag representing task completion.
+  ///
+  /// The tag is kept in sync with the status of tasks, as used by
+  /// iCalendar. For tasks that carry a status already (e.g., because
+  /// another program completed them), the tag is adjusted accordingly.
+  pub fn set_completion_tag(&self, completion: Option<Tag>) {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    let mut borrow = self.0.try_borrow_mut().unwrap();
+    if let Some(completion) = &completion {
+      let () = borrow
+        .tasks
+        .iter()
+        .for_each(|task| task.sync_tag(completion));
+    }
+    borrow.completion = completion;
+    borrow.generation += 1;
+  }
+
+  /// Retrieve the generation of the tasks, which changes whenever they
+  /// or any of the orders they are positioned in are modified.
+  #[inline]
+  pub fn generation(&self) -> usize {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    self.0.try_borrow().unwrap().generation
+  }
+
   /// Invoke a user-provided function on an iterator over all tasks.
   #[inline]
   pub fn iter<F, R>(&self, mut f: F) -> R
@@ -661,21 +1217,59 @@ impl Tasks {
     f(self.0.try_borrow().unwrap().tasks.iter())
   }
 
+  /// Invoke a user-provided function on an iterator over all tasks, in
+  /// the order described by `order`.
+  pub fn iter_in<F, R>(&self, order: &Order, mut f: F) -> R
+  where
+    F: FnMut(OrderIter<'_>) -> R,
+  {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    let borrow = self.0.try_borrow().unwrap();
+    let tasks = order
+      .sort(&borrow.tasks)
+      .into_iter()
+      .map(|(task, _position)| task)
+      .collect::<Vec<_>>();
+    f(tasks.into_iter())
+  }
+
   /// Add a new task.
   pub fn add(&self, task: TaskBuilder, after: Option<Rc<Task>>) -> Rc<Task> {
+    self.add_to(task, after, None)
+  }
+
+  /// Add a new task, positioning it after `after` in `order` as well.
+  pub fn add_in(&self, order: &Rc<Order>, task: TaskBuilder, after: Option<Rc<Task>>) -> Rc<Task> {
+    self.add_to(task, after, Some(Rc::clone(order)))
+  }
+
+  /// Add a new task, optionally positioning it in `order`.
+  fn add_to(
+    &self,
+    task: TaskBuilder,
+    after: Option<Rc<Task>>,
+    order: Option<Rc<Order>>,
+  ) -> Rc<Task> {
     // SANITY: The type's API surface prevents any borrows from escaping
     //         a function call and we don't call methods on `self` while
     //         a borrow is active.
     let mut borrow = self.0.try_borrow_mut().unwrap();
+    borrow.generation += 1;
     let TasksInner {
       ref mut templates,
+      ref completion,
       ref mut operations,
       ref mut tasks,
       ..
     } = borrow.deref_mut();
 
     let task = task.build(Rc::clone(templates));
-    let op = TaskOp::add(Rc::new(task), after);
+    if let Some(completion) = completion {
+      let () = task.sync_status(completion);
+    }
+    let op = TaskOp::add(Rc::new(task), after, order);
     // SANITY: We know that an "add" operation always returns a task, so
     //         this unwrap will never panic.
     let task = operations.exec(op, tasks).unwrap();
@@ -689,6 +1283,7 @@ impl Tasks {
     //         a function call and we don't call methods on `self` while
     //         a borrow is active.
     let mut borrow = self.0.try_borrow_mut().unwrap();
+    borrow.generation += 1;
     let TasksInner {
       ref mut operations,
       ref mut tasks,
@@ -705,60 +1300,149 @@ impl Tasks {
     //         a function call and we don't call methods on `self` while
     //         a borrow is active.
     let mut borrow = self.0.try_borrow_mut().unwrap();
+    borrow.generation += 1;
     let TasksInner {
+      ref completion,
       ref mut operations,
       ref mut tasks,
       ..
     } = borrow.deref_mut();
 
+    if let Some(completion) = completion {
+      let () = updated.sync_status(completion);
+    }
     let op = TaskOp::update(task, updated);
     operations.exec(op, tasks);
   }
 
   /// Reorder the task referenced by `to_move` before `other`.
   pub fn move_before(&self, to_move: Rc<Task>, other: Rc<Task>) {
-    if !Rc::ptr_eq(&to_move, &other) {
-      // SANITY: The type's API surface prevents any borrows from escaping
-      //         a function call and we don't call methods on `self` while
-      //         a borrow is active.
-      let mut borrow = self.0.try_borrow_mut().unwrap();
-      let TasksInner {
-        ref mut operations,
-        ref mut tasks,
-        ..
-      } = borrow.deref_mut();
-
-      let to = Target::Before(other);
-      let op = TaskOp::move_(to_move, to);
-      operations.exec(op, tasks);
-    }
+    self.move_to(to_move, Target::Before(other), None)
   }
 
   /// Reorder the tasks referenced by `to_move` after `other`.
   pub fn move_after(&self, to_move: Rc<Task>, other: Rc<Task>) {
-    if !Rc::ptr_eq(&to_move, &other) {
+    self.move_to(to_move, Target::After(other), None)
+  }
+
+  /// Reorder the task referenced by `to_move` before `other` within
+  /// `order`, leaving its global position untouched.
+  pub fn move_before_in(&self, order: &Rc<Order>, to_move: Rc<Task>, other: Rc<Task>) {
+    self.move_to(to_move, Target::Before(other), Some(Rc::clone(order)))
+  }
+
+  /// Reorder the task referenced by `to_move` after `other` within
+  /// `order`, leaving its global position untouched.
+  pub fn move_after_in(&self, order: &Rc<Order>, to_move: Rc<Task>, other: Rc<Task>) {
+    self.move_to(to_move, Target::After(other), Some(Rc::clone(order)))
+  }
+
+  /// Reorder a task, either globally or within `order`.
+  fn move_to(&self, to_move: Rc<Task>, to: Target, order: Option<Rc<Order>>) {
+    if !Rc::ptr_eq(&to_move, to.task()) {
       // SANITY: The type's API surface prevents any borrows from escaping
       //         a function call and we don't call methods on `self` while
       //         a borrow is active.
       let mut borrow = self.0.try_borrow_mut().unwrap();
+      borrow.generation += 1;
       let TasksInner {
         ref mut operations,
         ref mut tasks,
         ..
       } = borrow.deref_mut();
 
-      let to = Target::After(other);
-      let op = TaskOp::move_(to_move, to);
+      let op = if let Some(order) = order {
+        TaskOp::reorder(order, to_move, to)
+      } else {
+        TaskOp::move_(to_move, to)
+      };
       operations.exec(op, tasks);
     }
   }
 
+  /// Add a new tag template with the given name, returning a tag
+  /// instantiated from it.
+  pub fn add_template(&self, name: String) -> Result<Tag> {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    let mut borrow = self.0.try_borrow_mut().unwrap();
+    borrow.generation += 1;
+    let TasksInner {
+      ref templates,
+      ref mut operations,
+      ref mut tasks,
+      ..
+    } = borrow.deref_mut();
+
+    let () = templates.validate_name(&name)?;
+    let template = templates.new_template(name);
+    let op = TaskOp::add_template(Rc::clone(templates), Rc::clone(&template));
+    let _task = operations.exec(op, tasks);
+    Ok(Tag::new(template))
+  }
+
+  /// Rename a tag template, effectively renaming all tags instantiated
+  /// from it.
+  pub fn rename_template(&self, template: &Rc<Template>, name: String) -> Result<()> {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    let mut borrow = self.0.try_borrow_mut().unwrap();
+    borrow.generation += 1;
+    let TasksInner {
+      ref templates,
+      ref mut operations,
+      ref mut tasks,
+      ..
+    } = borrow.deref_mut();
+
+    if *template.name() != *name {
+      let () = templates.validate_name(&name)?;
+      let op = TaskOp::rename_template(Rc::clone(template), name);
+      let _task = operations.exec(op, tasks);
+    }
+    Ok(())
+  }
+
+  /// Remove a tag template along with all tags instantiated from it.
+  ///
+  /// The template representing task completion cannot be removed.
+  pub fn remove_template(&self, template: &Rc<Template>) -> Result<()> {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    let mut borrow = self.0.try_borrow_mut().unwrap();
+    borrow.generation += 1;
+    let TasksInner {
+      ref templates,
+      ref completion,
+      ref mut operations,
+      ref mut tasks,
+      ..
+    } = borrow.deref_mut();
+
+    if let Some(completion) = completion {
+      if completion.template() == *template {
+        bail!(
+          "tag `{}` marks tasks as complete and cannot be removed",
+          template.name()
+        )
+      }
+    }
+
+    let op = TaskOp::remove_template(Rc::clone(templates), Rc::clone(template));
+    let _task = operations.exec(op, tasks);
+    Ok(())
+  }
+
   /// Undo the "most recent" operation.
   pub fn undo(&self) -> Option<Option<Rc<Task>>> {
     // SANITY: The type's API surface prevents any borrows from escaping
     //         a function call and we don't call methods on `self` while
     //         a borrow is active.
     let mut borrow = self.0.try_borrow_mut().unwrap();
+    borrow.generation += 1;
     let TasksInner {
       ref mut operations,
       ref mut tasks,
@@ -774,6 +1458,7 @@ impl Tasks {
     //         a function call and we don't call methods on `self` while
     //         a borrow is active.
     let mut borrow = self.0.try_borrow_mut().unwrap();
+    borrow.generation += 1;
     let TasksInner {
       ref mut operations,
       ref mut tasks,
@@ -795,6 +1480,7 @@ pub mod tests {
   use crate::ser::tags::Template as SerTemplate;
   use crate::ser::tags::Templates as SerTemplates;
   use crate::test::make_tasks;
+  use crate::test::make_tasks_with_tags;
   use crate::test::COMPLETE_TAG;
 
 
@@ -830,7 +1516,7 @@ pub mod tests {
     let mut ops = Ops::new(3);
 
     let task1 = Rc::new(Task::new("task1"));
-    let op = TaskOp::add(task1, None);
+    let op = TaskOp::add(task1, None, None);
     ops.exec(op, &mut tasks);
     assert_eq!(tasks.iter().len(), 1);
     assert_eq!(tasks.get(0).unwrap().summary(), "task1");
@@ -854,7 +1540,7 @@ pub mod tests {
     let mut tasks = Db::from_iter_with_aux(iter);
     let mut ops = Ops::new(3);
     let task2 = Rc::new(Task::new("task2"));
-    let op = TaskOp::add(task2, None);
+    let op = TaskOp::add(task2, None, None);
     ops.exec(op, &mut tasks);
     assert_eq!(tasks.iter().len(), 2);
     assert_eq!(tasks.get(0).unwrap().summary(), "task1");
@@ -862,7 +1548,7 @@ pub mod tests {
 
     let task3 = Rc::new(Task::new("task3"));
     let after = Rc::clone(&tasks.get(0).unwrap());
-    let op = TaskOp::add(task3, Some(after));
+    let op = TaskOp::add(task3, Some(after), None);
     ops.exec(op, &mut tasks);
     assert_eq!(tasks.iter().len(), 3);
     assert_eq!(tasks.get(0).unwrap().summary(), "task1");
@@ -1066,6 +1752,130 @@ pub mod tests {
     assert_eq!(tasks, expected);
   }
 
+  /// Check that the completion tag and the status of tasks are kept in
+  /// sync.
+  #[test]
+  fn completion_tag_status_sync() {
+    let id = SerTemplateId::new(NonZeroUsize::new(42).unwrap());
+    let templates = vec![SerTemplate {
+      id,
+      name: COMPLETE_TAG.to_string(),
+    }];
+    let templates = Rc::new(Templates::with_serde(SerTemplates(templates)).unwrap());
+    let complete = templates.instantiate_from_name(COMPLETE_TAG).unwrap();
+
+    let task_vec = vec![
+      SerTask::new("legacy").with_tags([complete.to_serde()]),
+      SerTask::new("foreign").with_status(Status::Completed(None)),
+    ];
+    let tasks = Tasks::with_serde(SerTasks::from(task_vec), Rc::clone(&templates)).unwrap();
+    let () = tasks.set_completion_tag(Some(complete.clone()));
+
+    let task1 = tasks.iter(|mut iter| Rc::clone(iter.next().unwrap()));
+    let task2 = tasks.iter(|mut iter| Rc::clone(iter.nth(1).unwrap()));
+    assert!(task1.has_tag(&complete));
+    assert!(matches!(task1.status(), Some(Status::Completed(Some(..)))));
+    assert!(task2.has_tag(&complete));
+    assert_eq!(task2.status(), Some(Status::Completed(None)));
+
+    let mut updated = Task::clone(task2.deref());
+    assert!(updated.unset_tag(&complete));
+    let () = tasks.update(Rc::clone(&task2), updated);
+    assert_eq!(task2.status(), Some(Status::NeedsAction));
+
+    let mut updated = Task::clone(task2.deref());
+    assert!(updated.set_tag(complete.clone()));
+    let () = tasks.update(Rc::clone(&task2), updated);
+    assert!(matches!(task2.status(), Some(Status::Completed(Some(..)))));
+
+    assert!(tasks.undo().is_some());
+    assert_eq!(task2.status(), Some(Status::NeedsAction));
+
+    let task = Task::builder().set_summary("new");
+    let task3 = tasks.add(task, None);
+    assert_eq!(task3.status(), Some(Status::NeedsAction));
+  }
+
+  /// Check that we can add, rename, and remove tag templates and undo
+  /// and redo doing so.
+  #[test]
+  fn add_rename_remove_template() {
+    let (_, templates, tasks) = make_tasks_with_tags(8);
+    let templates = Rc::new(Templates::with_serde(SerTemplates(templates)).unwrap());
+    let tasks = Tasks::with_serde(SerTasks::from(tasks), Rc::clone(&templates)).unwrap();
+    let names = || {
+      templates
+        .iter()
+        .map(|template| template.name().to_string())
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(names(), [COMPLETE_TAG, "tag1", "tag2"]);
+
+    let tag = tasks.add_template("new".to_string()).unwrap();
+    assert_eq!(names(), [COMPLETE_TAG, "tag1", "tag2", "new"]);
+    assert!(tasks.add_template("new".to_string()).is_err());
+    assert!(tasks.add_template(String::new()).is_err());
+    assert!(tasks.add_template("my tag".to_string()).is_err());
+    assert!(tasks.add_template("1st".to_string()).is_err());
+
+    let task = tasks.iter(|mut iter| Rc::clone(iter.next().unwrap()));
+    let mut updated = Task::clone(task.deref());
+    assert!(updated.set_tag(tag.clone()));
+    let () = tasks.update(Rc::clone(&task), updated);
+
+    let template = tag.template();
+    let () = tasks
+      .rename_template(&template, "renamed".to_string())
+      .unwrap();
+    assert_eq!(names(), [COMPLETE_TAG, "tag1", "tag2", "renamed"]);
+    let task_tags = task.tags(|iter| iter.map(|tag| tag.name().to_string()).collect::<Vec<_>>());
+    assert_eq!(task_tags, ["renamed"]);
+    assert!(tasks
+      .rename_template(&template, "tag1".to_string())
+      .is_err());
+
+    let tag1 = templates.instantiate_from_name("tag1").unwrap();
+    let tagged = |tag: &Tag| tasks.iter(|iter| iter.filter(|task| task.has_tag(tag)).count());
+    assert_eq!(tagged(&tag1), 4);
+    let () = tasks.remove_template(&tag1.template()).unwrap();
+    assert_eq!(names(), [COMPLETE_TAG, "tag2", "renamed"]);
+    assert_eq!(tagged(&tag1), 0);
+
+    assert!(tasks.undo().is_some());
+    assert_eq!(names(), [COMPLETE_TAG, "tag1", "tag2", "renamed"]);
+    assert_eq!(tagged(&tag1), 4);
+
+    assert!(tasks.undo().is_some());
+    assert_eq!(names(), [COMPLETE_TAG, "tag1", "tag2", "new"]);
+    assert!(tasks.redo().is_some());
+    assert_eq!(names(), [COMPLETE_TAG, "tag1", "tag2", "renamed"]);
+    assert!(tasks.undo().is_some());
+
+    assert!(tasks.undo().is_some());
+    assert!(!task.has_tag(&tag));
+    assert!(tasks.undo().is_some());
+    assert_eq!(names(), [COMPLETE_TAG, "tag1", "tag2"]);
+    assert!(templates.instantiate_from_name("new").is_none());
+  }
+
+  /// Check that the tag template representing task completion cannot
+  /// be removed.
+  #[test]
+  fn remove_completion_template() {
+    let (_, templates, tasks) = make_tasks_with_tags(4);
+    let templates = Rc::new(Templates::with_serde(SerTemplates(templates)).unwrap());
+    let tasks = Tasks::with_serde(SerTasks::from(tasks), Rc::clone(&templates)).unwrap();
+    let complete = templates.instantiate_from_name(COMPLETE_TAG).unwrap();
+    let () = tasks.set_completion_tag(Some(complete.clone()));
+
+    let err = tasks.remove_template(&complete.template()).unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      format!("tag `{COMPLETE_TAG}` marks tasks as complete and cannot be removed")
+    );
+    assert!(templates.instantiate_from_name(COMPLETE_TAG).is_some());
+  }
+
   /// Check that moving a task before the first one works as expected.
   #[test]
   fn move_before_for_first() {
@@ -1147,4 +1957,76 @@ pub mod tests {
       tasks.move_after(task1, task2);
     })
   }
+
+  /// Check that tasks can be reordered within an `Order` without
+  /// affecting their global order.
+  #[test]
+  fn move_in_order() {
+    fn summaries(tasks: &Tasks, order: &Order) -> Vec<String> {
+      tasks.iter_in(order, |iter| iter.map(|task| task.summary()).collect())
+    }
+
+    let task_vec = make_tasks(4);
+    let tasks = Tasks::with_serde_tasks(task_vec.clone()).unwrap();
+    let order = Rc::new(Order::default());
+    let task1 = tasks.iter(|mut iter| Rc::clone(iter.next().unwrap()));
+    let task3 = tasks.iter(|mut iter| Rc::clone(iter.nth(2).unwrap()));
+    let task4 = tasks.iter(|mut iter| Rc::clone(iter.nth(3).unwrap()));
+    assert_eq!(summaries(&tasks, &order), ["1", "2", "3", "4"]);
+
+    tasks.move_after_in(&order, Rc::clone(&task1), Rc::clone(&task3));
+    assert_eq!(summaries(&tasks, &order), ["2", "3", "1", "4"]);
+    assert_eq!(tasks.to_serde().into_task_vec(), task_vec);
+    assert_eq!(order.to_serde(&tasks).len(), 1);
+
+    tasks.move_before_in(&order, Rc::clone(&task4), Rc::clone(&task3));
+    assert_eq!(summaries(&tasks, &order), ["2", "4", "3", "1"]);
+
+    let task = tasks.undo().unwrap().unwrap();
+    assert!(Rc::ptr_eq(&task, &task4));
+    assert_eq!(summaries(&tasks, &order), ["2", "3", "1", "4"]);
+    let _task = tasks.undo().unwrap();
+    assert_eq!(summaries(&tasks, &order), ["1", "2", "3", "4"]);
+    assert!(order.to_serde(&tasks).is_empty());
+
+    let _task = tasks.redo().unwrap();
+    assert_eq!(summaries(&tasks, &order), ["2", "3", "1", "4"]);
+
+    // Moving a task globally leaves it where it is in the order.
+    tasks.move_before(Rc::clone(&task1), Rc::clone(&task4));
+    assert_eq!(summaries(&tasks, &order), ["2", "3", "1", "4"]);
+
+    let builder = Task::builder().set_summary("5");
+    let task5 = tasks.add_in(&order, builder, Some(Rc::clone(&task3)));
+    assert_eq!(summaries(&tasks, &order), ["2", "3", "5", "1", "4"]);
+    let summaries = tasks.iter(|iter| iter.map(|task| task.summary()).collect::<Vec<_>>());
+    assert_eq!(summaries, ["2", "3", "5", "1", "4"].map(str::to_string));
+
+    let id = task5.id();
+    let () = tasks.remove(task5);
+    assert!(!order.to_serde(&tasks).contains_key(&id));
+  }
+
+  /// Check that we can always find a position for a task to move
+  /// within an `Order`.
+  #[test]
+  fn order_position_finding() {
+    let task_vec = make_tasks(5);
+    let tasks = Tasks::with_serde_tasks(task_vec).unwrap();
+    let order = Rc::new(Order::default());
+
+    (0..100_000).for_each(|_| {
+      let (task1, task2) = tasks.iter_in(&order, |mut iter| {
+        let _task0 = iter.next();
+        (
+          Rc::clone(iter.next().unwrap()),
+          Rc::clone(iter.next().unwrap()),
+        )
+      });
+      tasks.move_after_in(&order, task1, task2);
+    });
+
+    let count = tasks.iter_in(&order, |iter| iter.count());
+    assert_eq!(count, 5);
+  }
 }
@@ -122,18 +122,30 @@ pub fn default_tasks_and_tags() -> (SerUiConfig, SerTaskState) {
       SerView {
         name: "all".to_string(),
         formula: SerFormulaPair::default(),
+        sort: Vec::new(),
+        order: None,
+        groups: Vec::new(),
       },
       SerView {
         name: "tag complete".to_string(),
         formula: SerFormulaPair::from(Formula::Var(COMPLETE_TAG.to_string())),
+        sort: Vec::new(),
+        order: None,
+        groups: Vec::new(),
       },
       SerView {
         name: "tag2 || tag3".to_string(),
         formula: SerFormulaPair::from(Formula::from_str("tag2 | tag3").unwrap()),
+        sort: Vec::new(),
+        order: None,
+        groups: Vec::new(),
       },
       SerView {
         name: "tag1 && tag3".to_string(),
         formula: SerFormulaPair::from(Formula::from_str("tag1 & tag3").unwrap()),
+        sort: Vec::new(),
+        order: None,
+        groups: Vec::new(),
       },
     ],
     colors: Default::default(),
@@ -0,0 +1,967 @@
+// Copyright (C) 2025 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! A module providing the means for modifying a set of files in an
+//! all-or-nothing fashion.
+
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::fs::remove_file as remove_file_sync;
+use std::io::ErrorKind;
+use std::mem::take;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::bail;
+use anyhow::Context as _;
+use anyhow::Result;
+
+use tokio::fs::copy;
+use tokio::fs::create_dir_all;
+use tokio::fs::read_dir;
+use tokio::fs::read_to_string;
+use tokio::fs::remove_file;
+use tokio::fs::rename;
+use tokio::fs::try_exists;
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt as _;
+
+use uuid::Uuid;
+
+use crate::cap::FileCap;
+use crate::state::content_hash;
+
+
+/// The suffix of files containing staged contents.
+const STAGED_SUFFIX: &str = ".new";
+/// The suffix of files backing up the original contents of a file.
+const BACKUP_SUFFIX: &str = ".old";
+/// The name of the file recording the changes of a transaction being
+/// committed in a directory.
+const JOURNAL_NAME: &str = ".transaction.journal";
+/// The prefix of the journal entry containing the transaction's ID.
+const ID_MARKER: char = '@';
+/// The prefix of a journal entry for a directory participating in the
+/// transaction.
+const DIR_MARKER: char = '=';
+/// The prefix of a journal entry for a file being written.
+const WRITE_MARKER: char = '+';
+/// The prefix of a journal entry for a file being removed.
+const REMOVE_MARKER: char = '-';
+/// The prefix of the final journal entry, containing a checksum of all
+/// preceding ones.
+const CHECKSUM_MARKER: char = '#';
+
+
+/// Check whether a file name is that of a temporary file managed by a
+/// [`Transaction`].
+pub(crate) fn is_transaction_file(name: &OsStr) -> bool {
+  if name == JOURNAL_NAME {
+    return true
+  }
+
+  let name = name.as_encoded_bytes();
+  name.starts_with(b".")
+    && (name.ends_with(STAGED_SUFFIX.as_bytes()) || name.ends_with(BACKUP_SUFFIX.as_bytes()))
+}
+
+
+/// Derive the path of a temporary file used for working with the file
+/// at `path`.
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+  let mut name = OsString::from(".");
+  // SANITY: All paths we work with are those of files and, hence, have
+  //         a file name.
+  let () = name.push(path.file_name().unwrap());
+  let () = name.push(suffix);
+  path.with_file_name(name)
+}
+
+
+/// Flush the contents of a directory to disk, making renames and
+/// removals of files in it durable.
+async fn sync_dir(dir: &Path) -> Result<()> {
+  // Directories cannot be opened as files on all platforms.
+  if cfg!(unix) {
+    let () = File::open(dir)
+      .await
+      .with_context(|| format!("failed to open directory {}", dir.display()))?
+      .sync_all()
+      .await
+      .with_context(|| format!("failed to sync directory {}", dir.display()))?;
+  }
+  Ok(())
+}
+
+
+/// Remove a file, ignoring it not being present.
+async fn remove_if_present(path: &Path) -> Result<()> {
+  match remove_file(path).await {
+    Ok(()) => Ok(()),
+    Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+    Err(err) => Err(err).with_context(|| format!("failed to remove {}", path.display())),
+  }
+}
+
+
+/// Synchronously remove a file, reporting whether it is gone.
+fn remove_sync(path: &Path) -> bool {
+  match remove_file_sync(path) {
+    Ok(()) => true,
+    Err(err) => err.kind() == ErrorKind::NotFound,
+  }
+}
+
+
+/// A change to a file, as recorded in a journal.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Entry {
+  /// The file with the given name is being written.
+  Write(String),
+  /// The file with the given name is being removed.
+  Remove(String),
+}
+
+
+/// The journal of a transaction, as recorded in one of the directories
+/// participating in it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Journal {
+  /// The ID of the transaction, shared by the journals of all
+  /// participating directories.
+  id: String,
+  /// All directories participating in the transaction.
+  dirs: Vec<PathBuf>,
+  /// The changes to files in the directory the journal is stored in.
+  entries: Vec<Entry>,
+}
+
+impl Journal {
+  /// Convert the journal into its textual representation.
+  fn to_text(&self) -> Result<String> {
+    let mut text = format!("{ID_MARKER}{}\n", self.id);
+    for dir in &self.dirs {
+      let dir = dir
+        .to_str()
+        .filter(|dir| !dir.contains('\n'))
+        .with_context(|| format!("directory {} is not supported", dir.display()))?;
+      let () = text.push(DIR_MARKER);
+      let () = text.push_str(dir);
+      let () = text.push('\n');
+    }
+    for entry in &self.entries {
+      let (marker, name) = match entry {
+        Entry::Write(name) => (WRITE_MARKER, name),
+        Entry::Remove(name) => (REMOVE_MARKER, name),
+      };
+      let () = text.push(marker);
+      let () = text.push_str(name);
+      let () = text.push('\n');
+    }
+
+    let checksum = content_hash(text.as_bytes());
+    let () = text.push_str(&format!("{CHECKSUM_MARKER}{checksum:016x}\n"));
+    Ok(text)
+  }
+
+  /// Parse a journal from its textual representation.
+  ///
+  /// `None` is returned for a journal that lacks a valid checksum, as
+  /// is the case if writing it got interrupted.
+  fn from_text(text: &str) -> Result<Option<Self>> {
+    let Some(text) = text.strip_suffix('\n') else {
+      return Ok(None)
+    };
+    let (body, checksum) = match text.rfind('\n') {
+      Some(idx) => text.split_at(idx + 1),
+      None => ("", text),
+    };
+    let checksum = checksum
+      .strip_prefix(CHECKSUM_MARKER)
+      .and_then(|checksum| u64::from_str_radix(checksum, 16).ok());
+    if checksum != Some(content_hash(body.as_bytes())) {
+      return Ok(None)
+    }
+
+    let mut id = None;
+    let mut dirs = Vec::new();
+    let mut entries = Vec::new();
+    for line in body.lines() {
+      if let Some(value) = line.strip_prefix(ID_MARKER) {
+        id = Some(value.to_string());
+      } else if let Some(dir) = line.strip_prefix(DIR_MARKER) {
+        let () = dirs.push(PathBuf::from(dir));
+      } else if let Some(name) = line.strip_prefix(WRITE_MARKER) {
+        let () = entries.push(Entry::Write(name.to_string()));
+      } else if let Some(name) = line.strip_prefix(REMOVE_MARKER) {
+        let () = entries.push(Entry::Remove(name.to_string()));
+      } else {
+        bail!("encountered invalid journal entry: {line}")
+      }
+    }
+
+    let id = id.context("journal does not contain a transaction ID")?;
+    let journal = Self { id, dirs, entries };
+    Ok(Some(journal))
+  }
+
+  /// Load the journal stored in the provided directory, if any.
+  ///
+  /// A journal that got only partially written is treated as absent.
+  async fn load(dir: &Path) -> Result<Option<Self>> {
+    let path = dir.join(JOURNAL_NAME);
+    match read_to_string(&path).await {
+      Ok(text) => {
+        Self::from_text(&text).with_context(|| format!("failed to parse {}", path.display()))
+      },
+      Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+      Err(err) => Err(err).with_context(|| format!("failed to read {}", path.display())),
+    }
+  }
+}
+
+
+/// Remove staged contents and backups from the provided directory.
+///
+/// If `restore` is `true`, files that are missing but got backed up are
+/// restored from their backups.
+async fn clean_dir(dir: &Path, restore: bool) -> Result<()> {
+  let mut entries = match read_dir(dir).await {
+    Ok(entries) => entries,
+    Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+    Err(err) => return Err(err).with_context(|| format!("failed to read {}", dir.display())),
+  };
+
+  while let Some(entry) = entries
+    .next_entry()
+    .await
+    .with_context(|| format!("failed to iterate contents of {}", dir.display()))?
+  {
+    let file_name = entry.file_name();
+    let Some(name) = file_name.to_str().and_then(|name| name.strip_prefix('.')) else {
+      continue
+    };
+
+    if name.ends_with(STAGED_SUFFIX) {
+      let () = remove_if_present(&entry.path()).await?;
+    } else if let Some(original) = name.strip_suffix(BACKUP_SUFFIX) {
+      let path = dir.join(original);
+      let exists = try_exists(&path)
+        .await
+        .with_context(|| format!("failed to check existence of {}", path.display()))?;
+      // When rolling back, a missing original file can only have been
+      // removed by the interrupted transaction.
+      if restore && !exists {
+        let () = rename(entry.path(), &path)
+          .await
+          .with_context(|| format!("failed to restore {}", path.display()))?;
+      } else {
+        let () = remove_if_present(&entry.path()).await?;
+      }
+    }
+  }
+  Ok(())
+}
+
+
+/// Apply all changes recorded in the journal of the provided directory.
+async fn roll_forward(dir: &Path, journal: &Journal) -> Result<()> {
+  for entry in &journal.entries {
+    match entry {
+      Entry::Write(name) => {
+        let path = dir.join(name);
+        let staged = sibling(&path, STAGED_SUFFIX);
+        match rename(&staged, &path).await {
+          Ok(()) => (),
+          // The change has already been applied.
+          Err(err) if err.kind() == ErrorKind::NotFound => (),
+          Err(err) => {
+            return Err(err).with_context(|| format!("failed to move {} into place", path.display()))
+          },
+        }
+      },
+      Entry::Remove(name) => {
+        let () = remove_if_present(&dir.join(name)).await?;
+      },
+    }
+  }
+
+  let () = clean_dir(dir, false).await?;
+  sync_dir(dir).await
+}
+
+
+/// Recover from an interrupted commit of a [`Transaction`] in the
+/// provided directory.
+///
+/// A transaction counts as committed only once the journals of all
+/// directories participating in it have been written completely. In
+/// that case, all changes recorded in them are rolled forward, in all
+/// of these directories at once. Otherwise, the transaction is rolled
+/// back in the provided directory, discarding staged contents and
+/// restoring files from their backups.
+pub(crate) async fn recover(dir: &Path) -> Result<()> {
+  let exists = try_exists(dir)
+    .await
+    .with_context(|| format!("failed to check existence of {}", dir.display()))?;
+  if !exists {
+    return Ok(())
+  }
+
+  let mut journals = Vec::new();
+  if let Some(journal) = Journal::load(dir).await? {
+    for other in &journal.dirs {
+      match Journal::load(other).await? {
+        Some(other_journal) if other_journal.id == journal.id => {
+          let () = journals.push((other.clone(), other_journal));
+        },
+        _ => {
+          let () = journals.clear();
+          break
+        },
+      }
+    }
+  }
+
+  if journals.is_empty() {
+    let () = clean_dir(dir, true).await?;
+    let () = remove_if_present(&dir.join(JOURNAL_NAME)).await?;
+    sync_dir(dir).await
+  } else {
+    for (dir, journal) in &journals {
+      let () = roll_forward(dir, journal).await?;
+    }
+    // Journals have to be removed only once all changes have been
+    // applied, or recovery of another directory may roll back.
+    for (dir, _journal) in &journals {
+      let () = remove_if_present(&dir.join(JOURNAL_NAME)).await?;
+      let () = sync_dir(dir).await?;
+    }
+    Ok(())
+  }
+}
+
+
+/// A modification of a single file, as staged as part of a
+/// [`Transaction`].
+#[derive(Debug)]
+struct Change {
+  /// The path of the file being modified.
+  path: PathBuf,
+  /// The path of the file containing the new contents, or `None` if
+  /// the file is to be removed.
+  staged: Option<PathBuf>,
+  /// Whether the original file has been backed up.
+  backed_up: bool,
+  /// Whether the staged contents have been moved into place.
+  installed: bool,
+}
+
+impl Change {
+  /// Apply the change.
+  async fn apply(&mut self) -> Result<()> {
+    let path = &self.path;
+    let backup = sibling(path, BACKUP_SUFFIX);
+
+    if let Some(staged) = &self.staged {
+      // We copy instead of rename, so that the original file stays in
+      // place until it is atomically replaced with the staged one.
+      match copy(path, &backup).await {
+        Ok(_count) => self.backed_up = true,
+        Err(err) if err.kind() == ErrorKind::NotFound => (),
+        Err(err) => {
+          return Err(err).with_context(|| format!("failed to back up {}", path.display()))
+        },
+      }
+
+      let () = rename(staged, path)
+        .await
+        .with_context(|| format!("failed to move {} into place", path.display()))?;
+      self.installed = true;
+    } else {
+      match rename(path, &backup).await {
+        Ok(()) => self.backed_up = true,
+        Err(err) if err.kind() == ErrorKind::NotFound => (),
+        Err(err) => return Err(err).with_context(|| format!("failed to remove {}", path.display())),
+      }
+    }
+    Ok(())
+  }
+
+  /// Revert the (potentially partially) applied change.
+  async fn revert(&mut self) -> Result<()> {
+    let path = &self.path;
+
+    if self.backed_up && (self.installed || self.staged.is_none()) {
+      let () = rename(sibling(path, BACKUP_SUFFIX), path)
+        .await
+        .with_context(|| format!("failed to restore {}", path.display()))?;
+      self.backed_up = false;
+    } else if self.installed {
+      let () = remove_file(path)
+        .await
+        .with_context(|| format!("failed to remove {}", path.display()))?;
+    }
+
+    self.installed = false;
+    Ok(())
+  }
+
+  /// Remove all temporary files associated with the change.
+  ///
+  /// This is a best-effort operation, as left over files are taken care
+  /// of by recovery. The return value indicates whether all files got
+  /// removed.
+  fn clean_up(&self) -> bool {
+    let mut cleaned = true;
+    if let Some(staged) = &self.staged {
+      if !self.installed {
+        cleaned &= remove_sync(staged);
+      }
+    }
+    if self.backed_up {
+      cleaned &= remove_sync(&sibling(&self.path, BACKUP_SUFFIX));
+    }
+    cleaned
+  }
+}
+
+
+/// A set of file modifications that are applied in an all-or-nothing
+/// fashion.
+///
+/// Changes are staged first, by writing new contents into temporary
+/// files next to the ones being modified. Only once all changes have
+/// been staged successfully are they committed, by recording them in a
+/// journal in each affected directory and then moving the staged files
+/// into place. Each journal names all affected directories and the
+/// transaction counts as committed once all of them have been written.
+/// Should moving files into place fail for any of them, already
+/// committed changes are reverted. Should the program terminate in the
+/// middle of a commit, [`recover`] completes it based on the journals
+/// or rolls it back, if they are incomplete.
+///
+/// All operations assume that the directories containing the files
+/// being modified are writeable, i.e., clients need to hold a
+/// [`WriteGuard`][crate::cap::WriteGuard] for each of them.
+#[derive(Debug, Default)]
+pub(crate) struct Transaction {
+  /// The staged changes.
+  changes: Vec<Change>,
+  /// The journals written as part of committing the changes.
+  journals: Vec<PathBuf>,
+}
+
+impl Transaction {
+  /// Create a new, empty `Transaction`.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Stage writing the provided data to the file represented by the
+  /// given [`FileCap`].
+  pub async fn write(&mut self, file_cap: &mut FileCap<'_>, data: &[u8]) -> Result<()> {
+    let path = file_cap.path().to_path_buf();
+    if let Some(dir) = path.parent() {
+      let () = create_dir_all(dir)
+        .await
+        .with_context(|| format!("failed to create directory {}", dir.display()))?;
+    }
+
+    let staged = sibling(&path, STAGED_SUFFIX);
+    let () = file_cap
+      .with_writeable_path(|path| async move {
+        let mut file = File::create(sibling(&path, STAGED_SUFFIX)).await?;
+        let () = file.write_all(data).await?;
+        let () = file.sync_all().await?;
+        Ok(())
+      })
+      .await
+      .with_context(|| format!("failed to write {}", staged.display()))?;
+
+    let change = Change {
+      path,
+      staged: Some(staged),
+      backed_up: false,
+      installed: false,
+    };
+    let () = self.changes.push(change);
+    Ok(())
+  }
+
+  /// Stage removal of the file represented by the given [`FileCap`].
+  pub fn remove(&mut self, file_cap: &FileCap<'_>) {
+    let change = Change {
+      path: file_cap.path().to_path_buf(),
+      staged: None,
+      backed_up: false,
+      installed: false,
+    };
+    let () = self.changes.push(change);
+  }
+
+  /// Record all staged changes in a journal in each of the affected
+  /// directories.
+  async fn record(&mut self) -> Result<()> {
+    let mut journals = BTreeMap::<&Path, Vec<Entry>>::new();
+    for change in &self.changes {
+      // SANITY: All paths we work with are those of files and, hence,
+      //         have a parent and a file name.
+      let dir = change.path.parent().unwrap();
+      let name = change.path.file_name().unwrap();
+      let name = name
+        .to_str()
+        .filter(|name| !name.contains('\n'))
+        .with_context(|| format!("file name {} is not supported", change.path.display()))?
+        .to_string();
+      let entry = if change.staged.is_some() {
+        Entry::Write(name)
+      } else {
+        Entry::Remove(name)
+      };
+
+      let () = journals.entry(dir).or_default().push(entry);
+    }
+
+    let id = Uuid::new_v4().to_string();
+    let dirs = journals
+      .keys()
+      .map(|dir| dir.to_path_buf())
+      .collect::<Vec<_>>();
+
+    for (dir, entries) in journals {
+      let journal = Journal {
+        id: id.clone(),
+        dirs: dirs.clone(),
+        entries,
+      };
+      let text = journal.to_text()?;
+
+      let path = dir.join(JOURNAL_NAME);
+      let () = self.journals.push(path.clone());
+      let mut file = File::create(&path)
+        .await
+        .with_context(|| format!("failed to create {}", path.display()))?;
+      let () = file
+        .write_all(text.as_bytes())
+        .await
+        .with_context(|| format!("failed to write {}", path.display()))?;
+      let () = file
+        .sync_all()
+        .await
+        .with_context(|| format!("failed to sync {}", path.display()))?;
+      let () = sync_dir(dir).await?;
+    }
+    Ok(())
+  }
+
+  /// Apply all staged changes.
+  async fn apply(&mut self) -> Result<()> {
+    for change in self.changes.iter_mut() {
+      let () = change.apply().await?;
+    }
+
+    let mut dirs = self
+      .changes
+      .iter()
+      .filter_map(|change| change.path.parent())
+      .collect::<Vec<_>>();
+    let () = dirs.sort();
+    let () = dirs.dedup();
+
+    for dir in dirs {
+      let () = sync_dir(dir).await?;
+    }
+    Ok(())
+  }
+
+  /// Commit all staged changes.
+  ///
+  /// If any of the changes fails to apply, all others are reverted.
+  pub async fn commit(mut self) -> Result<()> {
+    let mut result = self.record().await;
+    if result.is_ok() {
+      result = self.apply().await;
+    }
+
+    let result = if let Err(err) = result {
+      let mut err = err.context("failed to commit changes");
+      let mut reverted = true;
+      for change in self.changes.iter_mut().rev() {
+        if let Err(revert_err) = change.revert().await {
+          err = err.context(format!("failed to roll back changes: {revert_err:#}"));
+          reverted = false;
+        }
+      }
+
+      if !reverted {
+        // Leave staged files, backups, and journals in place, so that
+        // the commit can be completed by recovery on the next start.
+        let () = self.changes.clear();
+        let () = self.journals.clear();
+      }
+      Err(err)
+    } else {
+      Ok(())
+    };
+
+    let mut cleaned = true;
+    for change in take(&mut self.changes) {
+      cleaned &= change.clean_up();
+    }
+    // Journals have to be removed only once all backups are gone, or
+    // recovery may resurrect them.
+    let journals = take(&mut self.journals);
+    if cleaned {
+      for journal in journals {
+        let _result = remove_file_sync(journal);
+      }
+    }
+    result
+  }
+}
+
+impl Drop for Transaction {
+  fn drop(&mut self) {
+    // A transaction that was not committed should not leave any
+    // traces.
+    for change in &self.changes {
+      let _cleaned = change.clean_up();
+    }
+    for journal in &self.journals {
+      let _result = remove_file_sync(journal);
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::fs::read_dir;
+
+  use tempfile::TempDir;
+
+  use tokio::fs::read_to_string;
+  use tokio::fs::write;
+  use tokio::test;
+
+  use crate::cap::DirCap;
+
+
+  /// Write the journal of a transaction with the given ID and spanning
+  /// `dirs` into `dir`.
+  async fn write_journal(dir: &Path, id: &str, dirs: &[&Path], entries: Vec<Entry>) {
+    let journal = Journal {
+      id: id.to_string(),
+      dirs: dirs.iter().map(|dir| dir.to_path_buf()).collect(),
+      entries,
+    };
+    let () = write(dir.join(JOURNAL_NAME), journal.to_text().unwrap())
+      .await
+      .unwrap();
+  }
+
+  /// List the names of all files in the provided directory.
+  fn files(dir: &Path) -> Vec<OsString> {
+    read_dir(dir)
+      .unwrap()
+      .map(|entry| entry.unwrap().file_name())
+      .collect()
+  }
+
+
+  /// Check that we can detect the temporary files of a transaction.
+  #[test]
+  async fn transaction_file_detection() {
+    let path = Path::new("/tmp/file");
+    assert!(is_transaction_file(
+      sibling(path, STAGED_SUFFIX).file_name().unwrap()
+    ));
+    assert!(is_transaction_file(
+      sibling(path, BACKUP_SUFFIX).file_name().unwrap()
+    ));
+    assert!(is_transaction_file(OsStr::new(JOURNAL_NAME)));
+    assert!(!is_transaction_file(path.file_name().unwrap()));
+    assert!(!is_transaction_file(OsStr::new("file.new")));
+  }
+
+  /// Check that journals can be converted to text and back and that
+  /// incompletely written ones are detected.
+  #[test]
+  async fn journal_text_conversion() {
+    let journal = Journal {
+      id: "id".to_string(),
+      dirs: vec![PathBuf::from("/tmp/a"), PathBuf::from("/tmp/b")],
+      entries: vec![Entry::Write("a".to_string()), Entry::Remove("b".to_string())],
+    };
+    let text = journal.to_text().unwrap();
+    assert_eq!(Journal::from_text(&text).unwrap(), Some(journal));
+
+    for len in 0..text.len() {
+      assert_eq!(Journal::from_text(&text[..len]).unwrap(), None);
+    }
+
+    let text = text.replacen("+a", "+c", 1);
+    assert_eq!(Journal::from_text(&text).unwrap(), None);
+  }
+
+  /// Make sure that a transaction applies all staged changes.
+  #[test]
+  async fn commit_transaction() {
+    let root = TempDir::new().unwrap();
+    let () = write(root.path().join("a"), "a").await.unwrap();
+    let () = write(root.path().join("b"), "b").await.unwrap();
+
+    let mut dir_cap = DirCap::for_dir(root.path().join("dir")).await.unwrap();
+    let () = {
+      let write_guard = dir_cap.write().await.unwrap();
+      let mut transaction = Transaction::new();
+      let () = transaction
+        .write(&mut write_guard.file_cap(OsStr::new("new")), b"new")
+        .await
+        .unwrap();
+      transaction.commit().await.unwrap()
+    };
+
+    let mut dir_cap = DirCap::for_dir(root.path().to_path_buf()).await.unwrap();
+    let write_guard = dir_cap.write().await.unwrap();
+    let mut transaction = Transaction::new();
+    let () = transaction
+      .write(&mut write_guard.file_cap(OsStr::new("a")), b"A")
+      .await
+      .unwrap();
+    let () = transaction.remove(&write_guard.file_cap(OsStr::new("b")));
+    let () = transaction.commit().await.unwrap();
+
+    let a = read_to_string(root.path().join("a")).await.unwrap();
+    assert_eq!(a, "A");
+    assert!(!root.path().join("b").exists());
+    let new = read_to_string(root.path().join("dir").join("new"))
+      .await
+      .unwrap();
+    assert_eq!(new, "new");
+
+    // No temporary files should be left over.
+    let files = read_dir(root.path())
+      .unwrap()
+      .map(|entry| entry.unwrap().file_name())
+      .collect::<Vec<_>>();
+    assert_eq!(files.len(), 2, "{files:?}");
+  }
+
+  /// Check that a transaction that fails to commit leaves all files in
+  /// their original state.
+  #[test]
+  async fn roll_back_transaction() {
+    let root = TempDir::new().unwrap();
+    let () = write(root.path().join("a"), "a").await.unwrap();
+    let () = write(root.path().join("b"), "b").await.unwrap();
+    let () = write(root.path().join("c"), "c").await.unwrap();
+
+    let mut dir_cap = DirCap::for_dir(root.path().to_path_buf()).await.unwrap();
+    let write_guard = dir_cap.write().await.unwrap();
+    let mut transaction = Transaction::new();
+    let () = transaction
+      .write(&mut write_guard.file_cap(OsStr::new("a")), b"A")
+      .await
+      .unwrap();
+    let () = transaction.remove(&write_guard.file_cap(OsStr::new("b")));
+    let () = transaction
+      .write(&mut write_guard.file_cap(OsStr::new("c")), b"C")
+      .await
+      .unwrap();
+    let () = transaction
+      .write(&mut write_guard.file_cap(OsStr::new("d")), b"D")
+      .await
+      .unwrap();
+
+    // Remove one of the staged files behind the transaction's back to
+    // make it fail moving it into place.
+    let () = remove_file(root.path().join(".c.new")).await.unwrap();
+    let error = transaction.commit().await.unwrap_err();
+    assert_eq!(error.to_string(), "failed to commit changes");
+
+    for (name, content) in [("a", "a"), ("b", "b"), ("c", "c")] {
+      let data = read_to_string(root.path().join(name)).await.unwrap();
+      assert_eq!(data, content);
+    }
+    let files = read_dir(root.path())
+      .unwrap()
+      .map(|entry| entry.unwrap().file_name())
+      .collect::<Vec<_>>();
+    assert_eq!(files.len(), 3, "{files:?}");
+  }
+
+  /// Make sure that dropping a transaction without committing it
+  /// removes all staged data.
+  #[test]
+  async fn drop_transaction() {
+    let root = TempDir::new().unwrap();
+    let mut dir_cap = DirCap::for_dir(root.path().to_path_buf()).await.unwrap();
+    let write_guard = dir_cap.write().await.unwrap();
+    let mut transaction = Transaction::new();
+    let () = transaction
+      .write(&mut write_guard.file_cap(OsStr::new("a")), b"A")
+      .await
+      .unwrap();
+    assert!(root.path().join(".a.new").exists());
+
+    drop(transaction);
+    assert!(!root.path().join(".a.new").exists());
+    assert!(!root.path().join("a").exists());
+  }
+
+  /// Check that recovery rolls forward a transaction that was
+  /// interrupted after it got recorded in the journal.
+  #[test]
+  async fn recover_roll_forward() {
+    let root = TempDir::new().unwrap();
+    let () = write(root.path().join("a"), "a").await.unwrap();
+    let () = write(root.path().join("b"), "b").await.unwrap();
+    let () = write(root.path().join("c"), "c").await.unwrap();
+
+    // Simulate a crash after `a` got replaced (leaving its backup) but
+    // before `b` got removed and `c` got replaced.
+    let () = write(root.path().join("a"), "A").await.unwrap();
+    let () = write(root.path().join(".a.old"), "a").await.unwrap();
+    let () = write(root.path().join(".c.new"), "C").await.unwrap();
+    let entries = vec![
+      Entry::Write("a".to_string()),
+      Entry::Remove("b".to_string()),
+      Entry::Write("c".to_string()),
+    ];
+    let () = write_journal(root.path(), "id", &[root.path()], entries).await;
+
+    let () = recover(root.path()).await.unwrap();
+
+    let a = read_to_string(root.path().join("a")).await.unwrap();
+    assert_eq!(a, "A");
+    assert!(!root.path().join("b").exists());
+    let c = read_to_string(root.path().join("c")).await.unwrap();
+    assert_eq!(c, "C");
+
+    let files = read_dir(root.path())
+      .unwrap()
+      .map(|entry| entry.unwrap().file_name())
+      .collect::<Vec<_>>();
+    assert_eq!(files.len(), 2, "{files:?}");
+  }
+
+  /// Check that recovery rolls back a transaction that was interrupted
+  /// before it got recorded in the journal.
+  #[test]
+  async fn recover_roll_back() {
+    let root = TempDir::new().unwrap();
+    let () = write(root.path().join("a"), "a").await.unwrap();
+    let () = write(root.path().join(".a.new"), "A").await.unwrap();
+    let () = write(root.path().join(".b.old"), "b").await.unwrap();
+
+    let () = recover(root.path()).await.unwrap();
+
+    let a = read_to_string(root.path().join("a")).await.unwrap();
+    assert_eq!(a, "a");
+    let b = read_to_string(root.path().join("b")).await.unwrap();
+    assert_eq!(b, "b");
+
+    let files = read_dir(root.path())
+      .unwrap()
+      .map(|entry| entry.unwrap().file_name())
+      .collect::<Vec<_>>();
+    assert_eq!(files.len(), 2, "{files:?}");
+  }
+
+  /// Check that recovery rolls forward a transaction spanning multiple
+  /// directories in all of them, once all journals have been written.
+  #[test]
+  async fn recover_roll_forward_multiple_dirs() {
+    let root1 = TempDir::new().unwrap();
+    let root2 = TempDir::new().unwrap();
+    let dirs = [root1.path(), root2.path()];
+    let () = write(root1.path().join("a"), "a").await.unwrap();
+    let () = write(root2.path().join("b"), "b").await.unwrap();
+
+    // Simulate a crash after `a` got replaced, but before `b` was.
+    let () = write(root1.path().join("a"), "A").await.unwrap();
+    let () = write(root1.path().join(".a.old"), "a").await.unwrap();
+    let () = write(root2.path().join(".b.new"), "B").await.unwrap();
+    let entries = vec![Entry::Write("a".to_string())];
+    let () = write_journal(root1.path(), "id", &dirs, entries).await;
+    let entries = vec![Entry::Write("b".to_string())];
+    let () = write_journal(root2.path(), "id", &dirs, entries).await;
+
+    // Recovery of either directory completes the transaction as a
+    // whole.
+    let () = recover(root1.path()).await.unwrap();
+    let a = read_to_string(root1.path().join("a")).await.unwrap();
+    assert_eq!(a, "A");
+    let b = read_to_string(root2.path().join("b")).await.unwrap();
+    assert_eq!(b, "B");
+    assert_eq!(files(root1.path()).len(), 1);
+    assert_eq!(files(root2.path()).len(), 1);
+
+    let () = recover(root2.path()).await.unwrap();
+    let b = read_to_string(root2.path().join("b")).await.unwrap();
+    assert_eq!(b, "B");
+  }
+
+  /// Check that recovery rolls back a transaction spanning multiple
+  /// directories if not all journals have been written.
+  #[test]
+  async fn recover_roll_back_incomplete_journals() {
+    let root1 = TempDir::new().unwrap();
+    let root2 = TempDir::new().unwrap();
+    let root3 = TempDir::new().unwrap();
+    let dirs = [root1.path(), root2.path(), root3.path()];
+    let () = write(root1.path().join("a"), "a").await.unwrap();
+    let () = write(root2.path().join("b"), "b").await.unwrap();
+    let () = write(root3.path().join("c"), "c").await.unwrap();
+
+    // Simulate a crash after the journal of the first directory got
+    // written, while the one of the second directory was still being
+    // written and that of the third one is left over from an earlier
+    // transaction.
+    let () = write(root1.path().join(".a.new"), "A").await.unwrap();
+    let () = write(root2.path().join(".b.new"), "B").await.unwrap();
+    let () = write(root3.path().join(".c.new"), "C").await.unwrap();
+    let entries = vec![Entry::Write("a".to_string())];
+    let () = write_journal(root1.path(), "id", &dirs, entries).await;
+    let entries = vec![Entry::Write("b".to_string())];
+    let () = write_journal(root2.path(), "id", &dirs, entries).await;
+    let path = root2.path().join(JOURNAL_NAME);
+    let journal = read_to_string(&path).await.unwrap();
+    let () = write(&path, &journal[..journal.len() - 2]).await.unwrap();
+    let entries = vec![Entry::Write("c".to_string())];
+    let () = write_journal(root3.path(), "other-id", &dirs, entries).await;
+
+    for dir in dirs {
+      let () = recover(dir).await.unwrap();
+    }
+
+    let a = read_to_string(root1.path().join("a")).await.unwrap();
+    assert_eq!(a, "a");
+    let b = read_to_string(root2.path().join("b")).await.unwrap();
+    assert_eq!(b, "b");
+    let c = read_to_string(root3.path().join("c")).await.unwrap();
+    assert_eq!(c, "c");
+    for dir in dirs {
+      assert_eq!(files(dir).len(), 1, "{:?}", files(dir));
+    }
+  }
+
+  /// Make sure that recovery of a directory without any transaction
+  /// data leaves it untouched.
+  #[test]
+  async fn recover_nothing() {
+    let root = TempDir::new().unwrap();
+    let () = write(root.path().join("a"), "a").await.unwrap();
+
+    let () = recover(root.path()).await.unwrap();
+    let () = recover(&root.path().join("non-existent")).await.unwrap();
+
+    let a = read_to_string(root.path().join("a")).await.unwrap();
+    assert_eq!(a, "a");
+  }
+}
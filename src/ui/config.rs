@@ -2,7 +2,6 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use std::path::Path;
-use std::rc::Rc;
 
 use anyhow::anyhow;
 use anyhow::Context as _;
@@ -16,8 +15,10 @@ use crate::ser::ToSerde;
 use crate::state::load_state_from_file;
 use crate::state::save_state_to_file;
 use crate::state::should_save_state;
+use crate::state::stage_state_to_file;
 use crate::state::TaskState;
 use crate::tags::Tag;
+use crate::transaction::Transaction;
 use crate::view::View;
 use crate::view::ViewBuilder;
 
@@ -59,20 +60,13 @@ impl Config {
     let templates = task_state.templates();
     let tasks = task_state.tasks();
 
-    let mut views = views
-      .into_iter()
-      .map(|view| {
-        let name = view.name.clone();
-        let view = View::with_serde(view, Rc::clone(templates), Rc::clone(tasks))
-          .with_context(|| format!("failed to instantiate view '{name}'"))?;
-        Ok(view)
-      })
-      .collect::<Result<Vec<_>>>()?;
+    let builder = ViewBuilder::new(templates, tasks);
+    let mut views = builder.with_serde(views)?;
 
     // For convenience for the user, we add a default view capturing
     // all tasks if no other views have been configured.
     if views.is_empty() {
-      views.push(ViewBuilder::new(templates, tasks).build("all", "")?)
+      views.push(builder.build("all", "")?)
     }
 
     let toggle_tag = if let Some(toggle_tag) = toggle_tag {
@@ -105,6 +99,15 @@ impl Config {
   pub async fn save(&self, file_cap: &mut FileCap<'_>) -> Result<()> {
     save_state_to_file::<Json, _>(file_cap, &self.to_serde()).await
   }
+
+  /// Stage persisting of the configuration as part of the provided transaction.
+  pub(crate) async fn stage(
+    &self,
+    transaction: &mut Transaction,
+    file_cap: &mut FileCap<'_>,
+  ) -> Result<()> {
+    stage_state_to_file::<Json, _>(transaction, file_cap, &self.to_serde()).await
+  }
 }
 
 impl ToSerde for Config {
@@ -129,14 +132,18 @@ pub mod tests {
   use super::*;
 
   use std::ffi::OsStr;
+  use std::str::FromStr as _;
 
   use tempfile::TempDir;
 
   use tokio::test;
 
   use crate::cap::DirCap;
+  use crate::formula::Formula;
   use crate::ser::state::TaskState as SerTaskState;
   use crate::ser::tasks::Tasks as SerTasks;
+  use crate::ser::view::FormulaPair;
+  use crate::ser::view::View as SerView;
   use crate::test::make_tasks;
 
 
@@ -193,4 +200,38 @@ pub mod tests {
 
     let _new_config = Config::load(&config, &task_state).await.unwrap();
   }
+
+  /// Check that views referencing each other are resolved and that
+  /// cyclic references are reported.
+  #[test]
+  async fn view_references() {
+    fn view(name: &str, formula: &str) -> SerView {
+      SerView {
+        name: name.to_string(),
+        formula: FormulaPair::from(Formula::from_str(formula).unwrap()),
+        sort: Vec::new(),
+        order: None,
+        groups: Vec::new(),
+      }
+    }
+
+    let (_config, task_state) = make_config(3);
+    let config = SerUiConfig {
+      views: vec![view("all", "@none | !@none"), view("none", "false")],
+      ..Default::default()
+    };
+    let config = Config::with_serde(config, &task_state).unwrap();
+    assert_eq!(config.views[0].iter(|iter| iter.count()), 3);
+    assert!(config.views[1].is_empty());
+
+    let config = SerUiConfig {
+      views: vec![view("all", "@none | !@none"), view("none", "@all")],
+      ..Default::default()
+    };
+    let err = Config::with_serde(config, &task_state).unwrap_err();
+    assert_eq!(
+      format!("{err:#}"),
+      "failed to instantiate view 'none': encountered cyclic view reference `none` -> `all` -> `none`"
+    );
+  }
 }
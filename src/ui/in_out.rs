@@ -37,6 +37,15 @@ pub enum InOut {
   Saved,
   Search(String),
   Error(String),
+  /// An error pointing at a specific location within some input.
+  ErrorAt {
+    /// The error message.
+    error: String,
+    /// The input the error refers to.
+    input: String,
+    /// The byte offset into `input` that the error points at.
+    offset: usize,
+  },
   Input(Input),
   Clear,
 }
@@ -47,6 +56,18 @@ impl PartialEq for InOut {
       (InOut::Saved, InOut::Saved) => true,
       (InOut::Search(x), InOut::Search(y)) => x == y,
       (InOut::Error(x), InOut::Error(y)) => x == y,
+      (
+        InOut::ErrorAt {
+          error: error1,
+          input: input1,
+          offset: offset1,
+        },
+        InOut::ErrorAt {
+          error: error2,
+          input: input2,
+          offset: offset2,
+        },
+      ) => error1 == error2 && input1 == input2 && offset1 == offset2,
       (
         InOut::Input(Input {
           text: text1,
@@ -133,6 +154,11 @@ impl InOutAreaData {
     }
   }
 
+  /// Set the initial `InOut` state of the widget.
+  pub fn set_in_out(&mut self, in_out: InOut) {
+    self.in_out.set(in_out)
+  }
+
   /// Conditionally change the `InOut` state of the widget.
   ///
   /// # Notes
@@ -205,7 +231,7 @@ impl InOutArea {
         // between pre- and post-hook.
         if data.clear_gen.take() == Some(data.in_out.r#gen) {
           match data.in_out.get() {
-            InOut::Saved | InOut::Search(_) | InOut::Error(_) => {
+            InOut::Saved | InOut::Search(_) | InOut::Error(_) | InOut::ErrorAt { .. } => {
               data.change_state(parent, Some(InOut::Clear)).into_event()
             },
             InOut::Input(..) | InOut::Clear => None,
@@ -318,6 +344,15 @@ impl Handleable<Event, Message> for InOutArea {
           InOut::Saved => InOut::Saved,
           InOut::Search(x) => InOut::Search(x.clone()),
           InOut::Error(x) => InOut::Error(x.clone()),
+          InOut::ErrorAt {
+            error,
+            input,
+            offset,
+          } => InOut::ErrorAt {
+            error: error.clone(),
+            input: input.clone(),
+            offset: *offset,
+          },
           InOut::Input(Input { text, response_id }) => {
             let text = InputText::new(EditableText::clone(text.deref()));
             InOut::Input(Input {
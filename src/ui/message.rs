@@ -5,7 +5,9 @@ use std::rc::Rc;
 
 use gui::Id;
 
+use crate::tags::Tag;
 use crate::tasks::Task;
+use crate::view::View;
 
 use super::event::Event;
 use super::event::Ids;
@@ -59,6 +61,23 @@ pub enum Message {
   CollectedState(TabState),
   /// A message used to collect the state of all tabs.
   GetTabState(TabState),
+  /// Replace the views displayed along with the tag to toggle on user
+  /// initiated action.
+  SetViews(Vec<View>, Option<Tag>),
+  /// Replace the view displayed by a single tab along with the tag to
+  /// toggle on user initiated action.
+  SetView(View, Option<Tag>),
+  /// Add a tab displaying the given view after the selected one, along
+  /// with the tag to toggle on user initiated action.
+  AddTab(View, Option<Tag>),
+  /// Change the name of the tab with the given `Id`.
+  RenameTab(Id, String),
+  /// Remove the tab with the given `Id`.
+  RemoveTab(Id),
+  /// Reload tasks and UI configuration modified by another program.
+  /// The argument is used to indicate that reloading was performed, as
+  /// opposed to being deferred.
+  ReloadModified(bool),
   /// An indication that one or more widgets changed and that we should
   /// re-render them.
   Updated(Ids),
@@ -20,6 +20,7 @@ mod termui;
 pub use config::Config;
 pub use event::Event;
 pub use event::Ids;
+pub use in_out::InOut;
 pub use message::Message;
 pub use state::State;
 pub use term_renderer::TermRenderer as Renderer;
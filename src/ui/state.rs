@@ -12,6 +12,8 @@ use crate::ser::state::UiState as SerUiState;
 use crate::ser::ToSerde;
 use crate::state::load_state_from_file;
 use crate::state::save_state_to_file;
+use crate::state::stage_state_to_file;
+use crate::transaction::Transaction;
 
 
 /// A struct encapsulating the UI's "volatile" state.
@@ -22,6 +24,9 @@ pub struct State {
   pub selected_tasks: Vec<Option<usize>>,
   /// The currently selected `View`.
   pub selected_view: Option<usize>,
+  /// The names of the tags identifying the collapsed sections of each
+  /// view, indexed by view.
+  pub collapsed_sections: Vec<Vec<Option<String>>>,
 }
 
 impl State {
@@ -40,6 +45,7 @@ impl State {
     Self {
       selected_tasks: state.selected_tasks,
       selected_view: state.selected_view,
+      collapsed_sections: state.collapsed_sections,
     }
   }
 
@@ -47,6 +53,15 @@ impl State {
   pub async fn save(&self, file_cap: &mut FileCap<'_>) -> Result<()> {
     save_state_to_file::<Json, _>(file_cap, &self.to_serde()).await
   }
+
+  /// Stage persisting of the state as part of the provided transaction.
+  pub(crate) async fn stage(
+    &self,
+    transaction: &mut Transaction,
+    file_cap: &mut FileCap<'_>,
+  ) -> Result<()> {
+    stage_state_to_file::<Json, _>(transaction, file_cap, &self.to_serde()).await
+  }
 }
 
 impl ToSerde for State {
@@ -57,6 +72,7 @@ impl ToSerde for State {
     let state = SerUiState {
       selected_tasks: self.selected_tasks.clone(),
       selected_view: self.selected_view,
+      collapsed_sections: self.collapsed_sections.clone(),
     };
     state
   }
@@ -149,6 +149,9 @@ pub struct TabBarData {
   search: Search,
   /// A copied task. Used for copy & paste operations.
   copied_task: Option<Task>,
+  /// The widgets of tabs no longer present. Widgets cannot be removed
+  /// from the UI and so these are kept hidden, for reuse by new tabs.
+  unused: Vec<Id>,
 }
 
 impl TabBarData {
@@ -160,6 +163,7 @@ impl TabBarData {
       prev_selection: 0,
       search: Search::Unset,
       copied_task: None,
+      unused: Vec::new(),
     }
   }
 
@@ -188,7 +192,12 @@ impl TabBarData {
 #[gui(Event = Event, Message = Message)]
 pub struct TabBar {
   id: Id,
+  detail_dialog: Id,
+  tag_dialog: Id,
+  kseq: Id,
   in_out: Id,
+  /// The shared task database.
+  tasks: Rc<Tasks>,
 }
 
 impl TabBar {
@@ -209,31 +218,22 @@ impl TabBar {
     let count = views.len();
     let selected = selected.map(|x| min(x, isize::MAX as usize)).unwrap_or(0) as isize;
     let selected = sanitize_selection(selected, count);
-    let tab_bar = id;
+
+    let tab_bar = Self {
+      id,
+      detail_dialog,
+      tag_dialog,
+      kseq,
+      in_out,
+      tasks,
+    };
 
     let tabs = views
       .into_iter()
       .enumerate()
       .map(|(i, (view, task))| {
         let name = view.name().to_string();
-        let tasks = Rc::clone(&tasks);
-        let toggle_tag = toggle_tag.clone();
-        let task_list = cap.add_widget(
-          id,
-          Box::new(|| Box::new(TaskListBoxData::new(tasks, view, toggle_tag))),
-          Box::new(move |id, cap| {
-            Box::new(TaskListBox::new(
-              id,
-              cap,
-              kseq,
-              tab_bar,
-              detail_dialog,
-              tag_dialog,
-              in_out,
-              task,
-            ))
-          }),
-        );
+        let task_list = tab_bar.add_tab(cap, view, toggle_tag.clone(), task);
 
         if i == selected {
           cap.focus(task_list);
@@ -244,7 +244,6 @@ impl TabBar {
       })
       .collect();
 
-    let tab_bar = Self { id, in_out };
     let data = tab_bar.data_mut::<TabBarData>(cap);
     data.tabs = tabs;
     data.selection = selected as isize;
@@ -253,6 +252,177 @@ impl TabBar {
     tab_bar
   }
 
+  /// Create the widget for a tab displaying the given view.
+  ///
+  /// The widget of a tab no longer present is reused, if available.
+  /// Such a widget stays hidden.
+  fn add_tab(
+    &self,
+    cap: &mut dyn MutCap<Event, Message>,
+    view: View,
+    toggle_tag: Option<Tag>,
+    task: Option<usize>,
+  ) -> Id {
+    let data = self.data_mut::<TabBarData>(cap);
+    if let Some(tab) = data.unused.pop() {
+      // SANITY: All tabs are `TaskListBox` widgets and so their data
+      //         is guaranteed to be a `TaskListBoxData` object.
+      let data = cap.data_mut(tab).downcast_mut::<TaskListBoxData>().unwrap();
+      let () = data.reset(view, toggle_tag, task);
+      return tab
+    }
+
+    let tab_bar = self.id;
+    let tasks = Rc::clone(&self.tasks);
+    let Self {
+      kseq,
+      detail_dialog,
+      tag_dialog,
+      in_out,
+      ..
+    } = *self;
+
+    cap.add_widget(
+      tab_bar,
+      Box::new(|| Box::new(TaskListBoxData::new(tasks, view, toggle_tag))),
+      Box::new(move |id, cap| {
+        Box::new(TaskListBox::new(
+          id,
+          cap,
+          kseq,
+          tab_bar,
+          detail_dialog,
+          tag_dialog,
+          in_out,
+          task,
+        ))
+      }),
+    )
+  }
+
+  /// Replace the views displayed by the tabs.
+  ///
+  /// Existing tabs are reused, with their selection being retained.
+  async fn set_views(
+    &self,
+    cap: &mut dyn MutCap<Event, Message>,
+    views: Vec<View>,
+    toggle_tag: Option<Tag>,
+  ) -> Option<Message> {
+    let data = self.data::<TabBarData>(cap);
+    let mut old_tabs = data
+      .tabs
+      .iter()
+      .map(|(_, id)| id)
+      .copied()
+      .collect::<Vec<_>>()
+      .into_iter();
+
+    let mut tabs = Vec::with_capacity(views.len());
+    for view in views {
+      let name = view.name().to_string();
+      let tab = if let Some(tab) = old_tabs.next() {
+        let message = Message::SetView(view, toggle_tag.clone());
+        let _msg = cap.send(tab, message).await;
+        tab
+      } else {
+        let tab = self.add_tab(cap, view, toggle_tag.clone(), None);
+        let () = cap.hide(tab);
+        tab
+      };
+      let () = tabs.push((name, tab));
+    }
+
+    // Widgets cannot be removed from the UI, so we merely hide those
+    // of tabs no longer present, for later reuse.
+    let old_tabs = old_tabs.collect::<Vec<_>>();
+    for tab in old_tabs.iter() {
+      let () = cap.hide(*tab);
+    }
+
+    let data = self.data_mut::<TabBarData>(cap);
+    let () = data.unused.extend(old_tabs);
+    data.tabs = tabs;
+    let count = data.tabs.len();
+    data.selection = sanitize_selection(data.selection, count) as isize;
+    data.prev_selection = sanitize_selection(data.prev_selection, count) as isize;
+    let selected = data.selected_tab();
+    let () = cap.focus(selected);
+
+    Some(Message::updated(self.id))
+  }
+
+  /// Add a tab displaying the given view right after the selected one
+  /// and select it.
+  fn insert_tab(
+    &self,
+    cap: &mut dyn MutCap<Event, Message>,
+    view: View,
+    toggle_tag: Option<Tag>,
+  ) -> Option<Message> {
+    let name = view.name().to_string();
+    let tab = self.add_tab(cap, view, toggle_tag, None);
+
+    let data = self.data_mut::<TabBarData>(cap);
+    let selection = data.selection();
+    let selected = data.selected_tab();
+    let () = data.tabs.insert(selection + 1, (name, tab));
+    data.prev_selection = selection as isize;
+    data.selection = selection as isize + 1;
+
+    let () = cap.hide(selected);
+    let () = cap.focus(tab);
+    Some(Message::updated(self.id))
+  }
+
+  /// Change the name of the tab with the given `Id`.
+  fn rename_tab(&self, cap: &mut dyn MutCap<Event, Message>, tab: Id, name: String) -> bool {
+    let data = self.data_mut::<TabBarData>(cap);
+    if let Some((title, _)) = data.tabs.iter_mut().find(|(_, id)| *id == tab) {
+      *title = name;
+      true
+    } else {
+      false
+    }
+  }
+
+  /// Remove the tab with the given `Id`, selecting the one taking its
+  /// place.
+  fn remove_tab(&self, cap: &mut dyn MutCap<Event, Message>, tab: Id) -> bool {
+    let data = self.data_mut::<TabBarData>(cap);
+    let index = if let Some(index) = data.tabs.iter().position(|(_, id)| *id == tab) {
+      index
+    } else {
+      return false
+    };
+
+    let count = data.tabs.len();
+    // Tabs to the right of the removed one move one position to the
+    // left, while the removed one is replaced by its right neighbor,
+    // if any.
+    let adjust = |selection: isize| {
+      let selection = sanitize_selection(selection, count);
+      let selection = if selection > index {
+        selection - 1
+      } else {
+        selection
+      };
+      sanitize_selection(selection as isize, count - 1) as isize
+    };
+
+    let _removed = data.tabs.remove(index);
+    data.selection = adjust(data.selection);
+    data.prev_selection = adjust(data.prev_selection);
+    let selected = data.selected_tab();
+    // Widgets cannot be removed from the UI, so we merely hide the one
+    // of the removed tab, for later reuse.
+    let () = data.unused.push(tab);
+
+    let () = cap.hide(tab);
+    let () = cap.focus(selected);
+    true
+  }
+
   /// Initiate the search of a task based on a string.
   async fn start_task_search(
     &self,
@@ -565,6 +735,12 @@ impl Handleable<Event, Message> for TabBar {
         data.copied_task = Some(copied);
         None
       },
+      Message::SetViews(views, toggle_tag) => self.set_views(cap, views, toggle_tag).await,
+      Message::AddTab(view, toggle_tag) => self.insert_tab(cap, view, toggle_tag),
+      Message::RenameTab(tab, name) => self
+        .rename_tab(cap, tab, name)
+        .then(|| Message::updated(self.id)),
+      Message::RemoveTab(tab) => self.remove_tab(cap, tab).then(|| Message::updated(self.id)),
       message => panic!("Received unexpected message: {message:?}"),
     }
   }
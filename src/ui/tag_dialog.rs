@@ -1,6 +1,7 @@
 // Copyright (C) 2021-2025 Daniel Mueller <deso@posteo.net>
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use std::cell::Ref;
 use std::cmp::Ordering;
 use std::collections::HashSet;
 use std::rc::Rc;
@@ -15,10 +16,16 @@ use gui::MutCap;
 use gui::Widget;
 
 use crate::tags::Tag;
+use crate::tags::Template;
 use crate::tasks::Task;
+use crate::tasks::Tasks;
+use crate::text::EditableText;
 
 use super::event::Event;
 use super::event::Key;
+use super::in_out::InOut;
+use super::in_out::Input;
+use super::input::InputText;
 use super::message::Message;
 use super::message::MessageExt;
 use super::modal::Modal;
@@ -36,12 +43,19 @@ pub enum SetUnsetTag {
 
 impl SetUnsetTag {
   /// Retrieve the tag's name.
-  pub fn name(&self) -> &str {
+  pub fn name(&self) -> Ref<'_, str> {
     match self {
       Self::Unset(template) | Self::Set(template) => template.name(),
     }
   }
 
+  /// Retrieve the underlying tag.
+  fn tag(&self) -> &Tag {
+    match self {
+      Self::Unset(tag) | Self::Set(tag) => tag,
+    }
+  }
+
   /// Check whether the tag is set.
   pub fn is_set(&self) -> bool {
     match self {
@@ -90,6 +104,18 @@ fn prepare_tags(task: &Task) -> Vec<SetUnsetTag> {
 }
 
 
+/// Sort a list of tags such that set ones come first, with each group
+/// being sorted by name.
+fn sort_tags(tags: &mut [SetUnsetTag]) {
+  tags.sort_by(|lhs, rhs| {
+    rhs
+      .is_set()
+      .cmp(&lhs.is_set())
+      .then_with(|| cmp_template(lhs.tag(), rhs.tag()))
+  })
+}
+
+
 /// An enum indicating in which direction to search for the next desired
 /// entry.
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -101,6 +127,16 @@ enum Direction {
 }
 
 
+/// The kind of text input the dialog is waiting for.
+#[derive(Debug)]
+enum InputState {
+  /// The name of a new tag is being entered.
+  AddTag,
+  /// A new name for the given tag template is being entered.
+  RenameTag(Rc<Template>),
+}
+
+
 #[derive(Debug)]
 struct Data {
   /// The ID of the previously focused widget.
@@ -115,6 +151,8 @@ struct Data {
   selection: isize,
   /// Whether the user has started a "jump to" operation.
   jump_to: Option<Direction>,
+  /// The text input we are waiting for, if any.
+  input: Option<InputState>,
 }
 
 impl Data {
@@ -129,6 +167,7 @@ impl Data {
       tags,
       selection: 0,
       jump_to: None,
+      input: None,
     }
   }
 
@@ -160,6 +199,18 @@ impl Data {
     }
   }
 
+  /// Sort the tags and select the one instantiated from `template`.
+  fn sort_and_select(&mut self, template: &Rc<Template>) {
+    let () = sort_tags(&mut self.tags);
+    if let Some(idx) = self
+      .tags
+      .iter()
+      .position(|tag| tag.tag().template() == *template)
+    {
+      let _changed = self.select(idx as isize);
+    }
+  }
+
   /// Convert the `Data` into a `Task` (and its ID) with updated tags.
   fn into_task(mut self) -> (Rc<Task>, Task) {
     let tags = self.tags.into_iter().filter_map(|tag| match tag {
@@ -253,12 +304,113 @@ impl Selectable for TagDialogData {
 #[gui(Event = Event, Message = Message)]
 pub struct TagDialog {
   id: Id,
+  /// The ID of the input/output area, used for entering tag names.
+  in_out: Id,
+  /// The tasks whose tag templates we manage.
+  tasks: Rc<Tasks>,
 }
 
 impl TagDialog {
   /// Create a new `TagDialog`.
-  pub fn new(id: Id) -> Self {
-    Self { id }
+  pub fn new(id: Id, in_out: Id, tasks: Rc<Tasks>) -> Self {
+    Self { id, in_out, tasks }
+  }
+
+  /// Request input of a tag name from the user.
+  async fn input_name(
+    &self,
+    cap: &mut dyn MutCap<Event, Message>,
+    input: InputState,
+    name: String,
+  ) -> Option<Message> {
+    let data = self.data_mut::<TagDialogData>(cap);
+    data
+      .data
+      .as_mut()
+      .map(|data| data.input = Some(input))
+      .expect("dialog has no data set");
+
+    let mut text = EditableText::from_string(name);
+    let () = text.move_end();
+    let input = Input {
+      text: InputText::new(text),
+      response_id: self.id,
+    };
+    let message = Message::SetInOut(InOut::Input(input));
+    cap.send(self.in_out, message).await
+  }
+
+  /// Report an error to the user.
+  async fn report_error(
+    &self,
+    cap: &mut dyn MutCap<Event, Message>,
+    error: String,
+  ) -> Option<Message> {
+    let message = Message::SetInOut(InOut::Error(error));
+    cap
+      .send(self.in_out, message)
+      .await
+      .maybe_update(Some(Message::updated(self.id)))
+  }
+
+  /// Remove the tag template of the currently selected tag.
+  async fn remove_selected(&self, cap: &mut dyn MutCap<Event, Message>) -> Option<Message> {
+    let data = self.data_mut::<TagDialogData>(cap);
+    let template = data.selected_tag()?.tag().template();
+    match self.tasks.remove_template(&template) {
+      Ok(()) => {
+        let selection = data.selection(0);
+        let data = data.data.as_mut().expect("dialog has no data set");
+        let _tag = data.tags.remove(selection);
+        Some(Message::updated(self.id))
+      },
+      Err(err) => {
+        let error = format!("Failed to remove tag: {err}");
+        self.report_error(cap, error).await
+      },
+    }
+  }
+
+  /// Handle text entered by the user in response to an input request.
+  async fn handle_entered_text(
+    &self,
+    cap: &mut dyn MutCap<Event, Message>,
+    text: String,
+  ) -> Option<Message> {
+    let data = self
+      .data_mut::<TagDialogData>(cap)
+      .data
+      .as_mut()
+      .expect("dialog has no data set");
+    let input = data.input.take().expect("TagDialog is in unexpected state");
+
+    let result = match input {
+      InputState::AddTag => {
+        if text.is_empty() {
+          return None
+        }
+
+        self.tasks.add_template(text).map(|tag| {
+          // A newly created tag is most likely meant to be set on the
+          // task at hand.
+          let template = tag.template();
+          let () = data.tags.push(SetUnsetTag::Set(tag));
+          let () = data.sort_and_select(&template);
+        })
+      },
+      InputState::RenameTag(template) => self
+        .tasks
+        .rename_template(&template, text)
+        .map(|()| data.sort_and_select(&template)),
+    };
+
+    match result {
+      Ok(()) => Some(Message::updated(self.id)),
+      Err(err) => {
+        let error = format!("Failed to update tag: {err}");
+        self.report_error(cap, error).await
+      },
+    }
   }
 
   /// Handle a key press.
@@ -286,6 +438,19 @@ impl TagDialog {
         Some(Message::updated(cap.parent_id(self.id).unwrap()))
       },
       Key::Char(' ') => data.toggle_tag().then(|| Message::updated(self.id)),
+      Key::Char('a') => {
+        self
+          .input_name(cap, InputState::AddTag, String::new())
+          .await
+      },
+      Key::Char('d') => self.remove_selected(cap).await,
+      Key::Char('r') => {
+        let template = data.selected_tag()?.tag().template();
+        let name = template.name().to_string();
+        self
+          .input_name(cap, InputState::RenameTag(template), name)
+          .await
+      },
       Key::Char('f') => {
         data
           .data
@@ -401,6 +566,12 @@ impl Handleable<Event, Message> for TagDialog {
         self.make_focused(cap);
         Some(Message::updated(self.id))
       },
+      Message::EnteredText(text) => self.handle_entered_text(cap, text).await,
+      Message::InputCanceled => {
+        let data = self.data_mut::<TagDialogData>(cap);
+        let _input = data.data.as_mut().and_then(|data| data.input.take());
+        None
+      },
       message => panic!("Received unexpected message: {message:?}"),
     }
   }
@@ -4,6 +4,9 @@
 use std::cmp::min;
 use std::ops::Deref as _;
 use std::rc::Rc;
+use std::str::FromStr as _;
+
+use anyhow::Error;
 
 use async_trait::async_trait;
 
@@ -14,6 +17,8 @@ use gui::Id;
 use gui::MutCap;
 use gui::Widget;
 
+use crate::date::Date;
+use crate::formula::ParseError;
 use crate::tags::Tag;
 use crate::tasks::Task;
 use crate::tasks::Tasks;
@@ -32,12 +37,22 @@ use super::tab_bar::SearchState;
 use super::tab_bar::TabState;
 
 
+/// The error reported when attempting to move a task past one that the
+/// view's sort keys or sections order differently.
+const SORTED_MOVE_ERROR: &str = "Cannot move task against the view's sort order or grouping";
+
+
 /// An enum representing the state a `TaskListBox` can be in.
 #[derive(Debug)]
 enum State {
   AddTask,
   EditTask { task: Rc<Task>, edited: Task },
+  EditDue { task: Rc<Task>, edited: Task },
+  EditStart { task: Rc<Task>, edited: Task },
   EditFormula,
+  AddView,
+  RenameView,
+  DuplicateView,
 }
 
 
@@ -67,6 +82,18 @@ impl TaskListBoxData {
     }
   }
 
+  /// Reset the object to represent the given view, with the task at
+  /// index `selected` being selected, as if it had just been created.
+  ///
+  /// Any ongoing interaction, such as the editing of a task, is
+  /// discarded.
+  pub fn reset(&mut self, view: View, toggle_tag: Option<Tag>, selected: Option<usize>) {
+    self.view = view;
+    self.toggle_tag = toggle_tag;
+    self.selection = initial_selection(selected);
+    self.state = None;
+  }
+
   /// Retrieve the selected task and its ID, if any.
   fn selected_task(&self) -> Option<Rc<Task>> {
     let selection = self.selection(0);
@@ -74,6 +101,58 @@ impl TaskListBoxData {
   }
 }
 
+
+/// Convert the index of the task to select initially into a selection.
+fn initial_selection(selected: Option<usize>) -> isize {
+  selected.map(|x| min(x, isize::MAX as usize)).unwrap_or(0) as isize
+}
+
+/// Create an [`Input`] for editing an optional date.
+fn date_input(date: Option<Date>, response_id: Id) -> Input {
+  let string = date.map(|date| date.to_string()).unwrap_or_default();
+  let mut text = EditableText::from_string(string);
+  let () = text.move_end();
+
+  Input {
+    text: InputText::new(text),
+    response_id,
+  }
+}
+
+/// Create an [`InOut`] reporting a failed operation on a view, pointing
+/// at the offending location of the entered `input` if it is a formula
+/// that could not be parsed.
+fn view_error(context: &str, input: &str, err: Error) -> InOut {
+  let error = format!("{context}: {err}");
+  if let Some(err) = err.downcast_ref::<ParseError>() {
+    InOut::ErrorAt {
+      error,
+      input: input.to_string(),
+      offset: err.offset(),
+    }
+  } else {
+    InOut::Error(error)
+  }
+}
+
+/// Parse the text entered for a date, with empty text clearing it.
+///
+/// The entered date is converted to the kind of the `original` one, if
+/// any, so that editing does not change, say, a UTC date into a
+/// floating one.
+fn parse_date(text: &str, original: Option<Date>) -> Result<Option<Date>, String> {
+  if text.trim().is_empty() {
+    Ok(None)
+  } else {
+    Date::from_str(text)
+      .map(|date| match original {
+        Some(original) => Some(date.into_kind_of(&original)),
+        None => Some(date),
+      })
+      .map_err(|err| format!("Failed to update date: {err}"))
+  }
+}
+
 impl Selectable for TaskListBoxData {
   fn selection_index(&self) -> isize {
     self.selection
@@ -123,8 +202,7 @@ impl TaskListBox {
       in_out,
     };
     let data = task_list_box.data_mut::<TaskListBoxData>(cap);
-    let selected = selected.map(|x| min(x, isize::MAX as usize)).unwrap_or(0) as isize;
-    data.selection = selected;
+    data.selection = initial_selection(selected);
 
     task_list_box
   }
@@ -174,6 +252,33 @@ impl TaskListBox {
     self.handle_select_task(cap, task, None).await
   }
 
+  /// Update one of a task's dates with the user provided text.
+  async fn update_date(
+    &self,
+    cap: &mut dyn MutCap<Event, Message>,
+    task: Rc<Task>,
+    mut edited: Task,
+    text: &str,
+    get_date: fn(&Task) -> Option<Date>,
+    set_date: fn(&mut Task, Option<Date>),
+  ) -> Option<Message> {
+    match parse_date(text, get_date(&edited)) {
+      Ok(date) => {
+        let () = set_date(&mut edited, date);
+        let data = self.data_mut::<TaskListBoxData>(cap);
+        data.tasks.update(Rc::clone(&task), edited);
+        self
+          .select_task(cap, task)
+          .await
+          .maybe_update(Some(Message::updated(self.id)))
+      },
+      Err(error) => {
+        let message = Message::SetInOut(InOut::Error(error));
+        cap.send(self.in_out, message).await
+      },
+    }
+  }
+
   /// Search for a task containing the given string.
   fn search_task_index(
     &self,
@@ -338,6 +443,32 @@ impl Handleable<Event, Message> for TaskListBox {
             None
           }
         },
+        Key::Char('D') => {
+          if let Some(task) = data.selected_task() {
+            // Make a deep copy of the task.
+            let edited = Task::clone(task.deref());
+            let input = date_input(edited.due(), self.id);
+            data.state = Some(State::EditDue { task, edited });
+
+            let message = Message::SetInOut(InOut::Input(input));
+            cap.send(self.in_out, message).await.into_event()
+          } else {
+            None
+          }
+        },
+        Key::Char('S') => {
+          if let Some(task) = data.selected_task() {
+            // Make a deep copy of the task.
+            let edited = Task::clone(task.deref());
+            let input = date_input(edited.start(), self.id);
+            data.state = Some(State::EditStart { task, edited });
+
+            let message = Message::SetInOut(InOut::Input(input));
+            cap.send(self.in_out, message).await.into_event()
+          } else {
+            None
+          }
+        },
         Key::Char('t') => {
           if let Some(task) = data.selected_task() {
             // Make a deep copy of the task to work on.
@@ -365,9 +496,11 @@ impl Handleable<Event, Message> for TaskListBox {
               let builder = Task::builder()
                 .set_summary(task.summary())
                 .set_tags(task.tags(|tags| tags.cloned().collect::<Vec<_>>()))
-                .set_details(task.details());
+                .set_details(task.details())
+                .set_due(task.due())
+                .set_start(task.start());
               let data = self.data_mut::<TaskListBoxData>(cap);
-              let task = data.tasks.add(builder, data.selected_task());
+              let task = data.view.add(builder, data.selected_task());
               let result2 = self.select_task(cap, task).await;
 
               result1
@@ -397,8 +530,13 @@ impl Handleable<Event, Message> for TaskListBox {
               .view
               .iter(|mut iter| iter.nth(data.selection(1)).cloned());
             if let Some(other) = other {
-              let () = data.tasks.move_after(to_move, other);
-              data.change_selection(1).then(|| Event::updated(self.id))
+              if data.view.compare(&to_move, &other).is_ne() {
+                let message = Message::SetInOut(InOut::Error(SORTED_MOVE_ERROR.to_string()));
+                cap.send(self.in_out, message).await.into_event()
+              } else {
+                let () = data.view.move_after(to_move, other);
+                data.change_selection(1).then(|| Event::updated(self.id))
+              }
             } else {
               None
             }
@@ -413,8 +551,13 @@ impl Handleable<Event, Message> for TaskListBox {
                 .view
                 .iter(|mut iter| iter.nth(data.selection(-1)).cloned());
               if let Some(other) = other {
-                let () = data.tasks.move_before(to_move, other);
-                data.change_selection(-1).then(|| Event::updated(self.id))
+                if data.view.compare(&to_move, &other).is_ne() {
+                  let message = Message::SetInOut(InOut::Error(SORTED_MOVE_ERROR.to_string()));
+                  cap.send(self.in_out, message).await.into_event()
+                } else {
+                  let () = data.view.move_before(to_move, other);
+                  data.change_selection(-1).then(|| Event::updated(self.id))
+                }
               } else {
                 None
               }
@@ -429,6 +572,39 @@ impl Handleable<Event, Message> for TaskListBox {
         Key::Char('G') => data.select(isize::MAX).then(|| Event::updated(self.id)),
         Key::Char('j') => data.change_selection(1).then(|| Event::updated(self.id)),
         Key::Char('k') => data.change_selection(-1).then(|| Event::updated(self.id)),
+        Key::Char('z') => {
+          if let Some(selected) = data.selected_task() {
+            let first = data
+              .view
+              .iter(|mut iter| iter.position(|task| data.view.same_section(task, &selected)));
+            if data.view.collapse_section(&selected) {
+              // Tasks following the collapsed section move up to where
+              // it started.
+              let _changed = data.select(first.unwrap_or(0) as isize);
+              Some(Event::updated(self.id))
+            } else {
+              None
+            }
+          } else {
+            None
+          }
+        },
+        Key::Char('Z') => {
+          let selected = data.selected_task();
+          if data.view.expand_sections() {
+            if let Some(selected) = selected {
+              let idx = data
+                .view
+                .iter(|mut iter| iter.position(|task| Rc::ptr_eq(task, &selected)));
+              if let Some(idx) = idx {
+                let _changed = data.select(idx as isize);
+              }
+            }
+            Some(Event::updated(self.id))
+          } else {
+            None
+          }
+        },
         Key::Char('*') => {
           if let Some(selected) = data.selected_task() {
             let message = Message::StartTaskSearch(selected.summary());
@@ -476,7 +652,7 @@ impl Handleable<Event, Message> for TaskListBox {
                 // (which would be the only other way we can conjure
                 // up to handle this case), is much worse of a user
                 // experience.
-                data.view.positive_tag_iter().cloned().collect()
+                data.view.positive_tag_iter().collect()
               };
 
               // We want the new task to be displayed after the
@@ -493,7 +669,7 @@ impl Handleable<Event, Message> for TaskListBox {
               //       special case logic for the 'complete' tag.
               let after = data.selected_task();
               let builder = Task::builder().set_summary(text).set_tags(tags);
-              let task = data.tasks.add(builder, after);
+              let task = data.view.add(builder, after);
               self.select_task(cap, task).await
             } else {
               None
@@ -513,6 +689,16 @@ impl Handleable<Event, Message> for TaskListBox {
               Some(Message::updated(self.id))
             }
           },
+          State::EditDue { task, edited } => {
+            self
+              .update_date(cap, task, edited, text, Task::due, Task::set_due)
+              .await
+          },
+          State::EditStart { task, edited } => {
+            self
+              .update_date(cap, task, edited, text, Task::start, Task::set_start)
+              .await
+          },
           State::EditFormula => {
             // Update the formula used by this `TaskListBox`'s view.
             // Note that we just keep selection where it was, which may
@@ -527,12 +713,65 @@ impl Handleable<Event, Message> for TaskListBox {
             match data.view.try_replace_formula(text) {
               Ok(()) => Some(Message::updated(self.id)),
               Err(err) => {
-                let error = InOut::Error(format!("Failed to update formula: {err}"));
+                let error = view_error("Failed to update formula", text, err);
                 let message = Message::SetInOut(error);
                 cap.send(self.in_out, message).await
               },
             }
           },
+          State::AddView => {
+            // New views are named after their formula, which users can
+            // change later on.
+            if !text.is_empty() {
+              match data.view.create(text.clone(), text) {
+                Ok(view) => {
+                  let message = Message::AddTab(view, data.toggle_tag.clone());
+                  cap.send(self.tab_bar, message).await
+                },
+                Err(err) => {
+                  let error = view_error("Failed to add view", text, err);
+                  let message = Message::SetInOut(error);
+                  cap.send(self.in_out, message).await
+                },
+              }
+            } else {
+              None
+            }
+          },
+          State::RenameView => {
+            if !text.is_empty() {
+              match data.view.rename(text.clone()) {
+                Ok(()) => {
+                  let message = Message::RenameTab(self.id, text.clone());
+                  cap.send(self.tab_bar, message).await
+                },
+                Err(err) => {
+                  let error = view_error("Failed to rename view", text, err);
+                  let message = Message::SetInOut(error);
+                  cap.send(self.in_out, message).await
+                },
+              }
+            } else {
+              None
+            }
+          },
+          State::DuplicateView => {
+            if !text.is_empty() {
+              match data.view.duplicate(text.clone()) {
+                Ok(view) => {
+                  let message = Message::AddTab(view, data.toggle_tag.clone());
+                  cap.send(self.tab_bar, message).await
+                },
+                Err(err) => {
+                  let error = view_error("Failed to duplicate view", text, err);
+                  let message = Message::SetInOut(error);
+                  cap.send(self.in_out, message).await
+                },
+              }
+            } else {
+              None
+            }
+          },
         }
       },
       Message::UpdateTask(task, updated) => {
@@ -558,11 +797,55 @@ impl Handleable<Event, Message> for TaskListBox {
         let message = Message::SetInOut(InOut::Input(input));
         cap.send(self.in_out, message).await
       },
+      Message::GotKeySeq(
+        (Key::Char('v'), ..),
+        (key @ (Key::Char('a') | Key::Char('r') | Key::Char('y')), ..),
+      ) => {
+        let (state, string) = match key {
+          Key::Char('a') => (State::AddView, String::new()),
+          Key::Char('r') => (State::RenameView, data.view.name().to_string()),
+          Key::Char('y') => (State::DuplicateView, data.view.name().to_string()),
+          _ => unreachable!(),
+        };
+        data.state = Some(state);
+
+        let mut text = EditableText::from_string(string);
+        let () = text.move_end();
+
+        let input = Input {
+          text: InputText::new(text),
+          response_id: self.id,
+        };
+        let message = Message::SetInOut(InOut::Input(input));
+        cap.send(self.in_out, message).await
+      },
+      Message::GotKeySeq((Key::Char('v'), ..), (Key::Char('d'), ..)) => match data.view.delete() {
+        Ok(()) => {
+          let message = Message::RemoveTab(self.id);
+          cap.send(self.tab_bar, message).await
+        },
+        Err(err) => {
+          let error = view_error("Failed to delete view", "", err);
+          let message = Message::SetInOut(error);
+          cap.send(self.in_out, message).await
+        },
+      },
       Message::GotKeySeq(..) => None,
       Message::InputCanceled => {
         let _state = data.state.take();
         None
       },
+      Message::SetView(mut view, toggle_tag) => {
+        // Similar to when the formula is edited, we keep the selection
+        // where it was. The same goes for collapsed sections, as long
+        // as the view is still the same.
+        if *view.name() == *data.view.name() {
+          let () = view.set_collapsed_sections(&data.view.collapsed_sections());
+        }
+        data.view = view;
+        data.toggle_tag = toggle_tag;
+        Some(Message::updated(self.id))
+      },
       message => panic!("Received unexpected message: {message:?}"),
     }
   }
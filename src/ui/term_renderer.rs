@@ -13,6 +13,7 @@ use std::io::Result;
 use std::io::Write;
 use std::ops::Add;
 use std::ops::Sub;
+use std::rc::Rc;
 
 use termion::color::Bg;
 use termion::color::Fg;
@@ -36,6 +37,7 @@ use crate::text;
 use crate::text::Cursor;
 use crate::text::DisplayWidth as _;
 use crate::text::Width;
+use crate::view::Section;
 use crate::LINE_END;
 
 use super::detail_dialog::DetailDialog;
@@ -289,6 +291,15 @@ where
 }
 
 
+/// A row of a `TaskListBox`.
+enum TaskListRow<'view> {
+  /// The header of a section.
+  Section(&'view Section),
+  /// A task along with its index in the view.
+  Task(usize, Rc<Task>),
+}
+
+
 /// A struct containing rendering related widget data with an offset.
 #[derive(Default)]
 struct OffsetData {
@@ -498,9 +509,67 @@ where
     let () = self
       .writer
       .fill_line(x, y, w, self.colors.unselected_task_bg)?;
+
+    // Dates are displayed right aligned, but only if there is enough
+    // room for them after the summary.
+    let mut dates = Vec::new();
+    if let Some(start) = task.start() {
+      if !start.is_past_now() {
+        dates.push((
+          format!("start {start}"),
+          self.colors.task_date_fg,
+          self.colors.task_date_bg,
+        ));
+      }
+    }
+    if let Some(due) = task.due() {
+      let (fg, bg) = if !tagged && due.is_past_now() {
+        (self.colors.task_overdue_fg, self.colors.task_overdue_bg)
+      } else {
+        (self.colors.task_date_fg, self.colors.task_date_bg)
+      };
+      dates.push((format!("due {due}"), fg, bg));
+    }
+
+    if !dates.is_empty() {
+      let width = dates.iter().map(|(date, ..)| date.len() + 1).sum::<usize>() as u16;
+      let start = w.saturating_sub(TASK_LIST_MARGIN_X + width);
+      if start > x {
+        let mut x = start;
+        for (date, fg, bg) in dates {
+          x += 1;
+          self.writer.write(x, y, fg, bg, &date)?;
+          x += date.len() as u16;
+        }
+      }
+    }
     Ok(())
   }
 
+  /// Render the header of a section of a `TaskListBox`.
+  fn render_task_list_section(&self, section: &Section, y: u16, w: u16) -> Result<()> {
+    let mut x = 0;
+    let () = self
+      .writer
+      .fill_line(0, y, TASK_LIST_MARGIN_X, self.colors.unselected_task_bg)?;
+    x += TASK_LIST_MARGIN_X;
+
+    let state = if section.collapsed { "[+]" } else { "[-]" };
+    let header = format!("{state} {} ({})", section.title, section.count);
+    let () = self.writer.write(
+      x,
+      y,
+      self.colors.task_section_fg,
+      self.colors.task_section_bg,
+      &header,
+    )?;
+    x += header.display_width().as_usize() as u16;
+
+    self
+      .writer
+      .fill_line(x, y, w, self.colors.unselected_task_bg)
+  }
+
   /// Render a `TaskListBox`.
   fn render_task_list_box(&self, task_list: &TaskListBox, cap: &dyn Cap, bbox: BBox) -> Result<()> {
     let mut map = self.data.borrow_mut();
@@ -511,37 +580,66 @@ where
     let view = task_list.view(cap);
     let count = displayable_tasks(bbox);
     let selection = task_list.selection(cap);
-    let offset = window_start(data.offset, count, selection);
+    let sections = view.sections();
 
-    let () = view.iter(|iter| {
-      let mut tasks = iter.enumerate().skip(offset).take(count);
-
-      (0..bbox.h).try_for_each(|y| {
-        if y < TASK_LIST_MARGIN_Y
-          || y > bbox.h - TASK_LIST_MARGIN_Y
-          || (y - TASK_LIST_MARGIN_Y) % TAG_SPACE != 0
-        {
-          self
-            .writer
-            .fill_line(0, y, bbox.w, self.colors.unselected_task_bg)
-        } else if let Some((i, task)) = tasks.next() {
-          let tagged = task_list
-            .toggle_tag(cap)
-            .map(|toggle_tag| task.has_tag(&toggle_tag))
-            .unwrap_or(false);
-
-          let () = self.render_task_list_line(task, tagged, i == selection, y, bbox.w)?;
-
-          if i == selection && cap.is_focused(task_list.id()) {
-            cursor = Some((TASK_LIST_MARGIN_X + 6, y));
+    let rows = view.iter(|iter| {
+      let mut tasks = iter.cloned().enumerate();
+      if sections.is_empty() {
+        tasks.map(|(i, task)| TaskListRow::Task(i, task)).collect()
+      } else {
+        sections.iter().fold(Vec::new(), |mut rows, section| {
+          let () = rows.push(TaskListRow::Section(section));
+          if !section.collapsed {
+            let () = rows.extend(
+              tasks
+                .by_ref()
+                .take(section.count)
+                .map(|(i, task)| TaskListRow::Task(i, task)),
+            );
           }
-          Ok(())
-        } else {
-          self
-            .writer
-            .fill_line(0, y, bbox.w, self.colors.unselected_task_bg)
+          rows
+        })
+      }
+    });
+    // Section headers are never selected, but they shift the row of the
+    // selected task.
+    let selected_row = rows
+      .iter()
+      .position(|row| matches!(row, TaskListRow::Task(i, _) if *i == selection))
+      .unwrap_or(selection);
+    let offset = window_start(data.offset, count, selected_row);
+    let mut rows = rows.into_iter().skip(offset).take(count);
+
+    let () = (0..bbox.h).try_for_each(|y| {
+      if y < TASK_LIST_MARGIN_Y
+        || y > bbox.h - TASK_LIST_MARGIN_Y
+        || (y - TASK_LIST_MARGIN_Y) % TAG_SPACE != 0
+      {
+        self
+          .writer
+          .fill_line(0, y, bbox.w, self.colors.unselected_task_bg)
+      } else if let Some(row) = rows.next() {
+        match row {
+          TaskListRow::Section(section) => self.render_task_list_section(section, y, bbox.w),
+          TaskListRow::Task(i, task) => {
+            let tagged = task_list
+              .toggle_tag(cap)
+              .map(|toggle_tag| task.has_tag(&toggle_tag))
+              .unwrap_or(false);
+
+            let () = self.render_task_list_line(&task, tagged, i == selection, y, bbox.w)?;
+
+            if i == selection && cap.is_focused(task_list.id()) {
+              cursor = Some((TASK_LIST_MARGIN_X + 6, y));
+            }
+            Ok(())
+          },
         }
-      })
+      } else {
+        self
+          .writer
+          .fill_line(0, y, bbox.w, self.colors.unselected_task_bg)
+      }
     })?;
 
     // Set the cursor to the first character of the selected item. This
@@ -694,12 +792,13 @@ where
     let () = self.writer.fill_line(x, y, 1, self.colors.tag_dialog_bg)?;
     x += 1;
 
-    let () = self.writer.write(x, y, tag_fg, tag_bg, tag.name())?;
+    let name = tag.name();
+    let () = self.writer.write(x, y, tag_fg, tag_bg, &*name)?;
 
     // Fill the remainder of the line.
     let () = self
       .writer
-      .fill_line(x + tag.name().len() as u16, y, w, self.colors.tag_dialog_bg)?;
+      .fill_line(x + name.len() as u16, y, w, self.colors.tag_dialog_bg)?;
     Ok(())
   }
 
@@ -741,6 +840,72 @@ where
     Ok(())
   }
 
+  /// Render an error pointing at a specific location within some
+  /// input, as part of rendering an `InOutArea`.
+  ///
+  /// The input is shown on the line above the in/out area proper, with
+  /// a caret underneath the location in question, followed by the
+  /// error message.
+  fn render_error_at(&self, error: &str, input: &str, offset: usize, bbox: BBox) -> Result<()> {
+    let mut x = 0;
+    let y = bbox.h - 1;
+    let fg = self.colors.in_out_string_fg;
+    let bg = self.colors.in_out_string_bg;
+
+    let () = self.writer.write(
+      x,
+      y.saturating_sub(1),
+      self.colors.in_out_error_fg,
+      self.colors.in_out_error_bg,
+      ERROR_TEXT,
+    )?;
+    x += ERROR_TEXT.len() as u16;
+
+    let () = self.writer.fill_line(x, y.saturating_sub(1), 1, bg)?;
+    x += 1;
+
+    // If the location does not fit on the screen, skip over as many
+    // leading characters of the input as necessary for it to do so.
+    let offset = offset.min(input.len());
+    let count = bbox.w.saturating_sub(x) as usize;
+    let mut start = 0;
+    while start < offset && input[start..offset].display_width().as_usize() >= count {
+      start += input[start..]
+        .chars()
+        .next()
+        .map(char::len_utf8)
+        .unwrap_or(1);
+    }
+
+    let string = &input[start..];
+    let () = self.writer.write(x, y.saturating_sub(1), fg, bg, string)?;
+    let () = self.writer.fill_line(
+      x + string.display_width().as_usize() as u16,
+      y.saturating_sub(1),
+      bbox.w,
+      bg,
+    )?;
+
+    let caret = x + input[start..offset].display_width().as_usize() as u16;
+    let () = self.writer.fill_line(0, y, caret, bg)?;
+    let () = self.writer.write(
+      caret,
+      y,
+      self.colors.in_out_error_fg,
+      self.colors.in_out_error_bg,
+      "^",
+    )?;
+    let () = self.writer.fill_line(caret + 1, y, 1, bg)?;
+    let () = self.writer.write(caret + 2, y, fg, bg, error)?;
+    let () = self.writer.fill_line(
+      caret + 2 + error.display_width().as_usize() as u16,
+      y,
+      bbox.w,
+      bg,
+    )?;
+    Ok(())
+  }
+
   /// Render an `InOutArea`.
   fn render_input_output(&self, in_out: &InOutArea, cap: &dyn Cap, bbox: BBox) -> Result<()> {
     let mut x = 0;
@@ -765,6 +930,11 @@ where
         self.colors.in_out_error_bg,
         Some(e.as_ref()),
       ),
+      InOut::ErrorAt {
+        error,
+        input,
+        offset,
+      } => return self.render_error_at(error, input, *offset, bbox),
       InOut::Input(Input { text, .. }) => (
         INPUT_TEXT,
         self.colors.in_out_success_fg,
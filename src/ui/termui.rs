@@ -19,10 +19,14 @@ use gui::Widget;
 
 use crate::cap::DirCap;
 use crate::colors::Colors;
+use crate::ser::state::UiConfig as SerUiConfig;
+use crate::ser::ToSerde as _;
+use crate::state::Conflict;
 use crate::state::TaskState;
 use crate::tags::Tag;
 #[cfg(all(test, not(feature = "readline")))]
 use crate::tasks::Task;
+use crate::transaction::Transaction;
 use crate::view::View;
 
 use super::config::Config;
@@ -30,6 +34,7 @@ use super::detail_dialog::DetailDialog;
 use super::detail_dialog::DetailDialogData;
 use super::event::Event;
 use super::event::Key;
+use super::event::KeyEvent;
 use super::in_out::InOut;
 use super::in_out::InOutArea;
 use super::in_out::InOutAreaData;
@@ -49,6 +54,48 @@ use super::tag_dialog::TagDialogData;
 const CHAR_QUIT: char = 'q';
 /// The key used for quitting the program.
 const KEY_QUIT: Key = Key::Char(CHAR_QUIT);
+/// The key used for saving state.
+const KEY_SAVE: Key = Key::Char('w');
+/// The key used for reloading tasks modified by another program.
+const KEY_RELOAD: Key = Key::Char('r');
+/// The key used for merging tasks modified by another program.
+const KEY_MERGE: Key = Key::Char('m');
+/// The key used for overwriting tasks modified by another program.
+const KEY_OVERWRITE: Key = Key::Char('o');
+
+
+/// The ways in which a conflict with modifications made to the tasks
+/// by another program can be resolved.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Resolution {
+  /// Discard our changes in favor of the modified tasks.
+  Reload,
+  /// Merge our changes with the modified tasks.
+  Merge,
+  /// Overwrite the modified tasks with our changes.
+  Overwrite,
+}
+
+/// Create a warning about tags referenced by the given views that are
+/// not known, if any.
+fn unknown_tags_warning(views: &[View]) -> Option<String> {
+  let unknown = views
+    .iter()
+    .flat_map(|view| {
+      view
+        .unknown_tags()
+        .iter()
+        .map(move |tag| format!("`{tag}` (view `{}`)", view.name()))
+    })
+    .collect::<Vec<_>>();
+
+  (!unknown.is_empty()).then(|| {
+    format!(
+      "Views reference unknown tags, treating them as unset: {}",
+      unknown.join(", ")
+    )
+  })
+}
 
 
 /// The data associated with a `TermUi`.
@@ -69,6 +116,8 @@ pub struct TermUiData {
   colors: Colors,
   /// The tag to toggle on user initiated action.
   toggle_tag: Option<Tag>,
+  /// The UI configuration as it was last loaded or saved.
+  ui_config: SerUiConfig,
 }
 
 impl TermUiData {
@@ -80,6 +129,8 @@ impl TermUiData {
     colors: Colors,
     toggle_tag: Option<Tag>,
   ) -> Self {
+    let () = task_state.tasks().set_completion_tag(toggle_tag.clone());
+
     Self {
       tasks_dir_cap,
       task_state,
@@ -89,6 +140,7 @@ impl TermUiData {
       ui_state_file: ui_state_path.1,
       colors,
       toggle_tag,
+      ui_config: SerUiConfig::default(),
     }
   }
 }
@@ -115,11 +167,28 @@ impl TermUi {
       Box::new(|id, cap| Box::new(Kseq::new(id, cap))),
     );
 
+    // SANITY: Our data is guaranteed to be a `TermUiData` object.
+    let data = cap.data_mut(id).downcast_mut::<TermUiData>().unwrap();
+    let config = Config {
+      views,
+      colors: data.colors,
+      toggle_tag: data.toggle_tag.clone(),
+    };
+    data.ui_config = config.to_serde();
+    let mut views = config.views;
+    let warning = unknown_tags_warning(&views);
+
     let State {
       selected_tasks,
       selected_view,
+      collapsed_sections,
     } = state;
 
+    let () = views
+      .iter_mut()
+      .zip(collapsed_sections)
+      .for_each(|(view, collapsed)| view.set_collapsed_sections(&collapsed));
+
     let selected = selected_tasks.into_iter().chain(repeat(None));
     let views = views.into_iter().zip(selected).collect();
 
@@ -127,11 +196,25 @@ impl TermUi {
     //       one on-the-fly. But doing so will also require support for
     //       destroying widgets, which is something that the `gui` crate
     //       does not yet have.
+    let in_out = cap.add_widget(
+      id,
+      Box::new(|| {
+        let mut data = InOutAreaData::new();
+        if let Some(warning) = warning {
+          let () = data.set_in_out(InOut::Error(warning));
+        }
+        Box::new(data)
+      }),
+      Box::new(|id, cap| Box::new(InOutArea::new(id, cap))),
+    );
     let tag_dialog = cap.add_widget(
       id,
       Box::new(|| Box::new(TagDialogData::new())),
-      Box::new(|id, cap| {
-        let tag_dialog = TagDialog::new(id);
+      Box::new(move |id, cap| {
+        // SANITY: Our data is guaranteed to be a `TermUiData` object.
+        let data = cap.data(termui_id).downcast_ref::<TermUiData>().unwrap();
+        let tasks = Rc::clone(data.task_state.tasks());
+        let tag_dialog = TagDialog::new(id, in_out, tasks);
         let () = cap.hide(id);
         Box::new(tag_dialog)
       }),
@@ -145,11 +228,6 @@ impl TermUi {
         Box::new(detail_dialog)
       }),
     );
-    let in_out = cap.add_widget(
-      id,
-      Box::new(|| Box::new(InOutAreaData::new())),
-      Box::new(|id, cap| Box::new(InOutArea::new(id, cap))),
-    );
     let tab_bar = cap.add_widget(
       id,
       Box::new(|| Box::new(TabBarData::new())),
@@ -188,50 +266,199 @@ impl TermUi {
     ui_state: &State,
   ) -> Result<()> {
     let data = self.data_mut::<TermUiData>(cap);
-    // TODO: We risk data inconsistencies if the second save operation
-    //       fails.
-    {
-      let write_guard = data.ui_config_dir_cap.write().await?;
-      let mut file_cap = write_guard.file_cap(&data.ui_config_file);
-      let () = ui_config
-        .save(&mut file_cap)
-        .await
-        .context("failed to save UI configuration")?;
-    }
-    {
-      let write_guard = data.ui_state_dir_cap.write().await?;
-      let mut file_cap = write_guard.file_cap(&data.ui_state_file);
-      let () = ui_state
-        .save(&mut file_cap)
-        .await
-        .context("failed to save UI state")?;
-    }
-
-
+    let ui_config_guard = data.ui_config_dir_cap.write().await?;
+    let ui_state_guard = data.ui_state_dir_cap.write().await?;
+    let tasks_guard = data.tasks_dir_cap.write().await?;
+
+    // We stage all changes first and only commit them once everything
+    // else succeeded, in order to never end up with, say, a UI
+    // configuration referencing tags of tasks that failed to save.
+    let mut transaction = Transaction::new();
+    let mut file_cap = ui_config_guard.file_cap(&data.ui_config_file);
+    let () = ui_config
+      .stage(&mut transaction, &mut file_cap)
+      .await
+      .context("failed to save UI configuration")?;
+    let mut file_cap = ui_state_guard.file_cap(&data.ui_state_file);
+    let () = ui_state
+      .stage(&mut transaction, &mut file_cap)
+      .await
+      .context("failed to save UI state")?;
     let () = data
       .task_state
-      .save(&mut data.tasks_dir_cap)
+      .stage(&mut transaction, &tasks_guard)
       .await
       .context("failed to save task state")?;
-    Ok(())
+
+    let () = transaction.commit().await.context("failed to save state")?;
+    data.ui_config = ui_config.to_serde();
+    data
+      .task_state
+      .mark_saved(tasks_guard.path())
+      .await
+      .context("failed to record saved task state")
   }
 
   /// Save the current configuration and state.
+  ///
+  /// Should the tasks have been modified by another program, the user
+  /// is asked how to proceed, with `key_event` starting the key
+  /// sequence to continue with.
   async fn save_and_report(
     &self,
     cap: &mut dyn MutCap<Event, Message>,
     ui_config: &Config,
     ui_state: &State,
+    key_event: KeyEvent,
   ) -> Option<Message> {
     let in_out = match self.save_all(cap, ui_config, ui_state).await {
       Ok(_) => InOut::Saved,
-      Err(err) => InOut::Error(format!("{err}")),
+      Err(err) => {
+        if let Some(conflict) = err.downcast_ref::<Conflict>() {
+          let message = Message::StartKeySeq(self.id, key_event);
+          let _msg = cap.send(self.kseq, message).await;
+          InOut::Error(format!(
+            "{conflict}; press 'r' to reload, 'm' to merge, or 'o' to overwrite"
+          ))
+        } else {
+          InOut::Error(format!("{err}"))
+        }
+      },
     };
 
     let message = Message::SetInOut(in_out);
     cap.send(self.in_out, message).await
   }
 
+  /// Resolve a conflict with modifications made to the tasks by
+  /// another program, as chosen by the user.
+  async fn resolve_conflict(
+    &self,
+    cap: &mut dyn MutCap<Event, Message>,
+    key_event: KeyEvent,
+    resolution: Resolution,
+  ) -> Option<Message> {
+    let data = self.data::<TermUiData>(cap);
+    let tasks_dir = data.tasks_dir_cap.path();
+    let task_state = &data.task_state;
+
+    let result = match resolution {
+      Resolution::Reload => task_state
+        .reload(tasks_dir)
+        .await
+        .context("failed to reload tasks"),
+      Resolution::Merge => task_state
+        .merge(tasks_dir)
+        .await
+        .context("failed to merge tasks"),
+      Resolution::Overwrite => task_state
+        .accept_external_changes(tasks_dir)
+        .await
+        .context("failed to overwrite tasks"),
+    };
+
+    let message = match result {
+      // After a reload there is nothing left to save.
+      Ok(()) if resolution == Resolution::Reload => {
+        cap.send(self.in_out, Message::SetInOut(InOut::Clear)).await
+      },
+      Ok(()) => self.save(cap, key_event).await,
+      Err(err) => {
+        let message = Message::SetInOut(InOut::Error(format!("{err}")));
+        cap.send(self.in_out, message).await
+      },
+    };
+    // The tasks may have changed, so make sure to re-render everything.
+    Some(Message::updated(self.id)).maybe_update(message)
+  }
+
+  /// Check whether the user is currently interacting with anything
+  /// but the task list boxes, e.g., by editing a task or being in the
+  /// middle of a key sequence.
+  fn is_busy(&self, cap: &dyn MutCap<Event, Message>) -> bool {
+    match cap.focused() {
+      Some(focused) => cap.parent_id(focused) != Some(self.tab_bar),
+      None => false,
+    }
+  }
+
+  /// Reload tasks and UI configuration modified by another program.
+  ///
+  /// Modifications to tasks are merged with unsaved local ones. The UI
+  /// configuration is only reloaded if there are no unsaved local
+  /// changes to it.
+  async fn reload_modified(&self, cap: &mut dyn MutCap<Event, Message>) -> Result<Option<Message>> {
+    let data = self.data::<TermUiData>(cap);
+    let tasks_dir = data.tasks_dir_cap.path();
+    let task_state = &data.task_state;
+
+    let tasks_changed = !task_state
+      .external_changes(tasks_dir)
+      .await
+      .context("failed to check tasks for modifications")?
+      .is_empty();
+    if tasks_changed {
+      let () = task_state
+        .merge(tasks_dir)
+        .await
+        .context("failed to merge tasks modified by another program")?;
+    }
+
+    let ui_config_path = data.ui_config_dir_cap.path().join(&data.ui_config_file);
+    let config = Config::load(&ui_config_path, task_state)
+      .await
+      .context("failed to reload UI configuration")?;
+    let ser_config = config.to_serde();
+    if ser_config == data.ui_config {
+      let message = tasks_changed.then(|| Message::updated(self.id));
+      return Ok(message)
+    }
+
+    let (current, _state) = self.collect_config_and_state(cap).await;
+    if current.to_serde() != self.data::<TermUiData>(cap).ui_config {
+      // We do not attempt to merge configurations. Make sure that the
+      // user is aware that a save will overwrite the other changes,
+      // though.
+      let data = self.data_mut::<TermUiData>(cap);
+      data.ui_config = ser_config;
+
+      let error = "UI configuration was modified by another program; saving will overwrite it";
+      let message = Message::SetInOut(InOut::Error(error.to_string()));
+      let message = cap.send(self.in_out, message).await;
+      return Ok(Some(Message::updated(self.id)).maybe_update(message))
+    }
+
+    let Config {
+      views,
+      colors,
+      toggle_tag,
+    } = config;
+
+    let data = self.data_mut::<TermUiData>(cap);
+    let () = data
+      .task_state
+      .tasks()
+      .set_completion_tag(toggle_tag.clone());
+    // Note that colors are only picked up by the renderer on the next
+    // start, but we still want to preserve them on save.
+    data.colors = colors;
+    data.toggle_tag = toggle_tag.clone();
+    data.ui_config = ser_config;
+
+    let warning = unknown_tags_warning(&views);
+    let message = Message::SetViews(views, toggle_tag);
+    let message = cap.send(self.tab_bar, message).await;
+    let message = Some(Message::updated(self.id)).maybe_update(message);
+
+    if let Some(warning) = warning {
+      let warning = Message::SetInOut(InOut::Error(warning));
+      let warning = cap.send(self.in_out, warning).await;
+      Ok(message.maybe_update(warning))
+    } else {
+      Ok(message)
+    }
+  }
+
   async fn collect_config_and_state(
     &self,
     cap: &mut dyn MutCap<Event, Message>,
@@ -250,6 +477,10 @@ impl TermUi {
       selected: selected_view,
       ..
     } = state;
+    let collapsed_sections = views
+      .iter()
+      .map(|(view, _selected)| view.collapsed_sections())
+      .collect();
     let (views, selected_tasks) = views.into_iter().unzip();
 
     let data = self.data::<TermUiData>(cap);
@@ -261,15 +492,20 @@ impl TermUi {
     let state = State {
       selected_tasks,
       selected_view,
+      collapsed_sections,
     };
 
     (config, state)
   }
 
   /// Emit an event that will eventually cause the state to be saved.
-  async fn save(&self, cap: &mut dyn MutCap<Event, Message>) -> Option<Message> {
+  async fn save(
+    &self,
+    cap: &mut dyn MutCap<Event, Message>,
+    key_event: KeyEvent,
+  ) -> Option<Message> {
     let (config, state) = self.collect_config_and_state(cap).await;
-    self.save_and_report(cap, &config, &state).await
+    self.save_and_report(cap, &config, &state, key_event).await
   }
 }
 
@@ -321,7 +557,7 @@ impl Handleable<Event, Message> for TermUi {
             Some(Event::Quit)
           }
         },
-        Key::Char('w') => self.save(cap).await.into_event(),
+        KEY_SAVE => self.save(cap, key_event).await.into_event(),
         // All key events not handled at this point will just get
         // swallowed.
         _ => None,
@@ -338,7 +574,21 @@ impl Handleable<Event, Message> for TermUi {
         cap.send(self.tab_bar, message).await
       },
       Message::GotKeySeq((KEY_QUIT, ..), (KEY_QUIT, ..)) => Some(Message::Quit),
+      Message::GotKeySeq(key_event @ (KEY_SAVE, ..), (KEY_RELOAD, ..)) => {
+        self.resolve_conflict(cap, key_event, Resolution::Reload).await
+      },
+      Message::GotKeySeq(key_event @ (KEY_SAVE, ..), (KEY_MERGE, ..)) => {
+        self.resolve_conflict(cap, key_event, Resolution::Merge).await
+      },
+      Message::GotKeySeq(key_event @ (KEY_SAVE, ..), (KEY_OVERWRITE, ..)) => {
+        self.resolve_conflict(cap, key_event, Resolution::Overwrite).await
+      },
       Message::GotKeySeq(.., key) => Some(Message::UnhandledKey(key)),
+      Message::SetInOut(..) => {
+        // We merely relay this message to the InOutArea widget, for
+        // the benefit of parties not knowing about it.
+        cap.send(self.in_out, message).await
+      },
       #[cfg(all(test, not(feature = "readline")))]
       Message::GetTasks => {
         let data = self.data::<TermUiData>(cap);
@@ -356,6 +606,35 @@ impl Handleable<Event, Message> for TermUi {
       message => panic!("Received unexpected message: {message:?}"),
     }
   }
+
+  /// Respond to a message.
+  async fn respond(
+    &self,
+    message: &mut Message,
+    cap: &mut dyn MutCap<Event, Message>,
+  ) -> Option<Message> {
+    match message {
+      Message::ReloadModified(ref mut done) => {
+        if self.is_busy(cap) {
+          // We do not want to pull the rug from underneath the user.
+          // Try again later.
+          *done = false;
+          return None
+        }
+
+        *done = true;
+        match self.reload_modified(cap).await {
+          Ok(message) => message,
+          Err(err) => {
+            let message = Message::SetInOut(InOut::Error(format!("{err}")));
+            let message = cap.send(self.in_out, message).await;
+            Some(Message::updated(self.id)).maybe_update(message)
+          },
+        }
+      },
+      message => panic!("Received unexpected message: {message:?}"),
+    }
+  }
 }
 
 
@@ -366,7 +645,18 @@ impl Handleable<Event, Message> for TermUi {
 mod tests {
   use super::*;
 
+  use std::fs::create_dir;
+  use std::fs::read;
+  use std::fs::read_dir;
+  use std::fs::remove_dir;
+  use std::fs::write;
+  use std::path::Path;
   use std::rc::Rc;
+  use std::str::FromStr as _;
+
+  use chrono::Local;
+  use chrono::NaiveDateTime;
+  use chrono::TimeZone as _;
 
   use gui::Cap;
   use gui::Ui;
@@ -376,15 +666,18 @@ mod tests {
 
   use tokio::test;
 
+  use crate::date::Date;
+  use crate::formula::Formula;
   use crate::ser::state::TaskState as SerTaskState;
   use crate::ser::state::UiConfig as SerUiConfig;
   use crate::ser::state::UiState as SerUiState;
+  use crate::ser::tasks::Status;
   use crate::ser::tasks::Task as SerTask;
   use crate::ser::tasks::Tasks as SerTasks;
   use crate::ser::tasks::TasksMeta as SerTasksMeta;
   use crate::ser::view::FormulaPair;
+  use crate::ser::view::SortKey;
   use crate::ser::view::View as SerView;
-  use crate::ser::ToSerde as _;
   use crate::state::TaskState;
   use crate::test::default_tasks_and_tags;
   use crate::test::make_task_summaries;
@@ -411,6 +704,7 @@ mod tests {
   /// composition of tasks.
   struct TestUiBuilder {
     ui_config: SerUiConfig,
+    ui_state: SerUiState,
     task_state: SerTaskState,
   }
 
@@ -419,6 +713,7 @@ mod tests {
     fn new() -> TestUiBuilder {
       Self {
         ui_config: Default::default(),
+        ui_state: Default::default(),
         task_state: Default::default(),
       }
     }
@@ -435,6 +730,7 @@ mod tests {
 
       Self {
         ui_config: Default::default(),
+        ui_state: Default::default(),
         task_state: SerTaskState {
           tasks_meta: SerTasksMeta::default(),
           tasks: SerTasks::from(tasks.into()),
@@ -448,6 +744,7 @@ mod tests {
       let (ui_config, task_state) = default_tasks_and_tags();
       TestUiBuilder {
         ui_config,
+        ui_state: Default::default(),
         task_state,
       }
     }
@@ -484,7 +781,7 @@ mod tests {
       let ui_state_dir_cap = DirCap::for_dir(ui_state_file_dir).await.unwrap();
       let ui_state_file_name = ui_state_file.path().file_name().unwrap().to_os_string();
       let ui_state_path = (ui_state_dir_cap, ui_state_file_name);
-      let ui_state = State::default();
+      let ui_state = State::with_serde(self.ui_state);
 
       let (ui, _) = Ui::new(
         || {
@@ -556,6 +853,19 @@ mod tests {
       }
     }
 
+    /// Ask the UI to reload modifications made by another program,
+    /// returning whether it did so.
+    async fn reload_modified(&mut self) -> bool {
+      let root = self.ui.root_id();
+      let mut message = Message::ReloadModified(false);
+      let _msg = self.ui.call(root, &mut message).await;
+      if let Message::ReloadModified(done) = message {
+        done
+      } else {
+        unreachable!()
+      }
+    }
+
     /// Retrieve the names of all tabs.
     async fn views(&mut self) -> Vec<String> {
       let root = self.ui.root_id();
@@ -1202,6 +1512,46 @@ mod tests {
     assert!(tasks[3].has_tag(&complete_tag));
   }
 
+  /// Check that the completion tag is kept in sync with the status of
+  /// tasks.
+  #[test]
+  async fn complete_task_status() {
+    let mut builder = TestUiBuilder::with_default_tasks_and_tags();
+    // Pretend the first task got completed and the second one got
+    // reopened by another program.
+    builder.task_state.tasks.0[0].status = Some(Status::Completed(None));
+    builder.task_state.tasks.0[1].status = Some(Status::NeedsAction);
+
+    let mut ui = builder.build().await;
+    let tasks = ui.tasks().await;
+    let complete_tag = tasks[0]
+      .templates()
+      .instantiate_from_name(COMPLETE_TAG)
+      .unwrap();
+    assert!(tasks[0].has_tag(&complete_tag));
+    assert!(!tasks[1].has_tag(&complete_tag));
+    // Tasks without a status that carry the completion tag get marked
+    // as completed, while all others are left alone.
+    assert!(tasks[3].has_tag(&complete_tag));
+    assert!(matches!(
+      tasks[3].status(),
+      Some(Status::Completed(Some(..)))
+    ));
+    assert!(!tasks[2].has_tag(&complete_tag));
+    assert_eq!(tasks[2].status(), None);
+
+    let events = vec![Event::from(' '), Event::from('j'), Event::from(' ')];
+    let tasks = ui.handle(events).await.tasks().await;
+
+    assert!(!tasks[0].has_tag(&complete_tag));
+    assert_eq!(tasks[0].status(), Some(Status::NeedsAction));
+    assert!(tasks[1].has_tag(&complete_tag));
+    assert!(matches!(
+      tasks[1].status(),
+      Some(Status::Completed(Some(..)))
+    ));
+  }
+
   #[test]
   async fn edit_task() {
     let tasks = make_tasks(3);
@@ -1283,6 +1633,94 @@ mod tests {
     assert_eq!(tasks, expected)
   }
 
+  /// Check that we can set a task's due date.
+  #[test]
+  async fn edit_task_due_date() {
+    let tasks = make_tasks(3);
+    let events = vec![Event::from('j'), Event::from('D')]
+      .into_iter()
+      .chain("2025-03-01 12:00".chars().map(Event::from))
+      .chain([Event::from('\n')]);
+
+    let tasks = TestUiBuilder::with_ser_tasks(tasks)
+      .build()
+      .await
+      .handle(events)
+      .await
+      .tasks()
+      .await;
+
+    let expected = Date::from_str("2025-03-01 12:00").unwrap();
+    assert_eq!(tasks[0].due(), None);
+    assert_eq!(tasks[1].due(), Some(expected));
+    assert_eq!(tasks[2].due(), None);
+    assert_eq!(tasks[1].start(), None);
+  }
+
+  /// Check that editing a UTC date keeps it in UTC.
+  #[test]
+  async fn edit_task_utc_due_date() {
+    let due = Date::Utc("2025-03-01T12:00:00Z".parse().unwrap());
+    let tasks = vec![SerTask::new("task").with_due(due)];
+    // The date is displayed and entered as local time, so we construct
+    // the expected date from the local one, whatever the system's time
+    // zone.
+    let text = "2025-03-01 12:30";
+    let local = NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M").unwrap();
+    let expected = Date::Utc(Local.from_local_datetime(&local).unwrap().to_utc());
+    let events = [Event::from('D')]
+      .into_iter()
+      .chain((0..16).map(|_| Event::from(Key::Backspace)))
+      .chain(text.chars().map(Event::from))
+      .chain([Event::from('\n')]);
+
+    let mut ui = TestUiBuilder::with_ser_tasks(tasks).build().await;
+    let tasks = ui.handle(events).await.tasks().await;
+    assert_eq!(tasks[0].due(), Some(expected));
+  }
+
+  /// Check that we can change and clear a task's start date.
+  #[test]
+  async fn edit_task_start_date() {
+    let start = Date::from_str("2025-03-01").unwrap();
+    let tasks = vec![SerTask::new("task").with_start(start)];
+    let events = [Event::from('S')]
+      .into_iter()
+      .chain([Event::from(Key::Backspace)])
+      .chain([Event::from('2'), Event::from('\n')]);
+
+    let mut ui = TestUiBuilder::with_ser_tasks(tasks).build().await;
+    let tasks = ui.handle(events).await.tasks().await;
+    let expected = Date::from_str("2025-03-02").unwrap();
+    assert_eq!(tasks[0].start(), Some(expected));
+
+    // Removing all text clears the date.
+    let events = [Event::from('S')]
+      .into_iter()
+      .chain((0..10).map(|_| Event::from(Key::Backspace)))
+      .chain([Event::from('\n')]);
+    let tasks = ui.handle(events).await.tasks().await;
+    assert_eq!(tasks[0].start(), None);
+  }
+
+  /// Check that we report an error when the user enters an invalid
+  /// date.
+  #[test]
+  async fn edit_task_invalid_date() {
+    let due = Date::from_str("2025-03-01").unwrap();
+    let tasks = vec![SerTask::new("task").with_due(due.clone())];
+    let events = [Event::from('D')]
+      .into_iter()
+      .chain([Event::from('x'), Event::from('\n')]);
+
+    let mut ui = TestUiBuilder::with_ser_tasks(tasks).build().await;
+    let tasks = ui.handle(events).await.tasks().await;
+    assert_eq!(tasks[0].due(), Some(due));
+
+    let in_out = ui.in_out().await;
+    assert!(matches!(in_out, InOut::Error(..)), "{in_out:?}");
+  }
+
   #[test]
   async fn edit_task_multi_byte_char() {
     let tasks = make_tasks(1);
@@ -1555,6 +1993,159 @@ mod tests {
     assert_eq!(tasks, expected);
   }
 
+  /// Check that tasks can only be moved among tasks that a sorted view
+  /// orders equally.
+  #[test]
+  async fn move_task_in_sorted_view() {
+    let mut tasks = make_tasks(4);
+    tasks[0].status = Some(Status::Completed(None));
+    tasks[2].status = Some(Status::Completed(None));
+
+    let mut builder = TestUiBuilder::with_ser_tasks(tasks);
+    builder.ui_config.views = vec![SerView {
+      name: "sorted".to_string(),
+      formula: FormulaPair::default(),
+      sort: vec![SortKey::from_str("completed").unwrap()],
+      order: None,
+      groups: Vec::new(),
+    }];
+
+    let mut ui = builder.build().await;
+    // The view lists tasks 2, 4, 1, 3. Move task 2 down past task 4
+    // and then attempt to move it past the completed task 1.
+    let events = vec![Event::from('J'), Event::from('J')];
+    let state = ui.handle(events).await.in_out().await;
+    let expected =
+      InOut::Error("Cannot move task against the view's sort order or grouping".to_string());
+    assert_eq!(state, expected);
+
+    let tasks = ui.task_summaries().await;
+    let mut expected = make_task_summaries(4);
+    let () = expected[1..].rotate_left(1);
+    assert_eq!(tasks, expected);
+  }
+
+  /// Check that we can collapse and expand sections of a grouped view.
+  #[test]
+  async fn collapse_and_expand_sections() {
+    let mut builder = TestUiBuilder::with_default_tasks_and_tags();
+    builder.ui_config.views = vec![SerView {
+      name: "grouped".to_string(),
+      formula: FormulaPair::default(),
+      sort: Vec::new(),
+      order: None,
+      groups: vec![COMPLETE_TAG.to_string()],
+    }];
+
+    // The view lists all even tasks in the "complete" section, followed
+    // by all odd ones. Collapsing the former selects task 1, expanding
+    // all sections keeps task 3 selected after deleting task 1.
+    let events = vec![
+      Event::from('z'),
+      Event::from('d'),
+      Event::from('Z'),
+      Event::from('d'),
+    ];
+    let tasks = builder
+      .build()
+      .await
+      .handle(events)
+      .await
+      .task_summaries()
+      .await;
+    let expected = make_task_summaries(15)
+      .into_iter()
+      .filter(|summary| summary != "1" && summary != "3")
+      .collect::<Vec<_>>();
+    assert_eq!(tasks, expected);
+  }
+
+  /// Check that collapsed sections are persisted as part of the UI
+  /// state and restored on start.
+  #[test]
+  async fn persist_collapsed_sections() {
+    let view = SerView {
+      name: "grouped".to_string(),
+      formula: FormulaPair::default(),
+      sort: Vec::new(),
+      order: None,
+      groups: vec![COMPLETE_TAG.to_string()],
+    };
+    let mut builder = TestUiBuilder::with_default_tasks_and_tags();
+    builder.ui_config.views = vec![view.clone()];
+
+    let events = vec![Event::from('z'), Event::from('w')];
+    let (_config, state) = builder
+      .build()
+      .await
+      .handle(events)
+      .await
+      .load_config_and_state()
+      .await
+      .unwrap();
+    let expected = vec![vec![Some(COMPLETE_TAG.to_string())]];
+    assert_eq!(state.collapsed_sections, expected);
+
+    // With the "complete" section still collapsed, task 1 is the first
+    // one listed and gets deleted.
+    let mut builder = TestUiBuilder::with_default_tasks_and_tags();
+    builder.ui_config.views = vec![view];
+    builder.ui_state = state.to_serde();
+
+    let events = vec![Event::from('d')];
+    let tasks = builder
+      .build()
+      .await
+      .handle(events)
+      .await
+      .task_summaries()
+      .await;
+    let expected = make_task_summaries(15)
+      .into_iter()
+      .filter(|summary| summary != "1")
+      .collect::<Vec<_>>();
+    assert_eq!(tasks, expected);
+  }
+
+  /// Check that moving tasks in a view with its own order does not
+  /// affect the order of tasks in other views.
+  #[test]
+  async fn move_task_in_ordered_view() {
+    let mut builder = TestUiBuilder::with_ser_tasks(make_tasks(3));
+    builder.ui_config.views = vec![
+      SerView {
+        name: "own".to_string(),
+        formula: FormulaPair::default(),
+        sort: Vec::new(),
+        order: Some(Default::default()),
+        groups: Vec::new(),
+      },
+      SerView {
+        name: "all".to_string(),
+        formula: FormulaPair::default(),
+        sort: Vec::new(),
+        order: None,
+        groups: Vec::new(),
+      },
+    ];
+
+    let mut ui = builder.build().await;
+    let events = vec![Event::from('J'), Event::from('w')];
+    let tasks = ui.handle(events).await.task_summaries().await;
+    assert_eq!(tasks, make_task_summaries(3));
+
+    let (config, _state) = ui.load_config_and_state().await.unwrap();
+    let summaries = |view: &View| {
+      view.iter(|iter| {
+        iter
+          .map(|task| task.summary().to_string())
+          .collect::<Vec<_>>()
+      })
+    };
+    assert_eq!(summaries(&config.views[0]), ["2", "1", "3"]);
+    assert_eq!(summaries(&config.views[1]), ["1", "2", "3"]);
+  }
+
   #[test]
   async fn move_second_task_down() {
     let tasks = make_tasks(4);
@@ -1770,6 +2361,8 @@ mod tests {
       let c = c as char;
       if c != 'a'
         && c != 'e'
+        && c != 'D'
+        && c != 'S'
         && c != 'n'
         && c != 'N'
         && c != 't'
@@ -1789,6 +2382,20 @@ mod tests {
 
   /// Check that the `InOutArea` widget displays an error when
   /// attempting to quit the program while unsaved changes are present.
+  /// Check that the root widget relays in/out area changes, as done
+  /// for reporting that watching for modifications failed.
+  #[test]
+  async fn in_out_state_relayed() {
+    let mut ui = TestUiBuilder::with_ser_tasks(make_tasks(1)).build().await;
+    let root = ui.ui.root_id();
+    let message = Message::SetInOut(InOut::Error("failure".to_string()));
+    let message = ui.ui.send(root, message).await;
+    assert!(matches!(message, Some(Message::Updated(..))), "{message:?}");
+
+    let state = ui.in_out().await;
+    assert_eq!(state, InOut::Error("failure".to_string()));
+  }
+
   #[test]
   async fn in_out_state_on_unsaved_changes() {
     let tasks = make_tasks(2);
@@ -2458,6 +3065,9 @@ mod tests {
       views: vec![SerView {
         name: "all".to_string(),
         formula: FormulaPair::default(),
+        sort: Vec::new(),
+        order: None,
+        groups: Vec::new(),
       }],
       colors: Default::default(),
       toggle_tag: None,
@@ -2468,6 +3078,7 @@ mod tests {
     let expected = SerUiState {
       selected_tasks: vec![Some(0)],
       selected_view: Some(0),
+      collapsed_sections: vec![Vec::new()],
     };
     assert_eq!(state, expected);
   }
@@ -2493,6 +3104,9 @@ mod tests {
       views: vec![SerView {
         name: "all".to_string(),
         formula: FormulaPair::default(),
+        sort: Vec::new(),
+        order: None,
+        groups: Vec::new(),
       }],
       colors: Default::default(),
       toggle_tag: None,
@@ -2503,6 +3117,7 @@ mod tests {
     let expected = SerUiState {
       selected_tasks: vec![Some(2)],
       selected_view: Some(0),
+      collapsed_sections: vec![Vec::new()],
     };
     assert_eq!(state, expected)
   }
@@ -2534,6 +3149,194 @@ mod tests {
     assert_eq!(state.selected_view, Some(0));
   }
 
+  /// Make sure that saving is all-or-nothing, i.e., that the UI
+  /// configuration and state are left untouched if saving tasks fails.
+  #[test]
+  async fn save_all_or_nothing() {
+    let mut ui = TestUiBuilder::with_default_tasks_and_tags().build().await;
+    // Block the path at which one of the tasks would be staged, causing
+    // the save to fail.
+    let id = ui.tasks().await[3].id();
+    let blocker = ui.tasks_root.path().join(format!(".{id}.new"));
+    let () = create_dir(&blocker).unwrap();
+
+    let in_out = ui.handle([Event::from('w')]).await.in_out().await;
+    assert!(matches!(in_out, InOut::Error(..)), "{in_out:?}");
+
+    let ui_config = read(ui.ui_config_file.path()).unwrap();
+    assert_eq!(ui_config, b"");
+    let ui_state = read(ui.ui_state_file.path()).unwrap();
+    assert_eq!(ui_state, b"");
+    let tasks = read_dir(ui.tasks_root.path()).unwrap().count();
+    assert_eq!(tasks, 1);
+
+    // Once the problem is resolved, saving should succeed.
+    let () = remove_dir(&blocker).unwrap();
+    let in_out = ui.handle([Event::from('w')]).await.in_out().await;
+    assert_eq!(in_out, InOut::Saved);
+    let (config, _state) = ui.load_config_and_state().await.unwrap();
+    assert_eq!(config.views.len(), 4);
+  }
+
+  /// Add a task with the given summary to the tasks stored in `root`, as
+  /// another program would.
+  async fn add_task_externally(root: &Path, summary: &str) {
+    let task_state = TaskState::load(root).await.unwrap();
+    let _task = task_state
+      .tasks()
+      .add(Task::builder().set_summary(summary), None);
+    let mut root_cap = DirCap::for_dir(root.to_path_buf()).await.unwrap();
+    let () = task_state.save(&mut root_cap).await.unwrap();
+  }
+
+  /// Modify the UI configuration stored in `path`, as another program
+  /// would.
+  fn modify_config_externally<F>(path: &Path, f: F)
+  where
+    F: FnOnce(&mut SerUiConfig),
+  {
+    let mut config = serde_json::from_slice::<SerUiConfig>(&read(path).unwrap()).unwrap();
+    let () = f(&mut config);
+    let () = write(path, serde_json::to_vec(&config).unwrap()).unwrap();
+  }
+
+  /// Check that we ask the user how to proceed when tasks were modified
+  /// by another program, instead of silently overwriting them.
+  #[test]
+  async fn save_with_external_modifications() {
+    /// Load the summaries of the tasks stored in `root`.
+    async fn load_summaries(root: &Path) -> Vec<String> {
+      let task_state = TaskState::load(root).await.unwrap();
+      task_state
+        .tasks()
+        .iter(|iter| iter.map(|task| task.summary()).collect())
+    }
+
+    let mut ui = TestUiBuilder::with_ser_tasks(make_tasks(3)).build().await;
+    let root = ui.tasks_root.path().to_path_buf();
+    let in_out = ui.handle([Event::from('w')]).await.in_out().await;
+    assert_eq!(in_out, InOut::Saved);
+
+    let () = add_task_externally(&root, "4").await;
+    let in_out = ui
+      .handle([Event::from('d'), Event::from('w')])
+      .await
+      .in_out()
+      .await;
+    let expected = "1 task file(s) were modified externally; \
+                    press 'r' to reload, 'm' to merge, or 'o' to overwrite";
+    assert_eq!(in_out, InOut::Error(expected.to_string()));
+    assert_eq!(load_summaries(&root).await, ["1", "2", "3", "4"]);
+
+    let in_out = ui.handle([Event::from('m')]).await.in_out().await;
+    assert_eq!(in_out, InOut::Saved);
+    assert_eq!(ui.task_summaries().await, ["2", "3", "4"]);
+    assert_eq!(load_summaries(&root).await, ["2", "3", "4"]);
+
+    let () = add_task_externally(&root, "5").await;
+    let in_out = ui
+      .handle([Event::from('w'), Event::from('o')])
+      .await
+      .in_out()
+      .await;
+    assert_eq!(in_out, InOut::Saved);
+    assert_eq!(load_summaries(&root).await, ["2", "3", "4"]);
+
+    let () = add_task_externally(&root, "6").await;
+    let _ui = ui
+      .handle([Event::from('d'), Event::from('w'), Event::from('r')])
+      .await;
+    assert_eq!(ui.task_summaries().await, ["2", "3", "4", "6"]);
+    assert_eq!(load_summaries(&root).await, ["2", "3", "4", "6"]);
+  }
+
+  /// Check that tasks and views modified by another program are
+  /// reloaded.
+  #[test]
+  async fn reload_external_modifications() {
+    let mut ui = TestUiBuilder::with_default_tasks_and_tags().build().await;
+    let root = ui.tasks_root.path().to_path_buf();
+    let path = ui.ui_config_file.path().to_path_buf();
+    let in_out = ui.handle([Event::from('w')]).await.in_out().await;
+    assert_eq!(in_out, InOut::Saved);
+    assert!(ui.reload_modified().await);
+    let count = ui.task_summaries().await.len();
+
+    let () = add_task_externally(&root, "new task").await;
+    let () = modify_config_externally(&path, |config| {
+      config.views[0].name = "everything".to_string();
+      config.views.truncate(2);
+    });
+    assert!(ui.reload_modified().await);
+    let tasks = ui.task_summaries().await;
+    assert_eq!(tasks.len(), count + 1);
+    assert_eq!(tasks.last().unwrap(), "new task");
+    assert_eq!(ui.views().await, ["everything", "tag complete"]);
+
+    let () = modify_config_externally(&path, |config| {
+      let mut view = config.views[0].clone();
+      view.name = "another".to_string();
+      config.views.push(view);
+    });
+    assert!(ui.reload_modified().await);
+    assert_eq!(ui.views().await, ["everything", "tag complete", "another"]);
+    // There is nothing left to save.
+    let (config, _state) = ui.load_config_and_state().await.unwrap();
+    assert_eq!(
+      config.to_serde(),
+      serde_json::from_slice::<SerUiConfig>(&read(&path).unwrap()).unwrap()
+    );
+  }
+
+  /// Check that reloading modifications is deferred while the user is
+  /// editing a task.
+  #[test]
+  async fn defer_reload_while_editing() {
+    let mut ui = TestUiBuilder::with_ser_tasks(make_tasks(1)).build().await;
+    let root = ui.tasks_root.path().to_path_buf();
+    let in_out = ui.handle([Event::from('w')]).await.in_out().await;
+    assert_eq!(in_out, InOut::Saved);
+    let () = add_task_externally(&root, "2").await;
+
+    let _ui = ui.handle([Event::from('e')]).await;
+    assert!(!ui.reload_modified().await);
+    assert_eq!(ui.task_summaries().await, ["1"]);
+
+    let _ui = ui.handle([Event::from(Key::Esc)]).await;
+    assert!(ui.reload_modified().await);
+    assert_eq!(ui.task_summaries().await, ["1", "2"]);
+  }
+
+  /// Check that unsaved changes to the UI configuration are not
+  /// discarded when the configuration is modified by another program.
+  #[test]
+  async fn reload_config_with_unsaved_changes() {
+    let mut ui = TestUiBuilder::with_default_tasks_and_tags().build().await;
+    let path = ui.ui_config_file.path().to_path_buf();
+    let in_out = ui.handle([Event::from('w')]).await.in_out().await;
+    assert_eq!(in_out, InOut::Saved);
+
+    let _ui = ui.handle([Event::from('L')]).await;
+    let () = modify_config_externally(&path, |config| config.views.truncate(1));
+    assert!(ui.reload_modified().await);
+    let expected = "UI configuration was modified by another program; saving will overwrite it";
+    assert_eq!(ui.in_out().await, InOut::Error(expected.to_string()));
+    let views = ["tag complete", "all", "tag2 || tag3", "tag1 && tag3"];
+    assert_eq!(ui.views().await, views);
+
+    // We warn only once.
+    let _ui = ui.handle([Event::from('w')]).await;
+    assert!(ui.reload_modified().await);
+    assert_eq!(ui.in_out().await, InOut::Saved);
+    let (config, _state) = ui.load_config_and_state().await.unwrap();
+    let names = config
+      .views
+      .iter()
+      .map(|view| view.name().to_string())
+      .collect::<Vec<_>>();
+    assert_eq!(names, views);
+  }
+
   /// Check that we can save and load the UI config and state and get
   /// the expected object, when multiple properties were changed from
   /// their defaults.
@@ -2799,6 +3602,179 @@ mod tests {
     assert_eq!(tags, expected);
   }
 
+  /// Check that we can create a new tag from the tag dialog.
+  #[test]
+  async fn add_tag() {
+    let events = vec![
+      Event::from('t'),
+      Event::from('a'),
+      Event::from('n'),
+      Event::from('e'),
+      Event::from('w'),
+      Event::from('\n'),
+      Event::from('\n'),
+    ];
+    let tasks = TestUiBuilder::with_default_tasks_and_tags()
+      .build()
+      .await
+      .handle(events)
+      .await
+      .tasks()
+      .await;
+
+    let tags = tasks[0].tags(|iter| iter.map(|x| x.name().to_string()).collect::<Vec<_>>());
+    let expected = vec!["new"];
+    assert_eq!(tags, expected);
+  }
+
+  /// Check that creating a tag with an existing name is reported as an
+  /// error.
+  #[test]
+  async fn add_duplicate_tag() {
+    let events = vec![
+      Event::from('t'),
+      Event::from('a'),
+      Event::from('t'),
+      Event::from('a'),
+      Event::from('g'),
+      Event::from('1'),
+      Event::from('\n'),
+    ];
+    let mut ui = TestUiBuilder::with_default_tasks_and_tags().build().await;
+    let state = ui.handle(events).await.in_out().await;
+
+    let expected = InOut::Error("Failed to update tag: tag `tag1` already exists".to_string());
+    assert_eq!(state, expected);
+  }
+
+  /// Check that renaming a tag affects all tasks it is set on.
+  #[test]
+  async fn rename_tag() {
+    let events = vec![
+      Event::from('t'),
+      // Select tag1.
+      Event::from('j'),
+      Event::from('r'),
+      Event::from(Key::Backspace),
+      Event::from('x'),
+      Event::from('\n'),
+      // The renamed tag should still be selected.
+      Event::from(' '),
+      Event::from('\n'),
+    ];
+    let tasks = TestUiBuilder::with_default_tasks_and_tags()
+      .build()
+      .await
+      .handle(events)
+      .await
+      .tasks()
+      .await;
+
+    let tags = tasks[0].tags(|iter| iter.map(|x| x.name().to_string()).collect::<Vec<_>>());
+    let expected = vec!["tagx"];
+    assert_eq!(tags, expected);
+
+    let tags = tasks[4].tags(|iter| iter.map(|x| x.name().to_string()).collect::<Vec<_>>());
+    let expected = vec!["tagx"];
+    assert_eq!(tags, expected);
+  }
+
+  /// Check that removing a tag removes it from all tasks.
+  #[test]
+  async fn remove_tag() {
+    let events = vec![
+      Event::from('t'),
+      // Select and remove tag1.
+      Event::from('j'),
+      Event::from('d'),
+      // tag2 should be selected now.
+      Event::from(' '),
+      Event::from('\n'),
+    ];
+    let tasks = TestUiBuilder::with_default_tasks_and_tags()
+      .build()
+      .await
+      .handle(events)
+      .await
+      .tasks()
+      .await;
+
+    let tags = tasks[0].tags(|iter| iter.map(|x| x.name().to_string()).collect::<Vec<_>>());
+    let expected = vec!["tag2"];
+    assert_eq!(tags, expected);
+
+    let has_tag1 = tasks
+      .iter()
+      .any(|task| task.tags(|mut iter| iter.any(|tag| &*tag.name() == "tag1")));
+    assert!(!has_tag1);
+  }
+
+  /// Check that the tag used for toggling completion state cannot be
+  /// removed.
+  #[test]
+  async fn remove_completion_tag() {
+    let events = vec![Event::from('t'), Event::from('d')];
+    let mut ui = TestUiBuilder::with_default_tasks_and_tags().build().await;
+    let state = ui.handle(events).await.in_out().await;
+
+    let expected = InOut::Error(format!(
+      "Failed to remove tag: tag `{COMPLETE_TAG}` marks tasks as complete and cannot be removed"
+    ));
+    assert_eq!(state, expected);
+  }
+
+  /// Check that renaming a tag updates the formulas of views using it.
+  #[test]
+  async fn rename_tag_in_view_formula() {
+    let events = vec![
+      Event::from('t'),
+      // Select tag2.
+      Event::from('j'),
+      Event::from('j'),
+      Event::from('r'),
+      Event::from(Key::Backspace),
+      Event::from('x'),
+      Event::from('\n'),
+      Event::from('\n'),
+      Event::from('w'),
+    ];
+    let (config, _state) = TestUiBuilder::with_default_tasks_and_tags()
+      .build()
+      .await
+      .handle(events)
+      .await
+      .load_config_and_state()
+      .await
+      .unwrap();
+
+    assert_eq!(config.views[2].formula(), "tagx | tag3");
+    assert_eq!(config.views[2].unknown_tags(), &[] as &[String]);
+  }
+
+  /// Check that views referencing unknown tags are loaded, but cause a
+  /// warning to be displayed.
+  #[test]
+  async fn warn_on_unknown_view_tag() {
+    let mut builder = TestUiBuilder::with_default_tasks_and_tags();
+    let view = SerView {
+      name: "gone".to_string(),
+      formula: FormulaPair::from(Formula::from_str("tag1 & !removed").unwrap()),
+      sort: Vec::new(),
+      order: None,
+      groups: Vec::new(),
+    };
+    let () = builder.ui_config.views.push(view);
+
+    let mut ui = builder.build().await;
+    let expected = InOut::Error(
+      "Views reference unknown tags, treating them as unset: `removed` (view `gone`)".to_string(),
+    );
+    assert_eq!(ui.in_out().await, expected);
+
+    let views = ui.views().await;
+    assert_eq!(views.last().unwrap(), "gone");
+  }
+
   /// Check that a task is re-selected after its tags were changed.
   #[test]
   async fn tag_change_reselects_task() {
@@ -3049,6 +4025,31 @@ mod tests {
     );
   }
 
+  /// Check that we point at the location of a syntax error in an
+  /// entered formula.
+  #[test]
+  async fn update_view_formula_parse_error() {
+    let mut events = vec![Event::from('v'), Event::from('e')];
+    events.extend("a &| b".chars().map(Event::from));
+    events.push(Event::from('\n'));
+
+    let state = TestUiBuilder::with_default_tasks_and_tags()
+      .build()
+      .await
+      .handle(events)
+      .await
+      .in_out()
+      .await;
+
+    let expected = InOut::ErrorAt {
+      error: "Failed to update formula: unexpected `|` at offset 3; expected tag, `@`, `!`, or `(`"
+        .to_string(),
+      input: "a &| b".to_string(),
+      offset: 3,
+    };
+    assert_eq!(state, expected);
+  }
+
   /// Make sure that a view formula update triggers a "changed" warning.
   #[test]
   async fn update_view_formula_unsaved() {
@@ -3080,4 +4081,206 @@ mod tests {
       "{state:?}"
     );
   }
+
+  /// Check that we can create a new view from a formula.
+  #[test]
+  async fn add_view() {
+    let mut events = vec![Event::from('j'), Event::from('v'), Event::from('a')];
+    events.extend("tag1".chars().map(Event::from));
+    events.push(Event::from('\n'));
+    events.push(Event::from('w'));
+
+    let (config, state) = TestUiBuilder::with_default_tasks_and_tags()
+      .build()
+      .await
+      .handle(events)
+      .await
+      .load_config_and_state()
+      .await
+      .unwrap();
+
+    let names = config
+      .views
+      .iter()
+      .map(|view| view.name().to_string())
+      .collect::<Vec<_>>();
+    let expected = [
+      "all",
+      "tag1",
+      "tag complete",
+      "tag2 || tag3",
+      "tag1 && tag3",
+    ];
+    assert_eq!(names, expected);
+    assert_eq!(config.views[1].formula(), "tag1");
+
+    let state = state.to_serde();
+    assert_eq!(
+      state.selected_tasks,
+      vec![Some(1), Some(0), Some(0), Some(0), Some(0)]
+    );
+    assert_eq!(state.selected_view, Some(1));
+  }
+
+  /// Check that we can rename and duplicate a view.
+  #[test]
+  async fn rename_and_duplicate_view() {
+    let mut events = vec![
+      Event::from('l'),
+      Event::from('j'),
+      Event::from('j'),
+      Event::from('v'),
+      Event::from('r'),
+    ];
+    events.extend(repeat(Event::from(Key::Backspace)).take(8));
+    events.extend("done".chars().map(Event::from));
+    events.extend([Event::from('\n'), Event::from('v'), Event::from('y')]);
+    events.extend("2\n".chars().map(Event::from));
+    events.push(Event::from('w'));
+
+    let (config, state) = TestUiBuilder::with_default_tasks_and_tags()
+      .build()
+      .await
+      .handle(events)
+      .await
+      .load_config_and_state()
+      .await
+      .unwrap();
+
+    let names = config
+      .views
+      .iter()
+      .map(|view| view.name().to_string())
+      .collect::<Vec<_>>();
+    let expected = ["all", "tag done", "tag done2", "tag2 || tag3", "tag1 && tag3"];
+    assert_eq!(names, expected);
+    assert_eq!(config.views[2].formula(), config.views[1].formula());
+
+    let state = state.to_serde();
+    assert_eq!(
+      state.selected_tasks,
+      vec![Some(0), Some(2), Some(0), Some(0), Some(0)]
+    );
+    assert_eq!(state.selected_view, Some(2));
+  }
+
+  /// Check that renaming a view to the name of another one fails.
+  #[test]
+  async fn rename_view_to_existing_name() {
+    let mut events = vec![Event::from('v'), Event::from('r')];
+    events.extend(repeat(Event::from(Key::Backspace)).take(3));
+    events.extend("tag1 && tag3\n".chars().map(Event::from));
+
+    let state = TestUiBuilder::with_default_tasks_and_tags()
+      .build()
+      .await
+      .handle(events)
+      .await
+      .in_out()
+      .await;
+
+    let expected =
+      InOut::Error("Failed to rename view: view `tag1 && tag3` already exists".to_string());
+    assert_eq!(state, expected);
+  }
+
+  /// Check that we can delete a view, with the selection of the
+  /// remaining ones being retained.
+  #[test]
+  async fn delete_view() {
+    let events = vec![
+      Event::from('l'),
+      Event::from('j'),
+      Event::from('l'),
+      Event::from('j'),
+      Event::from('j'),
+      Event::from('h'),
+      Event::from('v'),
+      Event::from('d'),
+      Event::from('w'),
+    ];
+
+    let mut ui = TestUiBuilder::with_default_tasks_and_tags().build().await;
+    let views = ui.handle(events).await.views().await;
+    assert_eq!(views, ["all", "tag2 || tag3", "tag1 && tag3"]);
+
+    let (_config, state) = ui.load_config_and_state().await.unwrap();
+    let state = state.to_serde();
+    assert_eq!(state.selected_tasks, vec![Some(0), Some(2), Some(0)]);
+    assert_eq!(state.selected_view, Some(1));
+  }
+
+  /// Check that a view added after deleting another one starts out
+  /// fresh, despite reusing the deleted view's widget.
+  #[test]
+  async fn delete_and_add_view() {
+    let mut events = vec![
+      Event::from('l'),
+      Event::from('j'),
+      Event::from('j'),
+      Event::from('v'),
+      Event::from('d'),
+      Event::from('v'),
+      Event::from('a'),
+    ];
+    events.extend("tag1\n".chars().map(Event::from));
+    events.push(Event::from('w'));
+
+    let mut ui = TestUiBuilder::with_default_tasks_and_tags().build().await;
+    let views = ui.handle(events).await.views().await;
+    assert_eq!(views, ["all", "tag2 || tag3", "tag1", "tag1 && tag3"]);
+
+    let (config, state) = ui.load_config_and_state().await.unwrap();
+    assert_eq!(config.views[2].formula(), "tag1");
+
+    let state = state.to_serde();
+    assert_eq!(
+      state.selected_tasks,
+      vec![Some(0), Some(0), Some(0), Some(0)]
+    );
+    assert_eq!(state.selected_view, Some(2));
+  }
+
+  /// Check that views referenced by others as well as the last
+  /// remaining view cannot be deleted.
+  #[test]
+  async fn delete_view_error() {
+    let mut builder = TestUiBuilder::with_default_tasks_and_tags();
+    builder.ui_config.views = vec![
+      SerView {
+        name: "a".to_string(),
+        formula: FormulaPair::from(Formula::from_str("tag1").unwrap()),
+        sort: Vec::new(),
+        order: None,
+        groups: Vec::new(),
+      },
+      SerView {
+        name: "b".to_string(),
+        formula: FormulaPair::from(Formula::from_str("@a").unwrap()),
+        sort: Vec::new(),
+        order: None,
+        groups: Vec::new(),
+      },
+    ];
+
+    let mut ui = builder.build().await;
+    let events = vec![Event::from('v'), Event::from('d')];
+    let state = ui.handle(events).await.in_out().await;
+    let expected =
+      InOut::Error("Failed to delete view: view `a` is referenced by view `b`".to_string());
+    assert_eq!(state, expected);
+
+    let events = vec![
+      Event::from('l'),
+      Event::from('v'),
+      Event::from('d'),
+      Event::from('v'),
+      Event::from('d'),
+    ];
+    let state = ui.handle(events).await.in_out().await;
+    let expected =
+      InOut::Error("Failed to delete view: cannot delete the last remaining view".to_string());
+    assert_eq!(state, expected);
+    assert_eq!(ui.views().await, ["a"]);
+  }
 }
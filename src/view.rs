@@ -1,96 +1,700 @@
 // Copyright (C) 2017-2025 Daniel Mueller <deso@posteo.net>
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use std::cell::Cell;
+use std::cell::Ref;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::mem::take;
 use std::rc::Rc;
+use std::slice::Iter as SliceIter;
 use std::str::FromStr as _;
 
-use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context as _;
 use anyhow::Result;
 
+use chrono::DateTime;
+use chrono::Utc;
+
+use crate::formula::quote_var;
+use crate::formula::tokenize;
+use crate::formula::Field;
 use crate::formula::Formula;
-use crate::ser::view::formula_to_cnf;
+use crate::formula::Token;
 use crate::ser::view::FormulaPair;
-use crate::ser::view::TagLit as SerTagLit;
+use crate::ser::view::SortBy as SerSortBy;
+use crate::ser::view::SortKey as SerSortKey;
 use crate::ser::view::View as SerView;
 use crate::ser::ToSerde;
 use crate::tags::Tag;
 use crate::tags::Templates;
+use crate::tasks::Builder as TaskBuilder;
+use crate::tasks::Order;
 use crate::tasks::Task;
 use crate::tasks::TaskIter;
 use crate::tasks::Tasks;
 
 
-/// A literal describing whether a tag is negated or not.
+/// Check whether `text` contains `pattern`, which is expected to be in
+/// lower case, ignoring the case of `text`.
+///
+/// Contrary to lowercasing `text` up front, no allocation is necessary.
+fn contains_lowercase(text: &str, pattern: &str) -> bool {
+  pattern.is_empty()
+    || text.char_indices().any(|(idx, _)| {
+      let mut lower = text[idx..].chars().flat_map(char::to_lowercase);
+      pattern.chars().all(|c| lower.next() == Some(c))
+    })
+}
+
+
+/// A compiled representation of a view's formula, referencing tags
+/// directly.
+///
+/// Conjunctions and disjunctions are flattened and constant
+/// sub-expressions folded, so that evaluating an expression is linear
+/// in the size of the formula it was compiled from.
 #[derive(Clone, Debug)]
-enum TagLit {
-  Pos(Tag),
-  Neg(Tag),
+enum Expr {
+  /// A constant truth value.
+  Const(bool),
+  /// A tag, which evaluates to true for tasks that have it set.
+  Tag(Tag),
+  /// A check whether a field of a task contains a lower case string,
+  /// ignoring case.
+  Contains(Field, String),
+  /// A check whether a task has details.
+  HasDetails,
+  /// A reference to another view, which evaluates to true for all tasks
+  /// that are part of it.
+  View(Rc<Definition>),
+  /// The negation of an expression.
+  Not(Box<Expr>),
+  /// A conjunction of at least two expressions.
+  And(Box<[Expr]>),
+  /// A disjunction of at least two expressions.
+  Or(Box<[Expr]>),
 }
 
-impl TagLit {
-  /// Retrieve the contained `Tag`.
-  fn tag(&self) -> &Tag {
+impl Expr {
+  /// Compile a formula into an expression.
+  ///
+  /// Tags that are not known are treated as not being set on any task,
+  /// while references to views that are not known cause an error.
+  /// Known tags named like a keyword take precedence over the keyword,
+  /// so that formulas written before the keyword got introduced keep
+  /// their meaning.
+  fn compile(formula: &Formula, templates: &Templates, registry: &Registry) -> Result<Self> {
+    if let Some(tag) = formula
+      .keyword()
+      .and_then(|keyword| templates.instantiate_from_name(keyword))
+    {
+      return Ok(Expr::Tag(tag))
+    }
+
+    let expr = match formula {
+      Formula::Var(name) => templates
+        .instantiate_from_name(name)
+        .map(Expr::Tag)
+        .unwrap_or(Expr::Const(false)),
+      Formula::Const(value) => Expr::Const(*value),
+      Formula::Contains(field, string) => Expr::Contains(*field, string.to_lowercase()),
+      Formula::HasDetails => Expr::HasDetails,
+      Formula::View(name) => Expr::View(registry.find(name)?),
+      Formula::Not(formula) => match Self::compile(formula, templates, registry)? {
+        Expr::Const(value) => Expr::Const(!value),
+        Expr::Not(expr) => *expr,
+        expr => Expr::Not(Box::new(expr)),
+      },
+      Formula::And(..) => Self::compile_junction::<true>(formula, templates, registry)?,
+      Formula::Or(..) => Self::compile_junction::<false>(formula, templates, registry)?,
+    };
+    Ok(expr)
+  }
+
+  /// Compile a (possibly nested) conjunction (`AND == true`) or
+  /// disjunction (`AND == false`) into a single, flat expression.
+  fn compile_junction<const AND: bool>(
+    formula: &Formula,
+    templates: &Templates,
+    registry: &Registry,
+  ) -> Result<Self> {
+    fn collect<'f, const AND: bool>(formula: &'f Formula, operands: &mut Vec<&'f Formula>) {
+      match formula {
+        Formula::And(lhs, rhs) if AND => {
+          let () = collect::<AND>(lhs, operands);
+          let () = collect::<AND>(rhs, operands);
+        },
+        Formula::Or(lhs, rhs) if !AND => {
+          let () = collect::<AND>(lhs, operands);
+          let () = collect::<AND>(rhs, operands);
+        },
+        formula => operands.push(formula),
+      }
+    }
+
+    let mut operands = Vec::new();
+    let () = collect::<AND>(formula, &mut operands);
+
+    let mut exprs = Vec::with_capacity(operands.len());
+    for operand in operands {
+      match Self::compile(operand, templates, registry)? {
+        // The neutral element does not affect the result...
+        Expr::Const(value) if value == AND => (),
+        // ... while the absorbing one determines it.
+        Expr::Const(value) => return Ok(Expr::Const(value)),
+        // Operands may end up as the same kind of junction, e.g., by
+        // double negation.
+        Expr::And(nested) if AND => exprs.extend(nested.into_vec()),
+        Expr::Or(nested) if !AND => exprs.extend(nested.into_vec()),
+        expr => exprs.push(expr),
+      }
+    }
+
+    let expr = match exprs.len() {
+      0 => Expr::Const(AND),
+      // SANITY: We just checked that there is exactly one expression.
+      1 => exprs.pop().unwrap(),
+      _ if AND => Expr::And(exprs.into_boxed_slice()),
+      _ => Expr::Or(exprs.into_boxed_slice()),
+    };
+    Ok(expr)
+  }
+
+  /// Evaluate the expression for the given task.
+  fn eval(&self, task: &Task) -> bool {
     match self {
-      TagLit::Pos(tag) | TagLit::Neg(tag) => tag,
+      Expr::Const(value) => *value,
+      Expr::Tag(tag) => task.has_tag(tag),
+      Expr::Contains(field, string) => {
+        let contains = |text: &str| contains_lowercase(text, string);
+        match field {
+          Field::Summary => task.with_summary(contains),
+          Field::Details => task.with_details(contains),
+        }
+      },
+      Expr::HasDetails => task.with_details(|details| !details.is_empty()),
+      Expr::View(def) => def.expr.borrow().eval(task),
+      Expr::Not(expr) => !expr.eval(task),
+      Expr::And(exprs) => exprs.iter().all(|expr| expr.eval(task)),
+      Expr::Or(exprs) => exprs.iter().any(|expr| expr.eval(task)),
     }
   }
 
-  /// Check whether the literal is a positive one.
-  fn is_pos(&self) -> bool {
+  /// Collect all tags that are not negated, i.e., appear beneath an
+  /// even number of negations.
+  ///
+  /// Tags of referenced views are included.
+  fn positive_tags(&self, negated: bool, tags: &mut Vec<Tag>) {
     match self {
-      TagLit::Pos(_) => true,
-      TagLit::Neg(_) => false,
+      Expr::Const(..) | Expr::Contains(..) | Expr::HasDetails => (),
+      Expr::Tag(tag) => {
+        if !negated && !tags.contains(tag) {
+          let () = tags.push(tag.clone());
+        }
+      },
+      Expr::View(def) => def.expr.borrow().positive_tags(negated, tags),
+      Expr::Not(expr) => expr.positive_tags(!negated, tags),
+      Expr::And(exprs) | Expr::Or(exprs) => {
+        let () = exprs
+          .iter()
+          .for_each(|expr| expr.positive_tags(negated, tags));
+      },
+    }
+  }
+
+  /// Check whether the expression references the view described by
+  /// `def`, directly or by means of other views.
+  ///
+  /// The names of the views in between are recorded in `path`.
+  fn references(&self, def: &Rc<Definition>, path: &mut Vec<String>) -> bool {
+    match self {
+      Expr::Const(..) | Expr::Tag(..) | Expr::Contains(..) | Expr::HasDetails => false,
+      Expr::View(other) => {
+        if Rc::ptr_eq(other, def) {
+          return true
+        }
+
+        let () = path.push(other.name.borrow().clone());
+        if other.expr.borrow().references(def, path) {
+          return true
+        }
+        let _name = path.pop();
+        false
+      },
+      Expr::Not(expr) => expr.references(def, path),
+      Expr::And(exprs) | Expr::Or(exprs) => exprs.iter().any(|expr| expr.references(def, path)),
     }
   }
 }
 
 
-/// An object providing filtered iteration over an iterator of tasks.
+/// The part of a view that other views can reference.
+#[derive(Debug)]
+struct Definition {
+  /// The name of the view.
+  name: RefCell<String>,
+  /// The compiled formula of the view.
+  expr: RefCell<Expr>,
+}
+
+impl Definition {
+  /// Create a new `Definition` for a view not yet having a formula.
+  fn new(name: String) -> Rc<Self> {
+    Rc::new(Self {
+      name: RefCell::new(name),
+      expr: RefCell::new(Expr::Const(true)),
+    })
+  }
+}
+
+
+/// A registry of views, allowing them to reference each other by name.
+#[derive(Debug, Default)]
+struct Registry {
+  /// The definitions of all registered views.
+  definitions: RefCell<Vec<Rc<Definition>>>,
+  /// A counter increased whenever the formula of a view changes.
+  generation: Cell<usize>,
+}
+
+impl Registry {
+  /// Register the definition of a view.
+  fn register(&self, def: Rc<Definition>) {
+    let () = self.definitions.borrow_mut().push(def);
+  }
+
+  /// Make `expr` the compiled formula of the view described by `def`.
+  fn define(&self, def: &Definition, expr: Expr) {
+    *def.expr.borrow_mut() = expr;
+    let () = self.generation.set(self.generation.get() + 1);
+  }
+
+  /// Look up the definition of the view with the given name.
+  fn find(&self, name: &str) -> Result<Rc<Definition>> {
+    let definitions = self.definitions.borrow();
+    let mut iter = definitions.iter().filter(|def| *def.name.borrow() == name);
+
+    match (iter.next(), iter.next()) {
+      (Some(def), None) => Ok(Rc::clone(def)),
+      (Some(..), Some(..)) => bail!("encountered ambiguous reference to view `{name}`"),
+      (None, _) => bail!("encountered reference to unknown view `{name}`"),
+    }
+  }
+
+  /// Remove the definition of a view from the registry.
+  ///
+  /// Views referenced by others as well as the last remaining one
+  /// cannot be removed.
+  fn unregister(&self, def: &Rc<Definition>) -> Result<()> {
+    let mut definitions = self.definitions.borrow_mut();
+    let referencing = definitions
+      .iter()
+      .find(|other| other.expr.borrow().references(def, &mut Vec::new()));
+    if let Some(other) = referencing {
+      bail!(
+        "view `{}` is referenced by view `{}`",
+        def.name.borrow(),
+        other.name.borrow()
+      )
+    }
+
+    if definitions.len() <= 1 {
+      bail!("cannot delete the last remaining view")
+    }
+
+    let () = definitions.retain(|other| !Rc::ptr_eq(other, def));
+    Ok(())
+  }
+
+  /// Check whether a view other than the one described by `def` has the
+  /// given name.
+  fn is_taken(&self, name: &str, def: &Rc<Definition>) -> bool {
+    self
+      .definitions
+      .borrow()
+      .iter()
+      .any(|other| !Rc::ptr_eq(other, def) && *other.name.borrow() == name)
+  }
+}
+
+
+/// A part of the textual representation of a view's formula.
 #[derive(Clone, Debug)]
-pub struct Filter<'tasks> {
-  iter: TaskIter<'tasks>,
-  lits: &'tasks [Box<[TagLit]>],
+enum FormulaPart {
+  /// A tag, referenced by its current name.
+  Tag {
+    /// The tag.
+    tag: Tag,
+    /// The name of the tag at the time the formula was parsed.
+    name: String,
+    /// The tag as it appeared in the formula, possibly quoted.
+    text: String,
+  },
+  /// A reference to a view, by its current name.
+  View {
+    /// The definition of the referenced view.
+    def: Rc<Definition>,
+    /// The name of the view at the time the formula was parsed.
+    name: String,
+    /// The reference as it appeared in the formula, possibly quoted.
+    text: String,
+  },
+  /// Any other text, including names of unknown tags.
+  Text(String),
 }
 
-impl<'tasks> Filter<'tasks> {
-  /// Create a new `Filter` wrapping an iterator and filtering using the given set of literals.
-  fn new(iter: TaskIter<'tasks>, lits: &'tasks [Box<[TagLit]>]) -> Self {
-    Self { iter, lits }
+
+/// A criterion by which to sort tasks, referencing tags directly.
+#[derive(Clone, Debug)]
+enum SortBy {
+  /// Sort by summary, ignoring case.
+  Summary,
+  /// Sort by presence of a tag, with tasks that have it set coming
+  /// first.
+  Tag(Tag),
+  /// Sort by presence of a tag that is not known, which is treated as
+  /// not being set on any task.
+  UnknownTag(String),
+  /// Sort by completion state, with incomplete tasks coming first.
+  Completed,
+  /// Sort by creation time, with the oldest tasks coming first.
+  Created,
+}
+
+
+/// The value of a task with respect to a sort criterion.
+#[derive(Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum SortValue {
+  Text(String),
+  Flag(bool),
+  Time(Option<DateTime<Utc>>),
+}
+
+
+/// A key by which to sort tasks.
+#[derive(Clone, Debug)]
+struct Sort {
+  /// The criterion to sort by.
+  by: SortBy,
+  /// Whether to reverse the order.
+  descending: bool,
+}
+
+impl Sort {
+  /// Create a `Sort` object from a serializable sort key.
+  fn with_serde(key: SerSortKey, templates: &Templates) -> Self {
+    let SerSortKey { by, descending } = key;
+    let by = match by {
+      SerSortBy::Summary => SortBy::Summary,
+      SerSortBy::Tag(name) => templates
+        .instantiate_from_name(&name)
+        .map(SortBy::Tag)
+        .unwrap_or(SortBy::UnknownTag(name)),
+      SerSortBy::Completed => SortBy::Completed,
+      SerSortBy::Created => SortBy::Created,
+    };
+    Self { by, descending }
   }
 
-  /// Check if one of the given tags matches the available ones.
-  fn matches<'tag, I>(lits: &[TagLit], avail_tags: &I) -> bool
-  where
-    I: Iterator<Item = &'tag Tag> + Clone,
-  {
-    // Iterate over disjunctions and check if any of them matches.
-    for lit in lits {
-      let tag = lit.tag();
-      let must_exist = lit.is_pos();
+  /// Retrieve the value of the given task with respect to this key.
+  fn value(&self, task: &Task) -> SortValue {
+    match &self.by {
+      SortBy::Summary => SortValue::Text(task.summary().to_lowercase()),
+      SortBy::Tag(tag) => SortValue::Flag(!task.has_tag(tag)),
+      SortBy::UnknownTag(..) => SortValue::Flag(true),
+      SortBy::Completed => SortValue::Flag(task.is_completed()),
+      SortBy::Created => SortValue::Time(task.created()),
+    }
+  }
+
+  /// Retrieve the values of the given task with respect to all keys.
+  fn values(sort: &[Sort], task: &Task) -> Vec<SortValue> {
+    sort.iter().map(|sort| sort.value(task)).collect()
+  }
+
+  /// Compare values of two tasks, as retrieved via [`Sort::values`].
+  fn compare(sort: &[Sort], values1: &[SortValue], values2: &[SortValue]) -> Ordering {
+    sort
+      .iter()
+      .zip(values1.iter().zip(values2))
+      .map(|(sort, (value1, value2))| {
+        let ordering = value1.cmp(value2);
+        if sort.descending {
+          ordering.reverse()
+        } else {
+          ordering
+        }
+      })
+      .find(|ordering| ordering.is_ne())
+      .unwrap_or(Ordering::Equal)
+  }
+}
+
+impl ToSerde for Sort {
+  type Output = SerSortKey;
+
+  /// Convert this sort key into a serializable one.
+  fn to_serde(&self) -> Self::Output {
+    let by = match &self.by {
+      SortBy::Summary => SerSortBy::Summary,
+      SortBy::Tag(tag) => SerSortBy::Tag(tag.name().to_string()),
+      SortBy::UnknownTag(name) => SerSortBy::Tag(name.clone()),
+      SortBy::Completed => SerSortBy::Completed,
+      SortBy::Created => SerSortBy::Created,
+    };
+
+    SerSortKey {
+      by,
+      descending: self.descending,
+    }
+  }
+}
+
+
+/// The suffix of a group that matches all tags starting with what
+/// precedes it.
+const GROUP_WILDCARD: &str = "*";
+/// The title of the section containing tasks not belonging to any
+/// other.
+const OTHER_SECTION: &str = "other";
+
+
+/// A set of tags by which to group tasks into sections.
+#[derive(Clone, Debug)]
+enum Group {
+  /// A single tag, forming one section.
+  Tag(Tag),
+  /// A tag that is not known, forming no section.
+  UnknownTag(String),
+  /// All tags with names starting with the given prefix, forming one
+  /// section each.
+  Prefix(String),
+}
+
+impl Group {
+  /// Create a `Group` object from its serializable representation.
+  fn with_serde(group: String, templates: &Templates) -> Self {
+    if let Some(prefix) = group.strip_suffix(GROUP_WILDCARD) {
+      Self::Prefix(prefix.to_string())
+    } else {
+      templates
+        .instantiate_from_name(&group)
+        .map(Self::Tag)
+        .unwrap_or(Self::UnknownTag(group))
+    }
+  }
+}
+
+impl ToSerde for Group {
+  type Output = String;
+
+  /// Convert this group into a serializable one.
+  fn to_serde(&self) -> Self::Output {
+    match self {
+      Self::Tag(tag) => tag.name().to_string(),
+      Self::UnknownTag(name) => name.clone(),
+      Self::Prefix(prefix) => format!("{prefix}{GROUP_WILDCARD}"),
+    }
+  }
+}
+
+
+/// The sections of a grouped view.
+#[derive(Clone, Debug)]
+struct Sections {
+  /// The tags identifying all sections but the last one, which
+  /// contains tasks not having any of them.
+  tags: Vec<Tag>,
+  /// Whether a section is collapsed, by index.
+  collapsed: Vec<bool>,
+}
 
-      if avail_tags.clone().any(|x| x == tag) == must_exist {
-        return true
+impl Sections {
+  /// Create a `Sections` object for the given groups.
+  fn new(groups: &[Group], collapsed: &BTreeSet<Option<Tag>>, templates: &Templates) -> Self {
+    let mut tags = Vec::new();
+    for group in groups {
+      match group {
+        Group::Tag(tag) => {
+          if !tags.contains(tag) {
+            let () = tags.push(tag.clone());
+          }
+        },
+        Group::UnknownTag(..) => (),
+        Group::Prefix(prefix) => {
+          let mut matching = templates
+            .iter()
+            .filter(|template| template.name().starts_with(prefix.as_str()))
+            .map(Tag::new)
+            .filter(|tag| !tags.contains(tag))
+            .collect::<Vec<_>>();
+          let () = matching.sort_by(|tag1, tag2| tag1.name().cmp(&tag2.name()));
+          let () = tags.extend(matching);
+        },
       }
     }
-    false
+
+    let collapsed = tags
+      .iter()
+      .map(|tag| collapsed.contains(&Some(tag.clone())))
+      .chain([collapsed.contains(&None)])
+      .collect();
+
+    Self { tags, collapsed }
   }
 
-  /// Check if the given `tags` match this view's requirements.
-  fn matched_by<'tag, I>(&self, avail_tags: &I) -> bool
+  /// Retrieve the index of the section the given task belongs to.
+  fn index(&self, task: &Task) -> usize {
+    self
+      .tags
+      .iter()
+      .position(|tag| task.has_tag(tag))
+      .unwrap_or(self.tags.len())
+  }
+
+  /// Retrieve the tag identifying the section with the given index,
+  /// if any.
+  fn tag(&self, index: usize) -> Option<&Tag> {
+    self.tags.get(index)
+  }
+
+  /// Retrieve the non-empty sections that the tasks of an iterator
+  /// matching the given expression fall into, in order.
+  fn present<'tasks, I>(&self, iter: I, expr: &Expr) -> Vec<Section>
   where
-    I: Iterator<Item = &'tag Tag> + Clone,
+    I: Iterator<Item = &'tasks Rc<Task>>,
   {
-    // Iterate over conjunctions; all of them need to match.
-    for req_lits in self.lits {
-      // We could create a set for faster inclusion checks instead of
-      // passing in an iterator. However, typically tasks only use a
-      // small set of tags and so the allocation overhead is assumed to
-      // be higher than the iteration cost we incur right now.
-      if !Self::matches(req_lits, avail_tags) {
-        return false
+    let mut counts = vec![0; self.collapsed.len()];
+    let () = iter
+      .filter(|task| expr.eval(task))
+      .for_each(|task| counts[self.index(task)] += 1);
+
+    counts
+      .into_iter()
+      .enumerate()
+      .filter(|(_index, count)| *count > 0)
+      .map(|(index, count)| Section {
+        title: self
+          .tag(index)
+          .map(|tag| tag.name().to_string())
+          .unwrap_or_else(|| OTHER_SECTION.to_string()),
+        count,
+        collapsed: self.collapsed[index],
+      })
+      .collect()
+  }
+}
+
+
+/// A section of a grouped view, as presented to the user.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Section {
+  /// The section's title.
+  pub title: String,
+  /// The number of tasks in the section, including hidden ones.
+  pub count: usize,
+  /// Whether the section is collapsed, hiding its tasks.
+  pub collapsed: bool,
+}
+
+
+/// The tasks a `Filter` iterates over.
+#[derive(Clone, Debug)]
+enum FilterIter<'tasks> {
+  /// All tasks in their manual order, filtered lazily.
+  Manual {
+    iter: TaskIter<'tasks>,
+    expr: &'tasks Expr,
+  },
+  /// Matching tasks only, already sorted.
+  Sorted(SliceIter<'tasks, Rc<Task>>),
+}
+
+
+/// Collect the tasks of an iterator matching the given expression,
+/// sorted as per `sort`.
+///
+/// If `sections` is provided, tasks are grouped by section first,
+/// skipping those in collapsed sections.
+fn sort_tasks<'tasks, I>(
+  iter: I,
+  expr: &Expr,
+  sort: &[Sort],
+  sections: Option<&Sections>,
+) -> Vec<Rc<Task>>
+where
+  I: Iterator<Item = &'tasks Rc<Task>>,
+{
+  let mut tasks = iter
+    .filter(|task| expr.eval(task))
+    .filter_map(|task| {
+      let section = sections.map(|sections| sections.index(task));
+      if let (Some(sections), Some(section)) = (sections, section) {
+        if sections.collapsed[section] {
+          return None
+        }
       }
+      Some((section, Sort::values(sort, task), task))
+    })
+    .collect::<Vec<_>>();
+  // Sorting is stable and so tasks comparing equal stay in their
+  // manual order.
+  let () = tasks.sort_by(|(section1, values1, _), (section2, values2, _)| {
+    section1
+      .cmp(section2)
+      .then_with(|| Sort::compare(sort, values1, values2))
+  });
+
+  tasks
+    .into_iter()
+    .map(|(_section, _values, task)| Rc::clone(task))
+    .collect()
+}
+
+
+/// The tasks and sections of a sorted or grouped view in the order in
+/// which they are presented, along with the state they were derived
+/// from.
+#[derive(Clone, Debug)]
+struct Cache {
+  /// The generation of the tasks the cache was created for.
+  tasks: usize,
+  /// The generation of the view registry the cache was created for.
+  views: usize,
+  /// The tasks matching the view's formula, in order, excluding those
+  /// in collapsed sections.
+  sorted: Vec<Rc<Task>>,
+  /// The sections of the view, if it groups its tasks.
+  sections: Option<Sections>,
+  /// The non-empty sections, as presented to the user.
+  presented: Vec<Section>,
+}
+
+
+/// An object providing filtered iteration over an iterator of tasks.
+#[derive(Clone, Debug)]
+pub struct Filter<'tasks> {
+  iter: FilterIter<'tasks>,
+}
+
+impl<'tasks> Filter<'tasks> {
+  /// Create a new `Filter` wrapping an iterator and filtering using the
+  /// given expression.
+  fn new(iter: TaskIter<'tasks>, expr: &'tasks Expr) -> Self {
+    Self {
+      iter: FilterIter::Manual { iter, expr },
+    }
+  }
+
+  /// Create a new `Filter` yielding the provided tasks, which are
+  /// expected to match the view's formula and be sorted already.
+  fn sorted(tasks: &'tasks [Rc<Task>]) -> Self {
+    Self {
+      iter: FilterIter::Sorted(tasks.iter()),
     }
-    true
   }
 }
 
@@ -99,33 +703,24 @@ impl<'tasks> Iterator for Filter<'tasks> {
 
   /// Advance the iterator yielding the next matching task or None.
   fn next(&mut self) -> Option<Self::Item> {
-    // TODO: Should really be a for loop or even just a .find()
-    //       invocation, however, both versions do not compile due to
-    //       borrowing/ownership conflicts.
-    loop {
-      match self.iter.next() {
-        Some(task) => {
-          if task.tags(|iter| self.matched_by(&iter)) {
-            return Some(task)
-          }
-        },
-        None => return None,
-      }
+    match &mut self.iter {
+      FilterIter::Manual { iter, expr } => {
+        let expr = *expr;
+        iter.find(|task| expr.eval(task))
+      },
+      FilterIter::Sorted(iter) => iter.next(),
     }
   }
 }
 
 impl DoubleEndedIterator for Filter<'_> {
   fn next_back(&mut self) -> Option<Self::Item> {
-    loop {
-      match self.iter.next_back() {
-        Some(task) => {
-          if task.tags(|iter| self.matched_by(&iter)) {
-            return Some(task)
-          }
-        },
-        None => return None,
-      }
+    match &mut self.iter {
+      FilterIter::Manual { iter, expr } => {
+        let expr = *expr;
+        iter.rfind(|task| expr.eval(task))
+      },
+      FilterIter::Sorted(iter) => iter.next_back(),
     }
   }
 }
@@ -135,6 +730,8 @@ impl DoubleEndedIterator for Filter<'_> {
 pub struct ViewBuilder {
   templates: Rc<Templates>,
   tasks: Rc<Tasks>,
+  /// The registry of all views created by this builder.
+  registry: Rc<Registry>,
 }
 
 impl ViewBuilder {
@@ -143,10 +740,14 @@ impl ViewBuilder {
     Self {
       templates: Rc::clone(templates),
       tasks: Rc::clone(tasks),
+      registry: Default::default(),
     }
   }
 
   /// Build the final `View` instance.
+  ///
+  /// The view's formula may reference all views previously created by
+  /// this builder.
   pub fn build(&self, name: impl Into<String>, formula: &str) -> Result<View> {
     let formula = FormulaPair {
       formula: if formula.is_empty() {
@@ -157,12 +758,70 @@ impl ViewBuilder {
       string: formula.to_string(),
     };
 
-    View::from_formula(
-      name.into(),
+    // Only register the view once it got created successfully.
+    let def = Definition::new(name.into());
+    let view = View::from_formula(
+      Rc::clone(&def),
       formula,
+      true,
+      Rc::clone(&self.registry),
       Rc::clone(&self.templates),
       Rc::clone(&self.tasks),
-    )
+    )?;
+    let () = self.registry.register(def);
+    Ok(view)
+  }
+
+  /// Create `View` objects from serializable ones.
+  ///
+  /// Views may reference each other by name, irrespective of their
+  /// order, as long as no view ends up referencing itself. Tags
+  /// referenced by the views' formulas that are not known do not cause
+  /// an error. Rather, they are treated as not being set on any task
+  /// and can be inquired via [`View::unknown_tags`].
+  pub fn with_serde(&self, views: Vec<SerView>) -> Result<Vec<View>> {
+    let defs = views
+      .iter()
+      .map(|view| {
+        let def = Definition::new(view.name.clone());
+        let () = self.registry.register(Rc::clone(&def));
+        def
+      })
+      .collect::<Vec<_>>();
+
+    views
+      .into_iter()
+      .zip(defs)
+      .map(|(view, def)| {
+        let SerView {
+          name,
+          formula,
+          sort,
+          order,
+          groups,
+        } = view;
+        let sort = sort
+          .into_iter()
+          .map(|key| Sort::with_serde(key, &self.templates))
+          .collect();
+        let groups = groups
+          .into_iter()
+          .map(|group| Group::with_serde(group, &self.templates))
+          .collect();
+        let order = order.map(|order| Rc::new(Order::with_serde(order)));
+
+        View::from_formula(
+          def,
+          formula,
+          false,
+          Rc::clone(&self.registry),
+          Rc::clone(&self.templates),
+          Rc::clone(&self.tasks),
+        )
+        .map(|view| view.with_sort(sort).with_order(order).with_groups(groups))
+        .with_context(|| format!("failed to instantiate view '{name}'"))
+      })
+      .collect()
   }
 }
 
@@ -178,11 +837,14 @@ impl ViewBuilder {
 ///    likelihood of borrowing conflicts.
 #[derive(Clone, Debug)]
 pub struct View {
-  /// The name of the view.
-  // TODO: This attribute does not really belong in here. Once we have
-  //       the necessary infrastructure for storing it elsewhere it
-  //       should be removed from this struct.
-  name: String,
+  /// The name and compiled formula of the view, shared with other views
+  /// referencing it.
+  // TODO: The name does not really belong in here. Once we have the
+  //       necessary infrastructure for storing it elsewhere it should
+  //       be removed from this struct.
+  def: Rc<Definition>,
+  /// The registry of views that this view is part of.
+  registry: Rc<Registry>,
   /// A reference to the `Templates` object we use for instantiating
   /// tags.
   templates: Rc<Templates>,
@@ -190,60 +852,158 @@ pub struct View {
   /// available tasks.
   tasks: Rc<Tasks>,
   /// The textual representation of the logical formula describing the
-  /// view (and from which the Conjunctive Normal Form is derived).
-  formula: String,
-  /// Tags are stored in Conjunctive Normal Form, meaning we have a
-  /// large AND (all the "outer" elements) of ORs (all the inner ones).
-  lits: Box<[Box<[TagLit]>]>,
+  /// view.
+  ///
+  /// Tags and views are kept as such, so that the formula stays valid
+  /// when they are renamed.
+  formula: Box<[FormulaPart]>,
+  /// The keys by which to sort tasks, in order of priority.
+  sort: Box<[Sort]>,
+  /// The view's own manual order of tasks, if any.
+  order: Option<Rc<Order>>,
+  /// The tags by which to group tasks into sections.
+  groups: Box<[Group]>,
+  /// The sections that are collapsed, identified by their tag.
+  collapsed: BTreeSet<Option<Tag>>,
+  /// The names of tags referenced by the formula or sort keys that are
+  /// not known.
+  unknown_tags: Box<[String]>,
+  /// The cached order of tasks, if the view is sorted or grouped and
+  /// got iterated before.
+  cache: RefCell<Option<Cache>>,
 }
 
 impl View {
+  /// Create a `View` from a formula, making it the one used by `def`.
+  ///
+  /// If `strict` is `false`, tags that are not known are treated as
+  /// not being set on any task. Otherwise they cause an error.
   fn from_formula(
-    name: String,
+    def: Rc<Definition>,
     formula: FormulaPair,
+    strict: bool,
+    registry: Rc<Registry>,
     templates: Rc<Templates>,
     tasks: Rc<Tasks>,
   ) -> Result<Self> {
     let FormulaPair { string, formula } = formula;
 
-    let lits = if let Some(formula) = formula {
-      let cnf =
-        formula_to_cnf(formula).ok_or_else(|| anyhow!("encountered invalid tag with value `0`"))?;
-      let lits = cnf
-        .iter()
-        .map(|b| {
-          b.into_iter()
-            .map(|lit| {
-              let tag = templates
-                .instantiate_from_name(lit.name())
-                .ok_or_else(|| anyhow!("encountered invalid tag `{}`", lit.name()))?;
-              let lit = match lit {
-                SerTagLit::Pos(_) => TagLit::Pos(tag),
-                SerTagLit::Neg(_) => TagLit::Neg(tag),
-              };
-              Ok(lit)
-            })
-            .collect::<Result<Box<[_]>>>()
-        })
-        .collect::<Result<Box<[_]>>>()?;
-      lits
-    } else {
-      Box::default()
-    };
+    let mut unknown_tags = Vec::<String>::new();
+    let parts = tokenize(&string)
+      .into_iter()
+      .map(|token| match token {
+        Token::Var { name, text } => {
+          let text = text.to_string();
+          if let Some(tag) = templates.instantiate_from_name(&name) {
+            FormulaPart::Tag { tag, name, text }
+          } else {
+            if !unknown_tags.contains(&name) {
+              let () = unknown_tags.push(name);
+            }
+            FormulaPart::Text(text)
+          }
+        },
+        Token::View { name, text } => {
+          let text = text.to_string();
+          // Unknown views are reported as part of compilation.
+          if let Ok(def) = registry.find(&name) {
+            FormulaPart::View { def, name, text }
+          } else {
+            FormulaPart::Text(text)
+          }
+        },
+        Token::Keyword(text) => {
+          let text = text.to_string();
+          // Keywords refer to a known tag of the same name, if any.
+          if let Some(tag) = templates.instantiate_from_name(&text) {
+            let name = text.clone();
+            FormulaPart::Tag { tag, name, text }
+          } else {
+            FormulaPart::Text(text)
+          }
+        },
+        Token::Other(text) => FormulaPart::Text(text.to_string()),
+      })
+      .collect();
+
+    if strict {
+      if let Some(name) = unknown_tags.first() {
+        bail!("encountered invalid tag `{name}`")
+      }
+    }
+
+    let expr = formula
+      .as_ref()
+      .map(|formula| Expr::compile(formula, &templates, &registry))
+      .transpose()?
+      .unwrap_or(Expr::Const(true));
+
+    let mut path = Vec::new();
+    if expr.references(&def, &mut path) {
+      let name = def.name.borrow();
+      let path = path.iter().fold(format!("`{name}`"), |path, view| {
+        path + &format!(" -> `{view}`")
+      });
+      bail!("encountered cyclic view reference {path} -> `{name}`")
+    }
+
+    let () = registry.define(&def, expr);
 
     Ok(Self {
-      name,
+      def,
+      registry,
       templates,
       tasks,
-      formula: string,
-      lits,
+      formula: parts,
+      sort: Box::default(),
+      order: None,
+      groups: Box::default(),
+      collapsed: BTreeSet::new(),
+      unknown_tags: unknown_tags.into_boxed_slice(),
+      cache: RefCell::new(None),
     })
   }
 
-  /// Create a new `View` object from a serializable one.
-  pub fn with_serde(view: SerView, templates: Rc<Templates>, tasks: Rc<Tasks>) -> Result<Self> {
-    let SerView { name, formula } = view;
-    Self::from_formula(name, formula, templates, tasks)
+  /// Make the view order tasks manually independent of other views.
+  fn with_order(self, order: Option<Rc<Order>>) -> Self {
+    Self { order, ..self }
+  }
+
+  /// Make the view group its tasks into sections as per the given
+  /// groups.
+  fn with_groups(self, groups: Box<[Group]>) -> Self {
+    let mut unknown_tags = self.unknown_tags.into_vec();
+    for group in groups.iter() {
+      if let Group::UnknownTag(name) = group {
+        if !unknown_tags.contains(name) {
+          let () = unknown_tags.push(name.clone());
+        }
+      }
+    }
+
+    Self {
+      groups,
+      unknown_tags: unknown_tags.into_boxed_slice(),
+      ..self
+    }
+  }
+
+  /// Make the view sort its tasks as per the given keys.
+  fn with_sort(self, sort: Box<[Sort]>) -> Self {
+    let mut unknown_tags = self.unknown_tags.into_vec();
+    for key in sort.iter() {
+      if let SortBy::UnknownTag(name) = &key.by {
+        if !unknown_tags.contains(name) {
+          let () = unknown_tags.push(name.clone());
+        }
+      }
+    }
+
+    Self {
+      sort,
+      unknown_tags: unknown_tags.into_boxed_slice(),
+      ..self
+    }
   }
 
   /// Try to replace the formula used by this `View` with one
@@ -257,17 +1017,84 @@ impl View {
     };
     let pair = FormulaPair { string, formula };
 
-    let slf = Self::from_formula(
-      self.name.clone(),
+    let view = Self::from_formula(
+      Rc::clone(&self.def),
       pair,
+      true,
+      Rc::clone(&self.registry),
       Rc::clone(&self.templates),
       Rc::clone(&self.tasks),
-    )?;
+    )?
+    .with_sort(self.sort.clone())
+    .with_order(self.order.clone())
+    .with_groups(self.groups.clone());
+
+    *self = Self {
+      collapsed: take(&mut self.collapsed),
+      ..view
+    };
+    Ok(())
+  }
+
+  /// Rename the view.
+  ///
+  /// Formulas of views referencing this one reflect the new name.
+  pub fn rename(&mut self, name: String) -> Result<()> {
+    if self.registry.is_taken(&name, &self.def) {
+      bail!("view `{name}` already exists")
+    }
 
-    *self = slf;
+    let _name = self.def.name.replace(name);
+    Ok(())
+  }
+
+  /// Retrieve a builder creating views alongside this one, i.e., views
+  /// able to reference it and vice versa.
+  fn builder(&self) -> ViewBuilder {
+    ViewBuilder {
+      templates: Rc::clone(&self.templates),
+      tasks: Rc::clone(&self.tasks),
+      registry: Rc::clone(&self.registry),
+    }
+  }
+
+  /// Ensure that no view goes by the given name already.
+  fn check_name(&self, name: &str) -> Result<()> {
+    let def = Definition::new(name.to_string());
+    if self.registry.is_taken(name, &def) {
+      bail!("view `{name}` already exists")
+    }
     Ok(())
   }
 
+  /// Create a new view with the given name and formula alongside this
+  /// one.
+  pub fn create(&self, name: String, formula: &str) -> Result<View> {
+    let () = self.check_name(&name)?;
+    self.builder().build(name, formula)
+  }
+
+  /// Create a copy of the view, including its sort keys, manual order,
+  /// and groups, under the given name.
+  pub fn duplicate(&self, name: String) -> Result<View> {
+    let () = self.check_name(&name)?;
+    let view = SerView {
+      name,
+      ..self.to_serde()
+    };
+    let mut views = self.builder().with_serde(vec![view])?;
+    // SANITY: We provided exactly one view to create.
+    Ok(views.pop().unwrap())
+  }
+
+  /// Delete the view, so that other views can no longer reference it.
+  ///
+  /// Views still referenced by others as well as the last remaining
+  /// view cannot be deleted.
+  pub fn delete(&self) -> Result<()> {
+    self.registry.unregister(&self.def)
+  }
+
   /// Invoke a user-provided function on an iterator over the tasks
   /// represented by this view.
   #[inline]
@@ -275,20 +1102,200 @@ impl View {
   where
     F: FnMut(Filter<'_>) -> R,
   {
-    self.tasks.iter(|iter| f(Filter::new(iter, &self.lits)))
+    if self.order.is_none() && self.sort.is_empty() && self.groups.is_empty() {
+      let expr = self.def.expr.borrow();
+      self.tasks.iter(|iter| f(Filter::new(iter, &expr)))
+    } else {
+      let cache = self.cache();
+      f(Filter::sorted(&cache.sorted))
+    }
   }
 
-  /// Retrieve an iterator over all tags of the positive literals in
-  /// this `View`.
-  pub fn positive_tag_iter(&self) -> impl Iterator<Item = &Tag> {
-    self.lits.iter().flat_map(|disjunctions| {
-      disjunctions.iter().filter_map(|literal| match literal {
-        TagLit::Pos(tag) => Some(tag),
-        TagLit::Neg(..) => None,
+  /// Retrieve the cached order of tasks, recreating it if tasks or
+  /// views changed since it got created.
+  fn cache(&self) -> Ref<'_, Cache> {
+    let tasks = self.tasks.generation();
+    let views = self.registry.generation.get();
+    let current = self
+      .cache
+      .borrow()
+      .as_ref()
+      .is_some_and(|cache| cache.tasks == tasks && cache.views == views);
+
+    if !current {
+      let expr = self.def.expr.borrow();
+      let sections = (!self.groups.is_empty())
+        .then(|| Sections::new(&self.groups, &self.collapsed, &self.templates));
+      let sorted = if let Some(order) = &self.order {
+        self.tasks.iter_in(order, |iter| {
+          sort_tasks(iter, &expr, &self.sort, sections.as_ref())
+        })
+      } else {
+        self
+          .tasks
+          .iter(|iter| sort_tasks(iter, &expr, &self.sort, sections.as_ref()))
+      };
+      let presented = sections
+        .as_ref()
+        .map(|sections| self.tasks.iter(|iter| sections.present(iter, &expr)))
+        .unwrap_or_default();
+
+      *self.cache.borrow_mut() = Some(Cache {
+        tasks,
+        views,
+        sorted,
+        sections,
+        presented,
+      });
+    }
+
+    // SANITY: We made sure that the cache is populated above.
+    Ref::map(self.cache.borrow(), |cache| cache.as_ref().unwrap())
+  }
+
+  /// Retrieve the sections of the view, if it groups its tasks.
+  fn grouping(&self) -> Option<Ref<'_, Sections>> {
+    if self.groups.is_empty() {
+      None
+    } else {
+      Ref::filter_map(self.cache(), |cache| cache.sections.as_ref()).ok()
+    }
+  }
+
+  /// Retrieve the non-empty sections the view groups its tasks into,
+  /// in the order in which they are presented.
+  ///
+  /// If the view does not group tasks, the result is empty.
+  pub fn sections(&self) -> Vec<Section> {
+    if self.groups.is_empty() {
+      Vec::new()
+    } else {
+      self.cache().presented.clone()
+    }
+  }
+
+  /// Check whether two tasks belong to the same section.
+  ///
+  /// If the view does not group tasks, all tasks do.
+  pub fn same_section(&self, task1: &Task, task2: &Task) -> bool {
+    self
+      .grouping()
+      .map(|sections| sections.index(task1) == sections.index(task2))
+      .unwrap_or(true)
+  }
+
+  /// Collapse the section containing the given task, returning whether
+  /// it was expanded.
+  pub fn collapse_section(&mut self, task: &Task) -> bool {
+    let tag = self
+      .grouping()
+      .map(|sections| sections.tag(sections.index(task)).cloned());
+
+    if let Some(tag) = tag {
+      let _cache = self.cache.get_mut().take();
+      self.collapsed.insert(tag)
+    } else {
+      false
+    }
+  }
+
+  /// Expand all collapsed sections, returning whether any were.
+  pub fn expand_sections(&mut self) -> bool {
+    let collapsed = !self.collapsed.is_empty();
+    let () = self.collapsed.clear();
+    let _cache = self.cache.get_mut().take();
+    collapsed
+  }
+
+  /// Retrieve the names of the tags identifying collapsed sections,
+  /// with `None` representing the section of tasks not belonging to
+  /// any other.
+  pub fn collapsed_sections(&self) -> Vec<Option<String>> {
+    self
+      .collapsed
+      .iter()
+      .map(|tag| tag.as_ref().map(|tag| tag.name().to_string()))
+      .collect()
+  }
+
+  /// Collapse the sections identified by the given tag names, as
+  /// retrieved via [`View::collapsed_sections`].
+  ///
+  /// Names of tags that are not known are ignored.
+  pub fn set_collapsed_sections(&mut self, names: &[Option<String>]) {
+    self.collapsed = names
+      .iter()
+      .filter_map(|name| match name {
+        Some(name) => self.templates.instantiate_from_name(name).map(Some),
+        None => Some(None),
       })
+      .collect();
+    let _cache = self.cache.get_mut().take();
+  }
+
+  /// Add a new task after `after`, in the view's own order as well, if
+  /// it has one.
+  pub fn add(&self, task: TaskBuilder, after: Option<Rc<Task>>) -> Rc<Task> {
+    if let Some(order) = &self.order {
+      self.tasks.add_in(order, task, after)
+    } else {
+      self.tasks.add(task, after)
+    }
+  }
+
+  /// Reorder the task referenced by `to_move` before `other`.
+  ///
+  /// If the view has its own order, only that is affected.
+  pub fn move_before(&self, to_move: Rc<Task>, other: Rc<Task>) {
+    if let Some(order) = &self.order {
+      self.tasks.move_before_in(order, to_move, other)
+    } else {
+      self.tasks.move_before(to_move, other)
+    }
+  }
+
+  /// Reorder the task referenced by `to_move` after `other`.
+  ///
+  /// If the view has its own order, only that is affected.
+  pub fn move_after(&self, to_move: Rc<Task>, other: Rc<Task>) {
+    if let Some(order) = &self.order {
+      self.tasks.move_after_in(order, to_move, other)
+    } else {
+      self.tasks.move_after(to_move, other)
+    }
+  }
+
+  /// Check whether the view sorts its tasks by anything but their
+  /// manual order.
+  pub fn is_sorted(&self) -> bool {
+    !self.sort.is_empty()
+  }
+
+  /// Compare two tasks with respect to the view's sections and sort
+  /// keys, without considering their manual order.
+  pub fn compare(&self, task1: &Task, task2: &Task) -> Ordering {
+    let section = self
+      .grouping()
+      .map(|sections| sections.index(task1).cmp(&sections.index(task2)))
+      .unwrap_or(Ordering::Equal);
+
+    section.then_with(|| {
+      Sort::compare(
+        &self.sort,
+        &Sort::values(&self.sort, task1),
+        &Sort::values(&self.sort, task2),
+      )
     })
   }
 
+  /// Retrieve an iterator over all tags of the positive literals in
+  /// this `View`.
+  pub fn positive_tag_iter(&self) -> impl Iterator<Item = Tag> {
+    let mut tags = Vec::new();
+    let () = self.def.expr.borrow().positive_tags(false, &mut tags);
+    tags.into_iter()
+  }
+
   /// Check whether the view is empty or not.
   #[cfg(test)]
   pub fn is_empty(&self) -> bool {
@@ -296,13 +1303,44 @@ impl View {
   }
 
   /// Retrieve the view's name.
-  pub fn name(&self) -> &str {
-    &self.name
+  pub fn name(&self) -> Ref<'_, str> {
+    Ref::map(self.def.name.borrow(), String::as_str)
   }
 
   /// Retrieve the textual representation of the formula this view uses.
-  pub fn formula(&self) -> &str {
-    &self.formula
+  ///
+  /// Tags and views are represented by their current names.
+  pub fn formula(&self) -> String {
+    self.formula.iter().fold(String::new(), |mut string, part| {
+      match part {
+        FormulaPart::Tag { tag, name, text } => {
+          let current = tag.name();
+          // Preserve the formula as written, unless the tag got
+          // renamed.
+          if *current == **name {
+            string.push_str(text)
+          } else {
+            string.push_str(&quote_var(&current))
+          }
+        },
+        FormulaPart::View { def, name, text } => {
+          let current = def.name.borrow();
+          if *current == *name {
+            string.push_str(text)
+          } else {
+            string.push_str(&Formula::View(current.clone()).to_string())
+          }
+        },
+        FormulaPart::Text(text) => string.push_str(text),
+      }
+      string
+    })
+  }
+
+  /// Retrieve the names of tags referenced by the view's formula that
+  /// are not known.
+  pub fn unknown_tags(&self) -> &[String] {
+    &self.unknown_tags
   }
 }
 
@@ -312,13 +1350,16 @@ impl ToSerde for View {
   /// Convert this view into a serializable one.
   fn to_serde(&self) -> Self::Output {
     SerView {
-      name: self.name.clone(),
+      name: self.name().to_string(),
       formula: FormulaPair {
-        string: self.formula.clone(),
+        string: self.formula(),
         // We intend for the resulting object to the serializable and for
         // that we only need the string.
         formula: None,
       },
+      sort: self.sort.iter().map(Sort::to_serde).collect(),
+      order: self.order.as_ref().map(|order| order.to_serde(&self.tasks)),
+      groups: self.groups.iter().map(Group::to_serde).collect(),
     }
   }
 }
@@ -328,7 +1369,15 @@ impl ToSerde for View {
 mod tests {
   use super::*;
 
+  #[cfg(feature = "nightly")]
+  use std::hint::black_box;
+
+  #[cfg(feature = "nightly")]
+  use unstable_test::Bencher;
+
   use crate::ser::tags::Templates as SerTemplates;
+  use crate::ser::tasks::Status;
+  use crate::ser::tasks::Task as SerTask;
   use crate::ser::tasks::Tasks as SerTasks;
   use crate::tags::Templates;
   use crate::test::make_tasks;
@@ -358,6 +1407,21 @@ mod tests {
   }
 
 
+  /// Check that `contains_lowercase` matches irrespective of the case
+  /// of the text being searched.
+  #[test]
+  fn lowercase_containment() {
+    assert!(contains_lowercase("", ""));
+    assert!(contains_lowercase("Buy Milk", ""));
+    assert!(contains_lowercase("Buy Milk", "milk"));
+    assert!(contains_lowercase("BUY MILK", "y m"));
+    assert!(contains_lowercase("Straße Ärger", "ärger"));
+    assert!(contains_lowercase("İstanbul", "i\u{307}st"));
+    assert!(!contains_lowercase("Buy Milk", "milks"));
+    assert!(!contains_lowercase("Buy", "buy milk"));
+    assert!(!contains_lowercase("", "a"));
+  }
+
   /// Check that we can identify an empty `View`.
   #[test]
   fn is_empty() {
@@ -401,6 +1465,670 @@ mod tests {
     assert_eq!(pos_tags(&view), vec!["tag3", "tag2", "tag5"]);
   }
 
+  /// Check that the formula of a view reflects renamed tags.
+  #[test]
+  fn formula_with_renamed_tag() {
+    let (templates, tasks) = make_tagged_tasks(20);
+    let view = ViewBuilder::new(&templates, &tasks)
+      .build("test", "(tag3 | tag1)& !tag1")
+      .unwrap();
+
+    let tag1 = templates.instantiate_from_name("tag1").unwrap();
+    let () = tasks
+      .rename_template(&tag1.template(), "renamed".to_string())
+      .unwrap();
+    assert_eq!(view.formula(), "(tag3 | renamed)& !renamed");
+
+    let mut views = ViewBuilder::new(&templates, &tasks)
+      .with_serde(vec![view.to_serde()])
+      .unwrap();
+    let view = views.remove(0);
+    assert_eq!(view.unknown_tags(), &[] as &[String]);
+  }
+
+  /// Check that tags with names not adhering to the variable grammar of
+  /// formulas can be referenced by quoting them.
+  #[test]
+  fn filter_quoted_tags() {
+    let mut templates = Templates::new();
+    let () = templates.extend(["my tag", "other"]);
+    let templates = Rc::new(templates);
+    let my_tag = templates.instantiate_from_name("my tag").unwrap();
+    let tasks = vec![
+      SerTask::new("1").with_tags([my_tag.to_serde()]),
+      SerTask::new("2"),
+    ];
+    let tasks = Tasks::with_serde(SerTasks::from(tasks), Rc::clone(&templates)).unwrap();
+    let tasks = Rc::new(tasks);
+
+    let view = ViewBuilder::new(&templates, &tasks)
+      .build("test", r#""my tag" | "other""#)
+      .unwrap();
+    let () = view.iter(|mut iter| {
+      assert_eq!(iter.next().unwrap().summary(), "1");
+      assert!(iter.next().is_none());
+    });
+    assert_eq!(view.formula(), r#""my tag" | "other""#);
+
+    let () = tasks
+      .rename_template(&my_tag.template(), "mine".to_string())
+      .unwrap();
+    assert_eq!(view.formula(), r#"mine | "other""#);
+  }
+
+  /// Check that a stored formula referencing tags named like keywords
+  /// keeps referring to these tags.
+  #[test]
+  fn filter_tags_named_like_keywords() {
+    let mut templates = Templates::new();
+    let () = templates.extend(["true", "has-details"]);
+    let templates = Rc::new(templates);
+    let true_tag = templates.instantiate_from_name("true").unwrap();
+    let details_tag = templates.instantiate_from_name("has-details").unwrap();
+    let tasks = vec![
+      SerTask::new("1").with_tags([true_tag.to_serde()]),
+      SerTask::new("2").with_details("details"),
+      SerTask::new("3").with_tags([details_tag.to_serde()]),
+      SerTask::new("4"),
+    ];
+    let tasks = Tasks::with_serde(SerTasks::from(tasks), Rc::clone(&templates)).unwrap();
+    let tasks = Rc::new(tasks);
+
+    let view = serde_json::from_str::<SerView>(
+      r#"{"name": "test", "formula": "(true | has-details) & !false"}"#,
+    )
+    .unwrap();
+    let mut views = ViewBuilder::new(&templates, &tasks)
+      .with_serde(vec![view])
+      .unwrap();
+    let view = views.remove(0);
+    assert_eq!(view.unknown_tags(), &[] as &[String]);
+
+    let summaries = view.iter(|iter| {
+      iter
+        .map(|task| task.summary().to_string())
+        .collect::<Vec<_>>()
+    });
+    assert_eq!(summaries, vec!["1", "3"]);
+
+    // The keywords are tags and, hence, reflect renames.
+    let () = tasks
+      .rename_template(&true_tag.template(), "done".to_string())
+      .unwrap();
+    assert_eq!(view.formula(), "(done | has-details) & !false");
+  }
+
+  /// Check that tags referenced by a view but not known are treated as
+  /// not set on any task.
+  #[test]
+  fn filter_unknown_tags() {
+    fn summaries(view: &View) -> Vec<String> {
+      view.iter(|iter| {
+        iter
+          .map(|task| task.summary().to_string())
+          .collect::<Vec<_>>()
+      })
+    }
+
+    let (templates, tasks) = make_tagged_tasks(20);
+    let builder = ViewBuilder::new(&templates, &tasks);
+    let tag1 = summaries(&builder.build("test", "tag1").unwrap());
+    assert!(!tag1.is_empty());
+
+    let err = builder.build("test", "tag1 & unknown").unwrap_err();
+    assert_eq!(err.to_string(), "encountered invalid tag `unknown`");
+
+    let view = |formula: &str| {
+      let view = SerView {
+        name: "test".to_string(),
+        formula: FormulaPair {
+          string: formula.to_string(),
+          formula: Some(Formula::from_str(formula).unwrap()),
+        },
+        sort: Vec::new(),
+        order: None,
+        groups: Vec::new(),
+      };
+      let mut views = ViewBuilder::new(&templates, &tasks)
+        .with_serde(vec![view])
+        .unwrap();
+      views.remove(0)
+    };
+
+    let unknown = view("tag1 & unknown");
+    assert_eq!(unknown.unknown_tags(), ["unknown"]);
+    assert_eq!(unknown.formula(), "tag1 & unknown");
+    assert!(unknown.is_empty());
+
+    assert_eq!(summaries(&view("tag1 | unknown")), tag1);
+    assert_eq!(summaries(&view("tag1 & !unknown")), tag1);
+    assert_eq!(summaries(&view("tag1 & (!unknown | unknown)")), tag1);
+    assert_eq!(summaries(&view("tag1 & !(unknown | unknown2)")), tag1);
+  }
+
+  /// Check that formulas are compiled into flat expressions with
+  /// constant sub-expressions folded.
+  #[test]
+  fn formula_compilation() {
+    let (templates, tasks) = make_tagged_tasks(20);
+    let view = |formula: &str| {
+      let view = SerView {
+        name: "test".to_string(),
+        formula: FormulaPair::from(Formula::from_str(formula).unwrap()),
+        sort: Vec::new(),
+        order: None,
+        groups: Vec::new(),
+      };
+      let mut views = ViewBuilder::new(&templates, &tasks)
+        .with_serde(vec![view])
+        .unwrap();
+      views.remove(0)
+    };
+
+    let expr = |formula: &str| view(formula).def.expr.borrow().clone();
+
+    assert!(matches!(expr("!!tag1"), Expr::Tag(..)));
+    assert!(matches!(expr("!tag1"), Expr::Not(..)));
+    assert!(matches!(expr("tag1 & unknown"), Expr::Const(false)));
+    assert!(matches!(expr("tag1 | !unknown"), Expr::Const(true)));
+    assert!(matches!(expr("tag1 & !unknown"), Expr::Tag(..)));
+    assert!(
+      matches!(expr("tag1 & (tag2 & !!(tag3 & tag4))"), Expr::And(exprs) if exprs.len() == 4)
+    );
+    assert!(matches!(expr("tag1 | (tag2 & tag3) | tag4"), Expr::Or(exprs) if exprs.len() == 3));
+  }
+
+  /// Check that we can filter tasks based on their summary and details.
+  #[test]
+  fn filter_predicates() {
+    let (_, templates, _) = make_tasks_with_tags(0);
+    let templates = Rc::new(Templates::with_serde(SerTemplates(templates)).unwrap());
+    let complete = templates.instantiate_from_name(COMPLETE_TAG).unwrap();
+    let tasks = vec![
+      SerTask::new("Deploy service").with_details("see https://example.com"),
+      SerTask::new("deploy docs").with_tags([complete.to_serde()]),
+      SerTask::new("write docs").with_details("HTTP"),
+      SerTask::new("review"),
+    ];
+    let tasks = Tasks::with_serde(SerTasks::from(tasks), Rc::clone(&templates)).unwrap();
+    let tasks = Rc::new(tasks);
+    let builder = ViewBuilder::new(&templates, &tasks);
+
+    let summaries = |formula: &str| {
+      let view = builder.build("test", formula).unwrap();
+      view.iter(|iter| {
+        iter
+          .map(|task| task.summary().to_string())
+          .collect::<Vec<_>>()
+      })
+    };
+
+    assert_eq!(
+      summaries(r#"summary~"DEPLOY""#),
+      ["Deploy service", "deploy docs"]
+    );
+    assert_eq!(
+      summaries(r#"summary~"deploy" & !complete"#),
+      ["Deploy service"]
+    );
+    assert_eq!(
+      summaries(r#"details~"http""#),
+      ["Deploy service", "write docs"]
+    );
+    assert_eq!(summaries("has-details"), ["Deploy service", "write docs"]);
+    assert_eq!(summaries("!has-details"), ["deploy docs", "review"]);
+    assert_eq!(summaries("true").len(), 4);
+    assert!(summaries("false").is_empty());
+    assert!(summaries("complete & false").is_empty());
+    assert_eq!(summaries("complete | false"), ["deploy docs"]);
+
+    // Only tags are considered positive literals.
+    let view = builder
+      .build("test", r#"summary~"x" | has-details | complete"#)
+      .unwrap();
+    assert_eq!(view.positive_tag_iter().count(), 1);
+    assert_eq!(view.unknown_tags(), &[] as &[String]);
+  }
+
+  /// Create serializable views from pairs of names and formulas.
+  fn ser_views(views: &[(&str, &str)]) -> Vec<SerView> {
+    views
+      .iter()
+      .map(|(name, formula)| SerView {
+        name: name.to_string(),
+        formula: FormulaPair {
+          string: formula.to_string(),
+          formula: Some(Formula::from_str(formula).unwrap()),
+        },
+        sort: Vec::new(),
+        order: None,
+        groups: Vec::new(),
+      })
+      .collect()
+  }
+
+  /// Check that views can reference each other.
+  #[test]
+  fn view_references() {
+    fn summaries(view: &View) -> Vec<String> {
+      view.iter(|iter| {
+        iter
+          .map(|task| task.summary().to_string())
+          .collect::<Vec<_>>()
+      })
+    }
+
+    let (templates, tasks) = make_tagged_tasks(20);
+    let builder = ViewBuilder::new(&templates, &tasks);
+    let views = builder
+      .with_serde(ser_views(&[
+        ("both", "@first & @\"second view\""),
+        ("first", "tag1 | tag2"),
+        ("second view", "!tag2"),
+      ]))
+      .unwrap();
+
+    let expected = builder.build("expected", "(tag1 | tag2) & !tag2").unwrap();
+    assert_eq!(summaries(&views[0]), summaries(&expected));
+    assert!(!views[0].is_empty());
+
+    let tags = views[0]
+      .positive_tag_iter()
+      .map(|tag| tag.name().to_string())
+      .collect::<Vec<_>>();
+    assert_eq!(tags, ["tag1", "tag2"]);
+
+    // Views created later on can reference existing ones as well.
+    let view = builder.build("other", "!@first").unwrap();
+    let expected = builder.build("expected2", "!tag1 & !tag2").unwrap();
+    assert_eq!(summaries(&view), summaries(&expected));
+
+    // Changes to a view's formula are reflected by views referencing
+    // it.
+    let mut first = views[1].clone();
+    let () = first.try_replace_formula("tag1").unwrap();
+    let expected = builder.build("expected3", "tag1 & !tag2").unwrap();
+    assert_eq!(summaries(&views[0]), summaries(&expected));
+
+    let err = builder.build("unknown", "@unknown").unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      "encountered reference to unknown view `unknown`"
+    );
+  }
+
+  /// Make sure that cyclic references between views are detected.
+  #[test]
+  fn cyclic_view_references() {
+    let (templates, tasks) = make_tagged_tasks(20);
+
+    let err = ViewBuilder::new(&templates, &tasks)
+      .with_serde(ser_views(&[("a", "tag1 | @a")]))
+      .unwrap_err();
+    assert_eq!(
+      err.root_cause().to_string(),
+      "encountered cyclic view reference `a` -> `a`"
+    );
+
+    let err = ViewBuilder::new(&templates, &tasks)
+      .with_serde(ser_views(&[("a", "@b"), ("b", "@c"), ("c", "!@a")]))
+      .unwrap_err();
+    assert_eq!(err.to_string(), "failed to instantiate view 'c'");
+    assert_eq!(
+      err.root_cause().to_string(),
+      "encountered cyclic view reference `c` -> `a` -> `b` -> `c`"
+    );
+
+    let builder = ViewBuilder::new(&templates, &tasks);
+    let mut views = builder
+      .with_serde(ser_views(&[("a", "tag1"), ("b", "@a")]))
+      .unwrap();
+    let err = views[0].try_replace_formula("@b").unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      "encountered cyclic view reference `a` -> `b` -> `a`"
+    );
+    // The view should not have been changed.
+    assert_eq!(views[0].formula(), "tag1");
+    assert!(!views[1].is_empty());
+  }
+
+  /// Check that renaming a view updates the formulas of views
+  /// referencing it.
+  #[test]
+  fn rename_referenced_view() {
+    let (templates, tasks) = make_tagged_tasks(20);
+    let builder = ViewBuilder::new(&templates, &tasks);
+    let mut views = builder
+      .with_serde(ser_views(&[
+        ("a", "tag1"),
+        ("b", "@a & !@a"),
+        ("c", "tag2"),
+      ]))
+      .unwrap();
+
+    let () = views[0].rename("my view".to_string()).unwrap();
+    assert_eq!(views[0].name().to_string(), "my view");
+    assert_eq!(views[1].formula(), r#"@"my view" & !@"my view""#);
+
+    let err = views[0].rename("c".to_string()).unwrap_err();
+    assert_eq!(err.to_string(), "view `c` already exists");
+
+    let views = ViewBuilder::new(&templates, &tasks)
+      .with_serde(views.iter().map(View::to_serde).collect())
+      .unwrap();
+    assert_eq!(views[1].formula(), r#"@"my view" & !@"my view""#);
+  }
+
+  /// Check that we can create, duplicate, and delete views alongside
+  /// existing ones.
+  #[test]
+  fn create_duplicate_delete_view() {
+    let (templates, tasks) = make_tagged_tasks(20);
+    let builder = ViewBuilder::new(&templates, &tasks);
+    let mut views = ser_views(&[("a", "tag1"), ("b", "tag2")]);
+    views[0].sort = vec![SerSortKey::from_str("-summary").unwrap()];
+    views[0].groups = vec![COMPLETE_TAG.to_string()];
+    let views = builder.with_serde(views).unwrap();
+
+    let err = views[0].create("b".to_string(), "tag3").unwrap_err();
+    assert_eq!(err.to_string(), "view `b` already exists");
+
+    let created = views[0].create("c".to_string(), "@a & !@b").unwrap();
+    assert_eq!(created.formula(), "@a & !@b");
+    assert!(!created.is_empty());
+
+    let duplicate = views[0].duplicate("d".to_string()).unwrap();
+    let expected = SerView {
+      name: "d".to_string(),
+      ..views[0].to_serde()
+    };
+    assert_eq!(duplicate.to_serde(), expected);
+
+    let err = views[1].delete().unwrap_err();
+    assert_eq!(err.to_string(), "view `b` is referenced by view `c`");
+    let () = created.delete().unwrap();
+    let () = views[1].delete().unwrap();
+    let () = duplicate.delete().unwrap();
+
+    let err = views[0].delete().unwrap_err();
+    assert_eq!(err.to_string(), "cannot delete the last remaining view");
+    // Deleted views can no longer be referenced.
+    let err = views[0].create("e".to_string(), "@b").unwrap_err();
+    assert_eq!(err.to_string(), "encountered reference to unknown view `b`");
+  }
+
+  /// Check that views can sort their tasks.
+  #[test]
+  fn sorted_view() {
+    fn summaries(view: &View) -> Vec<String> {
+      view.iter(|iter| {
+        iter
+          .map(|task| task.summary().to_string())
+          .collect::<Vec<_>>()
+      })
+    }
+
+    let mut templates = Templates::new();
+    let () = templates.extend(["x"]);
+    let templates = Rc::new(templates);
+    let x = templates.instantiate_from_name("x").unwrap();
+    let created = |secs| DateTime::from_timestamp(secs, 0);
+    let tasks = vec![
+      SerTask {
+        created: created(2),
+        ..SerTask::new("b").with_tags([x.to_serde()])
+      },
+      SerTask {
+        created: created(1),
+        ..SerTask::new("C").with_status(Status::Completed(None))
+      },
+      SerTask::new("a").with_tags([x.to_serde()]),
+      SerTask {
+        created: created(3),
+        ..SerTask::new("d")
+      },
+    ];
+    let tasks = Tasks::with_serde(SerTasks::from(tasks), Rc::clone(&templates)).unwrap();
+    let tasks = Rc::new(tasks);
+    let builder = ViewBuilder::new(&templates, &tasks);
+
+    let view = |formula: &str, sort: &[&str]| {
+      let view = SerView {
+        name: "test".to_string(),
+        formula: FormulaPair {
+          string: formula.to_string(),
+          formula: Formula::from_str(formula).ok(),
+        },
+        sort: sort
+          .iter()
+          .map(|key| SerSortKey::from_str(key).unwrap())
+          .collect(),
+        order: None,
+        groups: Vec::new(),
+      };
+      builder.with_serde(vec![view]).unwrap().remove(0)
+    };
+
+    assert_eq!(summaries(&view("", &[])), ["b", "C", "a", "d"]);
+    assert_eq!(summaries(&view("", &["summary"])), ["a", "b", "C", "d"]);
+    assert_eq!(summaries(&view("", &["-summary"])), ["d", "C", "b", "a"]);
+    assert_eq!(summaries(&view("", &["tag:x"])), ["b", "a", "C", "d"]);
+    assert_eq!(summaries(&view("", &["completed"])), ["b", "a", "d", "C"]);
+    assert_eq!(summaries(&view("", &["created"])), ["a", "C", "b", "d"]);
+    assert_eq!(summaries(&view("", &["-created"])), ["d", "b", "C", "a"]);
+    assert_eq!(
+      summaries(&view("", &["tag:x", "-summary"])),
+      ["b", "a", "d", "C"]
+    );
+    assert_eq!(summaries(&view("!x", &["summary"])), ["C", "d"]);
+
+    let reversed = view("", &["summary"]).iter(|iter| {
+      iter
+        .rev()
+        .map(|task| task.summary().to_string())
+        .collect::<Vec<_>>()
+    });
+    assert_eq!(reversed, ["d", "C", "b", "a"]);
+
+    let unknown = view("", &["tag:unknown"]);
+    assert_eq!(summaries(&unknown), ["b", "C", "a", "d"]);
+    assert_eq!(unknown.unknown_tags(), ["unknown"]);
+
+    let tagged = view("", &["-tag:x"]);
+    let mut view = view("", &["completed"]);
+    let (b, c, a) = view.iter(|mut iter| {
+      let b = Rc::clone(iter.next().unwrap());
+      let a = Rc::clone(iter.next().unwrap());
+      let _d = iter.next().unwrap();
+      let c = Rc::clone(iter.next().unwrap());
+      (b, c, a)
+    });
+    assert!(view.is_sorted());
+    assert_eq!(view.compare(&b, &a), Ordering::Equal);
+    assert_eq!(view.compare(&c, &b), Ordering::Greater);
+
+    let () = view.try_replace_formula("!x").unwrap();
+    assert_eq!(summaries(&view), ["d", "C"]);
+
+    let () = tasks
+      .rename_template(&x.template(), "y".to_string())
+      .unwrap();
+    let sort = tagged
+      .to_serde()
+      .sort
+      .iter()
+      .map(SerSortKey::to_string)
+      .collect::<Vec<_>>();
+    assert_eq!(sort, ["-tag:y"]);
+  }
+
+  /// Check that the order of tasks of a sorted view reflects changes
+  /// to tasks as well as to referenced views.
+  #[test]
+  fn sorted_view_updates() {
+    fn summaries(view: &View) -> Vec<String> {
+      view.iter(|iter| iter.map(|task| task.summary()).collect::<Vec<_>>())
+    }
+
+    let (templates, tasks) = make_tagged_tasks(4);
+    let builder = ViewBuilder::new(&templates, &tasks);
+    let mut referenced = builder.build("referenced", "").unwrap();
+    let view = SerView {
+      name: "sorted".to_string(),
+      formula: FormulaPair {
+        string: "@referenced".to_string(),
+        formula: Formula::from_str("@referenced").ok(),
+      },
+      sort: vec![SerSortKey::from_str("-summary").unwrap()],
+      order: None,
+      groups: Vec::new(),
+    };
+    let view = builder.with_serde(vec![view]).unwrap().remove(0);
+    assert_eq!(summaries(&view), ["4", "3", "2", "1"]);
+
+    let task = view.iter(|iter| Rc::clone(iter.last().unwrap()));
+    let mut updated = Task::clone(&task);
+    let () = updated.set_summary("5".to_string());
+    let () = tasks.update(task, updated);
+    assert_eq!(summaries(&view), ["5", "4", "3", "2"]);
+
+    let _task = view.add(Task::builder().set_summary("0"), None);
+    assert_eq!(summaries(&view), ["5", "4", "3", "2", "0"]);
+
+    let _task = tasks.undo().unwrap();
+    assert_eq!(summaries(&view), ["5", "4", "3", "2"]);
+
+    let () = referenced.try_replace_formula("complete").unwrap();
+    assert_eq!(summaries(&view), ["4", "2"]);
+  }
+
+  /// Check that views can group their tasks into sections.
+  #[test]
+  fn grouped_view() {
+    fn summaries(view: &View) -> Vec<String> {
+      view.iter(|iter| {
+        iter
+          .map(|task| task.summary().to_string())
+          .collect::<Vec<_>>()
+      })
+    }
+
+    fn section(title: &str, count: usize, collapsed: bool) -> Section {
+      Section {
+        title: title.to_string(),
+        count,
+        collapsed,
+      }
+    }
+
+    let mut templates = Templates::new();
+    let () = templates.extend(["prio-low", "prio-high", "work"]);
+    let templates = Rc::new(templates);
+    let tag = |name| templates.instantiate_from_name(name).unwrap().to_serde();
+    let tasks = vec![
+      SerTask::new("a").with_tags([tag("prio-low")]),
+      SerTask::new("b").with_tags([tag("work")]),
+      SerTask::new("c"),
+      SerTask::new("d").with_tags([tag("prio-high"), tag("work")]),
+      SerTask::new("e").with_tags([tag("prio-low")]),
+    ];
+    let tasks = Tasks::with_serde(SerTasks::from(tasks), Rc::clone(&templates)).unwrap();
+    let tasks = Rc::new(tasks);
+    let view = SerView {
+      name: "test".to_string(),
+      formula: FormulaPair::default(),
+      sort: Vec::new(),
+      order: None,
+      groups: vec![
+        "work".to_string(),
+        "prio-*".to_string(),
+        "unknown".to_string(),
+      ],
+    };
+    let mut view = ViewBuilder::new(&templates, &tasks)
+      .with_serde(vec![view])
+      .unwrap()
+      .remove(0);
+
+    assert_eq!(summaries(&view), ["b", "d", "a", "e", "c"]);
+    assert_eq!(
+      view.sections(),
+      [
+        section("work", 2, false),
+        section("prio-low", 2, false),
+        section("other", 1, false),
+      ]
+    );
+    assert_eq!(view.unknown_tags(), ["unknown"]);
+
+    let (a, b, c, d) = view.iter(|mut iter| {
+      let b = Rc::clone(iter.next().unwrap());
+      let d = Rc::clone(iter.next().unwrap());
+      let a = Rc::clone(iter.next().unwrap());
+      let _e = iter.next().unwrap();
+      let c = Rc::clone(iter.next().unwrap());
+      (a, b, c, d)
+    });
+    assert!(view.same_section(&b, &d));
+    assert!(!view.same_section(&a, &c));
+    assert_eq!(view.compare(&b, &d), Ordering::Equal);
+    assert_eq!(view.compare(&c, &a), Ordering::Greater);
+
+    assert!(view.collapse_section(&a));
+    assert!(!view.collapse_section(&a));
+    assert_eq!(summaries(&view), ["b", "d", "c"]);
+    assert_eq!(view.sections()[1], section("prio-low", 2, true));
+
+    let () = view.try_replace_formula("!work").unwrap();
+    assert_eq!(summaries(&view), ["c"]);
+
+    assert!(view.expand_sections());
+    assert!(!view.expand_sections());
+    assert_eq!(summaries(&view), ["a", "e", "c"]);
+    assert_eq!(view.to_serde().groups, ["work", "prio-*", "unknown"]);
+
+    let prio = tasks.add_template("prio-mid".to_string()).unwrap();
+    let task = Task::builder().set_summary("f").set_tags(vec![prio]);
+    let _task = view.add(task, None);
+    assert_eq!(summaries(&view), ["a", "e", "f", "c"]);
+    assert_eq!(
+      view.sections(),
+      [
+        section("prio-low", 2, false),
+        section("prio-mid", 1, false),
+        section("other", 1, false),
+      ]
+    );
+  }
+
+  /// Check that formulas whose conversion into Conjunctive Normal Form
+  /// would be exponential in size can be used.
+  #[test]
+  fn filter_large_formula() {
+    fn summaries(view: &View) -> Vec<String> {
+      view.iter(|iter| {
+        iter
+          .map(|task| task.summary().to_string())
+          .collect::<Vec<_>>()
+      })
+    }
+
+    let (templates, tasks) = make_tagged_tasks(20);
+    let builder = ViewBuilder::new(&templates, &tasks);
+
+    let formula = "(tag1 & tag2) | (tag3 & !tag4) | (tag5 & complete)";
+    let expected = summaries(&builder.build("test", formula).unwrap());
+    assert!(!expected.is_empty());
+
+    // In Conjunctive Normal Form, this formula would consist of 2^60
+    // clauses.
+    let formula = vec![formula; 20].join(" | ");
+    let view = builder.build("test", &formula).unwrap();
+    assert_eq!(summaries(&view), expected);
+    assert_eq!(view.positive_tag_iter().count(), 5);
+  }
+
   /// Check that we can correctly filter all completed tasks.
   #[test]
   fn filter_completions() {
@@ -545,4 +2273,59 @@ mod tests {
       assert!(iter.next().is_none());
     });
   }
+
+  /// Create a formula that is a disjunction of `clauses` conjunctions
+  /// of two tags each, referencing tags as created by
+  /// `make_tagged_tasks`.
+  #[cfg(feature = "nightly")]
+  fn make_large_formula(clauses: usize, tags: usize) -> String {
+    (0..clauses)
+      .map(|i| format!("(tag{} & !tag{})", i % tags + 1, (i + 1) % tags + 1))
+      .collect::<Vec<_>>()
+      .join(" | ")
+  }
+
+  /// Benchmark the creation of a view with a large formula.
+  #[cfg(feature = "nightly")]
+  #[bench]
+  fn bench_view_build_large_formula(b: &mut Bencher) {
+    let (templates, tasks) = make_tagged_tasks(2000);
+    let formula = make_large_formula(64, 500);
+    let builder = ViewBuilder::new(&templates, &tasks);
+
+    let () = b.iter(|| {
+      let _view = black_box(builder.build("test", black_box(&formula)).unwrap());
+    });
+  }
+
+  /// Benchmark the filtering of thousands of tasks with a small
+  /// formula.
+  #[cfg(feature = "nightly")]
+  #[bench]
+  fn bench_view_filter_small_formula(b: &mut Bencher) {
+    let (templates, tasks) = make_tagged_tasks(2000);
+    let view = ViewBuilder::new(&templates, &tasks)
+      .build("test", "(tag1 | tag3) & !complete")
+      .unwrap();
+
+    let () = b.iter(|| {
+      let _count = black_box(view.iter(|iter| iter.count()));
+    });
+  }
+
+  /// Benchmark the filtering of thousands of tasks with a large
+  /// formula.
+  #[cfg(feature = "nightly")]
+  #[bench]
+  fn bench_view_filter_large_formula(b: &mut Bencher) {
+    let (templates, tasks) = make_tagged_tasks(2000);
+    let formula = make_large_formula(64, 500);
+    let view = ViewBuilder::new(&templates, &tasks)
+      .build("test", &formula)
+      .unwrap();
+
+    let () = b.iter(|| {
+      let _count = black_box(view.iter(|iter| iter.count()));
+    });
+  }
 }
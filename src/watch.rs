@@ -0,0 +1,399 @@
+// Copyright (C) 2025 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Infrastructure for watching files for modifications made by other
+//! programs.
+//!
+//! The purpose of this module is to watch a set of directories using
+//! inotify(7) and send an event indicating that a modification was
+//! detected through a supplied `std::sync::mpsc::Sender` part of a
+//! `std::sync::mpsc::channel`. We are not interested in the details of
+//! the modification, as users will have to look at the affected files
+//! anyway.
+
+use std::ffi::CString;
+use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Result;
+use std::mem::size_of;
+use std::os::unix::ffi::OsStrExt as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::ptr::read_unaligned;
+use std::sync::mpsc::Sender;
+use std::thread;
+
+use libc::c_int;
+use libc::c_void;
+use libc::close;
+use libc::inotify_add_watch;
+use libc::inotify_event;
+use libc::inotify_init1;
+use libc::read;
+use libc::IN_CLOEXEC;
+use libc::IN_CLOSE_WRITE;
+use libc::IN_CREATE;
+use libc::IN_DELETE;
+use libc::IN_MASK_ADD;
+use libc::IN_MOVED_FROM;
+use libc::IN_MOVED_TO;
+use libc::IN_Q_OVERFLOW;
+
+use crate::transaction::is_transaction_file;
+use crate::Event;
+
+
+/// The events we are interested in. Files are usually either written in
+/// place or moved into place, but we also care about removals.
+const WATCH_MASK: u32 = IN_CLOSE_WRITE | IN_MOVED_TO | IN_MOVED_FROM | IN_DELETE;
+/// The events indicating that a directory we wait for may have been
+/// created inside its parent.
+const PARENT_MASK: u32 = IN_CREATE | IN_MOVED_TO;
+
+
+/// Check the return value of a system call.
+fn check(result: c_int) -> Result<c_int> {
+  if result < 0 {
+    Err(Error::last_os_error())
+  } else {
+    Ok(result)
+  }
+}
+
+
+/// Add a watch for the directory at `path` to the inotify instance
+/// referenced by `fd`.
+///
+/// Watches are always added to the ones already present for the same
+/// directory, as a directory may be the parent of another one we wait
+/// for and be watched itself.
+fn add_watch(fd: c_int, path: &Path, mask: u32) -> Result<c_int> {
+  // SANITY: Paths never contain NUL bytes.
+  let path = CString::new(path.as_os_str().as_bytes()).unwrap();
+  // SANITY: `path` is a valid NUL terminated string that outlives the
+  //         call and `fd` is an inotify file descriptor that we own.
+  check(unsafe { inotify_add_watch(fd, path.as_ptr(), mask | IN_MASK_ADD) })
+}
+
+
+/// A directory to watch.
+#[derive(Debug)]
+struct Watch {
+  /// The inotify watch descriptor.
+  wd: c_int,
+  /// The name of the only file in the directory to watch, if any.
+  file: Option<OsString>,
+}
+
+impl Watch {
+  /// Check whether the modification of the file with the given name is
+  /// of interest to us.
+  fn is_relevant(&self, name: &OsStr) -> bool {
+    if let Some(file) = &self.file {
+      file == name
+    } else {
+      // Left overs of in-progress save operations are of no concern.
+      // The final rename will be reported separately.
+      !is_transaction_file(name)
+    }
+  }
+}
+
+
+/// A directory that did not exist yet when we attempted to watch it.
+///
+/// We watch its parent instead and start watching the directory itself
+/// once it got created.
+#[derive(Debug)]
+struct Pending {
+  /// The inotify watch descriptor of the parent directory.
+  wd: c_int,
+  /// The path to the directory.
+  path: PathBuf,
+  /// The name of the only file in the directory to watch, if any.
+  file: Option<OsString>,
+}
+
+impl Pending {
+  /// Check whether the event about the file with the given name may
+  /// indicate that the directory got created.
+  fn is_created(&self, wd: c_int, name: &OsStr) -> bool {
+    self.wd == wd && self.path.file_name() == Some(name)
+  }
+
+  /// Attempt to watch the directory, returning `None` if it (still)
+  /// does not exist.
+  fn watch(&self, fd: c_int) -> Option<Result<Watch>> {
+    match add_watch(fd, &self.path, WATCH_MASK) {
+      Ok(wd) => Some(Ok(Watch {
+        wd,
+        file: self.file.clone(),
+      })),
+      Err(err) if err.kind() == ErrorKind::NotFound => None,
+      Err(err) => Some(Err(err)),
+    }
+  }
+}
+
+
+/// The set of directories we watch or wait for.
+#[derive(Debug)]
+struct Watches {
+  /// The directories being watched.
+  watches: Vec<Watch>,
+  /// The directories we wait to be created.
+  pending: Vec<Pending>,
+}
+
+impl Watches {
+  /// Watch the provided directories or, if they do not exist, their
+  /// parents.
+  fn new(fd: c_int, dirs: &[(&Path, Option<&OsStr>)]) -> Result<Self> {
+    let mut watches = Self {
+      watches: Vec::new(),
+      pending: Vec::new(),
+    };
+
+    for (dir, file) in dirs {
+      let file = file.map(OsStr::to_os_string);
+      match add_watch(fd, dir, WATCH_MASK) {
+        Ok(wd) => watches.watches.push(Watch { wd, file }),
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+          let parent = match dir.parent() {
+            Some(parent) if dir.file_name().is_some() => parent,
+            _ => continue,
+          };
+          let wd = match add_watch(fd, parent, PARENT_MASK) {
+            Ok(wd) => wd,
+            // Without a parent there is nothing to wait on.
+            Err(err) if err.kind() == ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+          };
+          let pending = Pending {
+            wd,
+            path: dir.to_path_buf(),
+            file,
+          };
+          // The directory may have been created before we started
+          // watching its parent.
+          match pending.watch(fd) {
+            Some(watch) => watches.watches.push(watch?),
+            None => watches.pending.push(pending),
+          }
+        },
+        Err(err) => return Err(err),
+      }
+    }
+    Ok(watches)
+  }
+
+  /// Handle the events contained in `buffer` and check whether any of
+  /// them is of interest to us.
+  ///
+  /// Directories we waited for that got created are watched from here
+  /// on. Their creation is considered relevant in its own right, as
+  /// files may have been put into them before we got to watch them.
+  fn handle(&mut self, fd: c_int, buffer: &[u8]) -> Result<bool> {
+    let mut relevant = false;
+    let mut offset = 0;
+    while offset + size_of::<inotify_event>() <= buffer.len() {
+      // SANITY: We just checked that the event header is within the
+      //         bounds of `buffer` and `read_unaligned` does not make
+      //         any assumptions about the alignment of the pointer we
+      //         cast.
+      let event = unsafe { read_unaligned(buffer[offset..].as_ptr().cast::<inotify_event>()) };
+      let name_start = offset + size_of::<inotify_event>();
+      let name_end = name_start + event.len as usize;
+      offset = name_end;
+
+      if event.mask & IN_Q_OVERFLOW != 0 {
+        // We may have missed something. Be conservative.
+        relevant = true;
+        continue
+      }
+
+      // The name may be cut short if the kernel reported a bogus
+      // length, in which case we just end up with an empty one.
+      let name = buffer.get(name_start..name_end).unwrap_or_default();
+      // The name is padded with NUL bytes.
+      let len = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+      let name = OsStr::from_bytes(&name[..len]);
+
+      if event.mask & WATCH_MASK != 0
+        && self
+          .watches
+          .iter()
+          .any(|watch| watch.wd == event.wd && watch.is_relevant(name))
+      {
+        relevant = true;
+      }
+
+      if event.mask & PARENT_MASK != 0 {
+        if let Some(idx) = self
+          .pending
+          .iter()
+          .position(|pending| pending.is_created(event.wd, name))
+        {
+          if let Some(watch) = self.pending[idx].watch(fd) {
+            let _pending = self.pending.remove(idx);
+            let () = self.watches.push(watch?);
+            relevant = true;
+          }
+        }
+      }
+    }
+    Ok(relevant)
+  }
+}
+
+
+/// Watch the provided directories and send an `Event::Modified` object
+/// upon modification of one of the files inside.
+///
+/// Each directory can optionally be accompanied by the name of the only
+/// file in it to care about. Directories that do not exist are watched
+/// once they get created, provided that their parent exists. Should
+/// watching fail later on, an `Event::WatchFailed` object is sent and no
+/// further modifications are reported.
+pub fn receive_modifications(
+  dirs: &[(&Path, Option<&OsStr>)],
+  send_event: Sender<Result<Event>>,
+) -> Result<()> {
+  // SANITY: `inotify_init1` has no memory safety related preconditions.
+  let fd = check(unsafe { inotify_init1(IN_CLOEXEC) })?;
+
+  let mut watches = match Watches::new(fd, dirs) {
+    Ok(watches) => watches,
+    Err(err) => {
+      // SANITY: `fd` is a file descriptor we own and do not use
+      //         afterwards.
+      let _result = unsafe { close(fd) };
+      return Err(err)
+    },
+  };
+
+  thread::spawn(move || {
+    // The buffer has to be able to hold at least one event with the
+    // maximum name length.
+    let mut buffer = [0u8; 4096];
+    loop {
+      // SANITY: The pointer and length describe `buffer`, which is
+      //         valid for writes of the given size.
+      let result = unsafe { read(fd, buffer.as_mut_ptr().cast::<c_void>(), buffer.len()) };
+      let result = if result < 0 {
+        let err = Error::last_os_error();
+        if err.kind() == ErrorKind::Interrupted {
+          continue
+        }
+        Err(err)
+      } else {
+        watches.handle(fd, &buffer[..result as usize])
+      };
+
+      let event = match result {
+        Ok(true) => Event::Modified,
+        Ok(false) => continue,
+        Err(err) => Event::WatchFailed(format!("failed to watch for modifications: {err}")),
+      };
+      let failed = matches!(event, Event::WatchFailed(..));
+
+      // If the receiving end is gone there is nobody left to care about
+      // modifications. If we failed, we can no longer tell whether
+      // anything got modified.
+      if send_event.send(Ok(event)).is_err() || failed {
+        // SANITY: `fd` is a file descriptor we own and do not use
+        //         afterwards.
+        let _result = unsafe { close(fd) };
+        break
+      }
+    }
+  });
+  Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::fs::create_dir;
+  use std::fs::write;
+  use std::sync::mpsc::channel;
+  use std::sync::mpsc::RecvTimeoutError;
+  use std::time::Duration;
+
+  use tempfile::TempDir;
+
+
+  /// Check that we get notified about modifications of watched files,
+  /// but not others.
+  #[test]
+  fn watch_modifications() {
+    let root = TempDir::new().unwrap();
+    let tasks = root.path().join("tasks");
+    let () = create_dir(&tasks).unwrap();
+    let missing = root.path().join("missing");
+
+    let (send, recv) = channel();
+    let dirs = [
+      (tasks.as_path(), None),
+      (root.path(), Some(OsStr::new("notnow.json"))),
+      (missing.as_path(), None),
+    ];
+    let () = receive_modifications(&dirs, send).unwrap();
+
+    let timeout = Duration::from_millis(100);
+    let () = write(tasks.join(".task.new"), b"").unwrap();
+    let () = write(root.path().join("other.json"), b"").unwrap();
+    assert_eq!(
+      recv.recv_timeout(timeout).unwrap_err(),
+      RecvTimeoutError::Timeout
+    );
+
+    let timeout = Duration::from_secs(10);
+    let () = write(tasks.join("task"), b"").unwrap();
+    assert!(matches!(
+      recv.recv_timeout(timeout),
+      Ok(Ok(Event::Modified))
+    ));
+
+    let () = write(root.path().join("notnow.json"), b"").unwrap();
+    assert!(matches!(
+      recv.recv_timeout(timeout),
+      Ok(Ok(Event::Modified))
+    ));
+  }
+
+  /// Check that directories that do not exist yet are watched once
+  /// they get created.
+  #[test]
+  fn watch_created_directory() {
+    let root = TempDir::new().unwrap();
+    let tasks = root.path().join("tasks");
+    let meta = tasks.join(".notnow");
+    let () = create_dir(&tasks).unwrap();
+
+    let (send, recv) = channel();
+    let dirs = [(tasks.as_path(), None), (meta.as_path(), None)];
+    let () = receive_modifications(&dirs, send).unwrap();
+
+    let timeout = Duration::from_secs(10);
+    let () = create_dir(&meta).unwrap();
+    assert!(matches!(
+      recv.recv_timeout(timeout),
+      Ok(Ok(Event::Modified))
+    ));
+
+    let timeout = Duration::from_millis(100);
+    while recv.recv_timeout(timeout).is_ok() {}
+
+    let timeout = Duration::from_secs(10);
+    let () = write(meta.join("tasks-meta.json"), b"").unwrap();
+    assert!(matches!(
+      recv.recv_timeout(timeout),
+      Ok(Ok(Event::Modified))
+    ));
+  }
+}